@@ -0,0 +1,932 @@
+//! Prints deterministic test vectors (PDAs, instruction encodings and serialized accounts)
+//! as JSON, so other client implementations can check byte-for-byte compatibility.
+//!
+//! Usage: `cargo run -p vault2-client --bin test-vectors > test-vectors.json`
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::Instruction;
+use serde_json::{json, Value};
+use vault2::{
+    ActivityKind, ActivityRecord, CapPolicy, EXTENSION_HISTORY_LEN, EXTENSION_RESERVED_BYTES,
+    OUTBOX_CAPACITY, REWARDS_RESERVED_BYTES, VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS,
+    VAULT_RESERVED_BYTES,
+};
+use vault2_client::*;
+
+// Fixed keys so every run produces the same output
+const USERS: [[u8; 32]; 2] = [[1; 32], [2; 32]];
+const MINTS: [[u8; 32]; 2] = [[10; 32], [11; 32]];
+const HOOK: [u8; 32] = [20; 32];
+const SWAP_PROGRAM: [u8; 32] = [21; 32];
+const INPUT_MINT: [u8; 32] = [12; 32];
+const VAULT_KEY: [u8; 32] = [30; 32];
+const CALLER: [u8; 32] = [40; 32];
+const PRICE_UPDATE: [u8; 32] = [50; 32];
+const TREASURY: [u8; 32] = [60; 32];
+const MEMBERSHIP_TOKEN_ACCOUNT: [u8; 32] = [61; 32];
+
+fn main() {
+    let vectors = json!({
+        "program_id": PROGRAM_ID.to_string(),
+        "pdas": pdas(),
+        "instructions": instructions(),
+        "accounts": accounts(),
+    });
+
+    println!("{}", serde_json::to_string_pretty(&vectors).unwrap());
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn pda(address: (Pubkey, u8)) -> Value {
+    json!({ "address": address.0.to_string(), "bump": address.1 })
+}
+
+fn pdas() -> Vec<Value> {
+    let mut vectors = vec![
+        json!({
+            "name": "event_authority",
+            "pda": pda(find_event_authority()),
+        }),
+        json!({
+            "name": "config",
+            "pda": pda(find_config_address()),
+        }),
+        json!({
+            "name": "config_history",
+            "pda": pda(find_config_history_address()),
+        }),
+        json!({
+            "name": "fee_tiers",
+            "pda": pda(find_fee_tiers_address()),
+        }),
+        json!({
+            "name": "credential_gate",
+            "pda": pda(find_credential_gate_address()),
+        }),
+        json!({
+            "name": "creation_gate",
+            "pda": pda(find_creation_gate_address()),
+        }),
+    ];
+
+    for user in USERS.map(Pubkey::new_from_array) {
+        let (state, state_bump) = find_state_address(&user);
+        vectors.push(json!({
+            "name": "vault",
+            "user": user.to_string(),
+            "state": pda((state, state_bump)),
+            "vault_authority": pda(find_vault_authority(&state)),
+            "outbox": pda(find_outbox_address(&state)),
+            "notes": pda(find_notes_address(&state)),
+            "price_condition": pda(find_price_condition_address(&state)),
+            "withdrawal_policy": pda(find_withdrawal_policy_address(&state)),
+            "pending_withdrawal": pda(find_pending_withdrawal_address(&state)),
+            "guardians": pda(find_guardians_address(&state)),
+            "recovery_proposal": pda(find_recovery_proposal_address(&state)),
+            "rewards": pda(find_rewards_address(&state)),
+            "extension": pda(find_extension_address(&state)),
+            "custody": pda(find_custody_address(&state)),
+            "rollover": pda(find_rollover_address(&state)),
+            "advance": pda(find_advance_address(&state)),
+            "template": pda(find_goal_template_address(1)),
+            "vault_template": pda(find_vault_template_address(&state)),
+            "blocked": pda(find_blocked_address(&user)),
+            "badge_mints": ([Badge::FirstDeposit, Badge::Streak, Badge::GoalReached])
+                .map(|badge| pda(find_badge_mint_address(&user, badge))),
+            "caller_contribution": pda(find_contribution_address(
+                &state,
+                &Pubkey::new_from_array(CALLER)
+            )),
+            "caller_subscription": pda(find_subscription_address(
+                &state,
+                &Pubkey::new_from_array(CALLER)
+            )),
+            "circle_1": pda(find_circle_address(&user, 1)),
+            "escrow_1": pda(find_escrow_address(&user, 1)),
+            "htlc_1": pda(find_htlc_address(&user, 1)),
+            "caller_payroll": pda(find_payroll_address(&user, &Pubkey::new_from_array(CALLER))),
+            "vault_token_accounts": MINTS
+                .map(Pubkey::new_from_array)
+                .iter()
+                .map(|mint| json!({
+                    "mint": mint.to_string(),
+                    "address": find_vault_token_account(&state, mint).to_string(),
+                }))
+                .collect::<Vec<_>>(),
+        }));
+    }
+
+    for mint in MINTS.map(Pubkey::new_from_array) {
+        vectors.push(json!({
+            "name": "approved_mint",
+            "mint": mint.to_string(),
+            "pda": pda(find_approved_mint_address(&mint)),
+        }));
+        vectors.push(json!({
+            "name": "mint_stats",
+            "mint": mint.to_string(),
+            "pda": pda(find_mint_stats_address(&mint)),
+        }));
+        vectors.push(json!({
+            "name": "leaderboard",
+            "mint": mint.to_string(),
+            "pda": pda(find_leaderboard_address(&mint)),
+        }));
+    }
+
+    vectors.push(json!({
+        "name": "approved_hook",
+        "hook_program": Pubkey::new_from_array(HOOK).to_string(),
+        "pda": pda(find_approved_hook_address(&Pubkey::new_from_array(HOOK))),
+    }));
+
+    vectors.push(json!({
+        "name": "approved_swap",
+        "swap_program": Pubkey::new_from_array(SWAP_PROGRAM).to_string(),
+        "pda": pda(find_approved_swap_address(&Pubkey::new_from_array(SWAP_PROGRAM))),
+    }));
+
+    vectors
+}
+
+fn instruction(name: &str, ix: Instruction) -> Value {
+    json!({
+        "name": name,
+        "program_id": ix.program_id.to_string(),
+        "accounts": ix
+            .accounts
+            .iter()
+            .map(|meta| json!({
+                "pubkey": meta.pubkey.to_string(),
+                "is_signer": meta.is_signer,
+                "is_writable": meta.is_writable,
+            }))
+            .collect::<Vec<_>>(),
+        "data": hex(&ix.data),
+    })
+}
+
+fn instructions() -> Vec<Value> {
+    let user = Pubkey::new_from_array(USERS[0]);
+    let mint = Pubkey::new_from_array(MINTS[0]);
+    let hook = Pubkey::new_from_array(HOOK);
+    let vault_key = Pubkey::new_from_array(VAULT_KEY);
+    let caller = Pubkey::new_from_array(CALLER);
+    let user_token_account =
+        anchor_spl::associated_token::get_associated_token_address(&user, &mint);
+    let price_update = Pubkey::new_from_array(PRICE_UPDATE);
+    let input_mint = Pubkey::new_from_array(INPUT_MINT);
+    let treasury = Pubkey::new_from_array(TREASURY);
+    let membership_token_account = Pubkey::new_from_array(MEMBERSHIP_TOKEN_ACCOUNT);
+    // The second user stands in for the wallet a vault is recovered to
+    let new_owner = Pubkey::new_from_array(USERS[1]);
+    // A stand-in for a swap route from the user's input token account into the vault
+    let swap = Instruction {
+        program_id: Pubkey::new_from_array(SWAP_PROGRAM),
+        accounts: vec![
+            AccountMeta::new_readonly(user, true),
+            AccountMeta::new(
+                anchor_spl::associated_token::get_associated_token_address(&user, &input_mint),
+                false,
+            ),
+            AccountMeta::new(
+                find_vault_token_account(&find_state_address(&user).0, &mint),
+                false,
+            ),
+        ],
+        data: vec![1, 2, 3, 4],
+    };
+
+    vec![
+        instruction(
+            "initialize",
+            initialize(
+                &user,
+                &user,
+                &mint,
+                100_000_000,
+                None,
+                None,
+                None,
+                None,
+                None,
+            ),
+        ),
+        instruction(
+            "initialize_with_deadline",
+            initialize(
+                &user,
+                &user,
+                &mint,
+                100_000_000,
+                Some(donation_deadline()),
+                None,
+                None,
+                None,
+                None,
+            ),
+        ),
+        instruction(
+            "initialize_sponsored",
+            initialize(
+                &user,
+                &caller,
+                &mint,
+                100_000_000,
+                None,
+                None,
+                None,
+                None,
+                None,
+            ),
+        ),
+        instruction(
+            "initialize_usd_target",
+            initialize(
+                &user,
+                &user,
+                &mint,
+                0,
+                None,
+                Some(usd_target()),
+                None,
+                None,
+                None,
+            ),
+        ),
+        instruction(
+            "initialize_ui_amount",
+            initialize(
+                &user,
+                &user,
+                &mint,
+                0,
+                None,
+                None,
+                Some("12.5".to_string()),
+                None,
+                None,
+            ),
+        ),
+        instruction(
+            "initialize_idempotent",
+            initialize_idempotent(
+                &user,
+                &user,
+                &mint,
+                100_000_000,
+                None,
+                None,
+                None,
+                None,
+                None,
+            ),
+        ),
+        instruction(
+            "initialize_custodial",
+            initialize_custodial(
+                &caller,
+                &user,
+                &mint,
+                100_000_000,
+                1_900_000_000,
+                None,
+                None,
+                None,
+            ),
+        ),
+        instruction(
+            "initialize_from_template",
+            initialize_from_template(&user, &user, 1, &mint, None, None, None),
+        ),
+        instruction(
+            "deposit",
+            deposit(
+                &user,
+                &user_token_account,
+                &mint,
+                None,
+                None,
+                FeeAccounts::default(),
+                50_000_000,
+            ),
+        ),
+        instruction(
+            "deposit_with_fee",
+            deposit(
+                &user,
+                &user_token_account,
+                &mint,
+                None,
+                None,
+                FeeAccounts {
+                    treasury: Some(treasury),
+                    membership_token_account: Some(membership_token_account),
+                },
+                50_000_000,
+            ),
+        ),
+        instruction(
+            "deposit_with_hook",
+            deposit(
+                &user,
+                &user_token_account,
+                &mint,
+                Some(&hook),
+                None,
+                FeeAccounts::default(),
+                50_000_000,
+            ),
+        ),
+        instruction(
+            "deposit_all",
+            deposit_all(
+                &user,
+                &user_token_account,
+                &mint,
+                None,
+                None,
+                FeeAccounts::default(),
+            ),
+        ),
+        instruction(
+            "deposit_with_memo",
+            deposit_with_memo(
+                &user,
+                &user_token_account,
+                &mint,
+                None,
+                None,
+                FeeAccounts::default(),
+                50_000_000,
+                "invoice 2024-0042".to_string(),
+            ),
+        ),
+        instruction(
+            "deposit_sol",
+            deposit_sol(&user, None, None, FeeAccounts::default(), 50_000_000),
+        ),
+        instruction(
+            "deposit_and_swap",
+            deposit_and_swap(
+                &user,
+                &user,
+                &input_mint,
+                &mint,
+                None,
+                Vec::new(),
+                None,
+                FeeAccounts::default(),
+                5_000_000,
+                4_900_000,
+                swap,
+            ),
+        ),
+        instruction(
+            "deposit_for",
+            deposit_for(
+                &caller,
+                &user,
+                &anchor_spl::associated_token::get_associated_token_address(&caller, &mint),
+                &mint,
+                None,
+                FeeAccounts::default(),
+                1_000_000,
+            ),
+        ),
+        instruction(
+            "deposit_via_delegate",
+            deposit_via_delegate(
+                &caller,
+                &user,
+                &user_token_account,
+                &mint,
+                None,
+                None,
+                FeeAccounts::default(),
+                1_000_000,
+            ),
+        ),
+        instruction(
+            "set_auto_save",
+            set_auto_save(&user, &caller, 10_000_000, 24 * 60 * 60),
+        ),
+        instruction("clear_auto_save", clear_auto_save(&user)),
+        instruction(
+            "withdraw",
+            withdraw(&user, &user, &mint, None, None, 20_000_000),
+        ),
+        instruction(
+            "withdraw_with_vault_key",
+            withdraw(&user, &user, &mint, Some(&vault_key), None, 20_000_000),
+        ),
+        instruction(
+            "withdraw_with_memo",
+            withdraw_with_memo(
+                &user,
+                &user,
+                &mint,
+                None,
+                None,
+                20_000_000,
+                "payout 2024-0007".to_string(),
+            ),
+        ),
+        instruction(
+            "withdraw_to",
+            withdraw_to(
+                &user,
+                &mint,
+                &anchor_spl::associated_token::get_associated_token_address(&caller, &mint),
+                &caller,
+                None,
+                None,
+                20_000_000,
+            ),
+        ),
+        instruction(
+            "borrow",
+            borrow(&user, &user, &mint, None, None, 10_000_000),
+        ),
+        instruction(
+            "repay",
+            repay(
+                &user,
+                &user_token_account,
+                &mint,
+                Some(&treasury),
+                10_100_000,
+            ),
+        ),
+        instruction(
+            "withdraw_sol",
+            withdraw_sol(&user, &user, None, None, 20_000_000),
+        ),
+        instruction("set_withdrawal_delay", set_withdrawal_delay(&user, 172_800)),
+        instruction(
+            "set_unlock_stream",
+            set_unlock_stream(
+                &user,
+                &mint,
+                1_700_000_000,
+                1_731_536_000,
+                Some(1_707_776_000),
+            ),
+        ),
+        instruction("self_freeze", self_freeze(&user, 2_592_000)),
+        instruction(
+            "set_withdrawal_approvers",
+            set_withdrawal_approvers(&user, vec![caller, vault_key], 2, &[caller]),
+        ),
+        instruction("request_withdrawal", request_withdrawal(&user, 20_000_000)),
+        instruction("approve_withdrawal", approve_withdrawal(&caller, &user)),
+        instruction(
+            "execute_withdrawal",
+            execute_withdrawal(&user, &user, &mint, Some(&vault_key), None),
+        ),
+        instruction("cancel_withdrawal", cancel_withdrawal(&user)),
+        instruction(
+            "set_guardians",
+            set_guardians(
+                &user,
+                Some(&vault_key),
+                vec![caller, vault_key],
+                2,
+                &[caller],
+            ),
+        ),
+        instruction(
+            "propose_recovery",
+            propose_recovery(&caller, &user, &new_owner),
+        ),
+        instruction("vote_recovery", vote_recovery(&vault_key, &user)),
+        instruction("veto_recovery", veto_recovery(&user, &caller)),
+        instruction(
+            "execute_recovery",
+            execute_recovery(&caller, &user, &new_owner, &mint, &caller),
+        ),
+        instruction("init_mint_stats", init_mint_stats(&caller, &mint)),
+        instruction("migrate_vault", migrate_vault(&caller, &user, &mint)),
+        instruction("init_vault_extension", init_vault_extension(&user)),
+        instruction(
+            "record_balance_snapshot",
+            record_balance_snapshot(&user, &mint),
+        ),
+        instruction(
+            "grant_delegate",
+            grant_delegate(
+                &user,
+                &caller,
+                DelegatePermissions {
+                    deposit: true,
+                    withdraw: true,
+                },
+                1_900_000_000,
+                10_000_000,
+            ),
+        ),
+        instruction("revoke_delegate", revoke_delegate(&user, &caller)),
+        instruction(
+            "session_deposit",
+            session_deposit(
+                &caller,
+                &user,
+                &mint,
+                None,
+                FeeAccounts::default(),
+                1_000_000,
+            ),
+        ),
+        instruction(
+            "session_withdraw",
+            session_withdraw(&caller, &user, &mint, None, 1_000_000),
+        ),
+        instruction(
+            "grant_subscription",
+            grant_subscription(&user, &caller, 5_000_000, 30 * 24 * 60 * 60),
+        ),
+        instruction("revoke_subscription", revoke_subscription(&user, &caller)),
+        instruction(
+            "pull_subscription",
+            pull_subscription(&caller, &user, &mint, &user_token_account, None),
+        ),
+        instruction("lock_tokens", lock_tokens(&user, 50_000_000, 3_600)),
+        instruction("set_deposit_hook", set_deposit_hook(&user, Some(&hook))),
+        instruction("clear_deposit_hook", set_deposit_hook(&user, None)),
+        instruction(
+            "set_vault_key",
+            set_vault_key(&user, None, Some(&vault_key), 100_000_000),
+        ),
+        instruction(
+            "set_balance_cap",
+            set_balance_cap(&user, Some(500_000_000), CapPolicy::Truncate),
+        ),
+        instruction("add_note", add_note(&user, "saving for a bike".to_string())),
+        instruction(
+            "set_release_on_unlock",
+            set_release_on_unlock(&user, Some(&vault_key), true),
+        ),
+        instruction(
+            "set_withdraw_cooldown",
+            set_withdraw_cooldown(&user, 86_400),
+        ),
+        instruction(
+            "set_withdraw_limit",
+            set_withdraw_limit(&user, Some(&vault_key), 250_000_000),
+        ),
+        instruction(
+            "crank_release",
+            crank_release(
+                &caller,
+                &anchor_spl::associated_token::get_associated_token_address(&caller, &mint),
+                &caller,
+                &user,
+                &mint,
+                None,
+            ),
+        ),
+        instruction(
+            "settle_deadline",
+            settle_deadline(
+                &caller,
+                &user,
+                &mint,
+                Some(&user_token_account),
+                Some(&user),
+                None,
+            ),
+        ),
+        instruction("claim_points", claim_points(&user, &mint)),
+        instruction("claim_badge", claim_badge(&user, Badge::GoalReached)),
+        instruction(
+            "create_circle",
+            create_circle(
+                &user,
+                1,
+                &mint,
+                vec![user, caller, vault_key],
+                10_000_000,
+                604_800,
+            ),
+        ),
+        instruction(
+            "contribute_to_circle",
+            contribute_to_circle(&caller, &user, 1, &mint, &user_token_account),
+        ),
+        instruction(
+            "payout_circle",
+            payout_circle(&caller, &user, 1, &mint, &user),
+        ),
+        instruction(
+            "claim_circle_refund",
+            claim_circle_refund(&caller, &user, 1, &mint, &user_token_account),
+        ),
+        instruction("close_circle", close_circle(&user, 1, &mint)),
+        instruction(
+            "create_payroll",
+            create_payroll(&user, &caller, &mint, 1_000_000, 14 * 24 * 60 * 60),
+        ),
+        instruction(
+            "fund_payroll",
+            fund_payroll(&user, &caller, &mint, &user_token_account, 10_000_000),
+        ),
+        instruction(
+            "claim_payroll",
+            claim_payroll(&caller, &user, &mint, &user_token_account),
+        ),
+        instruction(
+            "stop_payroll",
+            stop_payroll(&user, &caller, &mint, &user_token_account),
+        ),
+        instruction("close_payroll", close_payroll(&user, &caller, &mint)),
+        instruction(
+            "create_escrow",
+            create_escrow(
+                &user,
+                1,
+                &mint,
+                &user_token_account,
+                &caller,
+                Some(&vault_key),
+                25_000_000,
+                1_900_000_000,
+            ),
+        ),
+        instruction("approve_escrow", approve_escrow(&caller, &user, 1)),
+        instruction(
+            "release_escrow",
+            release_escrow(&caller, &user, 1, &mint, &caller),
+        ),
+        instruction(
+            "refund_escrow",
+            refund_escrow(&user, 1, &mint, &user_token_account),
+        ),
+        instruction(
+            "create_htlc",
+            create_htlc(
+                &user,
+                1,
+                &mint,
+                &user_token_account,
+                &caller,
+                hash(&[7; 32]).to_bytes(),
+                25_000_000,
+                1_900_000_000,
+            ),
+        ),
+        instruction(
+            "claim_htlc",
+            claim_htlc(&caller, &user, 1, &mint, &caller, [7; 32]),
+        ),
+        instruction(
+            "refund_htlc",
+            refund_htlc(&user, 1, &mint, &user_token_account),
+        ),
+        instruction(
+            "update_leaderboard",
+            update_leaderboard(&caller, &user, &mint),
+        ),
+        instruction(
+            "set_price_condition",
+            set_price_condition(
+                &user,
+                Some(&vault_key),
+                &price_update,
+                15_000,
+                PriceDirection::AtOrAbove,
+            ),
+        ),
+        instruction(
+            "check_price_condition",
+            check_price_condition(&caller, &user, &mint, &price_update),
+        ),
+        instruction("clear_price_condition", clear_price_condition(&user)),
+        instruction("set_rollover", set_rollover(&user, 1_000)),
+        instruction("clear_rollover", clear_rollover(&user)),
+        instruction("close_vault", close_vault(&user, &mint)),
+        instruction(
+            "finalize",
+            finalize(&user, &user_token_account, &mint, None),
+        ),
+        instruction(
+            "get_vault_status",
+            get_vault_status(&user, &mint, false, None),
+        ),
+    ]
+}
+
+fn usd_target() -> UsdTarget {
+    UsdTarget {
+        cents: 50_000,
+        price_account: Pubkey::new_from_array(PRICE_UPDATE),
+    }
+}
+
+fn donation_deadline() -> GoalDeadline {
+    GoalDeadline {
+        deadline_ts: 1_800_000_000,
+        consequence: DeadlineConsequence::Donate {
+            bps: 2_500,
+            recipient: Pubkey::new_from_array(CALLER),
+        },
+    }
+}
+
+fn account<T: AccountSerialize>(name: &str, account: &T) -> Value {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+
+    json!({ "name": name, "data": hex(&data) })
+}
+
+fn zero_copy_account<T: anchor_lang::ZeroCopy>(name: &str, account: &T) -> Value {
+    let data = [T::DISCRIMINATOR, bytemuck::bytes_of(account)].concat();
+
+    json!({ "name": name, "data": hex(&data) })
+}
+
+fn accounts() -> Vec<Value> {
+    let user = Pubkey::new_from_array(USERS[0]);
+    let mint = Pubkey::new_from_array(MINTS[0]);
+    let (state, state_bump) = find_state_address(&user);
+    let (_, vault_bump) = find_vault_authority(&state);
+    let (_, outbox_bump) = find_outbox_address(&state);
+    let (_, rewards_bump) = find_rewards_address(&state);
+
+    let vault = Vault {
+        amount: 100_000_000,
+        vault_bump,
+        state_bump,
+        mint,
+        vault_token_account: find_vault_token_account(&state, &mint),
+        lock_tranches: Default::default(),
+        deposit_hook: None,
+        vault_key: None,
+        vault_key_threshold: 0,
+        frozen_until: None,
+        flags: 0,
+        release_on_unlock: false,
+        balance_cap: None,
+        cap_policy: CapPolicy::Reject,
+        deadline: Some(GoalDeadline {
+            deadline_ts: 1_800_000_000,
+            consequence: DeadlineConsequence::ExtendLock { seconds: 86_400 },
+        }),
+        usd_target: None,
+        cooldown_seconds: 0,
+        last_withdraw_ts: 0,
+        max_withdraw_per_day: 0,
+        withdrawn_in_window: 0,
+        version: Vault::VERSION,
+        open_grants: 0,
+        decimals: 6,
+        reserved: [0; VAULT_RESERVED_BYTES],
+    };
+    let configured_vault = Vault {
+        lock_tranches: [
+            LockTranche {
+                amount: 100_000_000,
+                unlock_ts: 1_700_000_000,
+            },
+            LockTranche {
+                amount: 50_000_000,
+                unlock_ts: 1_705_000_000,
+            },
+            LockTranche::default(),
+            LockTranche::default(),
+        ],
+        deposit_hook: Some(Pubkey::new_from_array(HOOK)),
+        vault_key: Some(Pubkey::new_from_array(VAULT_KEY)),
+        vault_key_threshold: 10_000_000,
+        frozen_until: Some(1_700_086_400),
+        flags: VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS,
+        release_on_unlock: true,
+        balance_cap: Some(500_000_000),
+        cap_policy: CapPolicy::Truncate,
+        deadline: Some(donation_deadline()),
+        usd_target: Some(usd_target()),
+        cooldown_seconds: 86_400,
+        last_withdraw_ts: 1_700_050_000,
+        max_withdraw_per_day: 250_000_000,
+        withdrawn_in_window: 20_000_000,
+        ..vault.clone()
+    };
+
+    let mut outbox = Outbox {
+        state,
+        bump: outbox_bump,
+        last_id: 0,
+        records: [ActivityRecord::default(); OUTBOX_CAPACITY],
+    };
+    // Fill past the capacity so the ring has wrapped around
+    for id in 1..=(OUTBOX_CAPACITY as u64 + 2) {
+        outbox.last_id = id;
+        outbox.records[id as usize % OUTBOX_CAPACITY] = ActivityRecord {
+            id,
+            kind: if id == 1 {
+                ActivityKind::Initialized
+            } else {
+                ActivityKind::Deposit
+            },
+            amount: id * 1_000,
+            timestamp: 1_700_000_000 + id as i64,
+            slot: 250_000_000 + id,
+        };
+    }
+
+    let rewards = RewardState {
+        state,
+        bump: rewards_bump,
+        last_update: 1_700_000_000,
+        points: 1_234_567_890_123,
+        claimed_points: 86_400,
+        streak_days: 9,
+        last_deposit_day: 19_675,
+        badges_earned: Badge::FirstDeposit.bit() | Badge::Streak.bit(),
+        badges_claimed: Badge::FirstDeposit.bit(),
+        last_deposit_slot: 250_000_000,
+        last_withdraw_slot: 249_999_000,
+        reserved: [0; REWARDS_RESERVED_BYTES],
+    };
+
+    let mut extension = VaultExtension {
+        state,
+        bump: find_extension_address(&state).1,
+        version: VaultExtension::VERSION,
+        padding: [0; 6],
+        snapshot_count: 0,
+        snapshots: [BalanceSnapshot::default(); EXTENSION_HISTORY_LEN],
+        last_memo_hash: [0; 32],
+        reserved: [0; EXTENSION_RESERVED_BYTES],
+    };
+    // Hourly snapshots, enough to wrap around the history ring
+    for n in 0..(EXTENSION_HISTORY_LEN as u64 + 3) {
+        extension
+            .record_snapshot(BalanceSnapshot {
+                timestamp: 1_700_000_000 + n as i64 * 3_600,
+                balance: n * 1_000_000,
+            })
+            .unwrap();
+    }
+    extension.record_memo(hash(b"invoice 2024-0042").to_bytes());
+
+    let mint_stats = MintStats {
+        mint,
+        bump: find_mint_stats_address(&mint).1,
+        total_vaults: 42,
+        total_deposited: 9_000_000_000,
+        total_withdrawn: 1_500_000_000,
+        total_released: 2_500_000_000,
+    };
+
+    let fee_tiers = FeeTiers {
+        bump: find_fee_tiers_address().1,
+        membership_mint: Pubkey::new_from_array(MINTS[1]),
+        tiers: vec![
+            FeeTier {
+                min_balance: 100_000_000,
+                discount_bps: 5_000,
+            },
+            FeeTier {
+                min_balance: 1_000_000_000,
+                discount_bps: 10_000,
+            },
+        ],
+    };
+
+    let admin = Pubkey::new_from_array(CALLER);
+    let config_history = ConfigHistory {
+        bump: find_config_history_address().1,
+        entries: vec![
+            ConfigChange {
+                field: ConfigField::Admin,
+                old: ConfigValue::Pubkey(Pubkey::default()),
+                new: ConfigValue::Pubkey(admin),
+                slot: 250_000_000,
+                admin,
+            },
+            ConfigChange {
+                field: ConfigField::MaxBalanceCap,
+                old: ConfigValue::U64(u64::MAX),
+                new: ConfigValue::U64(1_000_000_000),
+                slot: 250_000_100,
+                admin,
+            },
+        ],
+    };
+
+    vec![
+        account("vault", &vault),
+        account("vault_configured", &configured_vault),
+        account("outbox_wrapped", &outbox),
+        account("rewards", &rewards),
+        zero_copy_account("extension_wrapped", &extension),
+        account("mint_stats", &mint_stats),
+        account("fee_tiers", &fee_tiers),
+        account("config_history", &config_history),
+    ]
+}
@@ -0,0 +1,3032 @@
+//! Client helpers for the vault2 program: PDA derivation, typed instruction builders and
+//! account fetch/deserialize helpers.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+};
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token::TokenAccount;
+
+pub use vault2::{
+    Advance, AutoSaveGrant, Badge, BalanceSnapshot, CapPolicy, Circle, CircleMember, ConfigChange,
+    ConfigField, ConfigHistory, ConfigValue, Contribution, Custody, DeadlineConsequence,
+    DelegatePermissions, Escrow, FeeTier, FeeTiers, GoalDeadline, GoalTemplate, GoalTemplateTerms,
+    Htlc, Leaderboard, LeaderboardEntry, LockTranche, MintStats, Outbox, Payroll,
+    PendingWithdrawal, PriceCondition, PriceDirection, RewardState, Rollover, SessionKey,
+    SubscriptionGrant, UsdTarget, Vault, VaultExtension, VaultStatus, VaultTemplate,
+    WithdrawalPolicy, ID as PROGRAM_ID,
+};
+use vault2::{
+    ADVANCE_SEED, AUTO_SAVE_SEED, BADGE_SEED, BLOCKED_SEED, CIRCLE_SEED, CONFIG_HISTORY_SEED,
+    CONFIG_SEED, CONTRIBUTION_SEED, CREATION_GATE_SEED, CREDENTIAL_GATE_SEED, CUSTODY_SEED,
+    DELEGATE_SEED, ESCROW_SEED, EXTENSION_SEED, FEE_TIERS_SEED, GUARDIANS_SEED, HOOK_SEED,
+    HTLC_SEED, LEADERBOARD_SEED, MINT_SEED, NOTES_SEED, OUTBOX_SEED, PAYROLL_SEED,
+    PRICE_CONDITION_SEED, PRICE_FEED_SEED, RECOVERY_SEED, REWARDS_SEED, ROLLOVER_SEED,
+    SELF_EXCLUSION_SEED, STATE_SEED, STATS_SEED, SUBSCRIPTION_SEED, SWAP_SEED, TEMPLATE_SEED,
+    UNLOCK_SCHEDULE_SEED, VAULT_SEED, VAULT_TEMPLATE_SEED, WITHDRAWAL_POLICY_SEED, WITHDRAWAL_SEED,
+};
+
+// The seed of the PDA the program signs its event self-CPIs with, set by Anchor
+const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+
+/// Returns the vault state PDA of `user`
+pub fn find_state_address(user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STATE_SEED, user.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the vault token account of a vault state: the vault authority's associated
+/// token account for `mint`
+pub fn find_vault_token_account(state: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(&find_vault_authority(state).0, mint)
+}
+
+/// Returns the vault authority PDA of a vault state
+pub fn find_vault_authority(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the activity outbox PDA of a vault state
+pub fn find_outbox_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OUTBOX_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the reward points PDA of a vault state
+pub fn find_rewards_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REWARDS_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the mint PDA of `user`'s milestone `badge`
+pub fn find_badge_mint_address(user: &Pubkey, badge: Badge) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BADGE_SEED, user.as_ref(), &[badge as u8]], &PROGRAM_ID)
+}
+
+/// Returns the contribution PDA recording what `depositor` gifted to a vault state
+pub fn find_contribution_address(state: &Pubkey, depositor: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[CONTRIBUTION_SEED, state.as_ref(), depositor.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the withdrawal policy PDA of a vault state
+pub fn find_withdrawal_policy_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WITHDRAWAL_POLICY_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the self-exclusion PDA of a vault state
+pub fn find_self_exclusion_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SELF_EXCLUSION_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the unlock stream PDA of a vault state
+pub fn find_unlock_schedule_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[UNLOCK_SCHEDULE_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the pending withdrawal PDA of a vault state
+pub fn find_pending_withdrawal_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WITHDRAWAL_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the guardians PDA of a vault state
+pub fn find_guardians_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GUARDIANS_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the custody PDA of a custodial vault state
+pub fn find_custody_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CUSTODY_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the rollover PDA of a vault state
+pub fn find_rollover_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ROLLOVER_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the advance PDA of a vault state
+pub fn find_advance_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ADVANCE_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the PDA of the admin's goal template `template_id`
+pub fn find_goal_template_address(template_id: u16) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TEMPLATE_SEED, &template_id.to_le_bytes()], &PROGRAM_ID)
+}
+
+/// Returns the PDA of the template terms a vault state was created with
+pub fn find_vault_template_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_TEMPLATE_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the recovery proposal PDA of a vault state
+pub fn find_recovery_proposal_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECOVERY_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the PDA of `creator`'s savings circle `circle_id`
+pub fn find_circle_address(creator: &Pubkey, circle_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[CIRCLE_SEED, creator.as_ref(), &circle_id.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the PDA of `depositor`'s escrow `escrow_id`
+pub fn find_escrow_address(depositor: &Pubkey, escrow_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ESCROW_SEED, depositor.as_ref(), &escrow_id.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the PDA of `owner`'s hashlock/timelock contract `htlc_id`
+pub fn find_htlc_address(owner: &Pubkey, htlc_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[HTLC_SEED, owner.as_ref(), &htlc_id.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the PDA of `employer`'s payroll for `employee`
+pub fn find_payroll_address(employer: &Pubkey, employee: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PAYROLL_SEED, employer.as_ref(), employee.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the vault creation gate PDA, which exists while creators must hold a membership token
+pub fn find_creation_gate_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CREATION_GATE_SEED], &PROGRAM_ID)
+}
+
+/// Returns the credential gate PDA, which exists while vault owners must hold a credential
+pub fn find_credential_gate_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CREDENTIAL_GATE_SEED], &PROGRAM_ID)
+}
+
+/// Returns the deposit fee tiers PDA
+pub fn find_fee_tiers_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_TIERS_SEED], &PROGRAM_ID)
+}
+
+/// Returns the aggregate stats PDA of a mint
+pub fn find_mint_stats_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STATS_SEED, mint.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the leaderboard PDA of a mint
+pub fn find_leaderboard_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LEADERBOARD_SEED, mint.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the zero-copy extension PDA of a vault state
+pub fn find_extension_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EXTENSION_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the session PDA of `delegate` on a vault state
+pub fn find_session_address(state: &Pubkey, delegate: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[DELEGATE_SEED, state.as_ref(), delegate.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the subscription PDA of `merchant` on a vault state
+pub fn find_subscription_address(state: &Pubkey, merchant: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SUBSCRIPTION_SEED, state.as_ref(), merchant.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the approval PDA of a swap program
+pub fn find_approved_swap_address(swap_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SWAP_SEED, swap_program.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the notes PDA of a vault state
+pub fn find_notes_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[NOTES_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the price condition PDA of a vault state
+pub fn find_price_condition_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PRICE_CONDITION_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the program-wide config PDA
+pub fn find_config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], &PROGRAM_ID)
+}
+
+/// Returns the PDA logging every config change
+pub fn find_config_history_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_HISTORY_SEED], &PROGRAM_ID)
+}
+
+/// Returns the admin approval PDA of a deposit hook program
+pub fn find_approved_hook_address(hook_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOOK_SEED, hook_program.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the admin approval PDA of a mint, checked while the mint allowlist is enabled
+pub fn find_approved_mint_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MINT_SEED, mint.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the PDA of the Pyth feed the admin expects for a mint's price
+pub fn find_price_feed_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PRICE_FEED_SEED, mint.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the admin blocklist entry PDA of an address, which exists while it is blocked
+pub fn find_blocked_address(address: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BLOCKED_SEED, address.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the auto-save authorization PDA of a vault state
+pub fn find_auto_save_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUTO_SAVE_SEED, state.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the PDA the program signs its event self-CPIs with
+pub fn find_event_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED], &PROGRAM_ID)
+}
+
+/// Builds an `initialize` instruction creating `user`'s vault for `mint` with a savings `target`,
+/// an optional `deadline` and an optional `usd_target` replacing the token target. A `ui_amount`
+/// such as "12.5" replaces `target` and is scaled by the mint's decimals on chain. `payer`
+/// pays for the accounts created, either `user` or a relayer sponsoring the transaction.
+/// `credential_token_account` must be the owner's token account of the credential mint while
+/// the admin requires one (see [`find_credential_gate_address`]), and `membership_token_account`
+/// the creator's token account of the membership mint while the admin gates vault creation
+/// (see [`find_creation_gate_address`]).
+#[allow(clippy::too_many_arguments)]
+pub fn initialize(
+    user: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    target: u64,
+    deadline: Option<GoalDeadline>,
+    usd_target: Option<UsdTarget>,
+    ui_amount: Option<String>,
+    credential_token_account: Option<&Pubkey>,
+    membership_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::Initialize {
+            user: *user,
+            payer: *payer,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            mint: *mint,
+            config: find_config_address().0,
+            approved_mint: find_approved_mint_address(mint).0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            creation_gate: find_creation_gate_address().0,
+            membership_token_account: membership_token_account.copied(),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::Initialize {
+            amount: target,
+            mint: *mint,
+            deadline,
+            usd_target,
+            ui_amount,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `initialize_idempotent` instruction. It takes the same accounts as [`initialize`],
+/// which now reuses an existing vault token account too, and is kept for older clients.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_idempotent(
+    user: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    target: u64,
+    deadline: Option<GoalDeadline>,
+    usd_target: Option<UsdTarget>,
+    ui_amount: Option<String>,
+    credential_token_account: Option<&Pubkey>,
+    membership_token_account: Option<&Pubkey>,
+) -> Instruction {
+    Instruction {
+        data: vault2::instruction::InitializeIdempotent {
+            amount: target,
+            mint: *mint,
+            deadline,
+            usd_target,
+            ui_amount: ui_amount.clone(),
+        }
+        .data(),
+        ..initialize(
+            user,
+            payer,
+            mint,
+            target,
+            deadline,
+            usd_target,
+            ui_amount,
+            credential_token_account,
+            membership_token_account,
+        )
+    }
+}
+
+/// Builds an `initialize_custodial` instruction in which `custodian` creates a vault of
+/// `mint` for `beneficiary`, who can't take anything out before `release_ts`. Both sign.
+/// `credential_token_account` is the beneficiary's and `membership_token_account` the
+/// custodian's, and both work as in [`initialize`], as does `ui_amount`.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_custodial(
+    custodian: &Pubkey,
+    beneficiary: &Pubkey,
+    mint: &Pubkey,
+    target: u64,
+    release_ts: i64,
+    ui_amount: Option<String>,
+    credential_token_account: Option<&Pubkey>,
+    membership_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(beneficiary);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::InitializeCustodial {
+            custodian: *custodian,
+            beneficiary: *beneficiary,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            mint: *mint,
+            config: find_config_address().0,
+            approved_mint: find_approved_mint_address(mint).0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            creation_gate: find_creation_gate_address().0,
+            membership_token_account: membership_token_account.copied(),
+            custody: find_custody_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::InitializeCustodial {
+            amount: target,
+            mint: *mint,
+            release_ts,
+            ui_amount,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `initialize_from_template` instruction creating `user`'s vault from the admin's
+/// goal template `template_id`. `mint` must be the template's mint, and a `ui_amount` replaces
+/// the template's target. `payer`, `credential_token_account` and `membership_token_account`
+/// work as in [`initialize`].
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_from_template(
+    user: &Pubkey,
+    payer: &Pubkey,
+    template_id: u16,
+    mint: &Pubkey,
+    ui_amount: Option<String>,
+    credential_token_account: Option<&Pubkey>,
+    membership_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::InitializeFromTemplate {
+            user: *user,
+            payer: *payer,
+            template: find_goal_template_address(template_id).0,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            mint: *mint,
+            config: find_config_address().0,
+            approved_mint: find_approved_mint_address(mint).0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            creation_gate: find_creation_gate_address().0,
+            membership_token_account: membership_token_account.copied(),
+            vault_template: find_vault_template_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::InitializeFromTemplate {
+            template_id,
+            ui_amount,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `deposit` instruction moving `amount` of `mint` from `user_token_account` into
+/// `user`'s vault.
+///
+/// `deposit_hook` must be the hook program set on the vault, if any. Accounts the hook
+/// itself needs are appended to `accounts` by the caller. `price_update` must be the
+/// vault's Pyth price update account when the vault has a USD target. `fee` lists the
+/// accounts needed while the admin charges a deposit fee.
+pub fn deposit(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    price_update: Option<&Pubkey>,
+    fee: FeeAccounts,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: deposit_accounts(
+            user,
+            user_token_account,
+            mint,
+            deposit_hook,
+            price_update,
+            fee,
+        )
+        .to_account_metas(None),
+        data: vault2::instruction::Deposit { amount }.data(),
+    }
+}
+
+/// Builds a `deposit_all` instruction sweeping the whole balance of `user_token_account`
+/// into `user`'s vault. `deposit_hook`, `price_update` and `fee` work as in [`deposit`].
+pub fn deposit_all(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    price_update: Option<&Pubkey>,
+    fee: FeeAccounts,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: deposit_accounts(
+            user,
+            user_token_account,
+            mint,
+            deposit_hook,
+            price_update,
+            fee,
+        )
+        .to_account_metas(None),
+        data: vault2::instruction::DepositAll {}.data(),
+    }
+}
+
+/// Builds a `deposit_sol` instruction wrapping `lamports` from `user`'s wallet into their
+/// wSOL associated token account and depositing them into `user`'s wrapped SOL vault. The
+/// wSOL account must exist, e.g. created idempotently earlier in the same transaction.
+/// `deposit_hook`, `price_update` and `fee` work as in [`deposit`].
+pub fn deposit_sol(
+    user: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    price_update: Option<&Pubkey>,
+    fee: FeeAccounts,
+    lamports: u64,
+) -> Instruction {
+    let mint = native_mint::ID;
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::Deposit {
+            system_program: Some(system_program::ID),
+            ..deposit_accounts(
+                user,
+                &get_associated_token_address(user, &mint),
+                &mint,
+                deposit_hook,
+                price_update,
+                fee,
+            )
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::DepositSol { lamports }.data(),
+    }
+}
+
+/// Builds a `deposit_with_memo` instruction, a [`deposit`] that also emits `memo` and stores
+/// its hash on the vault extension, which the owner must have created
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_with_memo(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    price_update: Option<&Pubkey>,
+    fee: FeeAccounts,
+    amount: u64,
+    memo: String,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::Deposit {
+            extension: Some(find_extension_address(&state).0),
+            ..deposit_accounts(
+                user,
+                user_token_account,
+                mint,
+                deposit_hook,
+                price_update,
+                fee,
+            )
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::DepositWithMemo { amount, memo }.data(),
+    }
+}
+
+// `deposit`, `deposit_all`, `deposit_sol` and `deposit_with_memo` share the same accounts
+fn deposit_accounts(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    price_update: Option<&Pubkey>,
+    fee: FeeAccounts,
+) -> vault2::accounts::Deposit {
+    let (state, _) = find_state_address(user);
+
+    vault2::accounts::Deposit {
+        user: *user,
+        user_token_account: *user_token_account,
+        vault_token_account: find_vault_token_account(&state, mint),
+        state,
+        vault_authority: find_vault_authority(&state).0,
+        outbox: find_outbox_address(&state).0,
+        rewards: find_rewards_address(&state).0,
+        mint_stats: find_mint_stats_address(mint).0,
+        config: find_config_address().0,
+        blocked_user: find_blocked_address(user).0,
+        treasury_token_account: fee.treasury_token_account(mint),
+        fee_tiers: find_fee_tiers_address().0,
+        vault_template: find_vault_template_address(&state).0,
+        membership_token_account: fee.membership_token_account,
+        mint: *mint,
+        deposit_hook_program: deposit_hook.copied(),
+        approved_hook: deposit_hook.map(|hook| find_approved_hook_address(hook).0),
+        price_update: price_update.copied(),
+        price_feed: find_price_feed_address(mint).0,
+        token_program: anchor_spl::token::ID,
+        system_program: None,
+        extension: None,
+        custody: find_custody_address(&state).0,
+        rollover: find_rollover_address(&state).0,
+        advance: find_advance_address(&state).0,
+        event_authority: find_event_authority().0,
+        program: PROGRAM_ID,
+    }
+}
+
+/// Accounts deposits need while the admin charges a deposit fee. The default (no fee
+/// accounts) works while there is no fee.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeeAccounts {
+    /// The config treasury, whose associated token account receives the fee
+    pub treasury: Option<Pubkey>,
+    /// The depositor's token account of the membership mint, for a fee tier discount
+    pub membership_token_account: Option<Pubkey>,
+}
+
+impl FeeAccounts {
+    /// Returns the treasury's associated token account for `mint`, if a treasury is set
+    pub fn treasury_token_account(&self, mint: &Pubkey) -> Option<Pubkey> {
+        self.treasury
+            .map(|treasury| get_associated_token_address(&treasury, mint))
+    }
+}
+
+/// Builds a `deposit_and_swap` instruction paying `amount_in` of `input_mint` from `user`'s
+/// associated token account into their vault of `mint`, through `swap`: a swap instruction
+/// (e.g. a Jupiter route) from that token account to the vault token account, signed by
+/// `user`. Fails unless at least `min_amount_out` reaches the vault. `deposit_hook`,
+/// `price_update` and `fee` work as in [`deposit`], and `payer`, who pays for the user's token
+/// account of `mint` if needed, as in [`initialize`]. The swap's accounts take the remaining
+/// accounts, so the accounts the hook needs are passed as `hook_accounts`.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_and_swap(
+    user: &Pubkey,
+    payer: &Pubkey,
+    input_mint: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    hook_accounts: Vec<AccountMeta>,
+    price_update: Option<&Pubkey>,
+    fee: FeeAccounts,
+    amount_in: u64,
+    min_amount_out: u64,
+    swap: Instruction,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    let mut accounts = vault2::accounts::DepositAndSwap {
+        user: *user,
+        payer: *payer,
+        input_mint: *input_mint,
+        user_input_token_account: get_associated_token_address(user, input_mint),
+        mint: *mint,
+        user_token_account: get_associated_token_address(user, mint),
+        vault_token_account: find_vault_token_account(&state, mint),
+        state,
+        vault_authority: find_vault_authority(&state).0,
+        outbox: find_outbox_address(&state).0,
+        rewards: find_rewards_address(&state).0,
+        mint_stats: find_mint_stats_address(mint).0,
+        config: find_config_address().0,
+        blocked_user: find_blocked_address(user).0,
+        treasury_token_account: fee.treasury_token_account(mint),
+        fee_tiers: find_fee_tiers_address().0,
+        vault_template: find_vault_template_address(&state).0,
+        membership_token_account: fee.membership_token_account,
+        swap_program: swap.program_id,
+        approved_swap: find_approved_swap_address(&swap.program_id).0,
+        deposit_hook_program: deposit_hook.copied(),
+        approved_hook: deposit_hook.map(|hook| find_approved_hook_address(hook).0),
+        price_update: price_update.copied(),
+        price_feed: find_price_feed_address(mint).0,
+        token_program: anchor_spl::token::ID,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+        custody: find_custody_address(&state).0,
+        rollover: find_rollover_address(&state).0,
+        advance: find_advance_address(&state).0,
+        event_authority: find_event_authority().0,
+        program: PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    // The swap's own accounts follow, forwarded as-is, then the hook's
+    accounts.extend(swap.accounts);
+    let hook_account_count = hook_accounts.len() as u8;
+    accounts.extend(hook_accounts);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: vault2::instruction::DepositAndSwap {
+            amount_in,
+            min_amount_out,
+            swap_data: swap.data,
+            hook_account_count,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `deposit_for` instruction in which `depositor` gifts `amount` of `mint` from
+/// their `depositor_token_account` into `owner`'s vault. `deposit_hook` and `fee` work as in
+/// [`deposit`], with the depositor paying the fee.
+pub fn deposit_for(
+    depositor: &Pubkey,
+    owner: &Pubkey,
+    depositor_token_account: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    fee: FeeAccounts,
+    amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::DepositFor {
+            depositor: *depositor,
+            owner: *owner,
+            depositor_token_account: *depositor_token_account,
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            config: find_config_address().0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_depositor: find_blocked_address(depositor).0,
+            treasury_token_account: fee.treasury_token_account(mint),
+            fee_tiers: find_fee_tiers_address().0,
+            vault_template: find_vault_template_address(&state).0,
+            membership_token_account: fee.membership_token_account,
+            mint: *mint,
+            deposit_hook_program: deposit_hook.copied(),
+            approved_hook: deposit_hook.map(|hook| find_approved_hook_address(hook).0),
+            contribution: find_contribution_address(&state, depositor).0,
+            token_program: anchor_spl::token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::DepositFor { amount }.data(),
+    }
+}
+
+/// Builds a `deposit_via_delegate` instruction in which `caller` pulls `amount` of `mint`
+/// from `owner`'s `user_token_account` into their vault. `owner` must have authorized
+/// `caller` with [`set_auto_save`] and approved the vault authority (see
+/// [`find_vault_authority`]) as delegate on that token account, for the amount plus any
+/// deposit fee. `deposit_hook`, `price_update` and `fee` work as in [`deposit`].
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_via_delegate(
+    caller: &Pubkey,
+    owner: &Pubkey,
+    user_token_account: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    price_update: Option<&Pubkey>,
+    fee: FeeAccounts,
+    amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::DepositViaDelegate {
+            caller: *caller,
+            owner: *owner,
+            user_token_account: *user_token_account,
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            auto_save: find_auto_save_address(&state).0,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            config: find_config_address().0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_caller: find_blocked_address(caller).0,
+            treasury_token_account: fee.treasury_token_account(mint),
+            fee_tiers: find_fee_tiers_address().0,
+            vault_template: find_vault_template_address(&state).0,
+            membership_token_account: fee.membership_token_account,
+            mint: *mint,
+            deposit_hook_program: deposit_hook.copied(),
+            approved_hook: deposit_hook.map(|hook| find_approved_hook_address(hook).0),
+            price_update: price_update.copied(),
+            price_feed: find_price_feed_address(mint).0,
+            token_program: anchor_spl::token::ID,
+            custody: find_custody_address(&state).0,
+            rollover: find_rollover_address(&state).0,
+            advance: find_advance_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::DepositViaDelegate { amount }.data(),
+    }
+}
+
+/// Builds a `set_auto_save` instruction letting `service` pull up to `max_amount` into
+/// `user`'s vault with [`deposit_via_delegate`], at most once every `interval_seconds`
+pub fn set_auto_save(
+    user: &Pubkey,
+    service: &Pubkey,
+    max_amount: u64,
+    interval_seconds: i64,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetAutoSave {
+            user: *user,
+            state,
+            auto_save: find_auto_save_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetAutoSave {
+            service: *service,
+            max_amount,
+            interval_seconds,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `clear_auto_save` instruction revoking the auto-save service of `user`'s vault
+pub fn clear_auto_save(user: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClearAutoSave {
+            user: *user,
+            state,
+            auto_save: find_auto_save_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClearAutoSave {}.data(),
+    }
+}
+
+/// Builds an `init_mint_stats` instruction creating the stats of `mint`, for mints whose
+/// vaults all predate them
+pub fn init_mint_stats(payer: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::InitMintStats {
+            payer: *payer,
+            mint: *mint,
+            mint_stats: find_mint_stats_address(mint).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::InitMintStats {}.data(),
+    }
+}
+
+/// Builds a `migrate_vault` instruction growing `owner`'s version 1 vault to the current
+/// layout, with `payer` topping up the rent. `mint` must be the vault's mint
+pub fn migrate_vault(payer: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::MigrateVault {
+            payer: *payer,
+            owner: *owner,
+            state: find_state_address(owner).0,
+            mint: *mint,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::MigrateVault {}.data(),
+    }
+}
+
+/// Builds an `init_vault_extension` instruction creating `user`'s vault extension account
+pub fn init_vault_extension(user: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::InitVaultExtension {
+            user: *user,
+            state,
+            extension: find_extension_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::InitVaultExtension {}.data(),
+    }
+}
+
+/// Builds a `record_balance_snapshot` instruction recording the balance of `owner`'s vault of
+/// `mint` in its extension account
+pub fn record_balance_snapshot(owner: &Pubkey, mint: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::RecordBalanceSnapshot {
+            owner: *owner,
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            extension: find_extension_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::RecordBalanceSnapshot {}.data(),
+    }
+}
+
+/// Builds a `grant_delegate` instruction letting `delegate` act on `user`'s vault with
+/// `permissions` until `expiry`, withdrawing at most `max_amount`
+pub fn grant_delegate(
+    user: &Pubkey,
+    delegate: &Pubkey,
+    permissions: DelegatePermissions,
+    expiry: i64,
+    max_amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::GrantDelegate {
+            user: *user,
+            state,
+            session: find_session_address(&state, delegate).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::GrantDelegate {
+            delegate: *delegate,
+            permissions,
+            expiry,
+            max_amount,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `revoke_delegate` instruction ending `delegate`'s session on `user`'s vault
+pub fn revoke_delegate(user: &Pubkey, delegate: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::RevokeDelegate {
+            user: *user,
+            state,
+            session: find_session_address(&state, delegate).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::RevokeDelegate {}.data(),
+    }
+}
+
+/// Builds a `session_deposit` instruction moving `amount` of `mint` from the session key
+/// `delegate`'s associated token account into `owner`'s vault. `deposit_hook` and `fee` work
+/// as in [`deposit`].
+pub fn session_deposit(
+    delegate: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    deposit_hook: Option<&Pubkey>,
+    fee: FeeAccounts,
+    amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SessionDeposit {
+            delegate: *delegate,
+            owner: *owner,
+            delegate_token_account: get_associated_token_address(delegate, mint),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            session: find_session_address(&state, delegate).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            config: find_config_address().0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_delegate: find_blocked_address(delegate).0,
+            treasury_token_account: fee.treasury_token_account(mint),
+            fee_tiers: find_fee_tiers_address().0,
+            vault_template: find_vault_template_address(&state).0,
+            membership_token_account: fee.membership_token_account,
+            mint: *mint,
+            deposit_hook_program: deposit_hook.copied(),
+            approved_hook: deposit_hook.map(|hook| find_approved_hook_address(hook).0),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SessionDeposit { amount }.data(),
+    }
+}
+
+/// Builds a `session_withdraw` instruction moving `amount` of `mint` from `owner`'s vault
+/// to the owner's associated token account, signed by the session key `delegate`.
+/// `credential_token_account` is the owner's, and works as in [`initialize`].
+pub fn session_withdraw(
+    delegate: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    credential_token_account: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SessionWithdraw {
+            delegate: *delegate,
+            owner: *owner,
+            mint: *mint,
+            owner_token_account: get_associated_token_address(owner, mint),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            session: find_session_address(&state, delegate).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            advance: find_advance_address(&state).0,
+            config: find_config_address().0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_delegate: find_blocked_address(delegate).0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SessionWithdraw { amount }.data(),
+    }
+}
+
+/// Builds a `grant_subscription` instruction letting `merchant` pull `amount` from `user`'s
+/// vault at most once every `interval_seconds`
+pub fn grant_subscription(
+    user: &Pubkey,
+    merchant: &Pubkey,
+    amount: u64,
+    interval_seconds: i64,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::GrantSubscription {
+            user: *user,
+            state,
+            subscription: find_subscription_address(&state, merchant).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::GrantSubscription {
+            merchant: *merchant,
+            amount,
+            interval_seconds,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `revoke_subscription` instruction ending `merchant`'s subscription on `user`'s
+/// vault
+pub fn revoke_subscription(user: &Pubkey, merchant: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::RevokeSubscription {
+            user: *user,
+            state,
+            subscription: find_subscription_address(&state, merchant).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::RevokeSubscription {}.data(),
+    }
+}
+
+/// Builds a `pull_subscription` instruction in which `merchant` pulls its subscription amount
+/// of `mint` from `owner`'s vault into `merchant_token_account`. `credential_token_account`
+/// is the owner's, and works as in [`initialize`].
+pub fn pull_subscription(
+    merchant: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    merchant_token_account: &Pubkey,
+    credential_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::PullSubscription {
+            merchant: *merchant,
+            owner: *owner,
+            mint: *mint,
+            merchant_token_account: *merchant_token_account,
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            subscription: find_subscription_address(&state, merchant).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            advance: find_advance_address(&state).0,
+            config: find_config_address().0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_merchant: find_blocked_address(merchant).0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::PullSubscription {}.data(),
+    }
+}
+
+/// Builds a `withdraw` instruction moving `amount` of `mint` from `user`'s vault to the
+/// user's associated token account, which the program creates if needed, paid for by `payer`.
+/// Pass the vault key when the amount is above the vault key threshold. `payer` and
+/// `credential_token_account` work as in [`initialize`].
+pub fn withdraw(
+    user: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    credential_token_account: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: withdraw_accounts(user, payer, mint, vault_key, credential_token_account)
+            .to_account_metas(None),
+        data: vault2::instruction::Withdraw { amount }.data(),
+    }
+}
+
+/// Builds a `withdraw_to` instruction moving `amount` of `mint` from `user`'s vault to
+/// `destination_token_account`, any token account of the vault's mint, owned by
+/// `destination_owner`. `vault_key` works as in [`withdraw`], and `credential_token_account`
+/// as in [`initialize`].
+pub fn withdraw_to(
+    user: &Pubkey,
+    mint: &Pubkey,
+    destination_token_account: &Pubkey,
+    destination_owner: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    credential_token_account: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::WithdrawTo {
+            user: *user,
+            vault_key: vault_key.copied(),
+            mint: *mint,
+            destination_token_account: *destination_token_account,
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            advance: find_advance_address(&state).0,
+            config: find_config_address().0,
+            blocked_user: find_blocked_address(user).0,
+            blocked_destination: find_blocked_address(destination_owner).0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::WithdrawTo { amount }.data(),
+    }
+}
+
+/// Builds a `borrow` instruction paying `user` an advance of `amount` out of the locked balance
+/// of their vault, into their associated token account for `mint`. `vault_key` and `payer` work
+/// as in [`withdraw`], and `credential_token_account` as in [`initialize`].
+pub fn borrow(
+    user: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    credential_token_account: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::Borrow {
+            user: *user,
+            payer: *payer,
+            vault_key: vault_key.copied(),
+            mint: *mint,
+            user_token_account: get_associated_token_address(user, mint),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            advance: find_advance_address(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            config: find_config_address().0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            custody: find_custody_address(&state).0,
+            blocked_user: find_blocked_address(user).0,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::Borrow { amount }.data(),
+    }
+}
+
+/// Builds a `repay` instruction moving up to `amount` of `mint` from `user_token_account` back
+/// into `user`'s vault against their advance. Passing `u64::MAX` repays the whole debt. The
+/// advance fee goes to the associated token account of the config `treasury`, required while
+/// one is set.
+pub fn repay(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    mint: &Pubkey,
+    treasury: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::Repay {
+            user: *user,
+            mint: *mint,
+            user_token_account: *user_token_account,
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            advance: find_advance_address(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            config: find_config_address().0,
+            treasury_token_account: treasury
+                .map(|treasury| get_associated_token_address(treasury, mint)),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::Repay { amount }.data(),
+    }
+}
+
+/// Builds a `withdraw_sol` instruction moving `amount` lamports of wrapped SOL from `user`'s
+/// vault to the user's wSOL associated token account and closing it, so the user gets the
+/// account's whole balance back as plain SOL. `vault_key` and `payer` work as in [`withdraw`].
+pub fn withdraw_sol(
+    user: &Pubkey,
+    payer: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    credential_token_account: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: withdraw_accounts(
+            user,
+            payer,
+            &native_mint::ID,
+            vault_key,
+            credential_token_account,
+        )
+        .to_account_metas(None),
+        data: vault2::instruction::WithdrawSol { amount }.data(),
+    }
+}
+
+/// Builds a `withdraw_with_memo` instruction, a [`withdraw`] that also emits `memo` and
+/// stores its hash on the vault extension, which the owner must have created
+pub fn withdraw_with_memo(
+    user: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    credential_token_account: Option<&Pubkey>,
+    amount: u64,
+    memo: String,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::Withdraw {
+            extension: Some(find_extension_address(&state).0),
+            ..withdraw_accounts(user, payer, mint, vault_key, credential_token_account)
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::WithdrawWithMemo { amount, memo }.data(),
+    }
+}
+
+// `withdraw`, `withdraw_sol` and `withdraw_with_memo` share the same accounts
+fn withdraw_accounts(
+    user: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    credential_token_account: Option<&Pubkey>,
+) -> vault2::accounts::Withdraw {
+    let (state, _) = find_state_address(user);
+
+    vault2::accounts::Withdraw {
+        user: *user,
+        payer: *payer,
+        vault_key: vault_key.copied(),
+        mint: *mint,
+        user_token_account: get_associated_token_address(user, mint),
+        vault_token_account: find_vault_token_account(&state, mint),
+        state,
+        vault_authority: find_vault_authority(&state).0,
+        outbox: find_outbox_address(&state).0,
+        rewards: find_rewards_address(&state).0,
+        mint_stats: find_mint_stats_address(mint).0,
+        withdrawal_policy: find_withdrawal_policy_address(&state).0,
+        self_exclusion: find_self_exclusion_address(&state).0,
+        unlock_schedule: find_unlock_schedule_address(&state).0,
+        advance: find_advance_address(&state).0,
+        config: find_config_address().0,
+        blocked_user: find_blocked_address(user).0,
+        credential_gate: find_credential_gate_address().0,
+        credential_token_account: credential_token_account.copied(),
+        token_program: anchor_spl::token::ID,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+        extension: None,
+        event_authority: find_event_authority().0,
+        program: PROGRAM_ID,
+    }
+}
+
+/// Builds a `set_withdrawal_delay` instruction making `user`'s withdrawals wait
+/// `delay_seconds` between `request_withdrawal` and `execute_withdrawal`
+pub fn set_withdrawal_delay(user: &Pubkey, delay_seconds: i64) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetWithdrawalDelay {
+            user: *user,
+            state,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetWithdrawalDelay { delay_seconds }.data(),
+    }
+}
+
+/// Builds a `set_unlock_stream` instruction making `user`'s current vault balance of `mint`
+/// withdrawable gradually, second by second, between `start_ts` and `end_ts`. With a
+/// `cliff_ts`, nothing is withdrawable before it.
+pub fn set_unlock_stream(
+    user: &Pubkey,
+    mint: &Pubkey,
+    start_ts: i64,
+    end_ts: i64,
+    cliff_ts: Option<i64>,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetUnlockStream {
+            user: *user,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetUnlockStream {
+            start_ts,
+            end_ts,
+            cliff_ts,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `self_freeze` instruction refusing every withdrawal from `user`'s vault for the
+/// next `duration_seconds`. The freeze can't be lifted early, only extended.
+pub fn self_freeze(user: &Pubkey, duration_seconds: i64) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SelfFreeze {
+            user: *user,
+            state,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SelfFreeze { duration_seconds }.data(),
+    }
+}
+
+/// Builds a `set_withdrawal_approvers` instruction requiring `threshold` of `approvers` to
+/// approve each of `user`'s withdrawal requests. Once approvers are set, changing them needs
+/// `threshold` of the current ones as `cosigners`.
+pub fn set_withdrawal_approvers(
+    user: &Pubkey,
+    approvers: Vec<Pubkey>,
+    threshold: u8,
+    cosigners: &[Pubkey],
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    let mut accounts = vault2::accounts::SetWithdrawalApprovers {
+        user: *user,
+        state,
+        withdrawal_policy: find_withdrawal_policy_address(&state).0,
+        system_program: system_program::ID,
+        event_authority: find_event_authority().0,
+        program: PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        cosigners
+            .iter()
+            .map(|cosigner| AccountMeta::new_readonly(*cosigner, true)),
+    );
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: vault2::instruction::SetWithdrawalApprovers {
+            approvers,
+            threshold,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `approve_withdrawal` instruction approving `owner`'s pending withdrawal
+pub fn approve_withdrawal(approver: &Pubkey, owner: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ApproveWithdrawal {
+            approver: *approver,
+            owner: *owner,
+            state,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            pending_withdrawal: find_pending_withdrawal_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ApproveWithdrawal {}.data(),
+    }
+}
+
+/// Builds a `set_guardians` instruction letting `threshold` of `guardians` recover `user`'s vault.
+/// Needs the vault key, when the vault has one, and `threshold` of the withdrawal approvers as
+/// `cosigners`, once approvers are set.
+pub fn set_guardians(
+    user: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    guardians: Vec<Pubkey>,
+    threshold: u8,
+    cosigners: &[Pubkey],
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    let mut accounts = vault2::accounts::SetGuardians {
+        user: *user,
+        vault_key: vault_key.copied(),
+        state,
+        guardians: find_guardians_address(&state).0,
+        withdrawal_policy: find_withdrawal_policy_address(&state).0,
+        system_program: system_program::ID,
+        self_exclusion: find_self_exclusion_address(&state).0,
+        event_authority: find_event_authority().0,
+        program: PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        cosigners
+            .iter()
+            .map(|cosigner| AccountMeta::new_readonly(*cosigner, true)),
+    );
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: vault2::instruction::SetGuardians {
+            guardians,
+            threshold,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `propose_recovery` instruction from `guardian`, proposing to recover `owner`'s vault
+/// to `new_owner`
+pub fn propose_recovery(guardian: &Pubkey, owner: &Pubkey, new_owner: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ProposeRecovery {
+            guardian: *guardian,
+            owner: *owner,
+            state,
+            guardians: find_guardians_address(&state).0,
+            recovery_proposal: find_recovery_proposal_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ProposeRecovery {
+            new_owner: *new_owner,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `vote_recovery` instruction from `guardian` for the recovery of `owner`'s vault
+pub fn vote_recovery(guardian: &Pubkey, owner: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::VoteRecovery {
+            guardian: *guardian,
+            owner: *owner,
+            state,
+            guardians: find_guardians_address(&state).0,
+            recovery_proposal: find_recovery_proposal_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::VoteRecovery {}.data(),
+    }
+}
+
+/// Builds a `veto_recovery` instruction for `user`, dropping the recovery `proposer` proposed
+pub fn veto_recovery(user: &Pubkey, proposer: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::VetoRecovery {
+            user: *user,
+            state,
+            proposer: *proposer,
+            recovery_proposal: find_recovery_proposal_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::VetoRecovery {}.data(),
+    }
+}
+
+/// Builds an `execute_recovery` instruction moving `owner`'s vault of `mint` to `new_owner`,
+/// once the recovery `proposer` proposed has enough votes and its veto period has passed
+pub fn execute_recovery(
+    caller: &Pubkey,
+    owner: &Pubkey,
+    new_owner: &Pubkey,
+    mint: &Pubkey,
+    proposer: &Pubkey,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ExecuteRecovery {
+            caller: *caller,
+            owner: *owner,
+            new_owner: *new_owner,
+            mint: *mint,
+            new_owner_token_account: get_associated_token_address(new_owner, mint),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            guardians: find_guardians_address(&state).0,
+            proposer: *proposer,
+            recovery_proposal: find_recovery_proposal_address(&state).0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_new_owner: find_blocked_address(new_owner).0,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            custody: find_custody_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ExecuteRecovery {}.data(),
+    }
+}
+
+/// Builds a `create_circle` instruction creating `creator`'s savings circle `circle_id`, in
+/// which `members` (in payout order) each pay `contribution` of `mint` every `period_seconds`
+pub fn create_circle(
+    creator: &Pubkey,
+    circle_id: u64,
+    mint: &Pubkey,
+    members: Vec<Pubkey>,
+    contribution: u64,
+    period_seconds: i64,
+) -> Instruction {
+    let (circle, _) = find_circle_address(creator, circle_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CreateCircle {
+            creator: *creator,
+            circle,
+            mint: *mint,
+            circle_token_account: get_associated_token_address(&circle, mint),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CreateCircle {
+            circle_id,
+            members,
+            contribution,
+            period_seconds,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `contribute_to_circle` instruction paying `member`'s contribution to the current
+/// round of `creator`'s circle `circle_id` from `member_token_account`
+pub fn contribute_to_circle(
+    member: &Pubkey,
+    creator: &Pubkey,
+    circle_id: u64,
+    mint: &Pubkey,
+    member_token_account: &Pubkey,
+) -> Instruction {
+    let (circle, _) = find_circle_address(creator, circle_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ContributeToCircle {
+            member: *member,
+            circle,
+            mint: *mint,
+            member_token_account: *member_token_account,
+            circle_token_account: get_associated_token_address(&circle, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ContributeToCircle {}.data(),
+    }
+}
+
+/// Builds a `payout_circle` instruction from `caller`, closing the current round of `creator`'s
+/// circle `circle_id` and paying its pot to `recipient`, the member whose turn it is
+pub fn payout_circle(
+    caller: &Pubkey,
+    creator: &Pubkey,
+    circle_id: u64,
+    mint: &Pubkey,
+    recipient: &Pubkey,
+) -> Instruction {
+    let (circle, _) = find_circle_address(creator, circle_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::PayoutCircle {
+            caller: *caller,
+            circle,
+            recipient: *recipient,
+            mint: *mint,
+            recipient_token_account: get_associated_token_address(recipient, mint),
+            circle_token_account: get_associated_token_address(&circle, mint),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::PayoutCircle {}.data(),
+    }
+}
+
+/// Builds a `claim_circle_refund` instruction paying `member`'s refunded contributions from
+/// `creator`'s circle `circle_id` to `member_token_account`
+pub fn claim_circle_refund(
+    member: &Pubkey,
+    creator: &Pubkey,
+    circle_id: u64,
+    mint: &Pubkey,
+    member_token_account: &Pubkey,
+) -> Instruction {
+    let (circle, _) = find_circle_address(creator, circle_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClaimCircleRefund {
+            member: *member,
+            circle,
+            mint: *mint,
+            member_token_account: *member_token_account,
+            circle_token_account: get_associated_token_address(&circle, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClaimCircleRefund {}.data(),
+    }
+}
+
+/// Builds a `close_circle` instruction closing `creator`'s settled circle `circle_id` and
+/// refunding its rent
+pub fn close_circle(creator: &Pubkey, circle_id: u64, mint: &Pubkey) -> Instruction {
+    let (circle, _) = find_circle_address(creator, circle_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CloseCircle {
+            creator: *creator,
+            circle,
+            circle_token_account: get_associated_token_address(&circle, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CloseCircle {}.data(),
+    }
+}
+
+/// Builds a `create_payroll` instruction in which `employer` sets up a payroll paying
+/// `employee` `amount_per_period` of `mint` per `period_seconds`
+pub fn create_payroll(
+    employer: &Pubkey,
+    employee: &Pubkey,
+    mint: &Pubkey,
+    amount_per_period: u64,
+    period_seconds: i64,
+) -> Instruction {
+    let (payroll, _) = find_payroll_address(employer, employee);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CreatePayroll {
+            employer: *employer,
+            employee: *employee,
+            payroll,
+            mint: *mint,
+            payroll_token_account: get_associated_token_address(&payroll, mint),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CreatePayroll {
+            amount_per_period,
+            period_seconds,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `fund_payroll` instruction moving `amount` from `employer_token_account` into
+/// `employer`'s payroll for `employee`
+pub fn fund_payroll(
+    employer: &Pubkey,
+    employee: &Pubkey,
+    mint: &Pubkey,
+    employer_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (payroll, _) = find_payroll_address(employer, employee);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::FundPayroll {
+            employer: *employer,
+            payroll,
+            mint: *mint,
+            employer_token_account: *employer_token_account,
+            payroll_token_account: get_associated_token_address(&payroll, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::FundPayroll { amount }.data(),
+    }
+}
+
+/// Builds a `claim_payroll` instruction paying `employee`'s accrued pay from `employer`'s
+/// payroll to `employee_token_account`
+pub fn claim_payroll(
+    employee: &Pubkey,
+    employer: &Pubkey,
+    mint: &Pubkey,
+    employee_token_account: &Pubkey,
+) -> Instruction {
+    let (payroll, _) = find_payroll_address(employer, employee);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClaimPayroll {
+            employee: *employee,
+            payroll,
+            mint: *mint,
+            employee_token_account: *employee_token_account,
+            payroll_token_account: get_associated_token_address(&payroll, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClaimPayroll {}.data(),
+    }
+}
+
+/// Builds a `stop_payroll` instruction ending the accrual of `employer`'s payroll for
+/// `employee` and returning the unearned funding to `employer_token_account`
+pub fn stop_payroll(
+    employer: &Pubkey,
+    employee: &Pubkey,
+    mint: &Pubkey,
+    employer_token_account: &Pubkey,
+) -> Instruction {
+    let (payroll, _) = find_payroll_address(employer, employee);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::StopPayroll {
+            employer: *employer,
+            payroll,
+            mint: *mint,
+            employer_token_account: *employer_token_account,
+            payroll_token_account: get_associated_token_address(&payroll, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::StopPayroll {}.data(),
+    }
+}
+
+/// Builds a `close_payroll` instruction closing `employer`'s settled payroll for `employee`
+/// and refunding its rent
+pub fn close_payroll(employer: &Pubkey, employee: &Pubkey, mint: &Pubkey) -> Instruction {
+    let (payroll, _) = find_payroll_address(employer, employee);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClosePayroll {
+            employer: *employer,
+            payroll,
+            payroll_token_account: get_associated_token_address(&payroll, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClosePayroll {}.data(),
+    }
+}
+
+/// Builds a `create_escrow` instruction in which `depositor` locks up `amount` of `mint` from
+/// `depositor_token_account` in escrow `escrow_id` for `counterparty`, refundable after
+/// `timeout_ts`
+#[allow(clippy::too_many_arguments)]
+pub fn create_escrow(
+    depositor: &Pubkey,
+    escrow_id: u64,
+    mint: &Pubkey,
+    depositor_token_account: &Pubkey,
+    counterparty: &Pubkey,
+    arbiter: Option<&Pubkey>,
+    amount: u64,
+    timeout_ts: i64,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(depositor, escrow_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CreateEscrow {
+            depositor: *depositor,
+            escrow,
+            mint: *mint,
+            depositor_token_account: *depositor_token_account,
+            escrow_token_account: get_associated_token_address(&escrow, mint),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CreateEscrow {
+            escrow_id,
+            counterparty: *counterparty,
+            arbiter: arbiter.copied(),
+            amount,
+            timeout_ts,
+        }
+        .data(),
+    }
+}
+
+/// Builds an `approve_escrow` instruction recording `approver`'s approval of `depositor`'s
+/// escrow `escrow_id`
+pub fn approve_escrow(approver: &Pubkey, depositor: &Pubkey, escrow_id: u64) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ApproveEscrow {
+            approver: *approver,
+            escrow: find_escrow_address(depositor, escrow_id).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ApproveEscrow {}.data(),
+    }
+}
+
+/// Builds a `release_escrow` instruction from `caller`, paying `depositor`'s approved escrow
+/// `escrow_id` to `counterparty`
+pub fn release_escrow(
+    caller: &Pubkey,
+    depositor: &Pubkey,
+    escrow_id: u64,
+    mint: &Pubkey,
+    counterparty: &Pubkey,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(depositor, escrow_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ReleaseEscrow {
+            caller: *caller,
+            escrow,
+            depositor: *depositor,
+            counterparty: *counterparty,
+            mint: *mint,
+            counterparty_token_account: get_associated_token_address(counterparty, mint),
+            escrow_token_account: get_associated_token_address(&escrow, mint),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ReleaseEscrow {}.data(),
+    }
+}
+
+/// Builds a `refund_escrow` instruction returning `depositor`'s expired escrow `escrow_id` to
+/// `depositor_token_account`
+pub fn refund_escrow(
+    depositor: &Pubkey,
+    escrow_id: u64,
+    mint: &Pubkey,
+    depositor_token_account: &Pubkey,
+) -> Instruction {
+    let (escrow, _) = find_escrow_address(depositor, escrow_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::RefundEscrow {
+            depositor: *depositor,
+            escrow,
+            mint: *mint,
+            depositor_token_account: *depositor_token_account,
+            escrow_token_account: get_associated_token_address(&escrow, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::RefundEscrow {}.data(),
+    }
+}
+
+/// Builds a `create_htlc` instruction in which `owner` locks up `amount` of `mint` from
+/// `owner_token_account` in contract `htlc_id`, claimable by `counterparty` with the preimage
+/// of `hashlock` until `timeout_ts`
+#[allow(clippy::too_many_arguments)]
+pub fn create_htlc(
+    owner: &Pubkey,
+    htlc_id: u64,
+    mint: &Pubkey,
+    owner_token_account: &Pubkey,
+    counterparty: &Pubkey,
+    hashlock: [u8; 32],
+    amount: u64,
+    timeout_ts: i64,
+) -> Instruction {
+    let (htlc, _) = find_htlc_address(owner, htlc_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CreateHtlc {
+            owner: *owner,
+            htlc,
+            mint: *mint,
+            owner_token_account: *owner_token_account,
+            htlc_token_account: get_associated_token_address(&htlc, mint),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CreateHtlc {
+            htlc_id,
+            counterparty: *counterparty,
+            hashlock,
+            amount,
+            timeout_ts,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `claim_htlc` instruction from `caller`, paying `owner`'s contract `htlc_id` to
+/// `counterparty` in exchange for `preimage`
+pub fn claim_htlc(
+    caller: &Pubkey,
+    owner: &Pubkey,
+    htlc_id: u64,
+    mint: &Pubkey,
+    counterparty: &Pubkey,
+    preimage: [u8; 32],
+) -> Instruction {
+    let (htlc, _) = find_htlc_address(owner, htlc_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClaimHtlc {
+            caller: *caller,
+            htlc,
+            owner: *owner,
+            counterparty: *counterparty,
+            mint: *mint,
+            counterparty_token_account: get_associated_token_address(counterparty, mint),
+            htlc_token_account: get_associated_token_address(&htlc, mint),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClaimHtlc { preimage }.data(),
+    }
+}
+
+/// Builds a `refund_htlc` instruction returning `owner`'s expired contract `htlc_id` to
+/// `owner_token_account`
+pub fn refund_htlc(
+    owner: &Pubkey,
+    htlc_id: u64,
+    mint: &Pubkey,
+    owner_token_account: &Pubkey,
+) -> Instruction {
+    let (htlc, _) = find_htlc_address(owner, htlc_id);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::RefundHtlc {
+            owner: *owner,
+            htlc,
+            mint: *mint,
+            owner_token_account: *owner_token_account,
+            htlc_token_account: get_associated_token_address(&htlc, mint),
+            token_program: anchor_spl::token::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::RefundHtlc {}.data(),
+    }
+}
+
+/// Builds an `update_leaderboard` instruction submitting `owner`'s vault to the leaderboard
+/// of `mint`, which also drops the vault from it if it was closed
+pub fn update_leaderboard(payer: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::UpdateLeaderboard {
+            payer: *payer,
+            mint: *mint,
+            leaderboard: find_leaderboard_address(mint).0,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            rewards: find_rewards_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::UpdateLeaderboard {}.data(),
+    }
+}
+
+/// Builds a `request_withdrawal` instruction asking to withdraw `amount` from `user`'s vault
+pub fn request_withdrawal(user: &Pubkey, amount: u64) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::RequestWithdrawal {
+            user: *user,
+            state,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            pending_withdrawal: find_pending_withdrawal_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::RequestWithdrawal { amount }.data(),
+    }
+}
+
+/// Builds an `execute_withdrawal` instruction completing `user`'s pending withdrawal from
+/// their vault of `mint`. `vault_key` must sign if the amount is above the vault key threshold.
+/// `payer` works as in [`withdraw`].
+pub fn execute_withdrawal(
+    user: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    credential_token_account: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ExecuteWithdrawal {
+            user: *user,
+            payer: *payer,
+            vault_key: vault_key.copied(),
+            mint: *mint,
+            user_token_account: get_associated_token_address(user, mint),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            pending_withdrawal: find_pending_withdrawal_address(&state).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            advance: find_advance_address(&state).0,
+            config: find_config_address().0,
+            blocked_user: find_blocked_address(user).0,
+            credential_gate: find_credential_gate_address().0,
+            credential_token_account: credential_token_account.copied(),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ExecuteWithdrawal {}.data(),
+    }
+}
+
+/// Builds a `cancel_withdrawal` instruction dropping `user`'s pending withdrawal
+pub fn cancel_withdrawal(user: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CancelWithdrawal {
+            user: *user,
+            state,
+            pending_withdrawal: find_pending_withdrawal_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CancelWithdrawal {}.data(),
+    }
+}
+
+/// Builds a `lock_tokens` instruction locking `amount` of `user`'s vault for
+/// `duration_seconds`, as a new lock tranche
+pub fn lock_tokens(user: &Pubkey, amount: u64, duration_seconds: i64) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::LockTokens {
+            user: *user,
+            state,
+            outbox: find_outbox_address(&state).0,
+            config: find_config_address().0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::LockTokens {
+            amount,
+            duration_seconds,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `set_deposit_hook` instruction setting (or clearing, with `None`) the deposit
+/// hook of `user`'s vault
+pub fn set_deposit_hook(user: &Pubkey, hook: Option<&Pubkey>) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetDepositHook {
+            user: *user,
+            state: find_state_address(user).0,
+            approved_hook: hook.map(|hook| find_approved_hook_address(hook).0),
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetDepositHook {
+            hook: hook.copied(),
+        }
+        .data(),
+    }
+}
+
+/// Builds a `set_vault_key` instruction setting (or clearing, with `None`) the offline key
+/// of `user`'s vault. `current_vault_key` must sign when the vault already has a key.
+pub fn set_vault_key(
+    user: &Pubkey,
+    current_vault_key: Option<&Pubkey>,
+    vault_key: Option<&Pubkey>,
+    threshold: u64,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetVaultKey {
+            user: *user,
+            current_vault_key: current_vault_key.copied(),
+            state: find_state_address(user).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetVaultKey {
+            vault_key: vault_key.copied(),
+            threshold,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `set_balance_cap` instruction capping the balance of `user`'s vault (or removing
+/// the cap, with `None`). `policy` decides whether deposits over the cap fail or are truncated.
+pub fn set_balance_cap(user: &Pubkey, cap: Option<u64>, policy: CapPolicy) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetBalanceCap {
+            user: *user,
+            state: find_state_address(user).0,
+            config: find_config_address().0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetBalanceCap { cap, policy }.data(),
+    }
+}
+
+/// Builds a `set_withdraw_cooldown` instruction requiring `cooldown_seconds` between
+/// withdrawals from `user`'s vault
+pub fn set_withdraw_cooldown(user: &Pubkey, cooldown_seconds: i64) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetWithdrawCooldown {
+            user: *user,
+            state: find_state_address(user).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetWithdrawCooldown { cooldown_seconds }.data(),
+    }
+}
+
+/// Builds a `set_withdraw_limit` instruction capping withdrawals from `user`'s vault at
+/// `max_withdraw_per_day` over any day (0 for no limit). Raising or removing the limit needs
+/// the vault key, when the vault has one.
+pub fn set_withdraw_limit(
+    user: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    max_withdraw_per_day: u64,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetWithdrawLimit {
+            user: *user,
+            vault_key: vault_key.copied(),
+            state: find_state_address(user).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetWithdrawLimit {
+            max_withdraw_per_day,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `set_release_on_unlock` instruction letting anyone crank the release of `user`'s
+/// vault once its lock expires (or stopping that, with `false`). Opting in needs the vault
+/// key, when the vault has one.
+pub fn set_release_on_unlock(
+    user: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    enabled: bool,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetReleaseOnUnlock {
+            user: *user,
+            vault_key: vault_key.copied(),
+            state,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetReleaseOnUnlock { enabled }.data(),
+    }
+}
+
+/// Builds a `crank_release` instruction releasing `owner`'s vault to the owner's associated
+/// token account, paying the crank reward to `cranker_token_account`, owned by `reward_owner`.
+/// `price_update` must be the vault's Pyth price update account when releasing a USD target.
+pub fn crank_release(
+    cranker: &Pubkey,
+    cranker_token_account: &Pubkey,
+    reward_owner: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    price_update: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CrankRelease {
+            cranker: *cranker,
+            cranker_token_account: *cranker_token_account,
+            owner: *owner,
+            mint: *mint,
+            owner_token_account: get_associated_token_address(owner, mint),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            price_update: price_update.copied(),
+            price_feed: find_price_feed_address(mint).0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_cranker: find_blocked_address(reward_owner).0,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            custody: find_custody_address(&state).0,
+            rollover: find_rollover_address(&state).0,
+            advance: find_advance_address(&state).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CrankRelease {}.data(),
+    }
+}
+
+/// Builds an `add_note` instruction appending `text` to the notes of `user`'s vault
+pub fn add_note(user: &Pubkey, text: String) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::AddNote {
+            user: *user,
+            state,
+            notes: find_notes_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::AddNote { text }.data(),
+    }
+}
+
+/// Builds a `settle_deadline` instruction running the consequence of the missed deadline of
+/// `owner`'s vault. `donation_token_account` and the `donation_recipient` named by the deadline
+/// are required when the consequence is a donation, and `price_update` when the vault has a USD
+/// target.
+pub fn settle_deadline(
+    caller: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    donation_token_account: Option<&Pubkey>,
+    donation_recipient: Option<&Pubkey>,
+    price_update: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SettleDeadline {
+            caller: *caller,
+            owner: *owner,
+            mint: *mint,
+            owner_token_account: get_associated_token_address(owner, mint),
+            donation_token_account: donation_token_account.copied(),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            price_update: price_update.copied(),
+            price_feed: find_price_feed_address(mint).0,
+            advance: find_advance_address(&state).0,
+            blocked_owner: find_blocked_address(owner).0,
+            blocked_recipient: donation_recipient
+                .map(|recipient| find_blocked_address(recipient).0),
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SettleDeadline {}.data(),
+    }
+}
+
+/// Builds a `claim_points` instruction claiming the reward points of `user`'s vault of `mint`
+pub fn claim_points(user: &Pubkey, mint: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClaimPoints {
+            user: *user,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            mint: *mint,
+            rewards: find_rewards_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClaimPoints {}.data(),
+    }
+}
+
+/// Builds a `claim_badge` instruction minting `user`'s soulbound `badge` to their Token-2022
+/// associated token account
+pub fn claim_badge(user: &Pubkey, badge: Badge) -> Instruction {
+    let (state, _) = find_state_address(user);
+    let (badge_mint, _) = find_badge_mint_address(user, badge);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClaimBadge {
+            user: *user,
+            state,
+            rewards: find_rewards_address(&state).0,
+            badge_mint,
+            user_badge_account: get_associated_token_address_with_program_id(
+                user,
+                &badge_mint,
+                &anchor_spl::token_2022::ID,
+            ),
+            token_program: anchor_spl::token_2022::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClaimBadge { badge }.data(),
+    }
+}
+
+/// Builds a `set_rollover` instruction making `user`'s vault keep its balance once it reaches
+/// its target, and move on to a new target `increase_bps` above the one reached
+pub fn set_rollover(user: &Pubkey, increase_bps: u16) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetRollover {
+            user: *user,
+            state,
+            rollover: find_rollover_address(&state).0,
+            system_program: system_program::ID,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetRollover { increase_bps }.data(),
+    }
+}
+
+/// Builds a `clear_rollover` instruction, so `user`'s vault releases on its target again
+pub fn clear_rollover(user: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClearRollover {
+            user: *user,
+            state,
+            rollover: find_rollover_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClearRollover {}.data(),
+    }
+}
+
+/// Builds a `set_price_condition` instruction releasing `user`'s vault once the price read from
+/// `price_account` crosses `threshold_cents` (USD cents per whole token) in `direction`. Needs
+/// the vault key, when the vault has one.
+pub fn set_price_condition(
+    user: &Pubkey,
+    vault_key: Option<&Pubkey>,
+    price_account: &Pubkey,
+    threshold_cents: u64,
+    direction: PriceDirection,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::SetPriceCondition {
+            user: *user,
+            vault_key: vault_key.copied(),
+            state,
+            price_condition: find_price_condition_address(&state).0,
+            system_program: system_program::ID,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::SetPriceCondition {
+            price_account: *price_account,
+            threshold_cents,
+            direction,
+        }
+        .data(),
+    }
+}
+
+/// Builds a `clear_price_condition` instruction removing `user`'s price condition
+pub fn clear_price_condition(user: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::ClearPriceCondition {
+            user: *user,
+            state,
+            price_condition: find_price_condition_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::ClearPriceCondition {}.data(),
+    }
+}
+
+/// Builds a `check_price_condition` instruction releasing `owner`'s vault of `mint` if its
+/// price condition is met. `price_update` must be the account named by the condition.
+pub fn check_price_condition(
+    caller: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    price_update: &Pubkey,
+) -> Instruction {
+    let (state, _) = find_state_address(owner);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CheckPriceCondition {
+            caller: *caller,
+            owner: *owner,
+            mint: *mint,
+            owner_token_account: get_associated_token_address(owner, mint),
+            vault_token_account: find_vault_token_account(&state, mint),
+            state,
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            mint_stats: find_mint_stats_address(mint).0,
+            price_condition: find_price_condition_address(&state).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            price_update: *price_update,
+            price_feed: find_price_feed_address(mint).0,
+            blocked_owner: find_blocked_address(owner).0,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+            custody: find_custody_address(&state).0,
+            advance: find_advance_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CheckPriceCondition {}.data(),
+    }
+}
+
+/// Builds a `close_vault` instruction closing `user`'s empty vault of `mint` and refunding its
+/// rent. The vault's child accounts are closed too.
+pub fn close_vault(user: &Pubkey, mint: &Pubkey) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::CloseVault {
+            user: *user,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            vault_authority: find_vault_authority(&state).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            notes: find_notes_address(&state).0,
+            price_condition: find_price_condition_address(&state).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            pending_withdrawal: find_pending_withdrawal_address(&state).0,
+            guardians: find_guardians_address(&state).0,
+            rollover: find_rollover_address(&state).0,
+            vault_template: find_vault_template_address(&state).0,
+            extension: find_extension_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            token_program: anchor_spl::token::ID,
+            custody: find_custody_address(&state).0,
+            advance: find_advance_address(&state).0,
+            recovery_proposal: find_recovery_proposal_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::CloseVault {}.data(),
+    }
+}
+
+/// Builds a `finalize` instruction completing `user`'s vault of `mint`: the balance is
+/// released to `user_token_account` if the target is reached now (nothing is left if an
+/// auto-release already sent it), then the vault is closed like [`close_vault`]. Append it
+/// to the deposit that reaches the goal to finish in one transaction. `price_update` must
+/// be the vault's Pyth price update account when it has a USD target.
+pub fn finalize(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    mint: &Pubkey,
+    price_update: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::Finalize {
+            user: *user,
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            vault_authority: find_vault_authority(&state).0,
+            user_token_account: *user_token_account,
+            mint: *mint,
+            mint_stats: find_mint_stats_address(mint).0,
+            outbox: find_outbox_address(&state).0,
+            rewards: find_rewards_address(&state).0,
+            notes: find_notes_address(&state).0,
+            price_condition: find_price_condition_address(&state).0,
+            withdrawal_policy: find_withdrawal_policy_address(&state).0,
+            pending_withdrawal: find_pending_withdrawal_address(&state).0,
+            guardians: find_guardians_address(&state).0,
+            rollover: find_rollover_address(&state).0,
+            vault_template: find_vault_template_address(&state).0,
+            extension: find_extension_address(&state).0,
+            self_exclusion: find_self_exclusion_address(&state).0,
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            price_update: price_update.copied(),
+            price_feed: find_price_feed_address(mint).0,
+            blocked_user: find_blocked_address(user).0,
+            token_program: anchor_spl::token::ID,
+            custody: find_custody_address(&state).0,
+            advance: find_advance_address(&state).0,
+            recovery_proposal: find_recovery_proposal_address(&state).0,
+            event_authority: find_event_authority().0,
+            program: PROGRAM_ID,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::Finalize {}.data(),
+    }
+}
+
+/// Builds a `get_vault_status` instruction for `user`'s vault of `mint`, meant to be simulated
+/// and decoded with [`decode_vault_status`]. Set `has_notes` to include the owner's notes.
+/// `price_update` must be the vault's Pyth price update account when it has a USD target.
+pub fn get_vault_status(
+    user: &Pubkey,
+    mint: &Pubkey,
+    has_notes: bool,
+    price_update: Option<&Pubkey>,
+) -> Instruction {
+    let (state, _) = find_state_address(user);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vault2::accounts::GetVaultStatus {
+            state,
+            vault_token_account: find_vault_token_account(&state, mint),
+            notes: has_notes.then(|| find_notes_address(&state).0),
+            unlock_schedule: find_unlock_schedule_address(&state).0,
+            mint: *mint,
+            price_update: price_update.copied(),
+            price_feed: find_price_feed_address(mint).0,
+        }
+        .to_account_metas(None),
+        data: vault2::instruction::GetVaultStatus {}.data(),
+    }
+}
+
+/// Decodes the return data of a simulated `get_vault_status` instruction
+pub fn decode_vault_status(return_data: &[u8]) -> Result<VaultStatus> {
+    VaultStatus::try_from_slice(return_data).map_err(Into::into)
+}
+
+/// Deserializes a `Vault` state account from its raw account data
+pub fn deserialize_vault(data: &[u8]) -> Result<Vault> {
+    Vault::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes an `Outbox` account from its raw account data
+pub fn deserialize_outbox(data: &[u8]) -> Result<Outbox> {
+    Outbox::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `RewardState` account from its raw account data
+pub fn deserialize_rewards(data: &[u8]) -> Result<RewardState> {
+    RewardState::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `MintStats` account from its raw account data
+pub fn deserialize_mint_stats(data: &[u8]) -> Result<MintStats> {
+    MintStats::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `Contribution` account from its raw account data
+pub fn deserialize_contribution(data: &[u8]) -> Result<Contribution> {
+    Contribution::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `SubscriptionGrant` account from its raw account data
+pub fn deserialize_subscription(data: &[u8]) -> Result<SubscriptionGrant> {
+    SubscriptionGrant::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `Circle` account from its raw account data
+pub fn deserialize_circle(data: &[u8]) -> Result<Circle> {
+    Circle::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `Payroll` account from its raw account data
+pub fn deserialize_payroll(data: &[u8]) -> Result<Payroll> {
+    Payroll::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes an `Escrow` account from its raw account data
+pub fn deserialize_escrow(data: &[u8]) -> Result<Escrow> {
+    Escrow::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes an `Htlc` account from its raw account data
+pub fn deserialize_htlc(data: &[u8]) -> Result<Htlc> {
+    Htlc::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `Custody` account from its raw account data
+pub fn deserialize_custody(data: &[u8]) -> Result<Custody> {
+    Custody::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `Rollover` account from its raw account data
+pub fn deserialize_rollover(data: &[u8]) -> Result<Rollover> {
+    Rollover::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes an `Advance` account from its raw account data
+pub fn deserialize_advance(data: &[u8]) -> Result<Advance> {
+    Advance::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `GoalTemplate` account from its raw account data
+pub fn deserialize_goal_template(data: &[u8]) -> Result<GoalTemplate> {
+    GoalTemplate::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `VaultTemplate` account from its raw account data
+pub fn deserialize_vault_template(data: &[u8]) -> Result<VaultTemplate> {
+    VaultTemplate::try_deserialize(&mut &data[..])
+}
+
+/// Deserializes a `Leaderboard` account from its raw account data
+pub fn deserialize_leaderboard(data: &[u8]) -> Result<Leaderboard> {
+    Leaderboard::try_deserialize(&mut &data[..])
+}
+
+/// Reads a zero-copy `VaultExtension` account from its raw account data
+pub fn deserialize_extension(data: &[u8]) -> Result<VaultExtension> {
+    if data.len() < vault2::VAULT_EXTENSION_SIZE {
+        return Err(ErrorCode::AccountDidNotDeserialize.into());
+    }
+    if data[..8] != *VaultExtension::DISCRIMINATOR {
+        return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+
+    Ok(bytemuck::pod_read_unaligned(
+        &data[8..vault2::VAULT_EXTENSION_SIZE],
+    ))
+}
+
+/// Deserializes the `ConfigHistory` account from its raw account data
+pub fn deserialize_config_history(data: &[u8]) -> Result<ConfigHistory> {
+    ConfigHistory::try_deserialize(&mut &data[..])
+}
+
+/// Read access to account data, implemented by callers for whichever RPC client they use
+/// (e.g. by wrapping `RpcClient::get_account_data`)
+pub trait AccountReader {
+    type Error;
+
+    fn get_account_data(&self, address: &Pubkey) -> std::result::Result<Vec<u8>, Self::Error>;
+}
+
+/// Errors returned by the fetch helpers
+#[derive(Debug)]
+pub enum ClientError<E> {
+    /// The account could not be read
+    Reader(E),
+    /// The account data is not of the expected type
+    Deserialize(anchor_lang::error::Error),
+}
+
+/// Fetches and deserializes the vault state of `user`
+pub fn fetch_vault<R: AccountReader>(
+    reader: &R,
+    user: &Pubkey,
+) -> std::result::Result<Vault, ClientError<R::Error>> {
+    let data = reader
+        .get_account_data(&find_state_address(user).0)
+        .map_err(ClientError::Reader)?;
+
+    deserialize_vault(&data).map_err(ClientError::Deserialize)
+}
+
+/// Fetches and deserializes the activity outbox of `user`'s vault
+pub fn fetch_outbox<R: AccountReader>(
+    reader: &R,
+    user: &Pubkey,
+) -> std::result::Result<Outbox, ClientError<R::Error>> {
+    let (state, _) = find_state_address(user);
+    let data = reader
+        .get_account_data(&find_outbox_address(&state).0)
+        .map_err(ClientError::Reader)?;
+
+    deserialize_outbox(&data).map_err(ClientError::Deserialize)
+}
+
+/// Fetches and deserializes the config change log
+pub fn fetch_config_history<R: AccountReader>(
+    reader: &R,
+) -> std::result::Result<ConfigHistory, ClientError<R::Error>> {
+    let data = reader
+        .get_account_data(&find_config_history_address().0)
+        .map_err(ClientError::Reader)?;
+
+    deserialize_config_history(&data).map_err(ClientError::Deserialize)
+}
+
+/// Fetches the current token balance of `user`'s vault
+pub fn fetch_vault_balance<R: AccountReader>(
+    reader: &R,
+    user: &Pubkey,
+) -> std::result::Result<u64, ClientError<R::Error>> {
+    // The vault records its token account, so the mint doesn't need to be known
+    let vault = fetch_vault(reader, user)?;
+    let data = reader
+        .get_account_data(&vault.vault_token_account)
+        .map_err(ClientError::Reader)?;
+
+    TokenAccount::try_deserialize(&mut &data[..])
+        .map(|account| account.amount)
+        .map_err(ClientError::Deserialize)
+}
@@ -0,0 +1,1818 @@
+//! The instruction handlers. They live in their own module because the code generated by
+//! `#[program]` still calls the deprecated `AccountInfo::realloc`, which is allowed for this
+//! module only.
+
+use crate::*;
+
+#[program]
+pub mod vault2 {
+    use super::*;
+
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        amount: u64,
+        mint: Pubkey,
+        deadline: Option<GoalDeadline>,
+        usd_target: Option<UsdTarget>,
+        ui_amount: Option<String>,
+    ) -> Result<()> {
+        // Delegate the actual initialization logic to the accounts implementation
+        ctx.accounts
+            .initialize(amount, deadline, usd_target, ui_amount, &ctx.bumps)?;
+
+        emit_cpi!(VaultInitialized {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            mint,
+            vault_token_account: ctx.accounts.vault_token_account.key(),
+            target: ctx.accounts.state.amount,
+        });
+
+        // Warn that the mint's freeze authority could freeze the vault and trap the savings
+        if let COption::Some(freeze_authority) = ctx.accounts.mint.freeze_authority {
+            emit_cpi!(FreezableMintAccepted {
+                user: ctx.accounts.user.key(),
+                state: ctx.accounts.state.key(),
+                mint,
+                freeze_authority,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Kept for clients built against the separate idempotent path: `initialize` itself now
+    /// reuses an existing vault token account
+    pub fn initialize_idempotent(
+        ctx: Context<Initialize>,
+        amount: u64,
+        mint: Pubkey,
+        deadline: Option<GoalDeadline>,
+        usd_target: Option<UsdTarget>,
+        ui_amount: Option<String>,
+    ) -> Result<()> {
+        initialize(ctx, amount, mint, deadline, usd_target, ui_amount)
+    }
+
+    pub fn initialize_custodial(
+        ctx: Context<InitializeCustodial>,
+        amount: u64,
+        mint: Pubkey,
+        release_ts: i64,
+        ui_amount: Option<String>,
+    ) -> Result<()> {
+        // Delegate the initialization to the accounts implementation
+        ctx.accounts
+            .initialize_custodial(amount, release_ts, ui_amount, &ctx.bumps)?;
+
+        emit_cpi!(VaultInitialized {
+            user: ctx.accounts.beneficiary.key(),
+            state: ctx.accounts.state.key(),
+            mint,
+            vault_token_account: ctx.accounts.vault_token_account.key(),
+            target: ctx.accounts.state.amount,
+        });
+        emit_cpi!(CustodialVaultCreated {
+            custodian: ctx.accounts.custodian.key(),
+            beneficiary: ctx.accounts.beneficiary.key(),
+            state: ctx.accounts.state.key(),
+            release_ts,
+        });
+
+        // Warn that the mint's freeze authority could freeze the vault and trap the savings
+        if let COption::Some(freeze_authority) = ctx.accounts.mint.freeze_authority {
+            emit_cpi!(FreezableMintAccepted {
+                user: ctx.accounts.beneficiary.key(),
+                state: ctx.accounts.state.key(),
+                mint,
+                freeze_authority,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn initialize_from_template(
+        ctx: Context<InitializeFromTemplate>,
+        template_id: u16,
+        ui_amount: Option<String>,
+    ) -> Result<()> {
+        // Delegate the initialization to the accounts implementation
+        ctx.accounts
+            .initialize_from_template(ui_amount, &ctx.bumps)?;
+
+        let mint = ctx.accounts.mint.key();
+        emit_cpi!(VaultInitialized {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            mint,
+            vault_token_account: ctx.accounts.vault_token_account.key(),
+            target: ctx.accounts.state.amount,
+        });
+
+        emit_cpi!(VaultCreatedFromTemplate {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            template_id,
+            locked_until: ctx.accounts.self_exclusion.frozen_until,
+        });
+
+        // Warn that the mint's freeze authority could freeze the vault and trap the savings
+        if let COption::Some(freeze_authority) = ctx.accounts.mint.freeze_authority {
+            emit_cpi!(FreezableMintAccepted {
+                user: ctx.accounts.user.key(),
+                state: ctx.accounts.state.key(),
+                mint,
+                freeze_authority,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        // Delegate the deposit logic to the accounts implementation
+        // The amount actually deposited can be lower if the vault's balance cap truncates it
+        let (amount, fee, outcome) = ctx.accounts.deposit(amount)?;
+
+        // Let the vault's approved hook program (if any) react to the deposit, fee included
+        invoke_deposit_hook(
+            &ctx.accounts.state,
+            &ctx.accounts.user,
+            ctx.accounts.deposit_hook_program.as_ref(),
+            ctx.accounts.approved_hook.as_ref(),
+            amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?,
+            ctx.remaining_accounts,
+        )?;
+
+        emit_cpi!(TokensDeposited {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        if fee > 0 {
+            emit_cpi!(FeeCharged {
+                state: ctx.accounts.state.key(),
+                depositor: ctx.accounts.user.key(),
+                treasury_token_account: treasury_key(&ctx.accounts.treasury_token_account),
+                fee,
+            });
+        }
+
+        // Report the auto-release (or rollover) separately so indexers can track completed goals
+        match outcome {
+            Some(TargetOutcome::Released(released)) => {
+                emit_cpi!(SavingsTargetReached {
+                    user: ctx.accounts.user.key(),
+                    state: ctx.accounts.state.key(),
+                    target: ctx.accounts.state.amount,
+                    released,
+                });
+            }
+            Some(TargetOutcome::RolledOver {
+                previous_target,
+                new_target,
+            }) => {
+                emit_cpi!(GoalRolledOver {
+                    user: ctx.accounts.user.key(),
+                    state: ctx.accounts.state.key(),
+                    previous_target,
+                    new_target,
+                });
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn deposit_all<'info>(ctx: Context<'_, '_, '_, 'info, Deposit<'info>>) -> Result<()> {
+        // Sweep the user's whole token balance into the vault
+        let amount = ctx.accounts.user_token_account.amount;
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        deposit(ctx, amount)
+    }
+
+    pub fn deposit_sol<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>,
+        lamports: u64,
+    ) -> Result<()> {
+        // Wrap the lamports into the user's wSOL account, then deposit them as usual
+        ctx.accounts.wrap_sol(lamports)?;
+
+        deposit(ctx, lamports)
+    }
+
+    pub fn deposit_with_memo<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>,
+        amount: u64,
+        memo: String,
+    ) -> Result<()> {
+        // Keep the memo's hash on the vault extension, and the memo itself in the event
+        let memo_hash = record_memo(&ctx.accounts.extension, &memo)?;
+
+        emit_cpi!(MemoAttached {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            kind: ActivityKind::Deposit,
+            memo,
+            memo_hash,
+        });
+
+        deposit(ctx, amount)
+    }
+
+    pub fn deposit_and_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAndSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        swap_data: Vec<u8>,
+        hook_account_count: u8,
+    ) -> Result<()> {
+        // The last `hook_account_count` remaining accounts are the hook's, the rest the swap's
+        let swap_account_count = ctx
+            .remaining_accounts
+            .len()
+            .checked_sub(hook_account_count as usize)
+            .ok_or(ErrorCode::DepositHookMismatch)?;
+        let (swap_accounts, hook_accounts) = ctx.remaining_accounts.split_at(swap_account_count);
+
+        // Delegate the swap and deposit logic to the accounts implementation
+        let (amount_in, amount_out, fee, outcome) = ctx.accounts.deposit_and_swap(
+            amount_in,
+            min_amount_out,
+            swap_data,
+            swap_accounts,
+        )?;
+
+        // Let the vault's approved hook program (if any) react to the swap output, fee included
+        invoke_deposit_hook(
+            &ctx.accounts.state,
+            &ctx.accounts.user,
+            ctx.accounts.deposit_hook_program.as_ref(),
+            ctx.accounts.approved_hook.as_ref(),
+            amount_out,
+            hook_accounts,
+        )?;
+
+        emit_cpi!(TokensSwapped {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            input_mint: ctx.accounts.input_mint.key(),
+            amount_in,
+            amount_out,
+        });
+
+        emit_cpi!(TokensDeposited {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount: amount_out.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?,
+        });
+
+        if fee > 0 {
+            emit_cpi!(FeeCharged {
+                state: ctx.accounts.state.key(),
+                depositor: ctx.accounts.user.key(),
+                treasury_token_account: treasury_key(&ctx.accounts.treasury_token_account),
+                fee,
+            });
+        }
+
+        match outcome {
+            Some(TargetOutcome::Released(released)) => {
+                emit_cpi!(SavingsTargetReached {
+                    user: ctx.accounts.user.key(),
+                    state: ctx.accounts.state.key(),
+                    target: ctx.accounts.state.amount,
+                    released,
+                });
+            }
+            Some(TargetOutcome::RolledOver {
+                previous_target,
+                new_target,
+            }) => {
+                emit_cpi!(GoalRolledOver {
+                    user: ctx.accounts.user.key(),
+                    state: ctx.accounts.state.key(),
+                    previous_target,
+                    new_target,
+                });
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn grant_delegate(
+        ctx: Context<GrantDelegate>,
+        delegate: Pubkey,
+        permissions: DelegatePermissions,
+        expiry: i64,
+        max_amount: u64,
+    ) -> Result<()> {
+        // Delegate the grant to the accounts implementation
+        ctx.accounts
+            .grant_delegate(delegate, permissions, expiry, max_amount, &ctx.bumps)?;
+
+        emit_cpi!(DelegateGranted {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            delegate,
+            permissions,
+            expiry,
+            max_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_delegate(ctx: Context<RevokeDelegate>) -> Result<()> {
+        // Delegate the revocation to the accounts implementation
+        ctx.accounts.revoke_delegate()?;
+
+        emit_cpi!(DelegateRevoked {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            delegate: ctx.accounts.session.delegate,
+        });
+
+        Ok(())
+    }
+
+    pub fn session_deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, SessionDeposit<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        // Delegate the deposit logic to the accounts implementation
+        let (amount, fee) = ctx.accounts.session_deposit(amount)?;
+
+        // Let the vault's approved hook program (if any) react to the deposit, fee included
+        invoke_deposit_hook(
+            &ctx.accounts.state,
+            &ctx.accounts.delegate,
+            ctx.accounts.deposit_hook_program.as_ref(),
+            ctx.accounts.approved_hook.as_ref(),
+            amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?,
+            ctx.remaining_accounts,
+        )?;
+
+        emit_cpi!(TokensDeposited {
+            user: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        if fee > 0 {
+            emit_cpi!(FeeCharged {
+                state: ctx.accounts.state.key(),
+                depositor: ctx.accounts.delegate.key(),
+                treasury_token_account: treasury_key(&ctx.accounts.treasury_token_account),
+                fee,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn session_withdraw(ctx: Context<SessionWithdraw>, amount: u64) -> Result<()> {
+        // Delegate the withdraw logic to the accounts implementation
+        ctx.accounts.session_withdraw(amount)?;
+
+        emit_cpi!(TokensWithdrawn {
+            user: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn grant_subscription(
+        ctx: Context<GrantSubscription>,
+        merchant: Pubkey,
+        amount: u64,
+        interval_seconds: i64,
+    ) -> Result<()> {
+        // Delegate the grant to the accounts implementation
+        ctx.accounts
+            .grant_subscription(merchant, amount, interval_seconds, &ctx.bumps)?;
+
+        emit_cpi!(SubscriptionGranted {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            merchant,
+            amount,
+            interval_seconds,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_subscription(ctx: Context<RevokeSubscription>) -> Result<()> {
+        // Delegate the revocation to the accounts implementation
+        ctx.accounts.revoke_subscription()?;
+
+        emit_cpi!(SubscriptionRevoked {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            merchant: ctx.accounts.subscription.merchant,
+        });
+
+        Ok(())
+    }
+
+    pub fn pull_subscription(ctx: Context<PullSubscription>) -> Result<()> {
+        // Delegate the pull to the accounts implementation
+        let amount = ctx.accounts.pull_subscription()?;
+
+        emit_cpi!(TokensWithdrawn {
+            user: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+        emit_cpi!(SubscriptionPulled {
+            merchant: ctx.accounts.merchant.key(),
+            user: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn deposit_for<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositFor<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        // Delegate the gift logic to the accounts implementation
+        let (amount, fee) = ctx.accounts.deposit_for(amount, &ctx.bumps)?;
+
+        // Let the vault's approved hook program (if any) react to the gift, fee included
+        invoke_deposit_hook(
+            &ctx.accounts.state,
+            &ctx.accounts.depositor,
+            ctx.accounts.deposit_hook_program.as_ref(),
+            ctx.accounts.approved_hook.as_ref(),
+            amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?,
+            ctx.remaining_accounts,
+        )?;
+
+        emit_cpi!(TokensDeposited {
+            user: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        // Credit the gift to whoever sent it, next to the regular deposit event
+        emit_cpi!(GiftDeposited {
+            depositor: ctx.accounts.depositor.key(),
+            user: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+            total_contributed: ctx.accounts.contribution.total_contributed,
+        });
+
+        if fee > 0 {
+            emit_cpi!(FeeCharged {
+                state: ctx.accounts.state.key(),
+                depositor: ctx.accounts.depositor.key(),
+                treasury_token_account: treasury_key(&ctx.accounts.treasury_token_account),
+                fee,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn deposit_via_delegate<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositViaDelegate<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        // Delegate the pull logic to the accounts implementation
+        let (amount, fee, outcome) = ctx.accounts.deposit_via_delegate(amount)?;
+
+        // Let the vault's approved hook program (if any) react to the pull, fee included. The
+        // tokens come from the owner, so the hook sees them as the depositor
+        invoke_deposit_hook(
+            &ctx.accounts.state,
+            &ctx.accounts.owner,
+            ctx.accounts.deposit_hook_program.as_ref(),
+            ctx.accounts.approved_hook.as_ref(),
+            amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?,
+            ctx.remaining_accounts,
+        )?;
+
+        emit_cpi!(TokensDeposited {
+            user: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        if fee > 0 {
+            emit_cpi!(FeeCharged {
+                state: ctx.accounts.state.key(),
+                depositor: ctx.accounts.owner.key(),
+                treasury_token_account: treasury_key(&ctx.accounts.treasury_token_account),
+                fee,
+            });
+        }
+
+        // Report the auto-release (or rollover) separately so indexers can track completed goals
+        match outcome {
+            Some(TargetOutcome::Released(released)) => {
+                emit_cpi!(SavingsTargetReached {
+                    user: ctx.accounts.owner.key(),
+                    state: ctx.accounts.state.key(),
+                    target: ctx.accounts.state.amount,
+                    released,
+                });
+            }
+            Some(TargetOutcome::RolledOver {
+                previous_target,
+                new_target,
+            }) => {
+                emit_cpi!(GoalRolledOver {
+                    user: ctx.accounts.owner.key(),
+                    state: ctx.accounts.state.key(),
+                    previous_target,
+                    new_target,
+                });
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn set_auto_save(
+        ctx: Context<SetAutoSave>,
+        service: Pubkey,
+        max_amount: u64,
+        interval_seconds: i64,
+    ) -> Result<()> {
+        // Delegate the authorization to the accounts implementation
+        ctx.accounts
+            .set_auto_save(service, max_amount, interval_seconds, &ctx.bumps)?;
+
+        emit_cpi!(AutoSaveSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            service,
+            max_amount,
+            interval_seconds,
+        });
+
+        Ok(())
+    }
+
+    pub fn clear_auto_save(ctx: Context<ClearAutoSave>) -> Result<()> {
+        // Stop counting the grant, the account is closed by the accounts constraints
+        ctx.accounts.clear_auto_save()?;
+
+        emit_cpi!(AutoSaveCleared {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        // Delegate the withdraw logic to the accounts implementation
+        ctx.accounts.withdraw(amount)?;
+
+        emit_cpi!(TokensWithdrawn {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_to(ctx: Context<WithdrawTo>, amount: u64) -> Result<()> {
+        // Delegate the withdraw logic to the accounts implementation
+        ctx.accounts.withdraw_to(amount)?;
+
+        emit_cpi!(TokensWithdrawn {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        emit_cpi!(WithdrawalRedirected {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            destination_token_account: ctx.accounts.destination_token_account.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
+        // Delegate the advance to the accounts implementation
+        let (fee, debt) = ctx.accounts.borrow(amount, &ctx.bumps)?;
+
+        emit_cpi!(AdvanceTaken {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+            fee,
+            debt,
+        });
+
+        Ok(())
+    }
+
+    pub fn repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
+        // Delegate the repayment to the accounts implementation
+        let (repaid, debt) = ctx.accounts.repay(amount)?;
+
+        emit_cpi!(AdvanceRepaid {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount: repaid,
+            debt,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_sol(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        // Withdraw into the user's wSOL account, then close it to hand back plain SOL
+        ctx.accounts.withdraw(amount)?;
+        ctx.accounts.unwrap_sol()?;
+
+        emit_cpi!(TokensWithdrawn {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_with_memo(ctx: Context<Withdraw>, amount: u64, memo: String) -> Result<()> {
+        // Keep the memo's hash on the vault extension, and the memo itself in the event
+        let memo_hash = record_memo(&ctx.accounts.extension, &memo)?;
+        ctx.accounts.withdraw(amount)?;
+
+        emit_cpi!(TokensWithdrawn {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        emit_cpi!(MemoAttached {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            kind: ActivityKind::Withdraw,
+            memo,
+            memo_hash,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_withdrawal_delay(
+        ctx: Context<SetWithdrawalDelay>,
+        delay_seconds: i64,
+    ) -> Result<()> {
+        // Delegate the policy update to the accounts implementation
+        let effective_at = ctx.accounts.set_withdrawal_delay(delay_seconds, &ctx.bumps)?;
+
+        emit_cpi!(WithdrawalDelaySet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            delay_seconds,
+            effective_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_unlock_stream(
+        ctx: Context<SetUnlockStream>,
+        start_ts: i64,
+        end_ts: i64,
+        cliff_ts: Option<i64>,
+    ) -> Result<()> {
+        // Without a cliff, the stream starts releasing right at its start
+        let cliff_ts = cliff_ts.unwrap_or(start_ts);
+
+        // Delegate the schedule logic to the accounts implementation
+        let amount = ctx
+            .accounts
+            .set_unlock_stream(start_ts, cliff_ts, end_ts, &ctx.bumps)?;
+
+        emit_cpi!(UnlockStreamSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+            start_ts,
+            cliff_ts,
+            end_ts,
+        });
+
+        Ok(())
+    }
+
+    pub fn self_freeze(ctx: Context<SelfFreeze>, duration_seconds: i64) -> Result<()> {
+        // Delegate the freeze to the accounts implementation
+        let frozen_until = ctx.accounts.self_freeze(duration_seconds, &ctx.bumps)?;
+
+        emit_cpi!(SelfFreezeSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            frozen_until,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_withdrawal_approvers<'info>(
+        ctx: Context<'_, '_, '_, 'info, SetWithdrawalApprovers<'info>>,
+        approvers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        // Delegate the policy update to the accounts implementation
+        ctx.accounts.set_withdrawal_approvers(
+            approvers.clone(),
+            threshold,
+            ctx.remaining_accounts,
+            &ctx.bumps,
+        )?;
+
+        emit_cpi!(WithdrawalApproversSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            approvers,
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_withdrawal(ctx: Context<ApproveWithdrawal>) -> Result<()> {
+        // Delegate the approval to the accounts implementation
+        let approvals = ctx.accounts.approve_withdrawal()?;
+
+        emit_cpi!(WithdrawalApproved {
+            approver: ctx.accounts.approver.key(),
+            state: ctx.accounts.state.key(),
+            amount: ctx.accounts.pending_withdrawal.amount,
+            approvals,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_guardians<'info>(
+        ctx: Context<'_, '_, '_, 'info, SetGuardians<'info>>,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        // Delegate the validation to the accounts implementation
+        ctx.accounts.set_guardians(
+            guardians.clone(),
+            threshold,
+            ctx.remaining_accounts,
+            &ctx.bumps,
+        )?;
+
+        emit_cpi!(GuardiansSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            guardians,
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    pub fn propose_recovery(ctx: Context<ProposeRecovery>, new_owner: Pubkey) -> Result<()> {
+        // Delegate the proposal to the accounts implementation
+        let executable_at = ctx.accounts.propose_recovery(new_owner, &ctx.bumps)?;
+
+        emit_cpi!(RecoveryProposed {
+            guardian: ctx.accounts.guardian.key(),
+            state: ctx.accounts.state.key(),
+            new_owner,
+            executable_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn vote_recovery(ctx: Context<VoteRecovery>) -> Result<()> {
+        // Delegate the vote to the accounts implementation
+        let votes = ctx.accounts.vote_recovery()?;
+
+        emit_cpi!(RecoveryVoted {
+            guardian: ctx.accounts.guardian.key(),
+            state: ctx.accounts.state.key(),
+            new_owner: ctx.accounts.recovery_proposal.new_owner,
+            votes,
+        });
+
+        Ok(())
+    }
+
+    pub fn veto_recovery(ctx: Context<VetoRecovery>) -> Result<()> {
+        // The proposal account is closed by the accounts constraints
+        emit_cpi!(RecoveryVetoed {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            new_owner: ctx.accounts.recovery_proposal.new_owner,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_recovery(ctx: Context<ExecuteRecovery>) -> Result<()> {
+        // Delegate the recovery to the accounts implementation
+        let recovered = ctx.accounts.execute_recovery()?;
+
+        emit_cpi!(VaultRecovered {
+            owner: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            new_owner: ctx.accounts.new_owner.key(),
+            recovered,
+        });
+
+        Ok(())
+    }
+
+    pub fn request_withdrawal(ctx: Context<RequestWithdrawal>, amount: u64) -> Result<()> {
+        // Delegate the request to the accounts implementation
+        let executable_at = ctx.accounts.request_withdrawal(amount, &ctx.bumps)?;
+
+        emit_cpi!(WithdrawalRequested {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+            executable_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_withdrawal(ctx: Context<ExecuteWithdrawal>) -> Result<()> {
+        // Delegate the withdraw logic to the accounts implementation
+        let amount = ctx.accounts.execute_withdrawal()?;
+
+        emit_cpi!(TokensWithdrawn {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_withdrawal(ctx: Context<CancelWithdrawal>) -> Result<()> {
+        // The request account is closed by the accounts constraints
+        emit_cpi!(WithdrawalCancelled {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount: ctx.accounts.pending_withdrawal.amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn lock_tokens(
+        ctx: Context<LockTokens>,
+        amount: u64,
+        duration_seconds: i64,
+    ) -> Result<()> {
+        // Delegate the lock logic to the accounts implementation
+        let locked_until = ctx.accounts.lock_tokens(amount, duration_seconds)?;
+
+        emit_cpi!(VaultLocked {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            amount,
+            locked_until,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_release_on_unlock(ctx: Context<SetReleaseOnUnlock>, enabled: bool) -> Result<()> {
+        // Opt the vault in or out of permissionless release once its lock expires
+        ctx.accounts.set_release_on_unlock(enabled)?;
+
+        emit_cpi!(ReleaseOnUnlockSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            enabled,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_withdraw_cooldown(
+        ctx: Context<SetWithdrawCooldown>,
+        cooldown_seconds: i64,
+    ) -> Result<()> {
+        // Delegate the validation to the accounts implementation
+        ctx.accounts.set_withdraw_cooldown(cooldown_seconds)?;
+
+        emit_cpi!(WithdrawCooldownSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            cooldown_seconds,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_withdraw_limit(
+        ctx: Context<SetWithdrawLimit>,
+        max_withdraw_per_day: u64,
+    ) -> Result<()> {
+        // Delegate the validation to the accounts implementation
+        ctx.accounts.set_withdraw_limit(max_withdraw_per_day)?;
+
+        emit_cpi!(WithdrawLimitSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            max_withdraw_per_day,
+        });
+
+        Ok(())
+    }
+
+    pub fn crank_release(ctx: Context<CrankRelease>) -> Result<()> {
+        // Delegate the release logic to the accounts implementation
+        let (released, reward) = ctx.accounts.crank_release()?;
+
+        emit_cpi!(VaultCranked {
+            owner: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            cranker: ctx.accounts.cranker.key(),
+            released,
+            reward,
+        });
+
+        Ok(())
+    }
+
+    pub fn settle_deadline(ctx: Context<SettleDeadline>) -> Result<()> {
+        // Delegate the settlement to the accounts implementation
+        let (consequence, returned, donated, locked_until) = ctx.accounts.settle_deadline()?;
+
+        emit_cpi!(DeadlineSettled {
+            owner: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            consequence,
+            returned,
+            donated,
+            locked_until,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_rollover(ctx: Context<SetRollover>, increase_bps: u16) -> Result<()> {
+        // Delegate the setting to the accounts implementation
+        ctx.accounts.set_rollover(increase_bps, &ctx.bumps)?;
+
+        emit_cpi!(RolloverSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            increase_bps,
+        });
+
+        Ok(())
+    }
+
+    pub fn clear_rollover(ctx: Context<ClearRollover>) -> Result<()> {
+        // The rollover account is closed by the accounts constraints
+        emit_cpi!(RolloverCleared {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn set_price_condition(
+        ctx: Context<SetPriceCondition>,
+        price_account: Pubkey,
+        threshold_cents: u64,
+        direction: PriceDirection,
+    ) -> Result<()> {
+        // Delegate the condition setup to the accounts implementation
+        ctx.accounts
+            .set_price_condition(price_account, threshold_cents, direction, &ctx.bumps)?;
+
+        emit_cpi!(PriceConditionSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            price_account,
+            threshold_cents,
+            direction,
+        });
+
+        Ok(())
+    }
+
+    pub fn clear_price_condition(ctx: Context<ClearPriceCondition>) -> Result<()> {
+        // The condition account is closed by the accounts constraints
+        emit_cpi!(PriceConditionCleared {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn check_price_condition(ctx: Context<CheckPriceCondition>) -> Result<()> {
+        // Delegate the check and release to the accounts implementation
+        let (price_cents, released) = ctx.accounts.check_price_condition()?;
+
+        emit_cpi!(PriceConditionTriggered {
+            owner: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            caller: ctx.accounts.caller.key(),
+            price_cents,
+            released,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_mint_stats(ctx: Context<InitMintStats>) -> Result<()> {
+        // Delegate the initialization to the accounts implementation
+        ctx.accounts.init_mint_stats(&ctx.bumps);
+
+        emit_cpi!(MintStatsCreated {
+            mint: ctx.accounts.mint.key(),
+            mint_stats: ctx.accounts.mint_stats.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn migrate_vault(ctx: Context<MigrateVault>) -> Result<()> {
+        // Delegate the migration to the accounts implementation
+        ctx.accounts.migrate_vault()?;
+
+        emit_cpi!(VaultMigrated {
+            owner: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            version: Vault::VERSION,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_vault_extension(ctx: Context<InitVaultExtension>) -> Result<()> {
+        // Delegate the initialization to the accounts implementation
+        ctx.accounts.init_vault_extension(&ctx.bumps)?;
+
+        emit_cpi!(VaultExtensionCreated {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            extension: ctx.accounts.extension.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn record_balance_snapshot(ctx: Context<RecordBalanceSnapshot>) -> Result<()> {
+        // Delegate the snapshot to the accounts implementation
+        let snapshot = ctx.accounts.record_balance_snapshot()?;
+
+        emit_cpi!(BalanceSnapshotRecorded {
+            owner: ctx.accounts.owner.key(),
+            state: ctx.accounts.state.key(),
+            timestamp: snapshot.timestamp,
+            balance: snapshot.balance,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_points(ctx: Context<ClaimPoints>) -> Result<()> {
+        // Delegate the claim to the accounts implementation
+        let points = ctx.accounts.claim_points()?;
+
+        emit_cpi!(PointsClaimed {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            points,
+            total_claimed: ctx.accounts.rewards.claimed_points,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_badge(ctx: Context<ClaimBadge>, badge: Badge) -> Result<()> {
+        // Delegate the mint to the accounts implementation
+        ctx.accounts.claim_badge(badge, &ctx.bumps)?;
+
+        emit_cpi!(BadgeClaimed {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            badge,
+            badge_mint: ctx.accounts.badge_mint.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn close_vault(ctx: Context<CloseVault>) -> Result<()> {
+        // Delegate the close logic to the accounts implementation
+        ctx.accounts.close_vault()?;
+
+        emit_cpi!(VaultClosed {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn finalize(ctx: Context<Finalize>) -> Result<()> {
+        // Delegate the release and close logic to the accounts implementation
+        let released = ctx.accounts.finalize()?;
+
+        emit_cpi!(VaultCompleted {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            released,
+        });
+
+        Ok(())
+    }
+
+    pub fn get_vault_status(ctx: Context<GetVaultStatus>) -> Result<VaultStatus> {
+        // Read-only: the status is returned to the caller through return data
+        ctx.accounts.get_vault_status()
+    }
+
+    pub fn add_note(ctx: Context<AddNote>, text: String) -> Result<()> {
+        // Delegate the note logic to the accounts implementation
+        ctx.accounts.add_note(text, &ctx.bumps)?;
+
+        emit_cpi!(NoteAdded {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            count: ctx.accounts.notes.count,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        // Delegate the config setup to the accounts implementation
+        ctx.accounts.initialize_config(&ctx.bumps)?;
+
+        emit_cpi!(ConfigInitialized {
+            admin: ctx.accounts.admin.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_deposit_hook(ctx: Context<ApproveDepositHook>) -> Result<()> {
+        // Delegate the approval to the accounts implementation
+        ctx.accounts.approve_deposit_hook(&ctx.bumps)?;
+
+        emit_cpi!(DepositHookApproved {
+            program_id: ctx.accounts.hook_program.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_deposit_hook(ctx: Context<RevokeDepositHook>) -> Result<()> {
+        // The approval account is closed by the accounts constraints
+        emit_cpi!(DepositHookRevoked {
+            program_id: ctx.accounts.approved_hook.program_id,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_swap_program(ctx: Context<ApproveSwapProgram>) -> Result<()> {
+        // Delegate the approval to the accounts implementation
+        ctx.accounts.approve_swap_program(&ctx.bumps)?;
+
+        emit_cpi!(SwapProgramApproved {
+            program_id: ctx.accounts.swap_program.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_swap_program(ctx: Context<RevokeSwapProgram>) -> Result<()> {
+        // The approval account is closed by the accounts constraints
+        emit_cpi!(SwapProgramRevoked {
+            program_id: ctx.accounts.approved_swap.program_id,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_mint(ctx: Context<ApproveMint>) -> Result<()> {
+        // Delegate the approval to the accounts implementation
+        ctx.accounts.approve_mint(&ctx.bumps)?;
+
+        emit_cpi!(MintApproved {
+            mint: ctx.accounts.mint.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn set_price_feed(ctx: Context<SetPriceFeed>, feed_id: [u8; 32]) -> Result<()> {
+        // Delegate the update to the accounts implementation
+        ctx.accounts.set_price_feed(feed_id, &ctx.bumps)?;
+
+        emit_cpi!(PriceFeedSet {
+            mint: ctx.accounts.mint.key(),
+            feed_id,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_mint(ctx: Context<RevokeMint>) -> Result<()> {
+        // The approval account is closed by the accounts constraints
+        emit_cpi!(MintRevoked {
+            mint: ctx.accounts.approved_mint.mint,
+        });
+
+        Ok(())
+    }
+
+    pub fn block_address(ctx: Context<BlockAddress>, address: Pubkey) -> Result<()> {
+        // Delegate the blocklist entry to the accounts implementation
+        ctx.accounts.block_address(address, &ctx.bumps)?;
+
+        emit_cpi!(AddressBlocked { address });
+
+        Ok(())
+    }
+
+    pub fn unblock_address(ctx: Context<UnblockAddress>) -> Result<()> {
+        // The blocklist entry is closed by the accounts constraints
+        emit_cpi!(AddressUnblocked {
+            address: ctx.accounts.blocked_address.address,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_goal_template(
+        ctx: Context<SetGoalTemplate>,
+        template_id: u16,
+        terms: GoalTemplateTerms,
+    ) -> Result<()> {
+        // Delegate the template update to the accounts implementation
+        ctx.accounts
+            .set_goal_template(template_id, terms.clone(), &ctx.bumps)?;
+
+        emit_cpi!(GoalTemplateSet { template_id, terms });
+
+        Ok(())
+    }
+
+    pub fn remove_goal_template(ctx: Context<RemoveGoalTemplate>) -> Result<()> {
+        // The template account is closed by the accounts constraints
+        emit_cpi!(GoalTemplateRemoved {
+            template_id: ctx.accounts.template.template_id,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_mint_allowlist(ctx: Context<SetMintAllowlist>, enabled: bool) -> Result<()> {
+        // Delegate the switch to the accounts implementation
+        ctx.accounts.set_mint_allowlist(enabled)?;
+
+        emit_cpi!(MintAllowlistSet { enabled });
+
+        Ok(())
+    }
+
+    pub fn set_reject_freezable_mints(
+        ctx: Context<SetRejectFreezableMints>,
+        enabled: bool,
+    ) -> Result<()> {
+        // Delegate the switch to the accounts implementation
+        ctx.accounts.set_reject_freezable_mints(enabled)?;
+
+        emit_cpi!(RejectFreezableMintsSet { enabled });
+
+        Ok(())
+    }
+
+    pub fn set_same_slot_guard(ctx: Context<SetSameSlotGuard>, enabled: bool) -> Result<()> {
+        // Delegate the switch to the accounts implementation
+        ctx.accounts.set_same_slot_guard(enabled)?;
+
+        emit_cpi!(SameSlotGuardSet { enabled });
+
+        Ok(())
+    }
+
+    pub fn set_surplus_policy(ctx: Context<SetSurplusPolicy>, policy: SurplusPolicy) -> Result<()> {
+        // Delegate the policy to the accounts implementation
+        ctx.accounts.set_surplus_policy(policy)?;
+
+        emit_cpi!(SurplusPolicySet { policy });
+
+        Ok(())
+    }
+
+    pub fn set_max_lock_duration(ctx: Context<SetMaxLockDuration>, seconds: i64) -> Result<()> {
+        // Delegate the bound to the accounts implementation
+        ctx.accounts.set_max_lock_duration(seconds)?;
+
+        emit_cpi!(MaxLockDurationSet { seconds });
+
+        Ok(())
+    }
+
+    pub fn set_deposit_fee(
+        ctx: Context<SetDepositFee>,
+        fee_bps: u16,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        // Delegate the update to the accounts implementation
+        ctx.accounts.set_deposit_fee(fee_bps, treasury)?;
+
+        emit_cpi!(DepositFeeSet { fee_bps, treasury });
+
+        Ok(())
+    }
+
+    pub fn set_fee_tiers(
+        ctx: Context<SetFeeTiers>,
+        membership_mint: Pubkey,
+        tiers: Vec<FeeTier>,
+    ) -> Result<()> {
+        // Delegate the update to the accounts implementation
+        ctx.accounts
+            .set_fee_tiers(membership_mint, tiers.clone(), &ctx.bumps)?;
+
+        emit_cpi!(FeeTiersSet {
+            membership_mint,
+            tiers,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_credential_gate(
+        ctx: Context<SetCredentialGate>,
+        credential_mint: Pubkey,
+    ) -> Result<()> {
+        // Delegate the update to the accounts implementation
+        ctx.accounts
+            .set_credential_gate(credential_mint, &ctx.bumps)?;
+
+        emit_cpi!(CredentialGateSet { credential_mint });
+
+        Ok(())
+    }
+
+    pub fn remove_credential_gate(ctx: Context<RemoveCredentialGate>) -> Result<()> {
+        // The gate account is closed by the accounts constraints
+        emit_cpi!(CredentialGateRemoved {
+            credential_mint: ctx.accounts.credential_gate.credential_mint,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_creation_gate(
+        ctx: Context<SetCreationGate>,
+        membership_mint: Pubkey,
+        min_balance: u64,
+    ) -> Result<()> {
+        // Delegate the update to the accounts implementation
+        ctx.accounts
+            .set_creation_gate(membership_mint, min_balance, &ctx.bumps)?;
+
+        emit_cpi!(CreationGateSet {
+            membership_mint,
+            min_balance,
+        });
+
+        Ok(())
+    }
+
+    pub fn remove_creation_gate(ctx: Context<RemoveCreationGate>) -> Result<()> {
+        // The gate account is closed by the accounts constraints
+        emit_cpi!(CreationGateRemoved {
+            membership_mint: ctx.accounts.creation_gate.membership_mint,
+        });
+
+        Ok(())
+    }
+
+    pub fn admin_freeze_vault(
+        ctx: Context<AdminFreezeVault>,
+        duration_seconds: i64,
+        block_deposits: bool,
+    ) -> Result<()> {
+        // Delegate the freeze to the accounts implementation
+        ctx.accounts.admin_freeze_vault(duration_seconds, block_deposits)?;
+
+        emit_cpi!(VaultFrozen {
+            admin: ctx.accounts.admin.key(),
+            state: ctx.accounts.state.key(),
+            frozen_until: ctx.accounts.state.frozen_until.unwrap_or_default(),
+            block_deposits,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_deposit_hook(ctx: Context<SetDepositHook>, hook: Option<Pubkey>) -> Result<()> {
+        // Delegate the hook update to the accounts implementation
+        ctx.accounts.set_deposit_hook(hook)?;
+
+        emit_cpi!(DepositHookSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            hook,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_vault_key(
+        ctx: Context<SetVaultKey>,
+        vault_key: Option<Pubkey>,
+        threshold: u64,
+    ) -> Result<()> {
+        // Delegate the vault key update to the accounts implementation
+        ctx.accounts.set_vault_key(vault_key, threshold)?;
+
+        emit_cpi!(VaultKeySet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            vault_key,
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_balance_cap_bounds(
+        ctx: Context<SetBalanceCapBounds>,
+        min_cap: u64,
+        max_cap: u64,
+    ) -> Result<()> {
+        // Delegate the bounds update to the accounts implementation
+        ctx.accounts.set_balance_cap_bounds(min_cap, max_cap)?;
+
+        emit_cpi!(BalanceCapBoundsSet { min_cap, max_cap });
+
+        Ok(())
+    }
+
+    pub fn set_balance_cap(
+        ctx: Context<SetBalanceCap>,
+        cap: Option<u64>,
+        policy: CapPolicy,
+    ) -> Result<()> {
+        // Delegate the cap update to the accounts implementation
+        ctx.accounts.set_balance_cap(cap, policy)?;
+
+        emit_cpi!(BalanceCapSet {
+            user: ctx.accounts.user.key(),
+            state: ctx.accounts.state.key(),
+            cap,
+            policy,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_circle(
+        ctx: Context<CreateCircle>,
+        circle_id: u64,
+        members: Vec<Pubkey>,
+        contribution: u64,
+        period_seconds: i64,
+    ) -> Result<()> {
+        // Delegate the validation to the accounts implementation
+        let start_ts = ctx.accounts.create_circle(
+            circle_id,
+            members.clone(),
+            contribution,
+            period_seconds,
+            &ctx.bumps,
+        )?;
+
+        emit_cpi!(CircleCreated {
+            creator: ctx.accounts.creator.key(),
+            circle: ctx.accounts.circle.key(),
+            mint: ctx.accounts.mint.key(),
+            members,
+            contribution,
+            period_seconds,
+            start_ts,
+        });
+
+        Ok(())
+    }
+
+    pub fn contribute_to_circle(ctx: Context<ContributeToCircle>) -> Result<()> {
+        // Delegate the contribution to the accounts implementation
+        let round = ctx.accounts.contribute_to_circle()?;
+
+        emit_cpi!(CircleContributed {
+            member: ctx.accounts.member.key(),
+            circle: ctx.accounts.circle.key(),
+            round,
+            amount: ctx.accounts.circle.contribution,
+        });
+
+        Ok(())
+    }
+
+    pub fn payout_circle(ctx: Context<PayoutCircle>) -> Result<()> {
+        // Delegate the payout to the accounts implementation
+        let (round, paid_out, defaulters) = ctx.accounts.payout_circle()?;
+
+        for member in defaulters {
+            emit_cpi!(CircleMemberDefaulted {
+                circle: ctx.accounts.circle.key(),
+                member,
+                round,
+            });
+        }
+        emit_cpi!(CirclePaidOut {
+            circle: ctx.accounts.circle.key(),
+            recipient: ctx.accounts.recipient.key(),
+            round,
+            amount: paid_out.unwrap_or(0),
+            forfeited: paid_out.is_none(),
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_circle_refund(ctx: Context<ClaimCircleRefund>) -> Result<()> {
+        // Delegate the refund to the accounts implementation
+        let amount = ctx.accounts.claim_circle_refund()?;
+
+        emit_cpi!(CircleRefundClaimed {
+            member: ctx.accounts.member.key(),
+            circle: ctx.accounts.circle.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_circle(ctx: Context<CloseCircle>) -> Result<()> {
+        // Delegate the token account close to the accounts implementation
+        ctx.accounts.close_circle()?;
+
+        emit_cpi!(CircleClosed {
+            creator: ctx.accounts.creator.key(),
+            circle: ctx.accounts.circle.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn create_payroll(
+        ctx: Context<CreatePayroll>,
+        amount_per_period: u64,
+        period_seconds: i64,
+    ) -> Result<()> {
+        // Delegate the validation to the accounts implementation
+        let start_ts =
+            ctx.accounts
+                .create_payroll(amount_per_period, period_seconds, &ctx.bumps)?;
+
+        emit_cpi!(PayrollCreated {
+            employer: ctx.accounts.employer.key(),
+            employee: ctx.accounts.employee.key(),
+            payroll: ctx.accounts.payroll.key(),
+            mint: ctx.accounts.mint.key(),
+            amount_per_period,
+            period_seconds,
+            start_ts,
+        });
+
+        Ok(())
+    }
+
+    pub fn fund_payroll(ctx: Context<FundPayroll>, amount: u64) -> Result<()> {
+        // Delegate the transfer to the accounts implementation
+        ctx.accounts.fund_payroll(amount)?;
+
+        emit_cpi!(PayrollFunded {
+            employer: ctx.accounts.employer.key(),
+            payroll: ctx.accounts.payroll.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_payroll(ctx: Context<ClaimPayroll>) -> Result<()> {
+        // Delegate the payout to the accounts implementation
+        let amount = ctx.accounts.claim_payroll()?;
+
+        emit_cpi!(PayrollClaimed {
+            employee: ctx.accounts.employee.key(),
+            payroll: ctx.accounts.payroll.key(),
+            amount,
+            total_claimed: ctx.accounts.payroll.claimed,
+        });
+
+        Ok(())
+    }
+
+    pub fn stop_payroll(ctx: Context<StopPayroll>) -> Result<()> {
+        // Delegate the refund to the accounts implementation
+        let (stopped_at, refunded) = ctx.accounts.stop_payroll()?;
+
+        emit_cpi!(PayrollStopped {
+            employer: ctx.accounts.employer.key(),
+            payroll: ctx.accounts.payroll.key(),
+            stopped_at,
+            refunded,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_payroll(ctx: Context<ClosePayroll>) -> Result<()> {
+        // Delegate the token account close to the accounts implementation
+        ctx.accounts.close_payroll()?;
+
+        emit_cpi!(PayrollClosed {
+            employer: ctx.accounts.employer.key(),
+            payroll: ctx.accounts.payroll.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn create_escrow(
+        ctx: Context<CreateEscrow>,
+        escrow_id: u64,
+        counterparty: Pubkey,
+        arbiter: Option<Pubkey>,
+        amount: u64,
+        timeout_ts: i64,
+    ) -> Result<()> {
+        // Delegate the validation and the deposit to the accounts implementation
+        ctx.accounts.create_escrow(
+            escrow_id,
+            counterparty,
+            arbiter,
+            amount,
+            timeout_ts,
+            &ctx.bumps,
+        )?;
+
+        emit_cpi!(EscrowCreated {
+            depositor: ctx.accounts.depositor.key(),
+            escrow: ctx.accounts.escrow.key(),
+            counterparty,
+            arbiter,
+            mint: ctx.accounts.mint.key(),
+            amount,
+            timeout_ts,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_escrow(ctx: Context<ApproveEscrow>) -> Result<()> {
+        // Delegate the approval to the accounts implementation
+        ctx.accounts.approve_escrow()?;
+
+        emit_cpi!(EscrowApproved {
+            escrow: ctx.accounts.escrow.key(),
+            approver: ctx.accounts.approver.key(),
+            releasable: ctx.accounts.escrow.is_releasable(),
+        });
+
+        Ok(())
+    }
+
+    pub fn release_escrow(ctx: Context<ReleaseEscrow>) -> Result<()> {
+        // Delegate the payout to the accounts implementation
+        let amount = ctx.accounts.release_escrow()?;
+
+        emit_cpi!(EscrowReleased {
+            escrow: ctx.accounts.escrow.key(),
+            counterparty: ctx.accounts.counterparty.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn refund_escrow(ctx: Context<RefundEscrow>) -> Result<()> {
+        // Delegate the refund to the accounts implementation
+        let amount = ctx.accounts.refund_escrow()?;
+
+        emit_cpi!(EscrowRefunded {
+            escrow: ctx.accounts.escrow.key(),
+            depositor: ctx.accounts.depositor.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_htlc(
+        ctx: Context<CreateHtlc>,
+        htlc_id: u64,
+        counterparty: Pubkey,
+        hashlock: [u8; 32],
+        amount: u64,
+        timeout_ts: i64,
+    ) -> Result<()> {
+        // Delegate the validation and the deposit to the accounts implementation
+        ctx.accounts.create_htlc(
+            htlc_id,
+            counterparty,
+            hashlock,
+            amount,
+            timeout_ts,
+            &ctx.bumps,
+        )?;
+
+        emit_cpi!(HtlcCreated {
+            owner: ctx.accounts.owner.key(),
+            htlc: ctx.accounts.htlc.key(),
+            counterparty,
+            mint: ctx.accounts.mint.key(),
+            amount,
+            hashlock,
+            timeout_ts,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_htlc(ctx: Context<ClaimHtlc>, preimage: [u8; 32]) -> Result<()> {
+        // Delegate the payout to the accounts implementation
+        let amount = ctx.accounts.claim_htlc(preimage)?;
+
+        // The preimage is published so the other leg of the swap can be claimed with it
+        emit_cpi!(HtlcClaimed {
+            htlc: ctx.accounts.htlc.key(),
+            counterparty: ctx.accounts.counterparty.key(),
+            amount,
+            preimage,
+        });
+
+        Ok(())
+    }
+
+    pub fn refund_htlc(ctx: Context<RefundHtlc>) -> Result<()> {
+        // Delegate the refund to the accounts implementation
+        let amount = ctx.accounts.refund_htlc()?;
+
+        emit_cpi!(HtlcRefunded {
+            htlc: ctx.accounts.htlc.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_leaderboard(ctx: Context<UpdateLeaderboard>) -> Result<()> {
+        // Delegate the ranking to the accounts implementation
+        let (balance, streak_days, balance_rank, streak_rank) =
+            ctx.accounts.update_leaderboard(&ctx.bumps)?;
+
+        emit_cpi!(LeaderboardUpdated {
+            mint: ctx.accounts.mint.key(),
+            state: ctx.accounts.state.key(),
+            balance,
+            streak_days,
+            balance_rank,
+            streak_rank,
+        });
+
+        Ok(())
+    }
+}
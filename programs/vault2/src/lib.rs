@@ -1,374 +1,14928 @@
+
 // Import necessary dependencies for Anchor framework and SPL token operations
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer as TokenTransfer};
+use anchor_spl::associated_token::{self, AssociatedToken};
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::system_program;
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, TransferChecked};
+use anchor_spl::token_2022::spl_token_2022::extension::ExtensionType;
+use anchor_spl::token_2022::spl_token_2022::instruction::AuthorityType;
+use anchor_spl::token_2022::{self, Token2022};
+use anchor_spl::token_2022_extensions::{self, NonTransferableMintInitialize};
+use anchor_spl::token_interface::TokenAccount as InterfaceTokenAccount;
 
 // Declare the program ID - this is the unique identifier for our vault program
 declare_id!("6Xf5BppD241vj5Pw5nYTpU78MEyvkQ5N77cCxdyB1rjH");
 
-#[program]
-pub mod vault2 {
-    use super::*;
+/// Denominator for values expressed in basis points
+#[constant]
+pub const BPS_DENOMINATOR: u64 = 10_000;
 
-    pub fn initialize(ctx: Context<Initialize>, amount: u64, mint: Pubkey) -> Result<()> {
-        // Delegate the actual initialization logic to the accounts implementation
-        ctx.accounts.initialize(amount, mint, &ctx.bumps)?;
+/// Shortest lock accepted by `lock_tokens` (in seconds)
+#[constant]
+pub const MIN_LOCK_DURATION_SECONDS: i64 = 1;
 
-        Ok(())
-    }
+/// Longest lock accepted by `lock_tokens` (10 years, in seconds). The admin can lower it
+/// with `set_max_lock_duration`.
+#[constant]
+pub const MAX_LOCK_DURATION_SECONDS: i64 = 10 * 365 * 24 * 60 * 60;
 
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        // Delegate the deposit logic to the accounts implementation
-        ctx.accounts.deposit(amount)?;
+/// Share of a cranked release paid to the cranker (in basis points, 0.1%)
+#[constant]
+pub const CRANK_REWARD_BPS: u64 = 10;
 
-        Ok(())
-    }
+/// Length of a deposit streak (in consecutive days) that earns a milestone bonus
+#[constant]
+pub const STREAK_MILESTONE_DAYS: u16 = 7;
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        // Delegate the withdraw logic to the accounts implementation
-        ctx.accounts.withdraw(amount)?;
+/// Points awarded for each streak milestone (one whole token saved for a day)
+#[constant]
+pub const STREAK_MILESTONE_POINTS: u64 = 24 * 60 * 60;
 
-        Ok(())
-    }
+/// Length of a deposit streak (in consecutive days) that earns the streak badge
+#[constant]
+pub const BADGE_STREAK_DAYS: u16 = 30;
 
-    pub fn lock_tokens(ctx: Context<LockTokens>, duration_seconds: i64) -> Result<()> {
-        // Delegate the lock logic to the accounts implementation
-        ctx.accounts.lock_tokens(duration_seconds)?;
+/// Longest freeze the admin can put on a vault (7 days, in seconds)
+#[constant]
+pub const MAX_FREEZE_DURATION_SECONDS: i64 = 7 * 24 * 60 * 60;
 
-        Ok(())
-    }
-}
+/// Longest freeze an owner can put on their own withdrawals at once (1 year, in seconds)
+#[constant]
+pub const MAX_SELF_FREEZE_SECONDS: i64 = 365 * 24 * 60 * 60;
+
+/// Latest release date a custodial vault can be given (21 years ahead, in seconds)
+#[constant]
+pub const MAX_CUSTODY_SECONDS: i64 = 21 * 365 * 24 * 60 * 60;
+
+/// Time after a freeze expires during which the vault can't be frozen again (1 day, in seconds)
+#[constant]
+pub const FREEZE_COOLDOWN_SECONDS: i64 = 24 * 60 * 60;
+
+/// Longest withdrawal cooldown an owner can set on a vault (30 days, in seconds)
+#[constant]
+pub const MAX_WITHDRAW_COOLDOWN_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+/// Length of the window over which `max_withdraw_per_day` applies (1 day, in seconds)
+#[constant]
+pub const WITHDRAW_LIMIT_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+
+/// Longest withdrawal delay an owner can set on a vault (30 days, in seconds)
+#[constant]
+pub const MAX_WITHDRAWAL_DELAY_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+/// Most approvers a vault can require withdrawals to be approved by
+#[constant]
+pub const MAX_WITHDRAWAL_APPROVERS: usize = 5;
+
+/// Most guardians a vault can register for social recovery
+#[constant]
+pub const MAX_GUARDIANS: usize = 5;
+
+/// Most members a savings circle can have
+#[constant]
+pub const MAX_CIRCLE_MEMBERS: usize = 10;
+
+/// Shortest round a savings circle can have (1 day, in seconds)
+#[constant]
+pub const MIN_CIRCLE_PERIOD_SECONDS: i64 = 24 * 60 * 60;
+
+/// Number of vaults ranked on each leaderboard of a mint
+#[constant]
+pub const LEADERBOARD_SIZE: usize = 10;
+
+/// Time the owner has to veto a recovery proposal before it can be executed (3 days, in seconds)
+#[constant]
+pub const RECOVERY_DELAY_SECONDS: i64 = 3 * 24 * 60 * 60;
+
+/// Largest increase of a vault's target when it rolls over into a new goal (100%, in basis
+/// points)
+#[constant]
+pub const MAX_ROLLOVER_INCREASE_BPS: u16 = 10_000;
+
+/// Largest advance an owner can take against their locked balance (50%, in basis points)
+#[constant]
+pub const MAX_ADVANCE_BPS: u64 = 5_000;
+
+/// Fee added to an advance's debt and paid to the treasury on repayment (1%, in basis points).
+/// Advances are free while no treasury is set.
+#[constant]
+pub const ADVANCE_FEE_BPS: u64 = 100;
+
+/// Maximum length of a goal template's name (in bytes)
+#[constant]
+pub const MAX_TEMPLATE_NAME_LEN: usize = 32;
+
+/// Highest protocol fee the admin can charge on deposits (5%, in basis points)
+#[constant]
+pub const MAX_DEPOSIT_FEE_BPS: u16 = 500;
+
+/// Most fee discount tiers the admin can set
+#[constant]
+pub const MAX_FEE_TIERS: usize = 4;
+
+/// Longest memo that can be attached to a deposit or withdrawal, in bytes
+#[constant]
+pub const MAX_MEMO_LEN: usize = 128;
+
+/// Number of balance snapshots a vault extension keeps before overwriting the oldest
+#[constant]
+pub const EXTENSION_HISTORY_LEN: usize = 32;
+
+/// Time that must pass between two balance snapshots of a vault (1 hour, in seconds)
+#[constant]
+pub const SNAPSHOT_INTERVAL_SECONDS: i64 = 60 * 60;
+
+/// Zeroed space at the end of the vault extension, for future fixed-size arrays
+#[constant]
+pub const EXTENSION_RESERVED_BYTES: usize = 480;
+
+/// Targets must be at least 1 / 10^MIN_TARGET_FRACTION_DIGITS of a whole token (one cent)
+#[constant]
+pub const MIN_TARGET_FRACTION_DIGITS: u8 = 2;
+
+/// Number of independent lock tranches a vault can hold at once
+#[constant]
+pub const MAX_LOCK_TRANCHES: usize = 4;
+
+/// Number of activity records kept in a vault's outbox before the oldest is overwritten
+#[constant]
+pub const OUTBOX_CAPACITY: usize = 16;
+
+/// Number of notes kept on a vault before the oldest is overwritten
+#[constant]
+pub const NOTES_CAPACITY: usize = 8;
+
+/// Maximum length of a single note (in bytes)
+#[constant]
+pub const MAX_NOTE_LENGTH: usize = 64;
+
+// Reserved space: accounts that are expected to grow carry a zeroed `reserved` tail so new
+// fields can be added without reallocating existing accounts. A new field takes its bytes
+// from the tail (shrink the matching `*_RESERVED_BYTES` by the field's size), which keeps
+// the `*_ACCOUNT_SIZE` constants, and every account already on chain, unchanged. The
+// assertions below the account definitions fail to compile if the two drift apart.
+// New boolean settings take a free bit of `Vault::flags` (a `VAULT_FLAG_*` constant) instead
+// of a byte each, and new optional values use a sentinel (0 for unset) rather than an
+// `Option`, whose encoded size varies and would move every field after it.
+
+/// Bytes reserved for future fields at the end of a `Vault`
+#[constant]
+pub const VAULT_RESERVED_BYTES: usize = 124;
+
+/// Total size of a `Vault` account, including the discriminator
+#[constant]
+pub const VAULT_ACCOUNT_SIZE: usize = 486;
+
+/// Size of a `Vault` account created before the layout version and reserve were added. Such
+/// vaults must be grown with `migrate_vault` before any other instruction can read them
+#[constant]
+pub const VAULT_V1_ACCOUNT_SIZE: usize = 358;
+
+/// `Vault::flags` bit set while the current admin freeze also blocks deposits
+#[constant]
+pub const VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS: u8 = 1 << 0;
+
+/// Bytes reserved for future fields at the end of the `Config`
+#[constant]
+pub const CONFIG_RESERVED_BYTES: usize = 18;
+
+/// Total size of the `Config` account, including the discriminator
+#[constant]
+pub const CONFIG_ACCOUNT_SIZE: usize = 121;
+
+/// Bytes reserved for future fields at the end of a `RewardState`
+#[constant]
+pub const REWARDS_RESERVED_BYTES: usize = 46;
+
+/// Total size of a `RewardState` account, including the discriminator
+#[constant]
+pub const REWARDS_ACCOUNT_SIZE: usize = 155;
+
+/// Largest age of an oracle price used to value a vault (in seconds)
+#[constant]
+pub const MAX_PRICE_AGE_SECONDS: i64 = 60;
+
+/// Largest oracle confidence interval accepted, relative to the price (2%, in basis points)
+#[constant]
+pub const MAX_PRICE_CONFIDENCE_BPS: u64 = 200;
+
+// PDA seeds, published in the IDL so clients derive addresses without copying them
+
+/// Seed prefix of the vault state, with the owner's key
+#[constant]
+pub const STATE_SEED: &[u8] = b"state";
+
+/// Seed prefix of the vault authority, with the state address
+#[constant]
+pub const VAULT_SEED: &[u8] = b"vault";
+
+/// Seed prefix of the activity outbox, with the state address
+#[constant]
+pub const OUTBOX_SEED: &[u8] = b"outbox";
+
+/// Seed prefix of the reward points, with the state address
+#[constant]
+pub const REWARDS_SEED: &[u8] = b"rewards";
+
+/// Seed prefix of the notes, with the state address
+#[constant]
+pub const NOTES_SEED: &[u8] = b"notes";
+
+/// Seed prefix of the vault extension, with the state address
+#[constant]
+pub const EXTENSION_SEED: &[u8] = b"extension";
+
+/// Seed prefix of the price condition, with the state address
+#[constant]
+pub const PRICE_CONDITION_SEED: &[u8] = b"price_condition";
+
+/// Seed prefix of a mint's expected Pyth price feed, with the mint address
+#[constant]
+pub const PRICE_FEED_SEED: &[u8] = b"price_feed";
+
+/// Seed prefix of the withdrawal policy, with the state address
+#[constant]
+pub const WITHDRAWAL_POLICY_SEED: &[u8] = b"withdrawal_policy";
+
+/// Seed prefix of the pending withdrawal, with the state address
+#[constant]
+pub const WITHDRAWAL_SEED: &[u8] = b"withdrawal";
+
+/// Seed prefix of the self-exclusion, with the state address
+#[constant]
+pub const SELF_EXCLUSION_SEED: &[u8] = b"self_exclusion";
+
+/// Seed prefix of the unlock stream, with the state address
+#[constant]
+pub const UNLOCK_SCHEDULE_SEED: &[u8] = b"unlock_schedule";
+
+/// Seed prefix of the guardians, with the state address
+#[constant]
+pub const GUARDIANS_SEED: &[u8] = b"guardians";
+
+/// Seed prefix of the recovery proposal, with the state address
+#[constant]
+pub const RECOVERY_SEED: &[u8] = b"recovery";
+
+/// Seed prefix of the session key, with the state address and the delegate's key
+#[constant]
+pub const DELEGATE_SEED: &[u8] = b"delegate";
+
+/// Seed prefix of a subscription grant, with the state address and the merchant's key
+#[constant]
+pub const SUBSCRIPTION_SEED: &[u8] = b"subscription";
 
+/// Seed prefix of the contribution record, with the state address and the depositor's key
+#[constant]
+pub const CONTRIBUTION_SEED: &[u8] = b"contribution";
+
+/// Seed prefix of a savings circle, with the creator's key and the circle id
+#[constant]
+pub const CIRCLE_SEED: &[u8] = b"circle";
+
+/// Seed prefix of a mint's leaderboard, with the mint address
+#[constant]
+pub const LEADERBOARD_SEED: &[u8] = b"leaderboard";
+
+/// Seed prefix of a badge mint, with the owner's key and the badge index
+#[constant]
+pub const BADGE_SEED: &[u8] = b"badge";
+
+/// Seed prefix of a custodial vault's custody record, with the vault state address
+#[constant]
+pub const CUSTODY_SEED: &[u8] = b"custody";
+
+/// Seed prefix of a vault's rollover setting, with the vault state address
+#[constant]
+pub const ROLLOVER_SEED: &[u8] = b"rollover";
+
+/// Seed prefix of a vault's outstanding advance, with the vault state address
+#[constant]
+pub const ADVANCE_SEED: &[u8] = b"advance";
+
+/// Seed prefix of a goal template, with the template id
+#[constant]
+pub const TEMPLATE_SEED: &[u8] = b"template";
+
+/// Seed prefix of the template terms a vault was created with, with the vault state address
+#[constant]
+pub const VAULT_TEMPLATE_SEED: &[u8] = b"vault_template";
+
+/// Seed prefix of a payroll, with the employer's and the employee's keys
+#[constant]
+pub const PAYROLL_SEED: &[u8] = b"payroll";
+
+/// Seed prefix of an escrow, with the depositor's key and the escrow id
+#[constant]
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+/// Seed prefix of a hashlock/timelock contract, with the owner's key and the contract id
+#[constant]
+pub const HTLC_SEED: &[u8] = b"htlc";
+
+/// Seed prefix of the mint stats, with the mint address
+#[constant]
+pub const STATS_SEED: &[u8] = b"stats";
+
+/// Seed prefix of the program config
+#[constant]
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Seed prefix of the config history
+#[constant]
+pub const CONFIG_HISTORY_SEED: &[u8] = b"config_history";
+
+/// Seed prefix of the fee tiers
+#[constant]
+pub const FEE_TIERS_SEED: &[u8] = b"fee_tiers";
+
+/// Seed prefix of the credential gate
+#[constant]
+pub const CREDENTIAL_GATE_SEED: &[u8] = b"credential_gate";
+
+/// Seed prefix of the vault creation gate
+#[constant]
+pub const CREATION_GATE_SEED: &[u8] = b"creation_gate";
+
+/// Seed prefix of the mint approval, with the mint address
+#[constant]
+pub const MINT_SEED: &[u8] = b"mint";
+
+/// Seed prefix of the deposit hook approval, with the hook program id
+#[constant]
+pub const HOOK_SEED: &[u8] = b"hook";
+
+/// Seed prefix of the swap program approval, with the swap program id
+#[constant]
+pub const SWAP_SEED: &[u8] = b"swap";
+
+/// Seed prefix of a blocklist entry, with the blocked address
+#[constant]
+pub const BLOCKED_SEED: &[u8] = b"blocked";
+
+/// Seed prefix of a vault's auto-save authorization, with the vault state address
+#[constant]
+pub const AUTO_SAVE_SEED: &[u8] = b"auto_save";
+
+// Layout offsets (including the discriminator), published in the IDL so indexers can build
+// `memcmp` filters without decoding every account
+
+/// Offset of `Vault::mint`
+#[constant]
+pub const VAULT_MINT_OFFSET: usize = 18;
+
+/// Offset of `Vault::vault_token_account`
+#[constant]
+pub const VAULT_TOKEN_ACCOUNT_OFFSET: usize = 50;
+
+/// Offset of the `state` field every per-vault child account starts with
+#[constant]
+pub const CHILD_STATE_OFFSET: usize = 8;
+
+/// Offset of `Contribution::contributor`
+#[constant]
+pub const CONTRIBUTION_CONTRIBUTOR_OFFSET: usize = 40;
+
+/// Offset of `SessionKey::delegate`
+#[constant]
+pub const SESSION_DELEGATE_OFFSET: usize = 41;
+
+pub mod oracle;
+
+// Model-checking harnesses for the pure lock math, only compiled by `cargo kani`
+#[cfg(kani)]
+mod verification;
+
+// Unit tests for the pure vault math, run by `cargo test`
+#[cfg(test)]
+mod tests;
+
+// The instruction handlers, see `instructions.rs`
+#[allow(deprecated)]
+mod instructions;
+pub use instructions::*;
+
+#[event_cpi]
 #[derive(Accounts)]
-#[instruction(amount: u64, mint: Pubkey)]
+#[instruction(amount: u64, mint_address: Pubkey)]
 pub struct Initialize<'info> {
-    /// The user creating the vault (must sign the transaction and pay for account creation)
+    /// The user creating the vault (must sign the transaction)
     #[account(mut)]
     pub user: Signer<'info>,
 
+    /// Pays for account creation, either the user or a relayer sponsoring the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
     /// The vault state account that stores configuration and metadata
     /// This account is created with a PDA derived from "state" + user's public key
     /// It stores the target amount, mint address, and vault token account address
     #[account(
         init, // Create a new account
-        payer = user, // User pays for account creation
-        seeds = [b"state", user.key().as_ref()], // PDA seeds for deterministic address
+        payer = payer, // Payer pays for account creation
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
         bump, // Store the bump seed for later use
         space = 8 + Vault::INIT_SPACE, // Allocate space for account data
     )]
     pub state: Account<'info, Vault>,
 
+    /// The vault's token account: the vault authority's associated token account for the mint,
+    /// so wallets and explorers can discover the vault's holdings. Anyone can create an
+    /// associated token account, so an existing one is reused as long as its mint and owner match
     #[account(
-        init, // Create a new token account
-        payer = user, // User pays for account creation
-        token::mint = mint, // Specify which token mint this account is for
-        token::authority = vault_authority, // Set vault authority as the account authority
-        seeds = [b"vault", state.key().as_ref()], // PDA seeds for deterministic address
-        bump, // Store the bump seed for later use
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = payer, // Payer pays for account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = vault_authority, // Ensure vault authority owns the account
     )]
     pub vault_token_account: Account<'info, TokenAccount>,
 
     /// CHECK: This is the vault authority PDA (no need to deserialize)
     #[account(
-        seeds = [b"vault", state.key().as_ref()], // Same seeds as vault token account
-        bump, // Must match the bump used for vault token account
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump, // Store the bump seed for signing later
     )]
     pub vault_authority: UncheckedAccount<'info>,
 
+    /// The vault's activity outbox, created alongside the vault
+    #[account(
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Outbox::INIT_SPACE, // Allocate space for account data
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, created alongside the vault
+    #[account(
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + RewardState::INIT_SPACE, // Allocate space for account data
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, created with the mint's first vault
+    #[account(
+        init_if_needed, // Create the account for the mint's first vault
+        payer = payer, // Payer pays for account creation
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + MintStats::INIT_SPACE, // Allocate space for account data
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
     /// The token mint account (read-only, used for validation)
-    #[account(mut)]
-    pub mint: Account<'info, token::Mint>,
+    /// Must match the mint passed as an instruction argument
+    #[account(
+        mut,
+        constraint = mint.key() == mint_address @ ErrorCode::MintMismatch, // Ensure the mint argument matches the mint account
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The program-wide config PDA, read to check whether the mint allowlist is enabled
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's approval of the mint, required while the mint allowlist is enabled
+    /// CHECK: The address is checked by the seeds, and the data is only read if needed
+    #[account(
+        seeds = [MINT_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub approved_mint: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// The admin's vault creation gate, if creators must hold a membership token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREATION_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub creation_gate: UncheckedAccount<'info>,
+
+    /// The creator's token account of the membership mint, required while a creation gate is set
+    #[account(
+        constraint = membership_token_account.owner == user.key() @ ErrorCode::MembershipMismatch, // Must be the creator's own account
+    )]
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
 
     /// The SPL Token program (required for token operations)
     pub token_program: Program<'info, Token>,
 
+    /// The Associated Token program (required to create the vault token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
     /// The System program (required for account creation)
     pub system_program: Program<'info, System>,
 }
 
 /// Implementation for the Initialize accounts
 impl<'info> Initialize<'info> {
-    pub fn initialize(&mut self, amount: u64, mint: Pubkey, bumps: &InitializeBumps) -> Result<()> {
-        // Store the target amount of tokens to save
-        self.state.amount = amount;
+    pub fn initialize(
+        &mut self,
+        amount: u64,
+        deadline: Option<GoalDeadline>,
+        usd_target: Option<UsdTarget>,
+        ui_amount: Option<String>,
+        bumps: &InitializeBumps,
+    ) -> Result<()> {
+        // A target given as a UI amount is scaled by the mint's decimals
+        let amount = target_amount(amount, ui_amount.as_deref(), self.mint.decimals)?;
 
-        // Store the vault authority bump seed for signing transactions later
-        self.state.vault_bump = bumps.vault_authority;
+        // The admin decides which vaults can be created, and for whom
+        CreationChecks {
+            config: &self.config,
+            approved_mint: &self.approved_mint,
+            creation_gate: &self.creation_gate,
+            membership_token_account: self.membership_token_account.as_deref(),
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+            mint: &self.mint,
+        }
+        .check(&self.user.key())?;
 
-        // Store the state account bump seed for validation
-        self.state.state_bump = bumps.state;
+        self.state.initialize(
+            amount,
+            deadline,
+            usd_target,
+            &self.mint,
+            self.vault_token_account.key(),
+            bumps.vault_authority,
+            bumps.state,
+        )?;
 
-        // Store the mint address to validate token operations
-        self.state.mint = mint;
+        // Set up the activity outbox and record the creation
+        self.outbox.initialize(self.state.key(), bumps.outbox, amount)?;
 
-        // Store the vault token account address for reference
-        self.state.vault_token_account = self.vault_token_account.key();
+        // Start accruing reward points from now
+        self.rewards
+            .initialize(self.state.key(), bumps.rewards, Clock::get()?.unix_timestamp);
 
-        // Initialize lock to None (unlocked)
-        self.state.locked_until = None;
+        // Count the vault in the mint's stats
+        self.mint_stats.register_vault(self.mint.key(), bumps.mint_stats);
 
         Ok(())
     }
 }
 
+#[event_cpi]
 #[derive(Accounts)]
-pub struct Deposit<'info> {
-    /// The user making the deposit (must sign the transaction)
+#[instruction(amount: u64, mint_address: Pubkey)]
+pub struct InitializeCustodial<'info> {
+    /// The custodian creating the vault (must sign the transaction and pay for account creation)
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub custodian: Signer<'info>,
 
-    /// The user's token account that contains the tokens to be deposited
-    /// Validates that:
-    /// - The account belongs to the user
-    /// - The account is for the correct token mint
-    #[account(
-        mut, // Account will be modified (token balance decreases)
-        constraint = user_token_account.owner == user.key(), // Ensure user owns the token account
-        constraint = user_token_account.mint == state.mint,  // Ensure correct token mint
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    /// The beneficiary who owns the vault (must sign the transaction). Their state address
+    /// can only hold one vault at a time, so nobody can take it without their consent
+    pub beneficiary: Signer<'info>,
 
-    /// The vault's token account that will receive the deposited tokens
-    /// Validates that this is the correct vault token account for this state
+    /// The vault state account, created for the beneficiary just like in `initialize`
     #[account(
-        mut, // Account will be modified (token balance increases)
-        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+        init, // Create a new account
+        payer = custodian, // Custodian pays for account creation
+        seeds = [STATE_SEED, beneficiary.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Vault::INIT_SPACE, // Allocate space for account data
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub state: Box<Account<'info, Vault>>,
 
-    /// The vault state account containing configuration and metadata
-    /// Validates using PDA seeds and stored bump seed
+    /// The vault's token account: the vault authority's associated token account for the mint,
+    /// reused if it already exists like in `initialize`
     #[account(
-        seeds = [b"state", user.key().as_ref()], // PDA seeds for deterministic address
-        bump = state.state_bump, // Use stored bump seed for validation
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = custodian, // Custodian pays for account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = vault_authority, // Ensure vault authority owns the account
     )]
-    pub state: Account<'info, Vault>,
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
 
-    /// The vault authority PDA that can sign transactions on behalf of the vault
-    /// This is used for automatic token release when target is reached
     /// CHECK: This is the vault authority PDA (no need to deserialize)
     #[account(
-        seeds = [b"vault", state.key().as_ref()], // Same seeds as vault token account
-        bump = state.vault_bump, // Use stored bump seed for validation
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump, // Store the bump seed for signing later
     )]
     pub vault_authority: UncheckedAccount<'info>,
 
-    /// The SPL Token program (required for token transfers)
-    pub token_program: Program<'info, Token>,
-}
+    /// The vault's activity outbox, created alongside the vault
+    #[account(
+        init, // Create a new account
+        payer = custodian, // Custodian pays for account creation
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Outbox::INIT_SPACE, // Allocate space for account data
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
 
-/// Implementation for the Deposit accounts
-impl<'info> Deposit<'info> {
-    pub fn deposit(&mut self, amount: u64) -> Result<()> {
-        // Step 1: Transfer tokens from user to vault using CPI (Cross-Program Invocation)
-        let cpi_program = self.token_program.to_account_info();
-        let cpi_accounts = TokenTransfer {
-            from: self.user_token_account.to_account_info(), // Source: user's token account
-            to: self.vault_token_account.to_account_info(),  // Destination: vault's token account
-            authority: self.user.to_account_info(),          // Authority: user (signs the transfer)
-        };
+    /// The vault's reward points, created alongside the vault
+    #[account(
+        init, // Create a new account
+        payer = custodian, // Custodian pays for account creation
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + RewardState::INIT_SPACE, // Allocate space for account data
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, created with the mint's first vault
+    #[account(
+        init_if_needed, // Create the account for the mint's first vault
+        payer = custodian, // Custodian pays for account creation
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + MintStats::INIT_SPACE, // Allocate space for account data
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The token mint account (read-only, used for validation)
+    /// Must match the mint passed as an instruction argument
+    #[account(
+        mut,
+        constraint = mint.key() == mint_address @ ErrorCode::MintMismatch, // Ensure the mint argument matches the mint account
+    )]
+    pub mint: Box<Account<'info, Mint>>,
 
-        // Create CPI context and execute the token transfer
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
+    /// The program-wide config PDA, read to check whether the mint allowlist is enabled
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
 
-        // Step 2: Check if savings target has been reached and handle auto-release
-        self.is_savings_target_reached()?;
+    /// The admin's approval of the mint, required while the mint allowlist is enabled
+    /// CHECK: The address is checked by the seeds, and the data is only read if needed
+    #[account(
+        seeds = [MINT_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub approved_mint: UncheckedAccount<'info>,
 
-        Ok(())
-    }
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
 
-    pub fn is_savings_target_reached(&self) -> Result<()> {
-        // Check if vault token balance is greater than or equal to target amount
-        if self.vault_token_account.amount >= self.state.amount {
-            // Target reached! Automatically send all tokens back to user
+    /// The beneficiary's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
 
-            // Prepare CPI accounts for transferring tokens back to user
-            let cpi_program = self.token_program.to_account_info();
-            let cpi_accounts = TokenTransfer {
-                from: self.vault_token_account.to_account_info(), // Source: vault's token account
-                to: self.user_token_account.to_account_info(), // Destination: user's token account
-                authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
-            };
+    /// The admin's vault creation gate, if creators must hold a membership token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREATION_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub creation_gate: UncheckedAccount<'info>,
 
-            // Create PDA seeds for signing the transaction
-            // The vault authority PDA must sign to authorize the transfer
-            let seeds = &[
-                b"vault", // Seed prefix
-                self.state.to_account_info().key.as_ref(), // State account key
-                &[self.state.vault_bump], // Bump seed
-            ];
+    /// The creator's token account of the membership mint, required while a creation gate is set
+    #[account(
+        constraint = membership_token_account.owner == custodian.key() @ ErrorCode::MembershipMismatch, // Must be the creator's own account
+    )]
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
 
-            let signer_seeds = &[&seeds[..]];
+    /// The vault's custody record, naming the custodian and the release date
+    #[account(
+        init, // Create a new account
+        payer = custodian, // Custodian pays for account creation
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Custody::INIT_SPACE, // Allocate space for account data
+    )]
+    pub custody: Box<Account<'info, Custody>>,
 
-            // Create CPI context with PDA signer and execute the transfer
-            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+    /// The beneficiary's self-exclusion, which refuses withdrawals until the release date
+    #[account(
+        init, // Create a new account
+        payer = custodian, // Custodian pays for account creation
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + SelfExclusion::INIT_SPACE, // Allocate space for account data
+    )]
+    pub self_exclusion: Box<Account<'info, SelfExclusion>>,
+
+    /// The SPL Token program (required for token operations)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the vault token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
 
-            // Transfer all tokens from vault back to user
-            token::transfer(cpi_ctx, self.vault_token_account.amount)?;
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeCustodial<'info> {
+    pub fn initialize_custodial(
+        &mut self,
+        amount: u64,
+        release_ts: i64,
+        ui_amount: Option<String>,
+        bumps: &InitializeCustodialBumps,
+    ) -> Result<()> {
+        // A target given as a UI amount is scaled by the mint's decimals
+        let amount = target_amount(amount, ui_amount.as_deref(), self.mint.decimals)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        // A custodian can't hold a vault for themselves, and the release must be in range
+        require_keys_neq!(
+            self.custodian.key(),
+            self.beneficiary.key(),
+            ErrorCode::InvalidCustody
+        );
+        require!(
+            release_ts > now && release_ts <= now.saturating_add(MAX_CUSTODY_SECONDS),
+            ErrorCode::InvalidCustody
+        );
+
+        // The admin decides which vaults can be created, and for whom
+        CreationChecks {
+            config: &self.config,
+            approved_mint: &self.approved_mint,
+            creation_gate: &self.creation_gate,
+            membership_token_account: self.membership_token_account.as_deref(),
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+            mint: &self.mint,
         }
+        .check(&self.beneficiary.key())?;
+
+        self.state.initialize(
+            amount,
+            None,
+            None,
+            &self.mint,
+            self.vault_token_account.key(),
+            bumps.vault_authority,
+            bumps.state,
+        )?;
+        self.outbox.initialize(self.state.key(), bumps.outbox, amount)?;
+        self.rewards.initialize(self.state.key(), bumps.rewards, now);
+        self.mint_stats.register_vault(self.mint.key(), bumps.mint_stats);
+
+        // Target releases wait for the release date, checked against the custody record
+        self.custody.state = self.state.key();
+        self.custody.bump = bumps.custody;
+        self.custody.custodian = self.custodian.key();
+        self.custody.release_ts = release_ts;
+
+        // ... and withdrawals are refused until then, like under a self-exclusion
+        self.self_exclusion.state = self.state.key();
+        self.self_exclusion.bump = bumps.self_exclusion;
+        self.self_exclusion.extend(release_ts);
 
         Ok(())
     }
 }
 
+#[event_cpi]
 #[derive(Accounts)]
-pub struct Withdraw<'info> {
-    /// The user making the withdrawal (must sign the transaction)
+#[instruction(template_id: u16)]
+pub struct InitializeFromTemplate<'info> {
+    /// The user creating the vault (must sign the transaction)
     #[account(mut)]
     pub user: Signer<'info>,
 
-    /// The user's token account that will receive the withdrawn tokens
-    /// Validates that:
-    /// - The account belongs to the user
-    /// - The account is for the correct token mint
+    /// Pays for account creation, either the user or a relayer sponsoring the transaction
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The goal template the vault is created from
     #[account(
-        mut, // Account will be modified (token balance increases)
-        constraint = user_token_account.owner == user.key(), // Ensure user owns the token account
-        constraint = user_token_account.mint == state.mint, // Ensure correct token mint
+        seeds = [TEMPLATE_SEED, &template_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = template.bump, // Use stored bump seed for validation
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub template: Box<Account<'info, GoalTemplate>>,
 
-    /// The vault's token account that contains the tokens to be withdrawn
-    /// Validates that this is the correct vault token account for this state
+    /// The vault state account, created just like in `initialize`
     #[account(
-        mut, // Account will be modified (token balance decreases)
-        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
-    )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Vault::INIT_SPACE, // Allocate space for account data
+    )]
+    pub state: Box<Account<'info, Vault>>,
 
-    /// The vault state account containing configuration and metadata
-    /// Validates using PDA seeds and stored bump seed
+    /// The vault's token account: the vault authority's associated token account for the mint,
+    /// reused if it already exists like in `initialize`
     #[account(
-        seeds = [b"state", user.key().as_ref()], // PDA seeds for deterministic address
-        bump = state.state_bump, // Use stored bump seed for validation
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = payer, // Payer pays for account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = vault_authority, // Ensure vault authority owns the account
     )]
-    pub state: Account<'info, Vault>,
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
 
-    /// The vault authority PDA that can sign transactions on behalf of the vault
-    /// This PDA must sign to authorize the withdrawal from the vault
     /// CHECK: This is the vault authority PDA (no need to deserialize)
     #[account(
-        seeds = [b"vault", state.key().as_ref()], // Same seeds as vault token account
-        bump = state.vault_bump, // Use stored bump seed for validation
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump, // Store the bump seed for signing later
     )]
     pub vault_authority: UncheckedAccount<'info>,
 
-    /// The SPL Token program (required for token transfers)
+    /// The vault's activity outbox, created alongside the vault
+    #[account(
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Outbox::INIT_SPACE, // Allocate space for account data
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, created alongside the vault
+    #[account(
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + RewardState::INIT_SPACE, // Allocate space for account data
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, created with the mint's first vault
+    #[account(
+        init_if_needed, // Create the account for the mint's first vault
+        payer = payer, // Payer pays for account creation
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + MintStats::INIT_SPACE, // Allocate space for account data
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The template's token mint
+    #[account(
+        mut,
+        constraint = mint.key() == template.terms.mint @ ErrorCode::MintMismatch, // Ensure the template's mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The program-wide config PDA, read to check whether the mint allowlist is enabled
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's approval of the mint, required while the mint allowlist is enabled
+    /// CHECK: The address is checked by the seeds, and the data is only read if needed
+    #[account(
+        seeds = [MINT_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub approved_mint: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// The admin's vault creation gate, if creators must hold a membership token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREATION_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub creation_gate: UncheckedAccount<'info>,
+
+    /// The creator's token account of the membership mint, required while a creation gate is set
+    #[account(
+        constraint = membership_token_account.owner == user.key() @ ErrorCode::MembershipMismatch, // Must be the creator's own account
+    )]
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The template terms the vault keeps, such as a deposit fee override
+    #[account(
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + VaultTemplate::INIT_SPACE, // Allocate space for account data
+    )]
+    pub vault_template: Box<Account<'info, VaultTemplate>>,
+
+    /// The owner's self-exclusion, which refuses withdrawals for the template's lock
+    #[account(
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + SelfExclusion::INIT_SPACE, // Allocate space for account data
+    )]
+    pub self_exclusion: Box<Account<'info, SelfExclusion>>,
+
+    /// The SPL Token program (required for token operations)
     pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the vault token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
 }
 
-/// Implementation for the Withdraw accounts
-impl<'info> Withdraw<'info> {
-    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
-        // Check if tokens are currently locked
-        if let Some(locked_until) = self.state.locked_until {
-            let clock = Clock::get()?;
+impl<'info> InitializeFromTemplate<'info> {
+    pub fn initialize_from_template(
+        &mut self,
+        ui_amount: Option<String>,
+        bumps: &InitializeFromTemplateBumps,
+    ) -> Result<()> {
+        // The admin decides which vaults can be created, and for whom
+        CreationChecks {
+            config: &self.config,
+            approved_mint: &self.approved_mint,
+            creation_gate: &self.creation_gate,
+            membership_token_account: self.membership_token_account.as_deref(),
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+            mint: &self.mint,
+        }
+        .check(&self.user.key())?;
+
+        // The saver may pick their own target, as a UI amount, instead of the template's
+        let terms = self.template.terms.clone();
+        let target = match ui_amount {
+            Some(ui_amount) => ui_amount_to_amount(&ui_amount, self.mint.decimals)?,
+            None => terms.target,
+        };
+        self.state.initialize(
+            target,
+            None,
+            None,
+            &self.mint,
+            self.vault_token_account.key(),
+            bumps.vault_authority,
+            bumps.state,
+        )?;
+        // The template paces withdrawals once the lock is over
+        self.state.cooldown_seconds = terms.cooldown_seconds;
+        self.state.max_withdraw_per_day = terms.max_withdraw_per_day;
+
+        let now = Clock::get()?.unix_timestamp;
+        self.outbox.initialize(self.state.key(), bumps.outbox, target)?;
+        self.rewards.initialize(self.state.key(), bumps.rewards, now);
+        self.mint_stats.register_vault(self.mint.key(), bumps.mint_stats);
+
+        // Keep the fee override, so later changes to the template don't reach this vault
+        self.vault_template.state = self.state.key();
+        self.vault_template.bump = bumps.vault_template;
+        self.vault_template.template_id = self.template.template_id;
+        self.vault_template.deposit_fee_bps = terms.deposit_fee_bps;
+
+        // Withdrawals are refused for the template's lock, like under a self-exclusion
+        self.self_exclusion.state = self.state.key();
+        self.self_exclusion.bump = bumps.self_exclusion;
+        self.self_exclusion
+            .extend(now.saturating_add(terms.lock_seconds));
+
+        Ok(())
+    }
+}
+
+/// Implementation for the Vault account
+impl Vault {
+    /// Locks `amount` until `unlock_ts` in a slot that is unused or has expired
+    pub fn add_lock_tranche(&mut self, now: i64, amount: u64, unlock_ts: i64) -> Result<()> {
+        let slot = self
+            .lock_tranches
+            .iter_mut()
+            .find(|tranche| !tranche.is_active(now))
+            .ok_or(ErrorCode::TooManyLockTranches)?;
+        *slot = LockTranche { amount, unlock_ts };
+
+        Ok(())
+    }
+
+    /// Locks the whole `balance` until at least `unlock_ts`. Active tranches are extended
+    /// (never shortened) and the rest of the balance goes into a new tranche, or into an
+    /// extended one when every slot is taken, so this can't fail.
+    pub fn lock_balance_until(&mut self, now: i64, balance: u64, unlock_ts: i64) {
+        for tranche in self.lock_tranches.iter_mut() {
+            if tranche.is_active(now) {
+                tranche.unlock_ts = tranche.unlock_ts.max(unlock_ts);
+            }
+        }
+
+        let rest = balance.saturating_sub(locked_amount(now, &self.lock_tranches));
+        if rest == 0 {
+            return;
+        }
+        if self.add_lock_tranche(now, rest, unlock_ts).is_err() {
+            // Every slot holds an active tranche, all of them now lasting until `unlock_ts`
+            self.lock_tranches[0].amount = self.lock_tranches[0].amount.saturating_add(rest);
+        }
+    }
+
+    /// Returns the vault's progress towards its target in the target's unit: tokens, or USD
+    /// cents valued at the oracle price read from `price_update` for USD targets, which must
+    /// publish the feed the admin set in the mint's `price_feed`.
+    /// Returns `(value, target)`.
+    pub fn target_progress(
+        &self,
+        balance: u64,
+        decimals: u8,
+        price_update: Option<&AccountInfo>,
+        price_feed: &AccountInfo,
+    ) -> Result<(u64, u64)> {
+        let Some(usd_target) = &self.usd_target else {
+            return Ok((balance, self.amount));
+        };
+
+        let price_update = price_update.ok_or(ErrorCode::PriceAccountMismatch)?;
+        require_keys_eq!(
+            price_update.key(),
+            usd_target.price_account,
+            ErrorCode::PriceAccountMismatch
+        );
+        let price = oracle::read_price(
+            price_update,
+            load_price_feed(price_feed)?,
+            Clock::get()?.unix_timestamp,
+        )?;
+        let value = oracle::usd_cents(balance, decimals, price.price as u64, price.exponent);
+
+        Ok((value, usd_target.cents))
+    }
+
+    /// Returns true if `balance` meets the vault's target (see `target_progress`). An NFT
+    /// has no savings target: it is only met once no lock tranche holds the token anymore.
+    pub fn target_reached(
+        &self,
+        balance: u64,
+        mint: &Mint,
+        price_update: Option<&AccountInfo>,
+        price_feed: &AccountInfo,
+    ) -> Result<bool> {
+        if is_nft_mint(mint) {
+            let now = Clock::get()?.unix_timestamp;
+            return Ok(balance > 0 && locked_amount(now, &self.lock_tranches) == 0);
+        }
+
+        let (value, target) =
+            self.target_progress(balance, mint.decimals, price_update, price_feed)?;
+        Ok(value >= target)
+    }
+
+    /// Moves the vault on to its next goal, `increase_bps` above the current target. Returns
+    /// the previous and the new target, in the target's unit (see `target_progress`).
+    pub fn roll_over(&mut self, increase_bps: u16) -> Result<(u64, u64)> {
+        let target = match &mut self.usd_target {
+            Some(usd_target) => &mut usd_target.cents,
+            None => &mut self.amount,
+        };
+        let previous = *target;
+        let increase = previous as u128 * increase_bps as u128 / 10_000;
+        *target = u64::try_from(increase)
+            .ok()
+            .and_then(|increase| previous.checked_add(increase))
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // The goal is met, so a deadline can no longer be missed
+        self.deadline = None;
+
+        Ok((previous, *target))
+    }
+
+    /// Checks that `amount` can be withdrawn from a `balance` now, under the vault's locks,
+    /// outstanding `debt` and the limits of [`Vault::authorize_outflow`]
+    pub fn authorize_withdrawal(
+        &mut self,
+        now: i64,
+        balance: u64,
+        unlock_schedule: Option<&UnlockSchedule>,
+        debt: u64,
+        amount: u64,
+        vault_key: Option<&Signer>,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+        require!(amount <= balance, ErrorCode::InsufficientVaultBalance);
+
+        // Only the part of the balance outside active lock tranches can be withdrawn
+        require!(
+            amount <= unlocked_amount(balance, now, &self.lock_tranches),
+            ErrorCode::TokensLocked
+        );
+        // An advance keeps its debt in the vault until it's repaid
+        require!(
+            amount <= unlocked_amount(balance, now, &self.lock_tranches).saturating_sub(debt),
+            ErrorCode::AdvanceOutstanding
+        );
+
+        self.authorize_outflow(now, balance, unlock_schedule, amount, vault_key)
+    }
+
+    /// Checks that `amount` can be paid out of a `balance` to the owner now, whether withdrawn,
+    /// advanced, or released on unlock or on a price condition: not held back by the unlock
+    /// stream, freeze, cooldown, daily limit or vault key. Records it against the cooldown and the daily limit.
+    pub fn authorize_outflow(
+        &mut self,
+        now: i64,
+        balance: u64,
+        unlock_schedule: Option<&UnlockSchedule>,
+        amount: u64,
+        vault_key: Option<&Signer>,
+    ) -> Result<()> {
+        // Nothing held back by the unlock stream, if any, can leave the vault
+        if let Some(schedule) = unlock_schedule {
             require!(
-                clock.unix_timestamp >= locked_until,
+                amount <= balance.saturating_sub(schedule.locked_at(now)),
                 ErrorCode::TokensLocked
             );
         }
 
-        // Prepare CPI accounts for transferring tokens from vault to user
-        let cpi_program = self.token_program.to_account_info();
-        let cpi_accounts = TokenTransfer {
-            from: self.vault_token_account.to_account_info(), // Source: vault's token account
-            to: self.user_token_account.to_account_info(), // Destination: user's token account
-            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        assert_not_frozen(self, false)?;
+
+        // Withdrawals are spaced out by the vault's cooldown, if any
+        require!(
+            cooldown_remaining(now, self.last_withdraw_ts, self.cooldown_seconds) == 0,
+            ErrorCode::WithdrawalCooldown
+        );
+
+        // ... and capped over any day by the vault's rate limit, if any
+        if self.max_withdraw_per_day > 0 {
+            let used = withdraw_window_usage(
+                self.max_withdraw_per_day,
+                self.withdrawn_in_window,
+                now.saturating_sub(self.last_withdraw_ts),
+            );
+            let remaining = self
+                .max_withdraw_per_day
+                .checked_sub(used)
+                .ok_or(ErrorCode::MathOverflow)?;
+            require!(amount <= remaining, ErrorCode::RateLimitExceeded);
+            self.withdrawn_in_window = used.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        }
+        self.last_withdraw_ts = now;
+
+        // Large withdrawals also need the vault's offline key, if one is set
+        if let Some(key) = self.vault_key {
+            if amount > self.vault_key_threshold {
+                assert_vault_key_signed(key, vault_key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns how much of `amount` can be deposited on top of `balance` under the balance cap
+    pub fn capped_deposit(&self, amount: u64, balance: u64) -> Result<u64> {
+        let Some(cap) = self.balance_cap else {
+            return Ok(amount);
         };
 
-        // Create PDA seeds for signing the transaction
-        // The vault authority PDA must sign to authorize the transfer from vault
-        let seeds = &[
-            b"vault", // Seed prefix
-            self.state.to_account_info().key.as_ref(), // State account key
-            &[self.state.vault_bump], // Bump seed
-        ];
-        let signer_seeds = &[&seeds[..]];
+        capped_amount(amount, balance, cap, self.cap_policy)
+            .ok_or_else(|| error!(ErrorCode::BalanceCapExceeded))
+    }
 
-        // Create CPI context with PDA signer and execute the transfer
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+    /// Sets up a new vault saving towards `amount` of `mint` (or towards `usd_target`, which
+    /// then replaces `amount`), optionally by `deadline`
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize(
+        &mut self,
+        amount: u64,
+        deadline: Option<GoalDeadline>,
+        usd_target: Option<UsdTarget>,
+        mint: &Account<Mint>,
+        vault_token_account: Pubkey,
+        vault_bump: u8,
+        state_bump: u8,
+    ) -> Result<()> {
+        match &usd_target {
+            // USD targets are valued through the oracle, the token amount isn't used
+            Some(usd_target) => require!(usd_target.cents > 0, ErrorCode::TargetTooSmall),
+            None => {
+                // A zero target would release the vault on its first deposit
+                require!(amount > 0, ErrorCode::ZeroAmount);
+                // Catch unit-conversion mistakes: the target must be a sensible amount for this mint
+                require!(amount >= min_target(mint.decimals), ErrorCode::TargetTooSmall);
+                require!(amount <= mint.supply, ErrorCode::TargetExceedsSupply);
+            }
+        }
 
-        // Transfer the specified amount of tokens from vault to user
-        token::transfer(cpi_ctx, amount)?;
+        if let Some(deadline) = &deadline {
+            deadline.validate(Clock::get()?.unix_timestamp)?;
+        }
+
+        // Store the target amount of tokens to save
+        self.amount = amount;
+
+        // Store the goal deadline, settled by `settle_deadline` if the target is missed
+        self.deadline = deadline;
+
+        // Store the USD target, if the vault saves towards a dollar value
+        self.usd_target = usd_target;
+
+        // Store the vault authority bump seed for signing transactions later
+        self.vault_bump = vault_bump;
+
+        // Store the state account bump seed for validation
+        self.state_bump = state_bump;
+
+        // Store the mint address to validate token operations
+        self.mint = mint.key();
+        self.decimals = mint.decimals;
+
+        // Store the vault token account address for reference
+        self.vault_token_account = vault_token_account;
+
+        // Nothing locked yet
+        self.lock_tranches = Default::default();
+
+        // No deposit hook until the owner opts in
+        self.deposit_hook = None;
+
+        // No vault key until the owner sets one
+        self.vault_key = None;
+        self.vault_key_threshold = 0;
+
+        // Never frozen
+        self.frozen_until = None;
+        self.flags = 0;
+
+        // Only released by a crank once the target is reached, until the owner opts in
+        self.release_on_unlock = false;
+
+        // No withdrawal cooldown until the owner sets one
+        self.cooldown_seconds = 0;
+        self.last_withdraw_ts = 0;
+
+        // No withdrawal limit until the owner sets one
+        self.max_withdraw_per_day = 0;
+        self.withdrawn_in_window = 0;
+
+        // Uncapped until the owner sets a cap
+        self.balance_cap = None;
+        self.cap_policy = CapPolicy::Reject;
+
+        // Nothing granted yet
+        self.open_grants = 0;
+
+        // A re-created vault starts on the current layout with a zeroed reserve
+        self.version = Vault::VERSION;
+        self.reserved = [0; VAULT_RESERVED_BYTES];
 
         Ok(())
     }
 }
 
+#[event_cpi]
 #[derive(Accounts)]
-pub struct LockTokens<'info> {
-    /// The user locking the tokens (must sign the transaction)
-    /// Only the vault owner can lock tokens
+pub struct Deposit<'info> {
+    /// The user making the deposit (must sign the transaction)
     #[account(mut)]
     pub user: Signer<'info>,
 
+    /// The user's token account that contains the tokens to be deposited
+    /// Validates that:
+    /// - The account belongs to the user
+    /// - The account is for the correct token mint
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = user_token_account.owner == user.key(), // Ensure user owns the token account
+        constraint = user_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's token account that will receive the deposited tokens
+    /// Validates that this is the correct vault token account for this state
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
     /// The vault state account containing configuration and metadata
     /// Validates using PDA seeds and stored bump seed
-    /// Also validates that the user is the owner of the vault
     #[account(
-        mut,
-        seeds = [b"state", user.key().as_ref()], // PDA seeds for deterministic address
+        mut, // The goal deadline is cleared when the deposit releases the vault
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
         bump = state.state_bump, // Use stored bump seed for validation
     )]
     pub state: Account<'info, Vault>,
-}
 
-/// Implementation for the LockTokens accounts
-impl<'info> LockTokens<'info> {
-    pub fn lock_tokens(&mut self, duration_seconds: i64) -> Result<()> {
-        // Get the current timestamp from the Solana clock
-        let clock = Clock::get()?;
+    /// The vault authority PDA that can sign transactions on behalf of the vault
+    /// This is used for automatic token release when target is reached
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
 
-        // Calculate the lock expiration timestamp
-        let locked_until = clock
-            .unix_timestamp
-            .checked_add(duration_seconds)
-            .ok_or(ErrorCode::InvalidLockDuration)?;
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
 
-        // Update the vault state with the lock expiration timestamp
-        self.state.locked_until = Some(locked_until);
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
 
-        Ok(())
-    }
-}
-#[account]
-#[derive(InitSpace)]
-pub struct Vault {
-    /// The target amount of tokens to save (in token's smallest unit)
-    /// When the vault balance reaches this amount, all tokens are automatically released
-    pub amount: u64,
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
 
-    /// The bump seed for the vault authority PDA
-    /// This is used to sign transactions on behalf of the vault
-    pub vault_bump: u8,
+    /// The program-wide config PDA, read for the protocol deposit fee
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
 
-    /// The bump seed for the state account PDA
-    /// This is used for validation when accessing the state account
-    pub state_bump: u8,
+    /// The admin's blocklist entry for the user, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_user: UncheckedAccount<'info>,
 
-    /// The mint address of the token type stored in this vault
-    /// This ensures all operations are performed on the correct token type
-    pub mint: Pubkey,
+    /// The treasury's token account for the vault's mint, required while a deposit fee is set
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = treasury_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = treasury_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+    )]
+    pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
 
-    /// The address of the vault's token account
-    /// This is where the actual tokens are stored
-    pub vault_token_account: Pubkey,
+    /// The fee discount tiers set by the admin
+    /// CHECK: The address is checked by the seeds, and the data is only read if the tiers exist
+    #[account(
+        seeds = [FEE_TIERS_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub fee_tiers: UncheckedAccount<'info>,
 
-    /// The timestamp until which tokens are locked (Unix timestamp in seconds)
-    /// If None, tokens are not locked and can be withdrawn at any time
-    /// If Some(timestamp), tokens cannot be withdrawn until the current time >= timestamp
-    pub locked_until: Option<i64>,
-}
+    /// The template terms the vault was created with, which may override the deposit fee
+    /// CHECK: May not exist, parsed by `load_vault_template`
+    #[account(
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub vault_template: UncheckedAccount<'info>,
 
-/// Custom error codes for the vault program
-#[error_code]
-pub enum ErrorCode {
-    /// Tokens are currently locked and cannot be withdrawn
-    #[msg("Tokens are currently locked and cannot be withdrawn")]
-    TokensLocked,
+    /// The depositor's token account of the membership mint, for a deposit fee discount
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
 
-    /// Invalid lock duration provided
-    #[msg("Invalid lock duration provided")]
-    InvalidLockDuration,
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The vault's deposit hook program, required when the vault has a hook set
+    /// CHECK: Validated against the hook stored in state before being invoked
+    pub deposit_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// The admin approval of the deposit hook program, required when the vault has a hook set
+    #[account(
+        seeds = [HOOK_SEED, approved_hook.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_hook.bump, // Use stored bump seed for validation
+    )]
+    pub approved_hook: Option<Account<'info, ApprovedHook>>,
+
+    /// The Pyth price update of the vault's mint, required when the vault has a USD target
+    /// CHECK: Checked against the vault's USD target and parsed by `oracle::read_price`
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The System program, required by `deposit_sol` to wrap lamports
+    pub system_program: Option<Program<'info, System>>,
+
+    /// The vault's extension account, required by `deposit_with_memo` to store the memo hash
+    #[account(
+        mut,
+        seeds = [EXTENSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub extension: Option<AccountLoader<'info, VaultExtension>>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Holds releases back while the vault is
+    /// in custody
+    #[account(
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_rollover`. Makes a reached target roll over into
+    /// the next goal instead of releasing
+    #[account(
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub rollover: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Holds releases back until the owner
+    /// repays their advance
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+}
+
+/// Implementation for the Deposit accounts
+impl<'info> Deposit<'info> {
+    /// Moves `lamports` from the user into their wSOL token account and syncs its balance
+    pub fn wrap_sol(&mut self, lamports: u64) -> Result<()> {
+        require_keys_eq!(self.mint.key(), native_mint::ID, ErrorCode::NotNativeMint);
+        require!(lamports > 0, ErrorCode::ZeroAmount);
+        let system = self
+            .system_program
+            .as_ref()
+            .ok_or(ErrorCode::SystemProgramRequired)?;
+
+        // Plain lamport transfer into the wrapped account
+        let cpi_accounts = system_program::Transfer {
+            from: self.user.to_account_info(), // Source: the user's wallet
+            to: self.user_token_account.to_account_info(), // Destination: the user's wSOL account
+        };
+        let cpi_ctx = CpiContext::new(system.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_ctx, lamports)?;
+
+        // Let the token program count the new lamports as wSOL
+        let cpi_accounts = token::SyncNative {
+            account: self.user_token_account.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token::sync_native(cpi_ctx)?;
+
+        // Pick up the synced balance before depositing
+        self.user_token_account.reload()
+    }
+
+    /// Returns the amount deposited, the protocol fee charged on top of it and what happened
+    /// to the vault, if the deposit reached the target
+    pub fn deposit(&mut self, amount: u64) -> Result<(u64, u64, Option<TargetOutcome>)> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, true)?;
+
+        // An NFT is deposited whole, exactly once
+        if is_nft_mint(&self.mint) {
+            require!(amount == 1, ErrorCode::InvalidNftAmount);
+        }
+
+        // Apply the vault's balance cap, if any
+        let amount = self.state.capped_deposit(amount, self.vault_token_account.amount)?;
+
+        // Split off any excess over the target before anything leaves the user's token account,
+        // and skim the protocol fee from the part the vault is credited with
+        let (amount, fee) = self.refunded_deposit(amount)?;
+
+        // Accrue points on the balance held so far, then extend the deposit streak
+        let now = Clock::get()?.unix_timestamp;
+        self.rewards
+            .accrue(now, self.vault_token_account.amount, self.mint.decimals);
+        self.rewards.record_deposit(now);
+        // Blocked wallets can't move tokens into or out of vaults
+        assert_not_blocked(&self.blocked_user)?;
+        // A deposit can't share its slot with a withdrawal while the guard is on
+        self.rewards
+            .record_deposit_slot(Clock::get()?.slot, same_slot_guard(&self.config)?)?;
+
+        if let (Some(treasury_token_account), true) = (&self.treasury_token_account, fee > 0) {
+            let cpi_accounts = TransferChecked {
+                from: self.user_token_account.to_account_info(), // Source: user's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to: treasury_token_account.to_account_info(), // Destination: treasury's token account
+                authority: self.user.to_account_info(), // Authority: user (signs the transfer)
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            token::transfer_checked(cpi_ctx, fee, self.mint.decimals)?;
+        }
+
+        // Step 1: Check up front whether the deposit reaches the savings target. If it does, the
+        // part of the deposit that would only be sent straight back stays with the user. Vaults
+        // that roll over keep it instead and move on to their next goal
+        let (outcome, deposited) = self.target().outcome(amount)?;
+        let released = match outcome {
+            Some(TargetOutcome::Released(released)) => Some(released),
+            _ => None,
+        };
+
+        // Step 2: Transfer the rest from user to vault using CPI (Cross-Program Invocation)
+        if deposited > 0 {
+            let cpi_program = self.token_program.to_account_info();
+            let cpi_accounts = TransferChecked {
+                from: self.user_token_account.to_account_info(), // Source: user's token account
+                mint: self.mint.to_account_info(),               // Mint: validated by the token program
+                to: self.vault_token_account.to_account_info(),  // Destination: vault's token account
+                authority: self.user.to_account_info(),          // Authority: user (signs the transfer)
+            };
+
+            // Create CPI context and execute the token transfer
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            token::transfer_checked(cpi_ctx, deposited, self.mint.decimals)?;
+        }
+
+        // Record what logically happened: the deposit, then any release
+        self.outbox.record(ActivityKind::Deposit, amount)?;
+        self.mint_stats.record_deposit(amount);
+        if let Some(released) = released {
+            // The goal is met, so a deadline can no longer be missed
+            self.state.deadline = None;
+            self.rewards.earn(Badge::GoalReached);
+            self.outbox.record(ActivityKind::Release, released)?;
+            self.mint_stats.record_release(released);
+        }
+
+        Ok((amount, fee, outcome))
+    }
+
+    /// Returns the part of a deposit of `amount` that the vault is credited with, and the
+    /// protocol fee on that part. Under `SurplusPolicy::Refund` a deposit that overshoots a
+    /// token target is cut down to what the target still needs, and the fee is only charged on
+    /// that. The rest of the deposit never leaves the user.
+    pub fn refunded_deposit(&mut self, amount: u64) -> Result<(u64, u64)> {
+        // Skim the protocol fee, only the rest is credited to the vault
+        let fee = self.protocol_fee(amount)?;
+        let credited = amount.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+
+        match self.target().refund_cut(credited)? {
+            // The fee on `needed` is at most the fee on `amount`, so the user still covers both
+            Some(needed) => Ok((needed, self.protocol_fee(needed)?)),
+            None => Ok((credited, fee)),
+        }
+    }
+
+    /// Borrows the accounts the deposit settles the vault's savings target with
+    pub fn target(&mut self) -> DepositTarget<'_, 'info> {
+        DepositTarget {
+            state: &mut self.state,
+            rewards: &mut self.rewards,
+            vault_token_account: &self.vault_token_account,
+            user_token_account: &self.user_token_account,
+            vault_authority: &self.vault_authority,
+            mint: &self.mint,
+            config: &self.config,
+            custody: &self.custody,
+            rollover: &self.rollover,
+            advance: &self.advance,
+            price_update: self
+                .price_update
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            price_feed: &self.price_feed,
+            token_program: &self.token_program,
+        }
+    }
+
+    /// Returns the protocol fee on a deposit of `amount` by the user
+    pub fn protocol_fee(&self, amount: u64) -> Result<u64> {
+        self.fee()
+            .amount(&self.user.key(), &self.state.mint, amount)
+    }
+
+    /// Borrows the accounts the deposit's protocol fee is worked out with
+    pub fn fee(&self) -> DepositFee<'_, 'info> {
+        DepositFee {
+            config: &self.config,
+            fee_tiers: &self.fee_tiers,
+            vault_template: &self.vault_template,
+            membership_token_account: self.membership_token_account.as_deref(),
+            treasury_token_account: self.treasury_token_account.as_deref(),
+        }
+    }
+}
+
+/// The accounts a deposit settles the vault's savings target with, borrowed from whichever
+/// instruction takes the deposit
+pub struct DepositTarget<'a, 'info> {
+    pub state: &'a mut Account<'info, Vault>,
+    pub rewards: &'a mut Account<'info, RewardState>,
+    pub vault_token_account: &'a Account<'info, TokenAccount>,
+    /// The owner's token account a release is sent to
+    pub user_token_account: &'a Account<'info, TokenAccount>,
+    pub vault_authority: &'a AccountInfo<'info>,
+    pub mint: &'a Account<'info, Mint>,
+    pub config: &'a AccountInfo<'info>,
+    pub custody: &'a AccountInfo<'info>,
+    pub rollover: &'a AccountInfo<'info>,
+    pub advance: &'a AccountInfo<'info>,
+    pub price_update: Option<AccountInfo<'info>>,
+    pub price_feed: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> DepositTarget<'a, 'info> {
+    /// Returns what happened to the vault if depositing `amount` reaches its savings target, and
+    /// the part of the deposit that still has to move into the vault. Vaults that roll over keep
+    /// the whole deposit and move on to their next goal
+    pub fn outcome(&mut self, amount: u64) -> Result<(Option<TargetOutcome>, u64)> {
+        Ok(match self.roll_over(amount)? {
+            Some(outcome) => (Some(outcome), amount),
+            None => match self.is_savings_target_reached(amount)? {
+                Some((released, deposited)) => (Some(TargetOutcome::Released(released)), deposited),
+                None => (None, amount),
+            },
+        })
+    }
+
+    /// Returns what the target still needs when `SurplusPolicy::Refund` cuts a deposit crediting
+    /// `credited` down to it
+    pub fn refund_cut(&self, credited: u64) -> Result<Option<u64>> {
+        if is_nft_mint(self.mint)
+            || self.state.usd_target.is_some()
+            || surplus_policy(self.config)? != SurplusPolicy::Refund
+        {
+            return Ok(None);
+        }
+
+        Ok(refund_cut(
+            self.state.amount,
+            self.vault_token_account.amount,
+            credited,
+        ))
+    }
+
+    /// Moves a vault that rolls over on to its next goal if depositing `amount` reaches its
+    /// savings target
+    pub fn roll_over(&mut self, amount: u64) -> Result<Option<TargetOutcome>> {
+        let Some(rollover) = load_rollover(self.rollover)? else {
+            return Ok(None);
+        };
+        // An NFT has no savings target to roll over
+        if is_nft_mint(self.mint) {
+            return Ok(None);
+        }
+
+        let balance = self
+            .vault_token_account
+            .amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if !self
+            .state
+            .target_reached(balance, self.mint, self.price_update.as_ref(), self.price_feed)?
+        {
+            return Ok(None);
+        }
+
+        self.rewards.earn(Badge::GoalReached);
+        let (previous_target, new_target) = self.state.roll_over(rollover.increase_bps)?;
+        Ok(Some(TargetOutcome::RolledOver {
+            previous_target,
+            new_target,
+        }))
+    }
+
+    /// Returns the balance released if depositing `amount` reaches the savings target, and the
+    /// part of the deposit that still goes into the vault. The tokens already in the vault are
+    /// sent back to the user and the emptied vault token account is closed. The deposit itself
+    /// is left with the user rather than making a round trip. Under `SurplusPolicy::Keep` only
+    /// the target is released, and the surplus stays in the vault.
+    pub fn is_savings_target_reached(&self, amount: u64) -> Result<Option<(u64, u64)>> {
+        // Depositing an NFT pledges it, so it's never released right away, a custodial vault
+        // keeps everything until its release date, and an advance must be repaid first
+        if is_nft_mint(self.mint)
+            || in_custody(self.custody, Clock::get()?.unix_timestamp)?
+            || outstanding_debt(self.advance)? > 0
+        {
+            return Ok(None);
+        }
+
+        // Check if the vault balance, including the deposit, reaches the target
+        let held = self.vault_token_account.amount;
+        let balance = held.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        if !self
+            .state
+            .target_reached(balance, self.mint, self.price_update.as_ref(), self.price_feed)?
+        {
+            return Ok(None);
+        }
+
+        // The release is a payout like any other, so the admin's freeze holds it back too
+        assert_not_frozen(self.state, false)?;
+
+        // Target reached! Automatically send the tokens already saved back to the user
+
+        // Create PDA seeds for signing the transaction
+        // The vault authority PDA must sign to authorize the transfer
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Vaults with a token target may keep what's above it. The deposit and the vault then
+        // settle only the difference to the target, in whichever direction
+        let target = self.state.amount;
+        if self.state.usd_target.is_none()
+            && balance > target
+            && surplus_policy(self.config)? == SurplusPolicy::Keep
+        {
+            let owed = target.saturating_sub(amount);
+            if owed > 0 {
+                let cpi_accounts = TransferChecked {
+                    from: self.vault_token_account.to_account_info(), // Source: vault's token account
+                    mint: self.mint.to_account_info(), // Mint: validated by the token program
+                    to: self.user_token_account.to_account_info(), // Destination: user's token account
+                    authority: self.vault_authority.clone(), // Authority: vault authority PDA
+                };
+                let cpi_ctx = CpiContext::new_with_signer(
+                    self.token_program.clone(),
+                    cpi_accounts,
+                    signer_seeds,
+                );
+                token::transfer_checked(cpi_ctx, owed, self.mint.decimals)?;
+            }
+
+            // The surplus stays in the vault token account
+            return Ok(Some((target, amount.saturating_sub(target))));
+        }
+
+        if held > 0 {
+            // Prepare CPI accounts for transferring tokens back to user
+            let cpi_program = self.token_program.clone();
+            let cpi_accounts = TransferChecked {
+                from: self.vault_token_account.to_account_info(), // Source: vault's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to: self.user_token_account.to_account_info(), // Destination: user's token account
+                authority: self.vault_authority.clone(), // Authority: vault authority PDA
+            };
+
+            // Create CPI context with PDA signer and execute the transfer
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+            token::transfer_checked(cpi_ctx, held, self.mint.decimals)?;
+        }
+
+        // The emptied vault token account stays open, like after any other release, until
+        // the owner closes the vault with `finalize` or `close_vault`
+        Ok(Some((balance, 0)))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositViaDelegate<'info> {
+    /// The auto-save service the owner authorized to trigger pulls (must sign the transaction)
+    pub caller: Signer<'info>,
+
+    /// The vault owner, who approved the vault authority as delegate on their token account
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The owner's token account the tokens are pulled from
+    /// The token program checks the vault authority's delegation and remaining allowance
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = user_token_account.owner == owner.key(), // Ensure owner owns the token account
+        constraint = user_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's token account that will receive the pulled tokens
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The owner's vault state account
+    #[account(
+        mut, // The goal deadline is cleared when the pull releases the vault
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The owner's authorization of the caller, which limits how much and how often it pulls
+    #[account(
+        mut, // The time of the pull is recorded
+        seeds = [AUTO_SAVE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = auto_save.bump, // Use stored bump seed for validation
+        constraint = auto_save.service == caller.key() @ ErrorCode::AutoSaveNotAuthorized, // Only the authorized service pulls
+    )]
+    pub auto_save: Box<Account<'info, AutoSaveGrant>>,
+
+    /// The vault authority PDA, acting as the approved delegate of the owner's token account
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The program-wide config PDA, read for the protocol deposit fee
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the caller, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, caller.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_caller: UncheckedAccount<'info>,
+
+    /// The treasury's token account for the vault's mint, required while a deposit fee is set
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = treasury_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = treasury_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+    )]
+    pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The fee discount tiers set by the admin
+    /// CHECK: The address is checked by the seeds, and the data is only read if the tiers exist
+    #[account(
+        seeds = [FEE_TIERS_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub fee_tiers: UncheckedAccount<'info>,
+
+    /// The template terms the vault was created with, which may override the deposit fee
+    /// CHECK: May not exist, parsed by `load_vault_template`
+    #[account(
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub vault_template: UncheckedAccount<'info>,
+
+    /// The depositor's token account of the membership mint, for a deposit fee discount
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The Pyth price update of the vault's mint, required when the vault has a USD target
+    /// CHECK: Checked against the vault's USD target and parsed by `oracle::read_price`
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+
+    /// The vault's deposit hook program, required when the vault has a hook set
+    /// CHECK: Validated against the hook stored in state before being invoked
+    pub deposit_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// The admin approval of the deposit hook program, required when the vault has a hook set
+    #[account(
+        seeds = [HOOK_SEED, approved_hook.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_hook.bump, // Use stored bump seed for validation
+    )]
+    pub approved_hook: Option<Account<'info, ApprovedHook>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Holds releases back while the vault is
+    /// in custody
+    #[account(
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_rollover`. Makes a reached target roll over into
+    /// the next goal instead of releasing
+    #[account(
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub rollover: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Holds releases back until the owner
+    /// repays their advance
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+}
+
+/// Implementation for the DepositViaDelegate accounts
+impl<'info> DepositViaDelegate<'info> {
+    /// Returns the amount deposited, which the vault's balance cap may have truncated, the
+    /// protocol fee charged on top of it and what happened to the vault, if the pull reached
+    /// the target
+    pub fn deposit_via_delegate(
+        &mut self,
+        amount: u64,
+    ) -> Result<(u64, u64, Option<TargetOutcome>)> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, true)?;
+
+        // The service pulls no more than the owner allowed, and no more often
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            amount <= self.auto_save.max_amount,
+            ErrorCode::AutoSaveLimitExceeded
+        );
+        require!(self.auto_save.is_due(now), ErrorCode::AutoSaveNotDue);
+        self.auto_save.last_pull_ts = Some(now);
+
+        // Apply the vault's balance cap, if any
+        let amount = self.state.capped_deposit(amount, self.vault_token_account.amount)?;
+
+        // Split off any excess over the target before anything leaves the owner's token account,
+        // and skim the protocol fee from the part the vault is credited with
+        let (amount, fee) = self.refunded_deposit(amount)?;
+
+        // Accrue points on the balance held so far, then extend the deposit streak
+        self.rewards
+            .accrue(now, self.vault_token_account.amount, self.mint.decimals);
+        self.rewards.record_deposit(now);
+        // Blocked wallets can't move tokens into or out of vaults
+        assert_not_blocked(&self.blocked_owner)?;
+        assert_not_blocked(&self.blocked_caller)?;
+        // A deposit can't share its slot with a withdrawal while the guard is on
+        self.rewards
+            .record_deposit_slot(Clock::get()?.slot, same_slot_guard(&self.config)?)?;
+
+        if let (Some(treasury_token_account), true) = (&self.treasury_token_account, fee > 0) {
+            self.pull(treasury_token_account.to_account_info(), fee)?;
+        }
+
+        // Like `deposit`, check up front whether the pull reaches the savings target. If it does,
+        // the part that would only be sent straight back stays in the owner's token account
+        let (outcome, deposited) = self.target().outcome(amount)?;
+        let released = match outcome {
+            Some(TargetOutcome::Released(released)) => Some(released),
+            _ => None,
+        };
+        if deposited > 0 {
+            self.pull(self.vault_token_account.to_account_info(), deposited)?;
+        }
+
+        // Record what logically happened: the deposit, then any release
+        self.outbox.record(ActivityKind::Deposit, amount)?;
+        self.mint_stats.record_deposit(amount);
+        if let Some(released) = released {
+            // The goal is met, so a deadline can no longer be missed
+            self.state.deadline = None;
+            self.rewards.earn(Badge::GoalReached);
+            self.outbox.record(ActivityKind::Release, released)?;
+            self.mint_stats.record_release(released);
+        }
+
+        Ok((amount, fee, outcome))
+    }
+
+    /// Moves `amount` from the owner's token account to `to`, with the vault authority acting
+    /// as the owner's delegate
+    pub fn pull(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.user_token_account.to_account_info(), // Source: owner's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to,
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA (delegate)
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+
+    /// Returns the part of a pull of `amount` that the vault is credited with, and the protocol
+    /// fee on that part, as `Deposit::refunded_deposit` does for a deposit
+    pub fn refunded_deposit(&mut self, amount: u64) -> Result<(u64, u64)> {
+        // Skim the protocol fee, only the rest is credited to the vault
+        let fee = self.protocol_fee(amount)?;
+        let credited = amount.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+
+        match self.target().refund_cut(credited)? {
+            // The fee on `needed` is at most the fee on `amount`, so the owner still covers both
+            Some(needed) => Ok((needed, self.protocol_fee(needed)?)),
+            None => Ok((credited, fee)),
+        }
+    }
+
+    /// Returns the protocol fee on a pull of `amount` from the owner
+    pub fn protocol_fee(&self, amount: u64) -> Result<u64> {
+        self.fee()
+            .amount(&self.owner.key(), &self.state.mint, amount)
+    }
+
+    /// Borrows the accounts the pull's protocol fee is worked out with
+    pub fn fee(&self) -> DepositFee<'_, 'info> {
+        DepositFee {
+            config: &self.config,
+            fee_tiers: &self.fee_tiers,
+            vault_template: &self.vault_template,
+            membership_token_account: self.membership_token_account.as_deref(),
+            treasury_token_account: self.treasury_token_account.as_deref(),
+        }
+    }
+
+    /// Borrows the accounts the pull settles the vault's savings target with
+    pub fn target(&mut self) -> DepositTarget<'_, 'info> {
+        DepositTarget {
+            state: &mut self.state,
+            rewards: &mut self.rewards,
+            vault_token_account: &self.vault_token_account,
+            user_token_account: &self.user_token_account,
+            vault_authority: &self.vault_authority,
+            mint: &self.mint,
+            config: &self.config,
+            custody: &self.custody,
+            rollover: &self.rollover,
+            advance: &self.advance,
+            price_update: self
+                .price_update
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            price_feed: &self.price_feed,
+            token_program: &self.token_program,
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetAutoSave<'info> {
+    /// The vault owner (must sign the transaction, pays for the authorization account)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, counting the vault's open grants
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // A new grant is counted in the state
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's auto-save authorization, replaced if one is already set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [AUTO_SAVE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + AutoSaveGrant::INIT_SPACE, // Allocate space for account data
+    )]
+    pub auto_save: Account<'info, AutoSaveGrant>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetAutoSave<'info> {
+    pub fn set_auto_save(
+        &mut self,
+        service: Pubkey,
+        max_amount: u64,
+        interval_seconds: i64,
+        bumps: &SetAutoSaveBumps,
+    ) -> Result<()> {
+        require!(
+            max_amount > 0 && interval_seconds >= 0,
+            ErrorCode::InvalidAutoSave
+        );
+
+        // Replacing the authorization keeps the same account, so only a new one is counted
+        if self.auto_save.state == Pubkey::default() {
+            self.state.open_grants = self
+                .state
+                .open_grants
+                .checked_add(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        // The last pull is kept when the authorization is replaced, so changing the terms
+        // can't be used to pull twice in one interval
+        self.auto_save.state = self.state.key();
+        self.auto_save.bump = bumps.auto_save;
+        self.auto_save.service = service;
+        self.auto_save.max_amount = max_amount;
+        self.auto_save.interval_seconds = interval_seconds;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClearAutoSave<'info> {
+    /// The vault owner (must sign the transaction, receives the authorization rent)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, counting the vault's open grants
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The cleared grant is no longer counted
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The auto-save authorization being cleared
+    #[account(
+        mut,
+        close = user, // Refund the rent to the user
+        seeds = [AUTO_SAVE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = auto_save.bump, // Use stored bump seed for validation
+    )]
+    pub auto_save: Account<'info, AutoSaveGrant>,
+}
+
+impl<'info> ClearAutoSave<'info> {
+    pub fn clear_auto_save(&mut self) -> Result<()> {
+        self.state.open_grants = self.state.open_grants.saturating_sub(1);
+
+        // The authorization account is closed by the accounts constraints
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositFor<'info> {
+    /// Whoever funds the vault, e.g. a relative (must sign the transaction, and pays for their
+    /// contribution record on their first gift)
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// The vault owner receiving the gift
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The depositor's token account that contains the tokens to be gifted
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = depositor_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = depositor_token_account.owner == depositor.key(), // Ensure depositor owns the token account
+        constraint = depositor_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's token account that will receive the gifted tokens
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The owner's vault state account
+    #[account(
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault authority PDA, checked as owner of the vault token account
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The program-wide config PDA, read for the protocol deposit fee
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the depositor, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, depositor.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_depositor: UncheckedAccount<'info>,
+
+    /// The treasury's token account for the vault's mint, required while a deposit fee is set
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = treasury_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = treasury_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+    )]
+    pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The fee discount tiers set by the admin
+    /// CHECK: The address is checked by the seeds, and the data is only read if the tiers exist
+    #[account(
+        seeds = [FEE_TIERS_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub fee_tiers: UncheckedAccount<'info>,
+
+    /// The template terms the vault was created with, which may override the deposit fee
+    /// CHECK: May not exist, parsed by `load_vault_template`
+    #[account(
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub vault_template: UncheckedAccount<'info>,
+
+    /// The depositor's token account of the membership mint, for a deposit fee discount
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The depositor's running contribution to the vault, created on their first gift
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = depositor, // Depositor pays for account creation
+        seeds = [CONTRIBUTION_SEED, state.key().as_ref(), depositor.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Contribution::INIT_SPACE, // Allocate space for account data
+    )]
+    pub contribution: Box<Account<'info, Contribution>>,
+
+    /// The vault's deposit hook program, required when the vault has a hook set
+    /// CHECK: Validated against the hook stored in state before being invoked
+    pub deposit_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// The admin approval of the deposit hook program, required when the vault has a hook set
+    #[account(
+        seeds = [HOOK_SEED, approved_hook.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_hook.bump, // Use stored bump seed for validation
+    )]
+    pub approved_hook: Option<Account<'info, ApprovedHook>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+/// Implementation for the DepositFor accounts
+impl<'info> DepositFor<'info> {
+    /// Returns the amount deposited, which the vault's balance cap may have truncated, and the
+    /// protocol fee the depositor paid on top of it
+    pub fn deposit_for(&mut self, amount: u64, bumps: &DepositForBumps) -> Result<(u64, u64)> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, true)?;
+
+        // Apply the vault's balance cap, if any
+        let amount = self.state.capped_deposit(amount, self.vault_token_account.amount)?;
+
+        // Skim the protocol fee, only the rest is credited to the vault
+        let fee = self
+            .fee()
+            .amount(&self.depositor.key(), &self.state.mint, amount)?;
+        let amount = amount.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+
+        // Accrue points on the balance held so far, then extend the owner's deposit streak
+        let now = Clock::get()?.unix_timestamp;
+        self.rewards
+            .accrue(now, self.vault_token_account.amount, self.mint.decimals);
+        self.rewards.record_deposit(now);
+        // Blocked wallets can't move tokens into or out of vaults
+        assert_not_blocked(&self.blocked_owner)?;
+        assert_not_blocked(&self.blocked_depositor)?;
+        // A deposit can't share its slot with a withdrawal while the guard is on
+        self.rewards
+            .record_deposit_slot(Clock::get()?.slot, same_slot_guard(&self.config)?)?;
+
+        // The depositor signs for their own tokens, the fee included
+        let mut transfers = vec![(self.vault_token_account.to_account_info(), amount)];
+        if let (Some(treasury_token_account), true) = (&self.treasury_token_account, fee > 0) {
+            transfers.push((treasury_token_account.to_account_info(), fee));
+        }
+        for (to, amount) in transfers {
+            let cpi_accounts = TransferChecked {
+                from: self.depositor_token_account.to_account_info(), // Source: depositor's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to,
+                authority: self.depositor.to_account_info(), // Authority: depositor (signs the transfer)
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+        }
+
+        self.outbox.record(ActivityKind::Deposit, amount)?;
+        self.mint_stats.record_deposit(amount);
+        self.contribution.record(
+            self.state.key(),
+            self.depositor.key(),
+            bumps.contribution,
+            amount,
+            now,
+        );
+
+        Ok((amount, fee))
+    }
+
+    /// Borrows the accounts the deposit's protocol fee is worked out with
+    pub fn fee(&self) -> DepositFee<'_, 'info> {
+        DepositFee {
+            config: &self.config,
+            fee_tiers: &self.fee_tiers,
+            vault_template: &self.vault_template,
+            membership_token_account: self.membership_token_account.as_deref(),
+            treasury_token_account: self.treasury_token_account.as_deref(),
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositAndSwap<'info> {
+    /// The user making the deposit (must sign the transaction, and the swap)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Pays for the user's vault mint token account if it has to be created, either the user
+    /// or a relayer
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The mint the user pays in, swapped into the vault's mint
+    pub input_mint: Box<Account<'info, Mint>>,
+
+    /// The user's token account the swap input is taken from
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = user_input_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = user_input_token_account.owner == user.key(), // Ensure user owns the token account
+        constraint = user_input_token_account.mint == input_mint.key() @ ErrorCode::MintMismatch, // Ensure correct input mint
+    )]
+    pub user_input_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The user's associated token account for the vault's mint, receiving the auto-release
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = payer, // Payer pays for account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = user, // Ensure user owns the token account
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account, which must be the swap's destination
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault state account containing configuration and metadata
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The goal deadline is cleared when the deposit releases the vault
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA, used for the auto-release (never passed to the swap)
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The program-wide config PDA, read for the protocol deposit fee
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the user, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_user: UncheckedAccount<'info>,
+
+    /// The treasury's token account for the vault's mint, required while a deposit fee is set
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = treasury_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = treasury_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+    )]
+    pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The fee discount tiers set by the admin
+    /// CHECK: The address is checked by the seeds, and the data is only read if the tiers exist
+    #[account(
+        seeds = [FEE_TIERS_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub fee_tiers: UncheckedAccount<'info>,
+
+    /// The template terms the vault was created with, which may override the deposit fee
+    /// CHECK: May not exist, parsed by `load_vault_template`
+    #[account(
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub vault_template: UncheckedAccount<'info>,
+
+    /// The depositor's token account of the membership mint, for a deposit fee discount
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The swap program to CPI into, e.g. Jupiter
+    /// CHECK: Checked against the admin approval before being invoked
+    pub swap_program: UncheckedAccount<'info>,
+
+    /// The admin approval of the swap program
+    #[account(
+        seeds = [SWAP_SEED, swap_program.key().as_ref()], // PDA seeds for deterministic address
+        bump = approved_swap.bump, // Use stored bump seed for validation
+    )]
+    pub approved_swap: Box<Account<'info, ApprovedSwap>>,
+
+    /// The Pyth price update of the vault's mint, required when the vault has a USD target
+    /// CHECK: Checked against the vault's USD target and parsed by `oracle::read_price`
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+
+    /// The vault's deposit hook program, required when the vault has a hook set
+    /// CHECK: Validated against the hook stored in state before being invoked
+    pub deposit_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// The admin approval of the deposit hook program, required when the vault has a hook set
+    #[account(
+        seeds = [HOOK_SEED, approved_hook.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_hook.bump, // Use stored bump seed for validation
+    )]
+    pub approved_hook: Option<Account<'info, ApprovedHook>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the user's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Holds releases back while the vault is
+    /// in custody
+    #[account(
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_rollover`. Makes a reached target roll over into
+    /// the next goal instead of releasing
+    #[account(
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub rollover: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Holds releases back until the owner
+    /// repays their advance
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+}
+
+/// Implementation for the DepositAndSwap accounts
+impl<'info> DepositAndSwap<'info> {
+    /// Swaps up to `amount_in` of the input mint into the vault through the approved swap
+    /// program, with `swap_data` and `remaining_accounts` forming the swap instruction.
+    /// Returns the amount swapped in, the swap output, the protocol fee skimmed from that
+    /// output and what happened to the vault, if the deposit reached the target.
+    pub fn deposit_and_swap(
+        &mut self,
+        amount_in: u64,
+        min_amount_out: u64,
+        swap_data: Vec<u8>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<(u64, u64, u64, Option<TargetOutcome>)> {
+        require!(amount_in > 0, ErrorCode::ZeroAmount);
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, true)?;
+        require_keys_eq!(
+            self.approved_swap.program_id,
+            self.swap_program.key(),
+            ErrorCode::SwapProgramNotApproved
+        );
+
+        // The vault authority must never be handed to the swap program
+        require!(
+            remaining_accounts
+                .iter()
+                .all(|account| account.key() != self.vault_authority.key()),
+            ErrorCode::SwapProgramNotApproved
+        );
+
+        let input_before = self.user_input_token_account.amount;
+        let balance_before = self.vault_token_account.amount;
+
+        // Accrue points on the balance held so far, then extend the deposit streak
+        let now = Clock::get()?.unix_timestamp;
+        self.rewards.accrue(now, balance_before, self.mint.decimals);
+        self.rewards.record_deposit(now);
+        // Blocked wallets can't move tokens into or out of vaults
+        assert_not_blocked(&self.blocked_user)?;
+        // A deposit can't share its slot with a withdrawal while the guard is on
+        self.rewards
+            .record_deposit_slot(Clock::get()?.slot, same_slot_guard(&self.config)?)?;
+
+        // The user signs the swap, which must deliver its output to the vault token account
+        let ix = anchor_lang::solana_program::instruction::Instruction {
+            program_id: self.swap_program.key(),
+            accounts: remaining_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.key(),
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: swap_data,
+        };
+        let mut infos = remaining_accounts.to_vec();
+        infos.push(self.swap_program.to_account_info());
+        anchor_lang::solana_program::program::invoke(&ix, &infos)?;
+
+        // Measure what the swap actually did rather than trusting its return data
+        self.user_input_token_account.reload()?;
+        self.vault_token_account.reload()?;
+        let spent = input_before.saturating_sub(self.user_input_token_account.amount);
+        let amount_out = self
+            .vault_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::SlippageExceeded)?;
+        require!(spent <= amount_in, ErrorCode::SwapOverspent);
+        require!(
+            amount_out > 0 && amount_out >= min_amount_out,
+            ErrorCode::SlippageExceeded
+        );
+
+        // Swap output can't be truncated after the fact, so it has to fit under the cap
+        require!(
+            self.state.capped_deposit(amount_out, balance_before)? == amount_out,
+            ErrorCode::BalanceCapExceeded
+        );
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // The swap delivers straight to the vault, so the protocol fee is skimmed from there
+        let fee = self
+            .fee()
+            .amount(&self.user.key(), &self.state.mint, amount_out)?;
+        if let (Some(treasury_token_account), true) = (&self.treasury_token_account, fee > 0) {
+            let cpi_accounts = TransferChecked {
+                from: self.vault_token_account.to_account_info(), // Source: vault's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to: treasury_token_account.to_account_info(), // Destination: treasury's token account
+                authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            token::transfer_checked(cpi_ctx, fee, self.mint.decimals)?;
+            self.vault_token_account.reload()?;
+        }
+
+        let credited = amount_out.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+        self.outbox.record(ActivityKind::Deposit, credited)?;
+        self.mint_stats.record_deposit(credited);
+
+        let price_update = self.price_update.as_ref().map(|account| account.to_account_info());
+        // Depositing an NFT pledges it, so it's never released right away, a custodial vault
+        // keeps everything until its release date, and an advance must be repaid first
+        if is_nft_mint(&self.mint)
+            || in_custody(&self.custody, Clock::get()?.unix_timestamp)?
+            || outstanding_debt(&self.advance)? > 0
+            || !self.state.target_reached(
+                self.vault_token_account.amount,
+                &self.mint,
+                price_update.as_ref(),
+                &self.price_feed,
+            )?
+        {
+            return Ok((spent, amount_out, fee, None));
+        }
+
+        // Vaults that roll over keep everything and move on to their next goal
+        if let Some(rollover) = load_rollover(&self.rollover)? {
+            self.rewards.earn(Badge::GoalReached);
+            let (previous_target, new_target) = self.state.roll_over(rollover.increase_bps)?;
+            let outcome = TargetOutcome::RolledOver {
+                previous_target,
+                new_target,
+            };
+            return Ok((spent, amount_out, fee, Some(outcome)));
+        }
+
+        // The release is a payout like any other, so the admin's freeze holds it back too
+        assert_not_frozen(&self.state, false)?;
+
+        // Target reached! Automatically send all tokens back to the user, or just the target
+        // if the vault keeps its surplus
+        let balance = self.vault_token_account.amount;
+        let keep_surplus = self.state.usd_target.is_none()
+            && balance > self.state.amount
+            && surplus_policy(&self.config)? == SurplusPolicy::Keep;
+        let released = if keep_surplus {
+            self.state.amount
+        } else {
+            balance
+        };
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.user_token_account.to_account_info(), // Destination: user's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, released, self.mint.decimals)?;
+
+        // The goal is met, so a deadline can no longer be missed
+        self.state.deadline = None;
+        self.rewards.earn(Badge::GoalReached);
+        self.outbox.record(ActivityKind::Release, released)?;
+        self.mint_stats.record_release(released);
+
+        let outcome = TargetOutcome::Released(released);
+        Ok((spent, amount_out, fee, Some(outcome)))
+    }
+
+    /// Borrows the accounts the deposit's protocol fee is worked out with
+    pub fn fee(&self) -> DepositFee<'_, 'info> {
+        DepositFee {
+            config: &self.config,
+            fee_tiers: &self.fee_tiers,
+            vault_template: &self.vault_template,
+            membership_token_account: self.membership_token_account.as_deref(),
+            treasury_token_account: self.treasury_token_account.as_deref(),
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct GrantDelegate<'info> {
+    /// The vault owner (must sign the transaction, pays for the session account)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, counting the vault's open grants
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // A new grant is counted in the state
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The delegate's session, replaced if the delegate already has one
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [DELEGATE_SEED, state.key().as_ref(), delegate.as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + SessionKey::INIT_SPACE, // Allocate space for account data
+    )]
+    pub session: Account<'info, SessionKey>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> GrantDelegate<'info> {
+    pub fn grant_delegate(
+        &mut self,
+        delegate: Pubkey,
+        permissions: DelegatePermissions,
+        expiry: i64,
+        max_amount: u64,
+        bumps: &GrantDelegateBumps,
+    ) -> Result<()> {
+        require!(
+            expiry > Clock::get()?.unix_timestamp,
+            ErrorCode::DelegateExpired
+        );
+
+        // Re-granting a key replaces its session, so only a new account is counted
+        if self.session.state == Pubkey::default() {
+            self.state.open_grants = self
+                .state
+                .open_grants
+                .checked_add(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        // A new grant starts a new session, with nothing withdrawn yet
+        self.session.state = self.state.key();
+        self.session.bump = bumps.session;
+        self.session.delegate = delegate;
+        self.session.permissions = permissions;
+        self.session.expiry = expiry;
+        self.session.max_amount = max_amount;
+        self.session.withdrawn = 0;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeDelegate<'info> {
+    /// The vault owner (must sign the transaction, receives the session rent)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, counting the vault's open grants
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The revoked grant is no longer counted
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The session being revoked
+    #[account(
+        mut,
+        close = user, // Refund the rent to the user
+        seeds = [DELEGATE_SEED, state.key().as_ref(), session.delegate.as_ref()], // PDA seeds for deterministic address
+        bump = session.bump, // Use stored bump seed for validation
+    )]
+    pub session: Account<'info, SessionKey>,
+}
+
+impl<'info> RevokeDelegate<'info> {
+    pub fn revoke_delegate(&mut self) -> Result<()> {
+        // Vaults migrated with sessions already open never counted them
+        self.state.open_grants = self.state.open_grants.saturating_sub(1);
+
+        // The session account is closed by the accounts constraints
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SessionDeposit<'info> {
+    /// The session key depositing its own tokens (must sign the transaction)
+    pub delegate: Signer<'info>,
+
+    /// The vault owner
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The session key's token account the tokens are taken from
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = delegate_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = delegate_token_account.owner == delegate.key(), // Ensure the delegate owns the token account
+        constraint = delegate_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub delegate_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's token account that will receive the tokens
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The owner's vault state account
+    #[account(
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault authority PDA
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The delegate's session granted by the owner
+    #[account(
+        seeds = [DELEGATE_SEED, state.key().as_ref(), delegate.key().as_ref()], // PDA seeds for deterministic address
+        bump = session.bump, // Use stored bump seed for validation
+    )]
+    pub session: Box<Account<'info, SessionKey>>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The program-wide config PDA, read for the protocol deposit fee
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the session key, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, delegate.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_delegate: UncheckedAccount<'info>,
+
+    /// The treasury's token account for the vault's mint, required while a deposit fee is set
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = treasury_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = treasury_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+    )]
+    pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The fee discount tiers set by the admin
+    /// CHECK: The address is checked by the seeds, and the data is only read if the tiers exist
+    #[account(
+        seeds = [FEE_TIERS_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub fee_tiers: UncheckedAccount<'info>,
+
+    /// The template terms the vault was created with, which may override the deposit fee
+    /// CHECK: May not exist, parsed by `load_vault_template`
+    #[account(
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub vault_template: UncheckedAccount<'info>,
+
+    /// The depositor's token account of the membership mint, for a deposit fee discount
+    pub membership_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The vault's deposit hook program, required when the vault has a hook set
+    /// CHECK: Validated against the hook stored in state before being invoked
+    pub deposit_hook_program: Option<UncheckedAccount<'info>>,
+
+    /// The admin approval of the deposit hook program, required when the vault has a hook set
+    #[account(
+        seeds = [HOOK_SEED, approved_hook.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_hook.bump, // Use stored bump seed for validation
+    )]
+    pub approved_hook: Option<Account<'info, ApprovedHook>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> SessionDeposit<'info> {
+    /// Returns the amount deposited, which the vault's balance cap may have truncated, and the
+    /// protocol fee charged on top of it
+    pub fn session_deposit(&mut self, amount: u64) -> Result<(u64, u64)> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        self.session.authorize(now, self.session.permissions.deposit)?;
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, true)?;
+
+        // Apply the vault's balance cap, if any
+        let amount = self.state.capped_deposit(amount, self.vault_token_account.amount)?;
+
+        // Skim the protocol fee, only the rest is credited to the vault
+        let fee = self
+            .fee()
+            .amount(&self.delegate.key(), &self.state.mint, amount)?;
+        let amount = amount.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+
+        // Accrue points on the balance held so far, then extend the deposit streak
+        self.rewards
+            .accrue(now, self.vault_token_account.amount, self.mint.decimals);
+        self.rewards.record_deposit(now);
+        // Blocked wallets can't move tokens into or out of vaults
+        assert_not_blocked(&self.blocked_owner)?;
+        assert_not_blocked(&self.blocked_delegate)?;
+        // A deposit can't share its slot with a withdrawal while the guard is on
+        self.rewards
+            .record_deposit_slot(Clock::get()?.slot, same_slot_guard(&self.config)?)?;
+
+        let mut transfers = vec![(self.vault_token_account.to_account_info(), amount)];
+        if let (Some(treasury_token_account), true) = (&self.treasury_token_account, fee > 0) {
+            transfers.push((treasury_token_account.to_account_info(), fee));
+        }
+        for (to, amount) in transfers {
+            let cpi_accounts = TransferChecked {
+                from: self.delegate_token_account.to_account_info(), // Source: delegate's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to,
+                authority: self.delegate.to_account_info(), // Authority: delegate (signs the transfer)
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+        }
+
+        self.outbox.record(ActivityKind::Deposit, amount)?;
+        self.mint_stats.record_deposit(amount);
+
+        Ok((amount, fee))
+    }
+
+    /// Borrows the accounts the deposit's protocol fee is worked out with
+    pub fn fee(&self) -> DepositFee<'_, 'info> {
+        DepositFee {
+            config: &self.config,
+            fee_tiers: &self.fee_tiers,
+            vault_template: &self.vault_template,
+            membership_token_account: self.membership_token_account.as_deref(),
+            treasury_token_account: self.treasury_token_account.as_deref(),
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SessionWithdraw<'info> {
+    /// The session key withdrawing for the owner (must sign the transaction)
+    /// Pays for the owner's token account if it has to be created
+    #[account(mut)]
+    pub delegate: Signer<'info>,
+
+    /// The vault owner, who always receives the withdrawn tokens
+    /// CHECK: Only used to derive the vault state PDA and the owner's token account
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The owner's associated token account that will receive the withdrawn tokens
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = delegate, // Delegate pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = owner, // Ensure the owner owns the token account
+        constraint = owner_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account that contains the tokens to be withdrawn
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The owner's vault state account
+    #[account(
+        mut, // The withdrawal time is recorded for the cooldown
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the withdrawal transfer
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The delegate's session granted by the owner
+    #[account(
+        mut, // The amount withdrawn is counted against the session cap
+        seeds = [DELEGATE_SEED, state.key().as_ref(), delegate.key().as_ref()], // PDA seeds for deterministic address
+        bump = session.bump, // Use stored bump seed for validation
+    )]
+    pub session: Box<Account<'info, SessionKey>>,
+
+    /// The vault's withdrawal policy, if the owner has set one
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze their own withdrawals
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, if the owner ever set one
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The vault's outstanding advance, if the owner borrowed against the locked balance
+    /// CHECK: May not exist, parsed by `outstanding_debt`
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// The program-wide config PDA, read to check whether the same-slot guard is on
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the session key, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, delegate.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_delegate: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the owner's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SessionWithdraw<'info> {
+    pub fn session_withdraw(&mut self, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        self.session
+            .authorize(clock.unix_timestamp, self.session.permissions.withdraw)?;
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+
+        // Sessions can't take the delayed or approved path, so they're shut out entirely
+        assert_immediate_withdrawal(&self.withdrawal_policy, clock.unix_timestamp)?;
+
+        // The session's own cap comes on top of every vault-wide rule
+        let withdrawn = self
+            .session
+            .withdrawn
+            .checked_add(amount)
+            .filter(|withdrawn| *withdrawn <= self.session.max_amount)
+            .ok_or(ErrorCode::DelegateLimitExceeded)?;
+        self.session.withdrawn = withdrawn;
+
+        // A session key can never stand in for the vault key
+        self.state.authorize_withdrawal(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+            outstanding_debt(&self.advance)?,
+            amount,
+            None,
+        )?;
+        // Blocked wallets can't move tokens into or out of vaults, and gated deployments only
+        // serve owners holding the credential
+        PayoutChecks {
+            blocked: &[&self.blocked_owner, &self.blocked_delegate],
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+        }
+        .check(&self.owner.key())?;
+        // A withdrawal can't share its slot with a deposit while the guard is on
+        self.rewards
+            .record_withdraw_slot(clock.slot, same_slot_guard(&self.config)?)?;
+
+        // Accrue points on the balance held so far
+        self.rewards.accrue(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            self.mint.decimals,
+        );
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.owner_token_account.to_account_info(), // Destination: owner's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.outbox.record(ActivityKind::Withdraw, amount)?;
+        self.mint_stats.record_withdrawal(amount);
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(merchant: Pubkey)]
+pub struct GrantSubscription<'info> {
+    /// The vault owner (must sign the transaction, pays for the subscription account)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, counting the vault's open grants
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // A new grant is counted in the state
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The merchant's subscription, replaced if the merchant already has one
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [SUBSCRIPTION_SEED, state.key().as_ref(), merchant.as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + SubscriptionGrant::INIT_SPACE, // Allocate space for account data
+    )]
+    pub subscription: Account<'info, SubscriptionGrant>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> GrantSubscription<'info> {
+    pub fn grant_subscription(
+        &mut self,
+        merchant: Pubkey,
+        amount: u64,
+        interval_seconds: i64,
+        bumps: &GrantSubscriptionBumps,
+    ) -> Result<()> {
+        require!(
+            amount > 0 && interval_seconds > 0,
+            ErrorCode::InvalidSubscription
+        );
+
+        // Replacing a merchant's grant keeps the same account, so only a new one is counted
+        if self.subscription.state == Pubkey::default() {
+            self.state.open_grants = self
+                .state
+                .open_grants
+                .checked_add(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        // The last pull is kept when a grant is replaced, so changing the terms can't be
+        // used to pull twice in one interval
+        self.subscription.state = self.state.key();
+        self.subscription.bump = bumps.subscription;
+        self.subscription.merchant = merchant;
+        self.subscription.amount = amount;
+        self.subscription.interval_seconds = interval_seconds;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeSubscription<'info> {
+    /// The vault owner (must sign the transaction, receives the subscription rent)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, counting the vault's open grants
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The revoked grant is no longer counted
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The subscription being revoked
+    #[account(
+        mut,
+        close = user, // Refund the rent to the user
+        seeds = [SUBSCRIPTION_SEED, state.key().as_ref(), subscription.merchant.as_ref()], // PDA seeds for deterministic address
+        bump = subscription.bump, // Use stored bump seed for validation
+    )]
+    pub subscription: Account<'info, SubscriptionGrant>,
+}
+
+impl<'info> RevokeSubscription<'info> {
+    pub fn revoke_subscription(&mut self) -> Result<()> {
+        // Vaults migrated with subscriptions already open never counted them
+        self.state.open_grants = self.state.open_grants.saturating_sub(1);
+
+        // The subscription account is closed by the accounts constraints
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PullSubscription<'info> {
+    /// The merchant pulling the subscription amount (must sign the transaction)
+    pub merchant: Signer<'info>,
+
+    /// The vault owner
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The merchant's token account receiving the pulled tokens
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = merchant_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = merchant_token_account.owner == merchant.key(), // Ensure the merchant owns the token account
+        constraint = merchant_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub merchant_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account that contains the tokens to be pulled
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The owner's vault state account
+    #[account(
+        mut, // The withdrawal time is recorded for the cooldown
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the transfer
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The merchant's subscription granted by the owner
+    #[account(
+        mut, // The pull time is recorded
+        seeds = [SUBSCRIPTION_SEED, state.key().as_ref(), merchant.key().as_ref()], // PDA seeds for deterministic address
+        bump = subscription.bump, // Use stored bump seed for validation
+    )]
+    pub subscription: Box<Account<'info, SubscriptionGrant>>,
+
+    /// The vault's withdrawal policy, if the owner has set one
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze their own withdrawals
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, if the owner ever set one
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The vault's outstanding advance, if the owner borrowed against the locked balance
+    /// CHECK: May not exist, parsed by `outstanding_debt`
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// The program-wide config PDA, read to check whether the same-slot guard is on
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the merchant, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, merchant.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_merchant: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> PullSubscription<'info> {
+    /// Pulls one subscription amount to the merchant and returns it
+    pub fn pull_subscription(&mut self) -> Result<u64> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(self.subscription.is_due(now), ErrorCode::SubscriptionNotDue);
+        let amount = self.subscription.amount;
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_self_excluded(&self.self_exclusion, now)?;
+
+        // Pulls can't take the delayed or approved path, so they're shut out entirely
+        assert_immediate_withdrawal(&self.withdrawal_policy, now)?;
+
+        // A pull is a withdrawal like any other, and never stands in for the vault key
+        self.state.authorize_withdrawal(
+            now,
+            self.vault_token_account.amount,
+            load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+            outstanding_debt(&self.advance)?,
+            amount,
+            None,
+        )?;
+        // Blocked wallets can't move tokens into or out of vaults, and gated deployments only
+        // serve owners holding the credential
+        PayoutChecks {
+            blocked: &[&self.blocked_owner, &self.blocked_merchant],
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+        }
+        .check(&self.owner.key())?;
+        // A withdrawal can't share its slot with a deposit while the guard is on
+        self.rewards
+            .record_withdraw_slot(Clock::get()?.slot, same_slot_guard(&self.config)?)?;
+        self.subscription.last_pull_ts = Some(now);
+
+        // Accrue points on the balance held so far
+        self.rewards
+            .accrue(now, self.vault_token_account.amount, self.mint.decimals);
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.merchant_token_account.to_account_info(), // Destination: merchant's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.outbox.record(ActivityKind::Withdraw, amount)?;
+        self.mint_stats.record_withdrawal(amount);
+
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    /// The user making the withdrawal (must sign the transaction)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Pays for the user's token account if it has to be created, either the user or a relayer
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The vault's offline key, required to sign withdrawals above the vault key threshold
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The user's associated token account that will receive the withdrawn tokens
+    /// Created if the user doesn't hold one (e.g. it was closed after a previous withdrawal)
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = payer, // Payer pays for account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = user, // Ensure user owns the token account
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's token account that contains the tokens to be withdrawn
+    /// Validates that this is the correct vault token account for this state
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The vault state account containing configuration and metadata
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The withdrawal time is recorded for the cooldown
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault authority PDA that can sign transactions on behalf of the vault
+    /// This PDA must sign to authorize the withdrawal from the vault
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The vault's withdrawal policy, if the owner has set a withdrawal delay
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze their own withdrawals
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, if the owner ever set one
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The vault's outstanding advance, if the owner borrowed against the locked balance
+    /// CHECK: May not exist, parsed by `outstanding_debt`
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// The program-wide config PDA, read to check whether the same-slot guard is on
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the user, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_user: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the user's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// The vault's extension account, required by `withdraw_with_memo` to store the memo hash
+    #[account(
+        mut,
+        seeds = [EXTENSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub extension: Option<AccountLoader<'info, VaultExtension>>,
+}
+
+/// Implementation for the Withdraw accounts
+impl<'info> Withdraw<'info> {
+    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        // Vaults with a withdrawal delay only pay out through `execute_withdrawal`
+        let clock = Clock::get()?;
+        assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+        // ... and so do vaults whose withdrawals need approvals
+        assert_immediate_withdrawal(&self.withdrawal_policy, clock.unix_timestamp)?;
+
+        self.state.authorize_withdrawal(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+            outstanding_debt(&self.advance)?,
+            amount,
+            self.vault_key.as_ref(),
+        )?;
+        // Blocked wallets can't move tokens into or out of vaults, and gated deployments only
+        // serve owners holding the credential
+        PayoutChecks {
+            blocked: &[&self.blocked_user],
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+        }
+        .check(&self.user.key())?;
+        // A withdrawal can't share its slot with a deposit while the guard is on
+        self.rewards
+            .record_withdraw_slot(clock.slot, same_slot_guard(&self.config)?)?;
+
+        // Accrue points on the balance held so far
+        self.rewards.accrue(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            self.mint.decimals,
+        );
+
+        // Prepare CPI accounts for transferring tokens from vault to user
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.user_token_account.to_account_info(), // Destination: user's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+
+        // Create PDA seeds for signing the transaction
+        // The vault authority PDA must sign to authorize the transfer from vault
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Create CPI context with PDA signer and execute the transfer
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+        // Transfer the specified amount of tokens from vault to user
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.outbox.record(ActivityKind::Withdraw, amount)?;
+        self.mint_stats.record_withdrawal(amount);
+
+        Ok(())
+    }
+
+    /// Closes the user's wSOL account, sending its balance and rent back as lamports
+    pub fn unwrap_sol(&mut self) -> Result<()> {
+        require_keys_eq!(self.mint.key(), native_mint::ID, ErrorCode::NotNativeMint);
+
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_accounts = token::CloseAccount {
+            account: self.user_token_account.to_account_info(), // Account being closed
+            destination: self.user.to_account_info(), // Lamports go back to the user
+            authority: self.user.to_account_info(), // Authority: the user owns the account
+        };
+        token::close_account(CpiContext::new(cpi_program, cpi_accounts))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct WithdrawTo<'info> {
+    /// The vault owner (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault's offline key, required to sign withdrawals above the vault key threshold
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// Any token account of the vault's mint that will receive the withdrawn tokens
+    /// Doesn't have to belong to the user (e.g. an exchange deposit address)
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = destination_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+        constraint = destination_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+        constraint = destination_token_account.key() != vault_token_account.key() @ ErrorCode::InvalidDestination, // Can't withdraw into the vault itself
+    )]
+    pub destination_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account that contains the tokens to be withdrawn
+    /// Validates that this is the correct vault token account for this state
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault state account containing configuration and metadata
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The withdrawal time is recorded for the cooldown
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that can sign transactions on behalf of the vault
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The vault's withdrawal policy, if the owner has set a withdrawal delay
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze their own withdrawals
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, if the owner ever set one
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The vault's outstanding advance, if the owner borrowed against the locked balance
+    /// CHECK: May not exist, parsed by `outstanding_debt`
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// The program-wide config PDA, read to check whether the same-slot guard is on
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the user, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_user: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the destination's owner, if they are blocked
+    /// CHECK: The address is checked by `assert_wallet_not_blocked`, and the entry may not exist
+    pub blocked_destination: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> WithdrawTo<'info> {
+    pub fn withdraw_to(&mut self, amount: u64) -> Result<()> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        // Same checks as `withdraw`, only the destination differs
+        let clock = Clock::get()?;
+        assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+        assert_immediate_withdrawal(&self.withdrawal_policy, clock.unix_timestamp)?;
+
+        self.state.authorize_withdrawal(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+            outstanding_debt(&self.advance)?,
+            amount,
+            self.vault_key.as_ref(),
+        )?;
+        // Blocked wallets can't move tokens into or out of vaults, and gated deployments only
+        // serve owners holding the credential
+        PayoutChecks {
+            blocked: &[&self.blocked_user],
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+        }
+        .check(&self.user.key())?;
+        // ... nor can the destination's owner
+        assert_wallet_not_blocked(
+            &self.blocked_destination,
+            &self.destination_token_account.owner,
+        )?;
+        // A withdrawal can't share its slot with a deposit while the guard is on
+        self.rewards
+            .record_withdraw_slot(clock.slot, same_slot_guard(&self.config)?)?;
+
+        // Accrue points on the balance held so far
+        self.rewards.accrue(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            self.mint.decimals,
+        );
+
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.destination_token_account.to_account_info(), // Destination: the chosen token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.outbox.record(ActivityKind::Withdraw, amount)?;
+        self.mint_stats.record_withdrawal(amount);
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Borrow<'info> {
+    /// The vault owner taking the advance (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// Pays for the advance account and the user's token account if they have to be created,
+    /// either the user or a relayer
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The vault's offline key, required to sign advances above the vault key threshold
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The user's associated token account that will receive the advance
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = payer, // Payer pays for account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = user, // Ensure user owns the token account
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account the advance is paid from
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The advance is recorded for the cooldown
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the transfer
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's outstanding advance, created with the first one
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = payer, // Payer pays for account creation
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Advance::INIT_SPACE, // Allocate space for account data
+    )]
+    pub advance: Box<Account<'info, Advance>>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The vault's withdrawal policy, if the owner has set a withdrawal delay
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze their own withdrawals
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, if the owner ever set one
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The program-wide config PDA, read for the treasury advance fees are paid to
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. A custodial vault can't be borrowed
+    /// against before its release date
+    #[account(
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the user, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_user: UncheckedAccount<'info>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the user's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Borrow<'info> {
+    /// Pays `amount` out of the locked balance to the owner as an advance. Returns the fee added
+    /// to the debt and the debt outstanding afterwards.
+    pub fn borrow(&mut self, amount: u64, bumps: &BorrowBumps) -> Result<(u64, u64)> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        // An advance leaves the vault like a withdrawal, so the same protections apply
+        let clock = Clock::get()?;
+        let balance = self.vault_token_account.amount;
+        PayoutChecks {
+            blocked: &[&self.blocked_user],
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+        }
+        .check(&self.user.key())?;
+        assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+        assert_not_in_custody(&self.custody, clock.unix_timestamp)?;
+        assert_immediate_withdrawal(&self.withdrawal_policy, clock.unix_timestamp)?;
+        self.state.authorize_outflow(
+            clock.unix_timestamp,
+            balance,
+            load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+            amount,
+            self.vault_key.as_ref(),
+        )?;
+
+        // Only locked tokens back advances, counting those already advanced as still there
+        let backing = locked_amount(clock.unix_timestamp, &self.state.lock_tranches)
+            .min(balance.saturating_add(self.advance.principal));
+        let principal = self
+            .advance
+            .principal
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            principal <= bps_share(backing, MAX_ADVANCE_BPS),
+            ErrorCode::AdvanceLimitExceeded
+        );
+
+        // The fee is paid to the treasury on repayment, so there is none without a treasury
+        let fee = match fee_treasury(&self.config)? {
+            Some(_) => bps_share(amount, ADVANCE_FEE_BPS),
+            None => 0,
+        };
+        self.advance.state = self.state.key();
+        self.advance.bump = bumps.advance;
+        self.advance.principal = principal;
+        self.advance.fee = self
+            .advance
+            .fee
+            .checked_add(fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Accrue points on the balance held so far
+        self.rewards
+            .accrue(clock.unix_timestamp, balance, self.mint.decimals);
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.user_token_account.to_account_info(), // Destination: user's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.outbox.record(ActivityKind::Withdraw, amount)?;
+        self.mint_stats.record_withdrawal(amount);
+
+        Ok((fee, self.advance.debt()))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Repay<'info> {
+    /// The vault owner repaying their advance (must sign the transaction)
+    /// Receives the advance account's rent once the debt is repaid
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The user's token account the repayment comes from
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = user_token_account.owner == user.key(), // Ensure user owns the token account
+        constraint = user_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account receiving the repayment
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA, checked as the owner of the vault token account
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's outstanding advance, closed once it's repaid
+    #[account(
+        mut,
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = advance.bump, // Use stored bump seed for validation
+    )]
+    pub advance: Box<Account<'info, Advance>>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The program-wide config PDA, read for the treasury advance fees are paid to
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The treasury's token account for the vault's mint, required to repay an advance fee
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = treasury_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = treasury_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+    )]
+    pub treasury_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> Repay<'info> {
+    /// Moves up to `amount` from the owner back into the vault, the principal first, and the fee
+    /// to the treasury. Returns the amount repaid and the debt still outstanding.
+    pub fn repay(&mut self, amount: u64) -> Result<(u64, u64)> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        // A fee can only be paid to the treasury, so it's waived if the admin has since removed it
+        let treasury = fee_treasury(&self.config)?;
+        if treasury.is_none() {
+            self.advance.fee = 0;
+        }
+
+        // Paying more than is owed settles the debt in full
+        let repaid = amount.min(self.advance.debt());
+        let principal_repaid = repaid.min(self.advance.principal);
+        let fee_repaid = repaid
+            .checked_sub(principal_repaid)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.advance.principal = self
+            .advance
+            .principal
+            .checked_sub(principal_repaid)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.advance.fee = self
+            .advance
+            .fee
+            .checked_sub(fee_repaid)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Accrue points on the balance held so far
+        self.rewards.accrue(
+            Clock::get()?.unix_timestamp,
+            self.vault_token_account.amount,
+            self.mint.decimals,
+        );
+
+        let mut transfers = vec![(self.vault_token_account.to_account_info(), principal_repaid)];
+        if let (Some(treasury), true) = (treasury, fee_repaid > 0) {
+            let treasury_token_account = self
+                .treasury_token_account
+                .as_ref()
+                .ok_or(ErrorCode::TreasuryMismatch)?;
+            require_keys_eq!(
+                treasury_token_account.owner,
+                treasury,
+                ErrorCode::TreasuryMismatch
+            );
+            require_keys_eq!(
+                treasury_token_account.mint,
+                self.mint.key(),
+                ErrorCode::MintMismatch
+            );
+            transfers.push((treasury_token_account.to_account_info(), fee_repaid));
+        }
+        for (to, amount) in transfers {
+            if amount == 0 {
+                continue;
+            }
+
+            let cpi_accounts = TransferChecked {
+                from: self.user_token_account.to_account_info(), // Source: user's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to,
+                authority: self.user.to_account_info(), // Authority: user must sign
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+        }
+
+        self.outbox
+            .record(ActivityKind::Deposit, principal_repaid)?;
+        self.mint_stats.record_deposit(principal_repaid);
+
+        // A repaid advance no longer holds anything back
+        let debt = self.advance.debt();
+        if debt == 0 {
+            self.advance.close(self.user.to_account_info())?;
+        }
+
+        Ok((repaid, debt))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetWithdrawalDelay<'info> {
+    /// The vault owner (must sign the transaction, pays for the policy account on first use)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's withdrawal policy, created the first time a delay is set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + WithdrawalPolicy::INIT_SPACE, // Allocate space for account data
+    )]
+    pub withdrawal_policy: Account<'info, WithdrawalPolicy>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetWithdrawalDelay<'info> {
+    /// Returns when the new delay takes effect
+    pub fn set_withdrawal_delay(
+        &mut self,
+        delay_seconds: i64,
+        bumps: &SetWithdrawalDelayBumps,
+    ) -> Result<i64> {
+        require!(
+            (0..=MAX_WITHDRAWAL_DELAY_SECONDS).contains(&delay_seconds),
+            ErrorCode::InvalidWithdrawalDelay
+        );
+
+        self.withdrawal_policy.state = self.state.key();
+        self.withdrawal_policy.bump = bumps.withdrawal_policy;
+
+        let now = Clock::get()?.unix_timestamp;
+        Ok(self.withdrawal_policy.set_delay(now, delay_seconds))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetUnlockStream<'info> {
+    /// The vault owner (must sign the transaction, pays for the schedule on first use)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's token account, whose balance the stream unlocks
+    #[account(
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's unlock stream, created the first time one is set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + UnlockSchedule::INIT_SPACE, // Allocate space for account data
+    )]
+    pub unlock_schedule: Account<'info, UnlockSchedule>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetUnlockStream<'info> {
+    /// Returns the amount streamed: the vault's whole balance
+    pub fn set_unlock_stream(
+        &mut self,
+        start_ts: i64,
+        cliff_ts: i64,
+        end_ts: i64,
+        bumps: &SetUnlockStreamBumps,
+    ) -> Result<u64> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            start_ts < end_ts
+                && (start_ts..=end_ts).contains(&cliff_ts)
+                && (MIN_LOCK_DURATION_SECONDS..=MAX_LOCK_DURATION_SECONDS)
+                    .contains(&end_ts.saturating_sub(now)),
+            ErrorCode::InvalidUnlockStream
+        );
+        // Like locks, a running stream can't be replaced, or it could be shortened
+        require!(
+            self.unlock_schedule.locked_at(now) == 0,
+            ErrorCode::UnlockStreamActive
+        );
+
+        self.unlock_schedule.state = self.state.key();
+        self.unlock_schedule.bump = bumps.unlock_schedule;
+        self.unlock_schedule.amount = self.vault_token_account.amount;
+        self.unlock_schedule.start_ts = start_ts;
+        self.unlock_schedule.cliff_ts = cliff_ts;
+        self.unlock_schedule.end_ts = end_ts;
+
+        Ok(self.unlock_schedule.amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SelfFreeze<'info> {
+    /// The vault owner freezing their own withdrawals (pays for the account on first use)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The owner's self-exclusion, created the first time they freeze themselves
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + SelfExclusion::INIT_SPACE, // Allocate space for account data
+    )]
+    pub self_exclusion: Account<'info, SelfExclusion>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SelfFreeze<'info> {
+    /// Returns when the owner can withdraw again
+    pub fn self_freeze(&mut self, duration_seconds: i64, bumps: &SelfFreezeBumps) -> Result<i64> {
+        require!(
+            (MIN_LOCK_DURATION_SECONDS..=MAX_SELF_FREEZE_SECONDS).contains(&duration_seconds),
+            ErrorCode::InvalidSelfFreezeDuration
+        );
+
+        self.self_exclusion.state = self.state.key();
+        self.self_exclusion.bump = bumps.self_exclusion;
+
+        let now = Clock::get()?.unix_timestamp;
+        let until =
+            lock_expiry(now, duration_seconds).ok_or(ErrorCode::InvalidSelfFreezeDuration)?;
+        Ok(self.self_exclusion.extend(until))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetWithdrawalApprovers<'info> {
+    /// The vault owner (must sign the transaction, pays for the policy account on first use)
+    /// Once approvers are set, `threshold` of them must co-sign as remaining accounts
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's withdrawal policy, created the first time approvers are set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + WithdrawalPolicy::INIT_SPACE, // Allocate space for account data
+    )]
+    pub withdrawal_policy: Account<'info, WithdrawalPolicy>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetWithdrawalApprovers<'info> {
+    pub fn set_withdrawal_approvers(
+        &mut self,
+        approvers: Vec<Pubkey>,
+        threshold: u8,
+        remaining_accounts: &[AccountInfo<'info>],
+        bumps: &SetWithdrawalApproversBumps,
+    ) -> Result<()> {
+        require!(
+            approvers.len() <= MAX_WITHDRAWAL_APPROVERS
+                && threshold as usize <= approvers.len()
+                && (threshold > 0 || approvers.is_empty())
+                && approvers
+                    .iter()
+                    .enumerate()
+                    .all(|(i, approver)| !approvers[..i].contains(approver)),
+            ErrorCode::InvalidApprovers
+        );
+
+        // Otherwise a stolen owner key could simply replace the approvers
+        let signed: Vec<Pubkey> = remaining_accounts
+            .iter()
+            .filter(|account| account.is_signer)
+            .map(|account| account.key())
+            .collect();
+        require!(
+            self.withdrawal_policy.count_approvals(&signed)
+                >= self.withdrawal_policy.threshold as usize,
+            ErrorCode::WithdrawalApprovalRequired
+        );
+
+        self.withdrawal_policy.state = self.state.key();
+        self.withdrawal_policy.bump = bumps.withdrawal_policy;
+        self.withdrawal_policy.approvers = approvers;
+        self.withdrawal_policy.threshold = threshold;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ApproveWithdrawal<'info> {
+    /// One of the vault's approvers (must sign the transaction)
+    pub approver: Signer<'info>,
+
+    /// The vault owner
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The owner's vault state account
+    #[account(
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's withdrawal policy listing the approvers
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = withdrawal_policy.bump, // Use stored bump seed for validation
+        constraint = withdrawal_policy.approvers.contains(&approver.key()) @ ErrorCode::Unauthorized, // Only approvers can approve
+    )]
+    pub withdrawal_policy: Account<'info, WithdrawalPolicy>,
+
+    /// The pending request being approved
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = pending_withdrawal.bump, // Use stored bump seed for validation
+    )]
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+}
+
+impl<'info> ApproveWithdrawal<'info> {
+    /// Returns the number of approvals the request has from the current approvers
+    pub fn approve_withdrawal(&mut self) -> Result<u8> {
+        let approver = self.approver.key();
+
+        // Approving twice doesn't count twice. Approvals from removed approvers are dropped
+        // to make room, so the list never outgrows the approver set
+        if !self.pending_withdrawal.approvals.contains(&approver) {
+            let approvers = &self.withdrawal_policy.approvers;
+            self.pending_withdrawal
+                .approvals
+                .retain(|approval| approvers.contains(approval));
+            self.pending_withdrawal.approvals.push(approver);
+        }
+
+        Ok(self
+            .withdrawal_policy
+            .count_approvals(&self.pending_withdrawal.approvals) as u8)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetGuardians<'info> {
+    /// The vault owner (must sign the transaction, pays for the guardians account on first use)
+    /// Once approvers are set, `threshold` of them must co-sign as remaining accounts
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault's offline key, required when the vault has one
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's guardians, created the first time they are set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [GUARDIANS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Guardians::INIT_SPACE, // Allocate space for account data
+    )]
+    pub guardians: Account<'info, Guardians>,
+
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`. Its approvers must co-sign
+    /// a change of guardians
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`. Refuses changes while the
+    /// owner's self-exclusion is running
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+}
+
+impl<'info> SetGuardians<'info> {
+    pub fn set_guardians(
+        &mut self,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+        remaining_accounts: &[AccountInfo<'info>],
+        bumps: &SetGuardiansBumps,
+    ) -> Result<()> {
+        require!(
+            guardians.len() <= MAX_GUARDIANS
+                && threshold as usize <= guardians.len()
+                && (threshold > 0 || guardians.is_empty())
+                && !guardians.contains(&self.user.key())
+                && guardians
+                    .iter()
+                    .enumerate()
+                    .all(|(i, guardian)| !guardians[..i].contains(guardian)),
+            ErrorCode::InvalidGuardians
+        );
+
+        // Guardians can hand the vault to a new owner, so a stolen owner key could otherwise
+        // name its own guardians to take the vault past the vault key and the approvers
+        if let Some(vault_key) = self.state.vault_key {
+            assert_vault_key_signed(vault_key, self.vault_key.as_ref())?;
+        }
+        assert_not_self_excluded(&self.self_exclusion, Clock::get()?.unix_timestamp)?;
+        if let Some(policy) = load_withdrawal_policy(&self.withdrawal_policy)? {
+            let signed: Vec<Pubkey> = remaining_accounts
+                .iter()
+                .filter(|account| account.is_signer)
+                .map(|account| account.key())
+                .collect();
+            require!(
+                policy.count_approvals(&signed) >= policy.threshold as usize,
+                ErrorCode::WithdrawalApprovalRequired
+            );
+        }
+
+        self.guardians.state = self.state.key();
+        self.guardians.bump = bumps.guardians;
+        self.guardians.guardians = guardians;
+        self.guardians.threshold = threshold;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ProposeRecovery<'info> {
+    /// The guardian proposing the recovery (must sign the transaction, pays for the proposal)
+    #[account(mut)]
+    pub guardian: Signer<'info>,
+
+    /// The vault owner who lost their key
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The owner's vault state account
+    #[account(
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's guardians
+    #[account(
+        seeds = [GUARDIANS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = guardians.bump, // Use stored bump seed for validation
+        constraint = guardians.guardians.contains(&guardian.key()) @ ErrorCode::Unauthorized, // Only guardians can propose
+    )]
+    pub guardians: Account<'info, Guardians>,
+
+    /// The recovery proposal, one at a time per vault
+    #[account(
+        init, // Create a new account
+        payer = guardian, // Guardian pays for account creation
+        seeds = [RECOVERY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + RecoveryProposal::INIT_SPACE, // Allocate space for account data
+    )]
+    pub recovery_proposal: Account<'info, RecoveryProposal>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ProposeRecovery<'info> {
+    /// Returns when the recovery can be executed, if the owner doesn't veto it
+    pub fn propose_recovery(
+        &mut self,
+        new_owner: Pubkey,
+        bumps: &ProposeRecoveryBumps,
+    ) -> Result<i64> {
+        require_keys_neq!(new_owner, self.owner.key(), ErrorCode::InvalidGuardians);
+
+        let now = Clock::get()?.unix_timestamp;
+        self.recovery_proposal.state = self.state.key();
+        self.recovery_proposal.bump = bumps.recovery_proposal;
+        self.recovery_proposal.proposer = self.guardian.key();
+        self.recovery_proposal.new_owner = new_owner;
+        self.recovery_proposal.executable_at = now
+            .checked_add(RECOVERY_DELAY_SECONDS)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Proposing counts as a vote
+        self.recovery_proposal.votes = vec![self.guardian.key()];
+
+        Ok(self.recovery_proposal.executable_at)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct VoteRecovery<'info> {
+    /// The guardian voting for the recovery (must sign the transaction)
+    pub guardian: Signer<'info>,
+
+    /// The vault owner who lost their key
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The owner's vault state account
+    #[account(
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's guardians
+    #[account(
+        seeds = [GUARDIANS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = guardians.bump, // Use stored bump seed for validation
+        constraint = guardians.guardians.contains(&guardian.key()) @ ErrorCode::Unauthorized, // Only guardians can vote
+    )]
+    pub guardians: Account<'info, Guardians>,
+
+    /// The recovery proposal being voted on
+    #[account(
+        mut,
+        seeds = [RECOVERY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = recovery_proposal.bump, // Use stored bump seed for validation
+    )]
+    pub recovery_proposal: Account<'info, RecoveryProposal>,
+}
+
+impl<'info> VoteRecovery<'info> {
+    /// Returns the number of votes the proposal has from the current guardians
+    pub fn vote_recovery(&mut self) -> Result<u8> {
+        let guardian = self.guardian.key();
+
+        // Voting twice doesn't count twice. Votes from removed guardians are dropped to
+        // make room, so the list never outgrows the guardian set
+        if !self.recovery_proposal.votes.contains(&guardian) {
+            let guardians = &self.guardians.guardians;
+            self.recovery_proposal
+                .votes
+                .retain(|vote| guardians.contains(vote));
+            self.recovery_proposal.votes.push(guardian);
+        }
+
+        Ok(self.guardians.count_votes(&self.recovery_proposal.votes) as u8)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct VetoRecovery<'info> {
+    /// The vault owner, who still holds their key (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The guardian who proposed the recovery, refunded the proposal rent
+    /// CHECK: Checked against the proposal
+    #[account(
+        mut,
+        address = recovery_proposal.proposer, // Only the proposer gets the rent back
+    )]
+    pub proposer: UncheckedAccount<'info>,
+
+    /// The recovery proposal being vetoed
+    #[account(
+        mut,
+        close = proposer, // Refund the rent to the proposer
+        seeds = [RECOVERY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = recovery_proposal.bump, // Use stored bump seed for validation
+    )]
+    pub recovery_proposal: Account<'info, RecoveryProposal>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ExecuteRecovery<'info> {
+    /// Whoever executes the recovery once it's due (must sign the transaction)
+    /// Pays for the new owner's token account if it has to be created
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// The vault owner who lost their key
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The wallet the vault is recovered to
+    /// CHECK: Checked against the proposal
+    #[account(
+        address = recovery_proposal.new_owner, // Only the proposed wallet
+    )]
+    pub new_owner: UncheckedAccount<'info>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The new owner's associated token account receiving the vault's balance
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = caller, // Caller pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = new_owner, // Ensure the new owner owns the token account
+        constraint = new_owner_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub new_owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account holding the tokens to recover
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The owner's vault state account
+    #[account(
+        mut, // The goal deadline is cleared on recovery
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the recovery transfer
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The vault's guardians
+    #[account(
+        seeds = [GUARDIANS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = guardians.bump, // Use stored bump seed for validation
+    )]
+    pub guardians: Box<Account<'info, Guardians>>,
+
+    /// The guardian who proposed the recovery, refunded the proposal rent
+    /// CHECK: Checked against the proposal
+    #[account(
+        mut,
+        address = recovery_proposal.proposer, // Only the proposer gets the rent back
+    )]
+    pub proposer: UncheckedAccount<'info>,
+
+    /// The recovery proposal, closed once executed
+    #[account(
+        mut,
+        close = proposer, // Refund the rent to the proposer
+        seeds = [RECOVERY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = recovery_proposal.bump, // Use stored bump seed for validation
+    )]
+    pub recovery_proposal: Box<Account<'info, RecoveryProposal>>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the new owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, new_owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_new_owner: UncheckedAccount<'info>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the new owner's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Holds releases back while the vault is
+    /// in custody
+    #[account(
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`. Holds the recovery back
+    /// while the owner's self-exclusion is running
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+}
+
+impl<'info> ExecuteRecovery<'info> {
+    /// Moves the whole balance to the new owner and returns the amount recovered
+    pub fn execute_recovery(&mut self) -> Result<u64> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, false)?;
+        // Blocked wallets can't move tokens out of vaults, nor recover them to a blocked wallet
+        assert_not_blocked(&self.blocked_owner)?;
+        assert_not_blocked(&self.blocked_new_owner)?;
+
+        // Only votes from the current guardians count
+        require!(
+            self.guardians.threshold > 0
+                && self.guardians.count_votes(&self.recovery_proposal.votes)
+                    >= self.guardians.threshold as usize,
+            ErrorCode::RecoveryNotApproved
+        );
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= self.recovery_proposal.executable_at,
+            ErrorCode::RecoveryNotReady
+        );
+        assert_not_in_custody(&self.custody, clock.unix_timestamp)?;
+        assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+
+        // Recovery hands over the owner's savings, not a way around their own locks
+        let balance = self.vault_token_account.amount;
+        require!(
+            locked_amount(clock.unix_timestamp, &self.state.lock_tranches) == 0,
+            ErrorCode::LockNotExpired
+        );
+
+        // Accrue points on the balance held so far
+        self.rewards
+            .accrue(clock.unix_timestamp, balance, self.mint.decimals);
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.new_owner_token_account.to_account_info(), // Destination: new owner's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, balance, self.mint.decimals)?;
+
+        // The old vault is emptied, so its deadline can no longer be missed
+        self.state.deadline = None;
+        self.outbox.record(ActivityKind::Recovery, balance)?;
+        self.mint_stats.record_withdrawal(balance);
+
+        Ok(balance)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(circle_id: u64)]
+pub struct CreateCircle<'info> {
+    /// The member organizing the circle (must sign the transaction, pays for its accounts)
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    /// The circle, one per creator and `circle_id`
+    #[account(
+        init, // Create a new account
+        payer = creator, // Creator pays for account creation
+        seeds = [CIRCLE_SEED, creator.key().as_ref(), &circle_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Circle::INIT_SPACE, // Allocate space for account data
+    )]
+    pub circle: Box<Account<'info, Circle>>,
+
+    /// The token mint the members contribute in
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The circle's token account holding each round's pot: the circle's associated token
+    /// account for the mint
+    #[account(
+        init, // Create a new token account
+        payer = creator, // Creator pays for account creation
+        associated_token::mint = mint, // Specify which token mint this account is for
+        associated_token::authority = circle, // The circle PDA signs the payouts
+    )]
+    pub circle_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token operations)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the circle token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateCircle<'info> {
+    /// Returns the start of the first round
+    pub fn create_circle(
+        &mut self,
+        circle_id: u64,
+        members: Vec<Pubkey>,
+        contribution: u64,
+        period_seconds: i64,
+        bumps: &CreateCircleBumps,
+    ) -> Result<i64> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            (2..=MAX_CIRCLE_MEMBERS).contains(&members.len())
+                && members
+                    .iter()
+                    .enumerate()
+                    .all(|(i, member)| !members[..i].contains(member))
+                && contribution > 0
+                && period_seconds >= MIN_CIRCLE_PERIOD_SECONDS,
+            ErrorCode::InvalidCircle
+        );
+
+        self.circle.creator = self.creator.key();
+        self.circle.circle_id = circle_id;
+        self.circle.bump = bumps.circle;
+        self.circle.mint = self.mint.key();
+        self.circle.contribution = contribution;
+        self.circle.period_seconds = period_seconds;
+        self.circle.start_ts = now;
+        self.circle.round = 0;
+        self.circle.members = members
+            .into_iter()
+            .map(|member| CircleMember {
+                member,
+                last_paid_round: None,
+                missed_rounds: 0,
+                refundable: 0,
+            })
+            .collect();
+
+        // Every round must end at a representable time, and every pot must fit in a u64
+        self.circle
+            .round_end(self.circle.members.len() as u8 - 1)
+            .ok_or(ErrorCode::InvalidCircle)?;
+        contribution
+            .checked_mul(self.circle.members.len() as u64)
+            .ok_or(ErrorCode::InvalidCircle)?;
+
+        Ok(now)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ContributeToCircle<'info> {
+    /// The member paying into the current round (must sign the transaction)
+    pub member: Signer<'info>,
+
+    /// The circle
+    #[account(
+        mut,
+        seeds = [CIRCLE_SEED, circle.creator.as_ref(), &circle.circle_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = circle.bump, // Use stored bump seed for validation
+    )]
+    pub circle: Box<Account<'info, Circle>>,
+
+    /// The circle's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = circle.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The member's token account paying the contribution
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = member_token_account.owner == member.key(), // Ensure the member owns the token account
+        constraint = member_token_account.mint == circle.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub member_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The circle's token account collecting the pot
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = circle, // Ensure it is the circle's account
+    )]
+    pub circle_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ContributeToCircle<'info> {
+    /// Pays the member's contribution into the current round and returns the round
+    pub fn contribute_to_circle(&mut self) -> Result<u8> {
+        require!(!self.circle.is_complete(), ErrorCode::CircleComplete);
+
+        let round = self.circle.round;
+        let member = self
+            .circle
+            .members
+            .iter_mut()
+            .find(|member| member.member == self.member.key())
+            .ok_or(ErrorCode::NotCircleMember)?;
+        // Members pay once per round, never ahead
+        require!(
+            member.last_paid_round != Some(round),
+            ErrorCode::CircleAlreadyPaid
+        );
+        member.last_paid_round = Some(round);
+
+        let cpi_accounts = TransferChecked {
+            from: self.member_token_account.to_account_info(), // Source: member's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.circle_token_account.to_account_info(), // Destination: circle's token account
+            authority: self.member.to_account_info(), // Authority: member (signs the transfer)
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token::transfer_checked(cpi_ctx, self.circle.contribution, self.mint.decimals)?;
+
+        Ok(round)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PayoutCircle<'info> {
+    /// Whoever pays out the round once it's due (must sign the transaction)
+    /// Pays for the recipient's token account if it has to be created
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// The circle
+    #[account(
+        mut,
+        seeds = [CIRCLE_SEED, circle.creator.as_ref(), &circle.circle_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = circle.bump, // Use stored bump seed for validation
+    )]
+    pub circle: Box<Account<'info, Circle>>,
+
+    /// The member whose turn it is
+    /// CHECK: Checked against the circle's rotation
+    pub recipient: UncheckedAccount<'info>,
+
+    /// The circle's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = circle.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The recipient's associated token account receiving the pot
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = caller, // Caller pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = recipient, // Ensure the recipient owns the token account
+    )]
+    pub recipient_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The circle's token account holding the pot
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = circle, // Ensure it is the circle's account
+    )]
+    pub circle_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the recipient's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PayoutCircle<'info> {
+    /// Closes the current round. Returns the round, the amount paid out (`None` if the
+    /// recipient forfeited it) and the members who didn't pay into it.
+    pub fn payout_circle(&mut self) -> Result<(u8, Option<u64>, Vec<Pubkey>)> {
+        require!(!self.circle.is_complete(), ErrorCode::CircleComplete);
+
+        let round = self.circle.round;
+        require_keys_eq!(
+            self.recipient.key(),
+            self.circle.members[round as usize].member,
+            ErrorCode::InvalidCircleRecipient
+        );
+
+        // A round closes early once everyone has paid, and otherwise at the end of its period
+        let now = Clock::get()?.unix_timestamp;
+        let everyone_paid = self
+            .circle
+            .members
+            .iter()
+            .all(|member| member.last_paid_round == Some(round));
+        let round_end = self
+            .circle
+            .round_end(round)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            everyone_paid || now >= round_end,
+            ErrorCode::CircleRoundNotOver
+        );
+
+        let mut defaulters = Vec::new();
+        let mut payers = 0u64;
+        for member in self.circle.members.iter_mut() {
+            if member.last_paid_round == Some(round) {
+                payers += 1;
+            } else {
+                member.missed_rounds = member
+                    .missed_rounds
+                    .checked_add(1)
+                    .ok_or(ErrorCode::MathOverflow)?;
+                defaulters.push(member.member);
+            }
+        }
+        let pot = self
+            .circle
+            .contribution
+            .checked_mul(payers)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.circle.round += 1;
+
+        // A member who skipped a round forfeits their pot, and this round's payers get
+        // their contributions back instead
+        if self.circle.members[round as usize].missed_rounds > 0 {
+            let contribution = self.circle.contribution;
+            for member in self.circle.members.iter_mut() {
+                if member.last_paid_round == Some(round) {
+                    member.refundable = member
+                        .refundable
+                        .checked_add(contribution)
+                        .ok_or(ErrorCode::MathOverflow)?;
+                }
+            }
+            return Ok((round, None, defaulters));
+        }
+
+        if pot > 0 {
+            let circle_id = self.circle.circle_id.to_le_bytes();
+            let seeds = &[
+                CIRCLE_SEED, // Seed prefix
+                self.circle.creator.as_ref(), // Creator key
+                circle_id.as_ref(), // Circle id
+                &[self.circle.bump], // Bump seed
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let cpi_accounts = TransferChecked {
+                from: self.circle_token_account.to_account_info(), // Source: circle's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to: self.recipient_token_account.to_account_info(), // Destination: recipient's token account
+                authority: self.circle.to_account_info(), // Authority: circle PDA
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            token::transfer_checked(cpi_ctx, pot, self.mint.decimals)?;
+        }
+
+        Ok((round, Some(pot), defaulters))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimCircleRefund<'info> {
+    /// The member claiming their refunded contributions (must sign the transaction)
+    pub member: Signer<'info>,
+
+    /// The circle
+    #[account(
+        mut,
+        seeds = [CIRCLE_SEED, circle.creator.as_ref(), &circle.circle_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = circle.bump, // Use stored bump seed for validation
+    )]
+    pub circle: Box<Account<'info, Circle>>,
+
+    /// The circle's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = circle.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The member's token account receiving the refund
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = member_token_account.owner == member.key(), // Ensure the member owns the token account
+        constraint = member_token_account.mint == circle.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub member_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The circle's token account holding the refunds
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = circle, // Ensure it is the circle's account
+    )]
+    pub circle_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ClaimCircleRefund<'info> {
+    /// Pays out the member's refundable contributions and returns the amount
+    pub fn claim_circle_refund(&mut self) -> Result<u64> {
+        let member = self
+            .circle
+            .members
+            .iter_mut()
+            .find(|member| member.member == self.member.key())
+            .ok_or(ErrorCode::NotCircleMember)?;
+        let amount = member.refundable;
+        require!(amount > 0, ErrorCode::ZeroAmount);
+        member.refundable = 0;
+
+        let circle_id = self.circle.circle_id.to_le_bytes();
+        let seeds = &[
+            CIRCLE_SEED, // Seed prefix
+            self.circle.creator.as_ref(), // Creator key
+            circle_id.as_ref(), // Circle id
+            &[self.circle.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.circle_token_account.to_account_info(), // Source: circle's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.member_token_account.to_account_info(), // Destination: member's token account
+            authority: self.circle.to_account_info(), // Authority: circle PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CloseCircle<'info> {
+    /// The circle's creator (receives the rent of the closed accounts)
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    /// The circle, closed at the end of the instruction
+    #[account(
+        mut,
+        close = creator, // Refund the circle rent to the creator
+        has_one = creator @ ErrorCode::Unauthorized, // Only the creator can close the circle
+        seeds = [CIRCLE_SEED, creator.key().as_ref(), &circle.circle_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = circle.bump, // Use stored bump seed for validation
+    )]
+    pub circle: Box<Account<'info, Circle>>,
+
+    /// The circle's token account, closed through the token program
+    #[account(
+        mut, // Account will be closed
+        associated_token::mint = circle.mint, // Ensure correct token mint
+        associated_token::authority = circle, // Ensure it is the circle's account
+    )]
+    pub circle_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required to close the token account)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> CloseCircle<'info> {
+    pub fn close_circle(&mut self) -> Result<()> {
+        // Every round must be paid out and every refund claimed, so no tokens are stranded
+        require!(
+            self.circle.is_complete() && self.circle_token_account.amount == 0,
+            ErrorCode::CircleNotSettled
+        );
+
+        let circle_id = self.circle.circle_id.to_le_bytes();
+        let seeds = &[
+            CIRCLE_SEED, // Seed prefix
+            self.circle.creator.as_ref(), // Creator key
+            circle_id.as_ref(), // Circle id
+            &[self.circle.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = token::CloseAccount {
+            account: self.circle_token_account.to_account_info(), // Account being closed
+            destination: self.creator.to_account_info(), // Rent goes back to the creator
+            authority: self.circle.to_account_info(), // Authority: circle PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::close_account(cpi_ctx)?;
+
+        // The circle account is closed by its `close = creator` constraint
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CreatePayroll<'info> {
+    /// The employer setting up the payroll (must sign the transaction, pays for its accounts)
+    #[account(mut)]
+    pub employer: Signer<'info>,
+
+    /// The employee being paid
+    /// CHECK: Any wallet can be paid
+    pub employee: UncheckedAccount<'info>,
+
+    /// The payroll, one per employer and employee
+    #[account(
+        init, // Create a new account
+        payer = employer, // Employer pays for account creation
+        seeds = [PAYROLL_SEED, employer.key().as_ref(), employee.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Payroll::INIT_SPACE, // Allocate space for account data
+    )]
+    pub payroll: Box<Account<'info, Payroll>>,
+
+    /// The token mint the employee is paid in
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The payroll's token account holding the funded pay: the payroll's associated token
+    /// account for the mint
+    #[account(
+        init, // Create a new token account
+        payer = employer, // Employer pays for account creation
+        associated_token::mint = mint, // Specify which token mint this account is for
+        associated_token::authority = payroll, // The payroll PDA signs the payouts
+    )]
+    pub payroll_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token operations)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the payroll token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreatePayroll<'info> {
+    /// Returns the start of the first pay period
+    pub fn create_payroll(
+        &mut self,
+        amount_per_period: u64,
+        period_seconds: i64,
+        bumps: &CreatePayrollBumps,
+    ) -> Result<i64> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            self.employer.key() != self.employee.key()
+                && amount_per_period > 0
+                && period_seconds > 0,
+            ErrorCode::InvalidPayroll
+        );
+
+        self.payroll.employer = self.employer.key();
+        self.payroll.employee = self.employee.key();
+        self.payroll.bump = bumps.payroll;
+        self.payroll.mint = self.mint.key();
+        self.payroll.amount_per_period = amount_per_period;
+        self.payroll.period_seconds = period_seconds;
+        self.payroll.start_ts = now;
+        self.payroll.stopped_at = None;
+        self.payroll.claimed = 0;
+
+        Ok(now)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FundPayroll<'info> {
+    /// The employer topping up the payroll (must sign the transaction)
+    pub employer: Signer<'info>,
+
+    /// The payroll
+    #[account(
+        has_one = employer @ ErrorCode::Unauthorized, // Only the employer funds the payroll
+        seeds = [PAYROLL_SEED, employer.key().as_ref(), payroll.employee.as_ref()], // PDA seeds for deterministic address
+        bump = payroll.bump, // Use stored bump seed for validation
+    )]
+    pub payroll: Box<Account<'info, Payroll>>,
+
+    /// The payroll's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = payroll.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The employer's token account paying in
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = employer_token_account.owner == employer.key(), // Ensure the employer owns the token account
+        constraint = employer_token_account.mint == payroll.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub employer_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The payroll's token account holding the funded pay
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = payroll, // Ensure it is the payroll's account
+    )]
+    pub payroll_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> FundPayroll<'info> {
+    pub fn fund_payroll(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+        // Nothing accrues after a stop, so there is nothing left to fund
+        require!(
+            self.payroll.stopped_at.is_none(),
+            ErrorCode::PayrollAlreadyStopped
+        );
+
+        let cpi_accounts = TransferChecked {
+            from: self.employer_token_account.to_account_info(), // Source: employer's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.payroll_token_account.to_account_info(), // Destination: payroll's token account
+            authority: self.employer.to_account_info(), // Authority: employer (signs the transfer)
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimPayroll<'info> {
+    /// The employee claiming their accrued pay (must sign the transaction)
+    pub employee: Signer<'info>,
+
+    /// The payroll
+    #[account(
+        mut,
+        has_one = employee @ ErrorCode::Unauthorized, // Only the employee claims the pay
+        seeds = [PAYROLL_SEED, payroll.employer.as_ref(), employee.key().as_ref()], // PDA seeds for deterministic address
+        bump = payroll.bump, // Use stored bump seed for validation
+    )]
+    pub payroll: Box<Account<'info, Payroll>>,
+
+    /// The payroll's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = payroll.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The employee's token account receiving the pay
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = employee_token_account.owner == employee.key(), // Ensure the employee owns the token account
+        constraint = employee_token_account.mint == payroll.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub employee_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The payroll's token account holding the funded pay
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = payroll, // Ensure it is the payroll's account
+    )]
+    pub payroll_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ClaimPayroll<'info> {
+    /// Pays out the accrued pay the payroll holds tokens for and returns the amount
+    pub fn claim_payroll(&mut self) -> Result<u64> {
+        let now = Clock::get()?.unix_timestamp;
+        // Pay the employer hasn't funded yet stays owed until they do
+        let amount = self
+            .payroll
+            .owed(now)
+            .min(self.payroll_token_account.amount);
+        require!(amount > 0, ErrorCode::ZeroAmount);
+        self.payroll.claimed = self
+            .payroll
+            .claimed
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let seeds = &[
+            PAYROLL_SEED, // Seed prefix
+            self.payroll.employer.as_ref(), // Employer key
+            self.payroll.employee.as_ref(), // Employee key
+            &[self.payroll.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.payroll_token_account.to_account_info(), // Source: payroll's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.employee_token_account.to_account_info(), // Destination: employee's token account
+            authority: self.payroll.to_account_info(), // Authority: payroll PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct StopPayroll<'info> {
+    /// The employer stopping the payroll (must sign the transaction)
+    pub employer: Signer<'info>,
+
+    /// The payroll
+    #[account(
+        mut,
+        has_one = employer @ ErrorCode::Unauthorized, // Only the employer stops the payroll
+        seeds = [PAYROLL_SEED, employer.key().as_ref(), payroll.employee.as_ref()], // PDA seeds for deterministic address
+        bump = payroll.bump, // Use stored bump seed for validation
+    )]
+    pub payroll: Box<Account<'info, Payroll>>,
+
+    /// The payroll's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = payroll.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The employer's token account receiving the unearned funding
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = employer_token_account.owner == employer.key(), // Ensure the employer owns the token account
+        constraint = employer_token_account.mint == payroll.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub employer_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The payroll's token account holding the funded pay
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = payroll, // Ensure it is the payroll's account
+    )]
+    pub payroll_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> StopPayroll<'info> {
+    /// Stops the accrual and returns the stop time and the funding refunded to the employer
+    pub fn stop_payroll(&mut self) -> Result<(i64, u64)> {
+        require!(
+            self.payroll.stopped_at.is_none(),
+            ErrorCode::PayrollAlreadyStopped
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        self.payroll.stopped_at = Some(now);
+
+        // Pay accrued so far stays with the employee, only the rest goes back
+        let refund = self
+            .payroll_token_account
+            .amount
+            .saturating_sub(self.payroll.owed(now));
+        if refund > 0 {
+            let seeds = &[
+                PAYROLL_SEED, // Seed prefix
+                self.payroll.employer.as_ref(), // Employer key
+                self.payroll.employee.as_ref(), // Employee key
+                &[self.payroll.bump], // Bump seed
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let cpi_accounts = TransferChecked {
+                from: self.payroll_token_account.to_account_info(), // Source: payroll's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to: self.employer_token_account.to_account_info(), // Destination: employer's token account
+                authority: self.payroll.to_account_info(), // Authority: payroll PDA
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            token::transfer_checked(cpi_ctx, refund, self.mint.decimals)?;
+        }
+
+        Ok((now, refund))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClosePayroll<'info> {
+    /// The payroll's employer (receives the rent of the closed accounts)
+    #[account(mut)]
+    pub employer: Signer<'info>,
+
+    /// The payroll, closed at the end of the instruction
+    #[account(
+        mut,
+        close = employer, // Refund the payroll rent to the employer
+        has_one = employer @ ErrorCode::Unauthorized, // Only the employer can close the payroll
+        seeds = [PAYROLL_SEED, employer.key().as_ref(), payroll.employee.as_ref()], // PDA seeds for deterministic address
+        bump = payroll.bump, // Use stored bump seed for validation
+    )]
+    pub payroll: Box<Account<'info, Payroll>>,
+
+    /// The payroll's token account, closed through the token program
+    #[account(
+        mut, // Account will be closed
+        associated_token::mint = payroll.mint, // Ensure correct token mint
+        associated_token::authority = payroll, // Ensure it is the payroll's account
+    )]
+    pub payroll_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required to close the token account)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ClosePayroll<'info> {
+    pub fn close_payroll(&mut self) -> Result<()> {
+        // The payroll must be stopped and the employee paid everything it holds
+        require!(
+            self.payroll.stopped_at.is_some() && self.payroll_token_account.amount == 0,
+            ErrorCode::PayrollNotSettled
+        );
+
+        let seeds = &[
+            PAYROLL_SEED, // Seed prefix
+            self.payroll.employer.as_ref(), // Employer key
+            self.payroll.employee.as_ref(), // Employee key
+            &[self.payroll.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = token::CloseAccount {
+            account: self.payroll_token_account.to_account_info(), // Account being closed
+            destination: self.employer.to_account_info(), // Rent goes back to the employer
+            authority: self.payroll.to_account_info(), // Authority: payroll PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::close_account(cpi_ctx)?;
+
+        // The payroll account is closed by its `close = employer` constraint
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(escrow_id: u64)]
+pub struct CreateEscrow<'info> {
+    /// The party locking up the tokens (must sign the transaction, pays for the accounts)
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// The escrow, one per depositor and `escrow_id`
+    #[account(
+        init, // Create a new account
+        payer = depositor, // Depositor pays for account creation
+        seeds = [ESCROW_SEED, depositor.key().as_ref(), &escrow_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Escrow::INIT_SPACE, // Allocate space for account data
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// The token mint of the escrowed tokens
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The depositor's token account paying in
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = depositor_token_account.owner == depositor.key(), // Ensure the depositor owns the token account
+        constraint = depositor_token_account.mint == mint.key() @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub depositor_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The escrow's token account holding the tokens: the escrow's associated token account
+    /// for the mint
+    #[account(
+        init, // Create a new token account
+        payer = depositor, // Depositor pays for account creation
+        associated_token::mint = mint, // Specify which token mint this account is for
+        associated_token::authority = escrow, // The escrow PDA signs the payout
+    )]
+    pub escrow_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token operations)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the escrow token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateEscrow<'info> {
+    pub fn create_escrow(
+        &mut self,
+        escrow_id: u64,
+        counterparty: Pubkey,
+        arbiter: Option<Pubkey>,
+        amount: u64,
+        timeout_ts: i64,
+        bumps: &CreateEscrowBumps,
+    ) -> Result<()> {
+        // The three parties must be distinct, so no one approves twice
+        let depositor = self.depositor.key();
+        require!(
+            amount > 0
+                && timeout_ts > Clock::get()?.unix_timestamp
+                && counterparty != depositor
+                && arbiter.is_none_or(|arbiter| arbiter != depositor && arbiter != counterparty),
+            ErrorCode::InvalidEscrow
+        );
+
+        self.escrow.depositor = depositor;
+        self.escrow.escrow_id = escrow_id;
+        self.escrow.bump = bumps.escrow;
+        self.escrow.counterparty = counterparty;
+        self.escrow.arbiter = arbiter;
+        self.escrow.mint = self.mint.key();
+        self.escrow.amount = amount;
+        self.escrow.timeout_ts = timeout_ts;
+        self.escrow.depositor_approved = false;
+        self.escrow.counterparty_approved = false;
+        self.escrow.arbiter_approved = false;
+
+        let cpi_accounts = TransferChecked {
+            from: self.depositor_token_account.to_account_info(), // Source: depositor's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.escrow_token_account.to_account_info(), // Destination: escrow's token account
+            authority: self.depositor.to_account_info(), // Authority: depositor (signs the transfer)
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ApproveEscrow<'info> {
+    /// The depositor, the counterparty or the arbiter (must sign the transaction)
+    pub approver: Signer<'info>,
+
+    /// The escrow
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, escrow.depositor.as_ref(), &escrow.escrow_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = escrow.bump, // Use stored bump seed for validation
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+}
+
+impl<'info> ApproveEscrow<'info> {
+    pub fn approve_escrow(&mut self) -> Result<()> {
+        // Approvals must come in before the refund path opens
+        require!(
+            Clock::get()?.unix_timestamp < self.escrow.timeout_ts,
+            ErrorCode::EscrowExpired
+        );
+
+        let approver = self.approver.key();
+        if approver == self.escrow.depositor {
+            self.escrow.depositor_approved = true;
+        } else if approver == self.escrow.counterparty {
+            self.escrow.counterparty_approved = true;
+        } else if Some(approver) == self.escrow.arbiter {
+            self.escrow.arbiter_approved = true;
+        } else {
+            return err!(ErrorCode::Unauthorized);
+        }
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ReleaseEscrow<'info> {
+    /// Whoever releases the approved escrow (must sign the transaction)
+    /// Pays for the counterparty's token account if it has to be created
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// The escrow, closed at the end of the instruction
+    #[account(
+        mut,
+        close = depositor, // Refund the escrow rent to the depositor
+        has_one = depositor, // The rent goes to the depositor
+        has_one = counterparty, // The tokens go to the counterparty
+        seeds = [ESCROW_SEED, depositor.key().as_ref(), &escrow.escrow_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = escrow.bump, // Use stored bump seed for validation
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// The depositor, who gets back the rent of the escrow accounts
+    /// CHECK: Checked against the escrow's depositor
+    #[account(mut)]
+    pub depositor: UncheckedAccount<'info>,
+
+    /// The counterparty receiving the tokens
+    /// CHECK: Checked against the escrow's counterparty
+    pub counterparty: UncheckedAccount<'info>,
+
+    /// The escrow's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = escrow.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The counterparty's associated token account receiving the tokens
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = caller, // Caller pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = counterparty, // Ensure the counterparty owns the token account
+    )]
+    pub counterparty_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The escrow's token account, emptied and closed
+    #[account(
+        mut, // Account will be closed
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = escrow, // Ensure it is the escrow's account
+    )]
+    pub escrow_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the counterparty's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ReleaseEscrow<'info> {
+    /// Pays the escrowed tokens to the counterparty and returns the amount
+    pub fn release_escrow(&mut self) -> Result<u64> {
+        require!(self.escrow.is_releasable(), ErrorCode::EscrowNotApproved);
+
+        let amount = self.escrow_token_account.amount;
+        let escrow_id = self.escrow.escrow_id.to_le_bytes();
+        let seeds = &[
+            ESCROW_SEED, // Seed prefix
+            self.escrow.depositor.as_ref(), // Depositor key
+            escrow_id.as_ref(), // Escrow id
+            &[self.escrow.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.escrow_token_account.to_account_info(), // Source: escrow's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.counterparty_token_account.to_account_info(), // Destination: counterparty's token account
+            authority: self.escrow.to_account_info(), // Authority: escrow PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        let cpi_accounts = token::CloseAccount {
+            account: self.escrow_token_account.to_account_info(), // Account being closed
+            destination: self.depositor.to_account_info(), // Rent goes back to the depositor
+            authority: self.escrow.to_account_info(), // Authority: escrow PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::close_account(cpi_ctx)?;
+
+        // The escrow account is closed by its `close = depositor` constraint
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RefundEscrow<'info> {
+    /// The depositor taking back an expired escrow (must sign the transaction)
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// The escrow, closed at the end of the instruction
+    #[account(
+        mut,
+        close = depositor, // Refund the escrow rent to the depositor
+        has_one = depositor @ ErrorCode::Unauthorized, // Only the depositor takes the refund
+        seeds = [ESCROW_SEED, depositor.key().as_ref(), &escrow.escrow_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = escrow.bump, // Use stored bump seed for validation
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+
+    /// The escrow's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = escrow.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The depositor's token account receiving the refund
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = depositor_token_account.owner == depositor.key(), // Ensure the depositor owns the token account
+        constraint = depositor_token_account.mint == escrow.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub depositor_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The escrow's token account, emptied and closed
+    #[account(
+        mut, // Account will be closed
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = escrow, // Ensure it is the escrow's account
+    )]
+    pub escrow_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> RefundEscrow<'info> {
+    /// Returns the escrowed tokens to the depositor and returns the amount
+    pub fn refund_escrow(&mut self) -> Result<u64> {
+        require!(
+            Clock::get()?.unix_timestamp >= self.escrow.timeout_ts,
+            ErrorCode::EscrowNotExpired
+        );
+        // Approvals recorded in time still release to the counterparty
+        require!(!self.escrow.is_releasable(), ErrorCode::EscrowReleasable);
+
+        let amount = self.escrow_token_account.amount;
+        let escrow_id = self.escrow.escrow_id.to_le_bytes();
+        let seeds = &[
+            ESCROW_SEED, // Seed prefix
+            self.escrow.depositor.as_ref(), // Depositor key
+            escrow_id.as_ref(), // Escrow id
+            &[self.escrow.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.escrow_token_account.to_account_info(), // Source: escrow's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.depositor_token_account.to_account_info(), // Destination: depositor's token account
+            authority: self.escrow.to_account_info(), // Authority: escrow PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        let cpi_accounts = token::CloseAccount {
+            account: self.escrow_token_account.to_account_info(), // Account being closed
+            destination: self.depositor.to_account_info(), // Rent goes back to the depositor
+            authority: self.escrow.to_account_info(), // Authority: escrow PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::close_account(cpi_ctx)?;
+
+        // The escrow account is closed by its `close = depositor` constraint
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(htlc_id: u64)]
+pub struct CreateHtlc<'info> {
+    /// The party locking up the tokens (must sign the transaction, pays for the accounts)
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The contract, one per owner and `htlc_id`
+    #[account(
+        init, // Create a new account
+        payer = owner, // Owner pays for account creation
+        seeds = [HTLC_SEED, owner.key().as_ref(), &htlc_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Htlc::INIT_SPACE, // Allocate space for account data
+    )]
+    pub htlc: Box<Account<'info, Htlc>>,
+
+    /// The token mint of the locked tokens
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The owner's token account paying in
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = owner_token_account.owner == owner.key(), // Ensure the owner owns the token account
+        constraint = owner_token_account.mint == mint.key() @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The contract's token account holding the tokens: the contract's associated token
+    /// account for the mint
+    #[account(
+        init, // Create a new token account
+        payer = owner, // Owner pays for account creation
+        associated_token::mint = mint, // Specify which token mint this account is for
+        associated_token::authority = htlc, // The contract PDA signs the payout
+    )]
+    pub htlc_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token operations)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the contract token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateHtlc<'info> {
+    pub fn create_htlc(
+        &mut self,
+        htlc_id: u64,
+        counterparty: Pubkey,
+        hashlock: [u8; 32],
+        amount: u64,
+        timeout_ts: i64,
+        bumps: &CreateHtlcBumps,
+    ) -> Result<()> {
+        require!(
+            amount > 0
+                && timeout_ts > Clock::get()?.unix_timestamp
+                && counterparty != self.owner.key(),
+            ErrorCode::InvalidHtlc
+        );
+
+        self.htlc.owner = self.owner.key();
+        self.htlc.htlc_id = htlc_id;
+        self.htlc.bump = bumps.htlc;
+        self.htlc.counterparty = counterparty;
+        self.htlc.mint = self.mint.key();
+        self.htlc.amount = amount;
+        self.htlc.hashlock = hashlock;
+        self.htlc.timeout_ts = timeout_ts;
+
+        let cpi_accounts = TransferChecked {
+            from: self.owner_token_account.to_account_info(), // Source: owner's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.htlc_token_account.to_account_info(), // Destination: contract's token account
+            authority: self.owner.to_account_info(), // Authority: owner (signs the transfer)
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimHtlc<'info> {
+    /// Whoever submits the preimage (must sign the transaction)
+    /// Pays for the counterparty's token account if it has to be created
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// The contract, closed at the end of the instruction
+    #[account(
+        mut,
+        close = owner, // Refund the contract rent to the owner
+        has_one = owner, // The rent goes to the owner
+        has_one = counterparty, // The tokens go to the counterparty
+        seeds = [HTLC_SEED, owner.key().as_ref(), &htlc.htlc_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = htlc.bump, // Use stored bump seed for validation
+    )]
+    pub htlc: Box<Account<'info, Htlc>>,
+
+    /// The owner, who gets back the rent of the contract accounts
+    /// CHECK: Checked against the contract's owner
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// The counterparty receiving the tokens
+    /// CHECK: Checked against the contract's counterparty
+    pub counterparty: UncheckedAccount<'info>,
+
+    /// The contract's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = htlc.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The counterparty's associated token account receiving the tokens
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = caller, // Caller pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = counterparty, // Ensure the counterparty owns the token account
+    )]
+    pub counterparty_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The contract's token account, emptied and closed
+    #[account(
+        mut, // Account will be closed
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = htlc, // Ensure it is the contract's account
+    )]
+    pub htlc_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the counterparty's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimHtlc<'info> {
+    /// Pays the locked tokens to the counterparty and returns the amount
+    pub fn claim_htlc(&mut self, preimage: [u8; 32]) -> Result<u64> {
+        // Past the timeout the owner may already be refunding, so claims are cut off
+        require!(
+            Clock::get()?.unix_timestamp < self.htlc.timeout_ts,
+            ErrorCode::HtlcExpired
+        );
+        require!(
+            anchor_lang::solana_program::hash::hash(&preimage).to_bytes() == self.htlc.hashlock,
+            ErrorCode::WrongPreimage
+        );
+
+        let amount = self.htlc_token_account.amount;
+        let htlc_id = self.htlc.htlc_id.to_le_bytes();
+        let seeds = &[
+            HTLC_SEED, // Seed prefix
+            self.htlc.owner.as_ref(), // Owner key
+            htlc_id.as_ref(), // Contract id
+            &[self.htlc.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.htlc_token_account.to_account_info(), // Source: contract's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.counterparty_token_account.to_account_info(), // Destination: counterparty's token account
+            authority: self.htlc.to_account_info(), // Authority: contract PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        let cpi_accounts = token::CloseAccount {
+            account: self.htlc_token_account.to_account_info(), // Account being closed
+            destination: self.owner.to_account_info(), // Rent goes back to the owner
+            authority: self.htlc.to_account_info(), // Authority: contract PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::close_account(cpi_ctx)?;
+
+        // The contract account is closed by its `close = owner` constraint
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RefundHtlc<'info> {
+    /// The owner taking back an expired contract (must sign the transaction)
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// The contract, closed at the end of the instruction
+    #[account(
+        mut,
+        close = owner, // Refund the contract rent to the owner
+        has_one = owner @ ErrorCode::Unauthorized, // Only the owner takes the refund
+        seeds = [HTLC_SEED, owner.key().as_ref(), &htlc.htlc_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = htlc.bump, // Use stored bump seed for validation
+    )]
+    pub htlc: Box<Account<'info, Htlc>>,
+
+    /// The contract's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        address = htlc.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The owner's token account receiving the refund
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = owner_token_account.owner == owner.key(), // Ensure the owner owns the token account
+        constraint = owner_token_account.mint == htlc.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The contract's token account, emptied and closed
+    #[account(
+        mut, // Account will be closed
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = htlc, // Ensure it is the contract's account
+    )]
+    pub htlc_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> RefundHtlc<'info> {
+    /// Returns the locked tokens to the owner and returns the amount
+    pub fn refund_htlc(&mut self) -> Result<u64> {
+        require!(
+            Clock::get()?.unix_timestamp >= self.htlc.timeout_ts,
+            ErrorCode::HtlcNotExpired
+        );
+
+        let amount = self.htlc_token_account.amount;
+        let htlc_id = self.htlc.htlc_id.to_le_bytes();
+        let seeds = &[
+            HTLC_SEED, // Seed prefix
+            self.htlc.owner.as_ref(), // Owner key
+            htlc_id.as_ref(), // Contract id
+            &[self.htlc.bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.htlc_token_account.to_account_info(), // Source: contract's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.owner_token_account.to_account_info(), // Destination: owner's token account
+            authority: self.htlc.to_account_info(), // Authority: contract PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        let cpi_accounts = token::CloseAccount {
+            account: self.htlc_token_account.to_account_info(), // Account being closed
+            destination: self.owner.to_account_info(), // Rent goes back to the owner
+            authority: self.htlc.to_account_info(), // Authority: contract PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::close_account(cpi_ctx)?;
+
+        // The contract account is closed by its `close = owner` constraint
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateLeaderboard<'info> {
+    /// Whoever submits the vault (must sign the transaction, pays for the leaderboard if needed)
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The mint the leaderboard ranks vaults of
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The mint's leaderboard, created by its first update
+    #[account(
+        init_if_needed, // Create the account on the mint's first update
+        payer = payer, // Payer pays for account creation
+        seeds = [LEADERBOARD_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Leaderboard::INIT_SPACE, // Allocate space for account data
+    )]
+    pub leaderboard: Box<Account<'info, Leaderboard>>,
+
+    /// CHECK: May not exist (a closed vault is dropped from the leaderboard), parsed by
+    /// `load_vault`
+    pub state: UncheckedAccount<'info>,
+
+    /// CHECK: Compared with the address the vault stores, parsed by `load_vault_token_account`
+    pub vault_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist for a closed vault, parsed by `load_rewards`
+    #[account(
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Bump seed for PDA derivation
+    )]
+    pub rewards: UncheckedAccount<'info>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> UpdateLeaderboard<'info> {
+    pub fn update_leaderboard(
+        &mut self,
+        bumps: &UpdateLeaderboardBumps,
+    ) -> Result<(u64, u16, Option<u8>, Option<u8>)> {
+        let now = Clock::get()?.unix_timestamp;
+        self.leaderboard.mint = self.mint.key();
+        self.leaderboard.bump = bumps.leaderboard;
+
+        // Read the stats from the vault's own accounts, so nobody can submit made-up numbers.
+        // A vault that no longer exists ranks with zeros, which removes it
+        let (balance, streak_days) = match load_vault(&self.state)? {
+            Some(vault) => {
+                require_keys_eq!(vault.mint, self.mint.key(), ErrorCode::MintMismatch);
+                require_keys_eq!(
+                    self.vault_token_account.key(),
+                    vault.vault_token_account,
+                    ErrorCode::VaultTokenAccountMismatch
+                );
+
+                // A token account closed along with the vault holds nothing
+                let balance = load_vault_token_account(&self.vault_token_account)?
+                    .map_or(0, |account| account.amount);
+                let streak_days =
+                    load_rewards(&self.rewards)?.map_or(0, |rewards| rewards.current_streak(now));
+                (balance, streak_days)
+            }
+            None => (0, 0),
+        };
+
+        let state = self.state.key();
+        let balance_rank = Leaderboard::rank(&mut self.leaderboard.top_balances, state, balance);
+        let streak_rank =
+            Leaderboard::rank(&mut self.leaderboard.top_streaks, state, streak_days as u64);
+
+        Ok((balance, streak_days, balance_rank, streak_rank))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RequestWithdrawal<'info> {
+    /// The vault owner (must sign the transaction, pays for the request account)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's withdrawal policy, if the owner has set a withdrawal delay
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The pending request, one at a time per vault
+    #[account(
+        init, // Create a new account
+        payer = user, // User pays for account creation
+        seeds = [WITHDRAWAL_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + PendingWithdrawal::INIT_SPACE, // Allocate space for account data
+    )]
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RequestWithdrawal<'info> {
+    /// Returns when the withdrawal can be executed
+    pub fn request_withdrawal(
+        &mut self,
+        amount: u64,
+        bumps: &RequestWithdrawalBumps,
+    ) -> Result<i64> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        let delay = load_withdrawal_policy(&self.withdrawal_policy)?
+            .map_or(0, |policy| policy.delay_at(now));
+
+        // Everything else is checked when the withdrawal is executed
+        self.pending_withdrawal.state = self.state.key();
+        self.pending_withdrawal.bump = bumps.pending_withdrawal;
+        self.pending_withdrawal.amount = amount;
+        self.pending_withdrawal.requested_at = now;
+        self.pending_withdrawal.executable_at =
+            now.checked_add(delay).ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(self.pending_withdrawal.executable_at)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ExecuteWithdrawal<'info> {
+    /// The vault owner (must sign the transaction, receives the request rent)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Pays for the user's token account if it has to be created, either the user or a relayer
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The vault's offline key, required to sign withdrawals above the vault key threshold
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The user's associated token account that will receive the withdrawn tokens
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = payer, // Payer pays for account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = user, // Ensure user owns the token account
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account that contains the tokens to be withdrawn
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut, // The withdrawal time is recorded for the cooldown
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the withdrawal transfer
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The pending request, closed once executed
+    #[account(
+        mut,
+        close = user, // Refund the rent to the user
+        seeds = [WITHDRAWAL_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = pending_withdrawal.bump, // Use stored bump seed for validation
+    )]
+    pub pending_withdrawal: Box<Account<'info, PendingWithdrawal>>,
+
+    /// The vault's withdrawal policy, if the owner has set one
+    /// CHECK: May not exist, parsed by `load_withdrawal_policy`
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze their own withdrawals
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, if the owner ever set one
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The vault's outstanding advance, if the owner borrowed against the locked balance
+    /// CHECK: May not exist, parsed by `outstanding_debt`
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// The program-wide config PDA, read to check whether the same-slot guard is on
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the user, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_user: UncheckedAccount<'info>,
+
+    /// The admin's credential gate, if vault owners must hold a credential token
+    /// CHECK: The address is checked by the seeds, and the data is only read if the gate exists
+    #[account(
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub credential_gate: UncheckedAccount<'info>,
+
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<Box<InterfaceAccount<'info, InterfaceTokenAccount>>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the user's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ExecuteWithdrawal<'info> {
+    /// Returns the amount withdrawn
+    pub fn execute_withdrawal(&mut self) -> Result<u64> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= self.pending_withdrawal.executable_at,
+            ErrorCode::WithdrawalNotReady
+        );
+        assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+
+        // Only approvals from the current approvers count
+        if let Some(policy) = load_withdrawal_policy(&self.withdrawal_policy)? {
+            require!(
+                policy.count_approvals(&self.pending_withdrawal.approvals)
+                    >= policy.threshold as usize,
+                ErrorCode::WithdrawalApprovalRequired
+            );
+        }
+
+        // The delay doesn't bypass any of the usual withdrawal rules
+        let amount = self.pending_withdrawal.amount;
+        self.state.authorize_withdrawal(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+            outstanding_debt(&self.advance)?,
+            amount,
+            self.vault_key.as_ref(),
+        )?;
+        // Blocked wallets can't move tokens into or out of vaults, and gated deployments only
+        // serve owners holding the credential
+        PayoutChecks {
+            blocked: &[&self.blocked_user],
+            credential_gate: &self.credential_gate,
+            credential_token_account: self.credential_token_account.as_deref(),
+        }
+        .check(&self.user.key())?;
+        // A withdrawal can't share its slot with a deposit while the guard is on
+        self.rewards
+            .record_withdraw_slot(clock.slot, same_slot_guard(&self.config)?)?;
+
+        // Accrue points on the balance held so far
+        self.rewards.accrue(
+            clock.unix_timestamp,
+            self.vault_token_account.amount,
+            self.mint.decimals,
+        );
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.user_token_account.to_account_info(), // Destination: user's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.outbox.record(ActivityKind::Withdraw, amount)?;
+        self.mint_stats.record_withdrawal(amount);
+
+        Ok(amount)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelWithdrawal<'info> {
+    /// The vault owner (must sign the transaction, receives the request rent)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The pending request being cancelled
+    #[account(
+        mut,
+        close = user, // Refund the rent to the user
+        seeds = [WITHDRAWAL_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = pending_withdrawal.bump, // Use stored bump seed for validation
+    )]
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct LockTokens<'info> {
+    /// The user locking the tokens (must sign the transaction)
+    /// Only the vault owner can lock tokens
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account containing configuration and metadata
+    /// Validates using PDA seeds and stored bump seed
+    /// Also validates that the user is the owner of the vault
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The program-wide config PDA, read for the admin's maximum lock duration
+    /// CHECK: The address is checked by the seeds, and the data is only read if the config exists
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub config: UncheckedAccount<'info>,
+}
+
+/// Implementation for the LockTokens accounts
+impl<'info> LockTokens<'info> {
+    /// Locks `amount` tokens for `duration_seconds` in a new tranche. Returns the tranche's
+    /// unlock timestamp.
+    pub fn lock_tokens(&mut self, amount: u64, duration_seconds: i64) -> Result<i64> {
+        require!(amount > 0, ErrorCode::ZeroAmount);
+
+        // Reject durations outside the published bounds, so a fat-fingered duration can't
+        // lock the savings away for good
+        require!(
+            duration_seconds >= MIN_LOCK_DURATION_SECONDS,
+            ErrorCode::InvalidLockDuration
+        );
+        require!(
+            duration_seconds <= max_lock_duration(&self.config)?,
+            ErrorCode::LockDurationTooLong
+        );
+
+        // Get the current timestamp from the Solana clock
+        let clock = Clock::get()?;
+
+        // Calculate the lock expiration timestamp
+        let locked_until = lock_expiry(clock.unix_timestamp, duration_seconds)
+            .ok_or(ErrorCode::InvalidLockDuration)?;
+
+        // Other tranches keep their own amounts and expiries
+        self.state
+            .add_lock_tranche(clock.unix_timestamp, amount, locked_until)?;
+
+        self.outbox.record(ActivityKind::Lock, amount)?;
+
+        Ok(locked_until)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetReleaseOnUnlock<'info> {
+    /// The vault owner (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault's offline key, required to opt in when the vault has one
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`. Refuses opting in while
+    /// the owner's self-exclusion is running
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+}
+
+impl<'info> SetReleaseOnUnlock<'info> {
+    pub fn set_release_on_unlock(&mut self, enabled: bool) -> Result<()> {
+        // Otherwise a stolen owner key could opt in to get a crank to pay the vault out
+        if enabled {
+            if let Some(vault_key) = self.state.vault_key {
+                assert_vault_key_signed(vault_key, self.vault_key.as_ref())?;
+            }
+            assert_not_self_excluded(&self.self_exclusion, Clock::get()?.unix_timestamp)?;
+        }
+
+        self.state.release_on_unlock = enabled;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetWithdrawCooldown<'info> {
+    /// The vault owner (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+}
+
+impl<'info> SetWithdrawCooldown<'info> {
+    pub fn set_withdraw_cooldown(&mut self, cooldown_seconds: i64) -> Result<()> {
+        require!(
+            (0..=MAX_WITHDRAW_COOLDOWN_SECONDS).contains(&cooldown_seconds),
+            ErrorCode::InvalidCooldown
+        );
+
+        // A shorter cooldown only takes effect once the running one has elapsed, so it
+        // can't be used to skip the wait
+        if cooldown_seconds < self.state.cooldown_seconds {
+            let now = Clock::get()?.unix_timestamp;
+            require!(
+                cooldown_remaining(now, self.state.last_withdraw_ts, self.state.cooldown_seconds)
+                    == 0,
+                ErrorCode::WithdrawalCooldown
+            );
+        }
+
+        self.state.cooldown_seconds = cooldown_seconds;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetWithdrawLimit<'info> {
+    /// The vault owner (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault's offline key, required to raise or remove the limit when the vault has one
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+}
+
+impl<'info> SetWithdrawLimit<'info> {
+    pub fn set_withdraw_limit(&mut self, max_withdraw_per_day: u64) -> Result<()> {
+        // Otherwise a stolen owner key could simply lift the limit before draining the vault
+        let current = self.state.max_withdraw_per_day;
+        let loosened = current > 0 && (max_withdraw_per_day == 0 || max_withdraw_per_day > current);
+        if loosened {
+            if let Some(vault_key) = self.state.vault_key {
+                assert_vault_key_signed(vault_key, self.vault_key.as_ref())?;
+            }
+        }
+
+        // What was already withdrawn keeps counting against the new limit
+        self.state.max_withdraw_per_day = max_withdraw_per_day;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CrankRelease<'info> {
+    /// Whoever cranks the release, paid CRANK_REWARD_BPS of it (must sign the transaction)
+    /// Pays for the owner's token account if it has to be created
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// The cranker's token account receiving the reward
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = cranker_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = cranker_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+        constraint = cranker_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub cranker_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault owner
+    /// CHECK: Only used to derive the vault state PDA and the owner's token account
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The owner's associated token account receiving the released tokens
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = cranker, // Cranker pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = owner, // Ensure owner owns the token account
+        constraint = owner_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account holding the tokens to release
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The owner's vault state account
+    #[account(
+        mut, // The goal deadline is cleared on release
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the release transfers
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The Pyth price update of the vault's mint, required when the vault has a USD target
+    /// CHECK: Checked against the vault's USD target and parsed by `oracle::read_price`
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner of the cranker's token account, if they are
+    /// blocked
+    /// CHECK: The address is checked by `assert_wallet_not_blocked`, and the entry may not exist
+    pub blocked_cranker: UncheckedAccount<'info>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the owner's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Holds releases back while the vault is
+    /// in custody
+    #[account(
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_rollover`. Makes a reached target roll over into
+    /// the next goal instead of releasing
+    #[account(
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub rollover: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Holds releases back until the owner
+    /// repays their advance
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `assert_immediate_withdrawal`. Holds releases on unlock
+    /// back while the owner's withdrawals need a delay or approvals
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`. Holds releases on unlock back
+    /// while the owner's unlock stream is still running
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`. Holds releases on unlock
+    /// back while the owner's self-exclusion is running
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+}
+
+/// Implementation for the CrankRelease accounts
+impl<'info> CrankRelease<'info> {
+    /// Returns the amount sent to the owner and the reward paid to the cranker
+    pub fn crank_release(&mut self) -> Result<(u64, u64)> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, false)?;
+        // Blocked wallets can't receive the release or the reward
+        assert_not_blocked(&self.blocked_owner)?;
+        assert_wallet_not_blocked(&self.blocked_cranker, &self.cranker_token_account.owner)?;
+
+        // Releasable once the target is reached, or once the lock has expired if the owner opted in
+        let balance = self.vault_token_account.amount;
+        let clock = Clock::get()?;
+        assert_not_in_custody(&self.custody, clock.unix_timestamp)?;
+        assert_no_advance(&self.advance)?;
+        let tranches = &self.state.lock_tranches;
+        let unlocked = self.state.release_on_unlock
+            && tranches.iter().any(|tranche| tranche.amount > 0)
+            && locked_amount(clock.unix_timestamp, tranches) == 0;
+        // Vaults with a USD target need the price update to check the target
+        let price_update = self.price_update.as_ref().map(|account| account.to_account_info());
+        // ... and vaults that roll over never release on their goal
+        let goal_reached = !unlocked
+            && load_rollover(&self.rollover)?.is_none()
+            && self
+                .state
+                .target_reached(balance, &self.mint, price_update.as_ref(), &self.price_feed)?;
+        require!(
+            balance > 0 && (unlocked || goal_reached),
+            ErrorCode::NotReleasable
+        );
+        // A release on unlock pays out like a withdrawal of the whole balance, so it's held to
+        // the owner's withdrawal rules. Otherwise a stolen owner key could lock for a second,
+        // opt in and crank its way around them
+        if unlocked {
+            assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+            assert_immediate_withdrawal(&self.withdrawal_policy, clock.unix_timestamp)?;
+            self.state.authorize_outflow(
+                clock.unix_timestamp,
+                balance,
+                load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+                balance,
+                None,
+            )?;
+        }
+
+        // Accrue points on the balance held so far
+        self.rewards
+            .accrue(clock.unix_timestamp, balance, self.mint.decimals);
+
+        let reward = crank_reward(balance);
+        let released = balance.checked_sub(reward).ok_or(ErrorCode::MathOverflow)?;
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Pay the cranker, then send the rest back to the owner
+        for (to, amount) in [
+            (self.cranker_token_account.to_account_info(), reward),
+            (self.owner_token_account.to_account_info(), released),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+
+            let cpi_accounts = TransferChecked {
+                from: self.vault_token_account.to_account_info(), // Source: vault's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to,
+                authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+        }
+
+        // The vault is released, so a deadline can no longer be missed
+        self.state.deadline = None;
+        if goal_reached {
+            self.rewards.earn(Badge::GoalReached);
+        }
+        self.outbox.record(ActivityKind::Release, balance)?;
+        self.mint_stats.record_release(balance);
+
+        Ok((released, reward))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetRollover<'info> {
+    /// The vault owner (must sign the transaction, pays for the rollover account on first use)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's rollover setting, created the first time one is set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Rollover::INIT_SPACE, // Allocate space for account data
+    )]
+    pub rollover: Account<'info, Rollover>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetRollover<'info> {
+    pub fn set_rollover(&mut self, increase_bps: u16, bumps: &SetRolloverBumps) -> Result<()> {
+        require!(
+            increase_bps <= MAX_ROLLOVER_INCREASE_BPS,
+            ErrorCode::InvalidRollover
+        );
+
+        // Setting a rollover again replaces the previous increase
+        self.rollover.state = self.state.key();
+        self.rollover.bump = bumps.rollover;
+        self.rollover.increase_bps = increase_bps;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClearRollover<'info> {
+    /// The vault owner (must sign the transaction, receives the rollover rent)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's rollover setting, closed to clear it
+    #[account(
+        mut,
+        close = user, // Refund the rent to the user
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rollover.bump, // Use stored bump seed for validation
+    )]
+    pub rollover: Account<'info, Rollover>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetPriceCondition<'info> {
+    /// The vault owner (must sign the transaction, pays for the condition account on first use)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault's offline key, required to set a condition when the vault has one
+    pub vault_key: Option<Signer<'info>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's price condition, created the first time one is set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = user, // User pays for account creation
+        seeds = [PRICE_CONDITION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + PriceCondition::INIT_SPACE, // Allocate space for account data
+    )]
+    pub price_condition: Account<'info, PriceCondition>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`. Refuses changes while the
+    /// owner's self-exclusion is running
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+}
+
+impl<'info> SetPriceCondition<'info> {
+    pub fn set_price_condition(
+        &mut self,
+        price_account: Pubkey,
+        threshold_cents: u64,
+        direction: PriceDirection,
+        bumps: &SetPriceConditionBumps,
+    ) -> Result<()> {
+        require!(threshold_cents > 0, ErrorCode::ZeroAmount);
+        // Anyone can trigger a condition, so a stolen owner key could otherwise set one that
+        // has already been met to pay the vault out
+        if let Some(vault_key) = self.state.vault_key {
+            assert_vault_key_signed(vault_key, self.vault_key.as_ref())?;
+        }
+        assert_not_self_excluded(&self.self_exclusion, Clock::get()?.unix_timestamp)?;
+
+        // Setting a condition again replaces the previous one
+        self.price_condition.state = self.state.key();
+        self.price_condition.bump = bumps.price_condition;
+        self.price_condition.price_account = price_account;
+        self.price_condition.threshold_cents = threshold_cents;
+        self.price_condition.direction = direction;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClearPriceCondition<'info> {
+    /// The vault owner (must sign the transaction, receives the condition rent)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's price condition, closed to clear it
+    #[account(
+        mut,
+        close = user, // Refund the rent to the user
+        seeds = [PRICE_CONDITION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = price_condition.bump, // Use stored bump seed for validation
+    )]
+    pub price_condition: Account<'info, PriceCondition>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CheckPriceCondition<'info> {
+    /// Whoever checks the condition, e.g. a keeper (must sign the transaction)
+    /// Pays for the owner's token account if it has to be created
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// The vault owner, refunded the condition rent once it triggers
+    /// CHECK: Only used to derive the vault state PDA and the owner's token account
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The owner's associated token account receiving the released tokens
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = caller, // Caller pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = owner, // Ensure owner owns the token account
+        constraint = owner_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault's token account holding the tokens to release
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The owner's vault state account
+    #[account(
+        mut, // The goal deadline is cleared on release
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the release transfer
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The vault's price condition, closed once it triggers
+    #[account(
+        mut,
+        close = owner, // A condition triggers once, its rent goes back to the owner
+        seeds = [PRICE_CONDITION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = price_condition.bump, // Use stored bump seed for validation
+    )]
+    pub price_condition: Box<Account<'info, PriceCondition>>,
+
+    /// The Pyth price update named by the condition
+    /// CHECK: Checked against the condition and parsed by `oracle::read_price`
+    #[account(
+        constraint = price_update.key() == price_condition.price_account @ ErrorCode::PriceAccountMismatch,
+    )]
+    pub price_update: UncheckedAccount<'info>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the owner's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Holds releases back while the vault is
+    /// in custody
+    #[account(
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Holds releases back until the owner
+    /// repays their advance
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `assert_immediate_withdrawal`. Holds the release back
+    /// while the owner's withdrawals need a delay or approvals
+    #[account(
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`. Holds the release back while
+    /// the owner's unlock stream is still running
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`. Holds the release back
+    /// while the owner's self-exclusion is running
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+}
+
+impl<'info> CheckPriceCondition<'info> {
+    /// Releases the vault to its owner if the oracle price has crossed the threshold.
+    /// Returns the price of one whole token (in USD cents) and the amount released.
+    pub fn check_price_condition(&mut self) -> Result<(u64, u64)> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        assert_not_frozen(&self.state, false)?;
+        // Blocked wallets can't move tokens into or out of vaults
+        assert_not_blocked(&self.blocked_owner)?;
+
+        let clock = Clock::get()?;
+        assert_not_in_custody(&self.custody, clock.unix_timestamp)?;
+        assert_no_advance(&self.advance)?;
+        let price = oracle::read_price(
+            &self.price_update,
+            load_price_feed(&self.price_feed)?,
+            clock.unix_timestamp,
+        )?;
+        // The value of one whole token, i.e. one unit with no decimals
+        let price_cents = oracle::usd_cents(1, 0, price.price as u64, price.exponent);
+        require!(
+            price_condition_met(
+                self.price_condition.direction,
+                price_cents,
+                self.price_condition.threshold_cents
+            ),
+            ErrorCode::PriceConditionNotMet
+        );
+
+        // A price condition doesn't override the owner's locks or withdrawal rules
+        let balance = self.vault_token_account.amount;
+        require!(balance > 0, ErrorCode::NotReleasable);
+        require!(
+            locked_amount(clock.unix_timestamp, &self.state.lock_tranches) == 0,
+            ErrorCode::LockNotExpired
+        );
+        assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+        assert_immediate_withdrawal(&self.withdrawal_policy, clock.unix_timestamp)?;
+        self.state.authorize_outflow(
+            clock.unix_timestamp,
+            balance,
+            load_unlock_schedule(&self.unlock_schedule)?.as_ref(),
+            balance,
+            None,
+        )?;
+
+        // Accrue points on the balance held so far
+        let decimals = self.mint.decimals;
+        self.rewards.accrue(clock.unix_timestamp, balance, decimals);
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault_token_account.to_account_info(), // Source: vault's token account
+            mint: self.mint.to_account_info(), // Mint: validated by the token program
+            to: self.owner_token_account.to_account_info(), // Destination: owner's token account
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer_checked(cpi_ctx, balance, decimals)?;
+
+        // The vault is released, so a deadline can no longer be missed
+        self.state.deadline = None;
+        self.outbox.record(ActivityKind::Release, balance)?;
+        self.mint_stats.record_release(balance);
+
+        Ok((price_cents, balance))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SettleDeadline<'info> {
+    /// Whoever settles the deadline (must sign the transaction)
+    /// Pays for the owner's token account if it has to be created
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// The vault owner
+    /// CHECK: Only used to derive the vault state PDA and the owner's token account
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault's token mint, passed so transfers can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+
+    /// The owner's associated token account receiving returned tokens
+    #[account(
+        init_if_needed, // Create the account if it doesn't exist yet
+        payer = caller, // Caller pays for the account creation
+        associated_token::mint = mint, // Ensure correct token mint
+        associated_token::authority = owner, // Ensure owner owns the token account
+        constraint = owner_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+    )]
+    pub owner_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The donation recipient's token account, required when the consequence is a donation
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = donation_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = donation_token_account.owner != vault_authority.key() @ ErrorCode::VaultOwnedAccount, // Can't be another account of the vault authority
+        constraint = donation_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub donation_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// The vault's token account
+    #[account(
+        mut, // Account will be modified (token balance decreases)
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The owner's vault state account
+    #[account(
+        mut,
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Box<Account<'info, Vault>>,
+
+    /// The vault authority PDA that signs the transfers
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, accrued before the balance changes
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The Pyth price update of the vault's mint, required when the vault has a USD target
+    /// CHECK: Checked against the vault's USD target and parsed by `oracle::read_price`
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Holds releases back until the owner
+    /// repays their advance
+    #[account(
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the owner, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_owner: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the donation recipient, required with the donation
+    /// recipient's token account
+    /// CHECK: The address is checked by `assert_wallet_not_blocked`, and the entry may not exist
+    pub blocked_recipient: Option<UncheckedAccount<'info>>,
+
+    /// The SPL Token program (required for token transfers)
+    pub token_program: Program<'info, Token>,
+
+    /// The Associated Token program (required to create the owner's token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: May not exist, parsed by `assert_not_self_excluded`. Holds consequences that
+    /// pay out back while the owner's self-exclusion is running
+    #[account(
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+}
+
+/// Implementation for the SettleDeadline accounts
+impl<'info> SettleDeadline<'info> {
+    /// Runs the missed deadline's consequence. Returns the consequence together with the
+    /// amounts returned to the owner and donated, and the lock expiry it set (if any).
+    pub fn settle_deadline(&mut self) -> Result<(DeadlineConsequence, u64, u64, Option<i64>)> {
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        // Only a deadline that passed with the target still unmet can be settled
+        let deadline = self.state.deadline.ok_or(ErrorCode::DeadlineNotSettleable)?;
+        let clock = Clock::get()?;
+        let balance = self.vault_token_account.amount;
+        require!(
+            clock.unix_timestamp >= deadline.deadline_ts,
+            ErrorCode::DeadlineNotSettleable
+        );
+        let price_update = self.price_update.as_ref().map(|account| account.to_account_info());
+        require!(
+            !self
+                .state
+                .target_reached(balance, &self.mint, price_update.as_ref(), &self.price_feed)?,
+            ErrorCode::DeadlineNotSettleable
+        );
+
+        // A deadline is settled once
+        self.state.deadline = None;
+
+        let mut extended_until = None;
+        let (returned, donated) = match deadline.consequence {
+            DeadlineConsequence::ExtendLock { seconds } => {
+                let locked_until = lock_expiry(clock.unix_timestamp, seconds)
+                    .ok_or(ErrorCode::InvalidLockDuration)?;
+                // Never shortens an existing tranche
+                self.state
+                    .lock_balance_until(clock.unix_timestamp, balance, locked_until);
+                self.outbox.record(ActivityKind::Lock, balance)?;
+                extended_until = Some(locked_until);
+                (0, 0)
+            }
+            DeadlineConsequence::ReturnFunds => (balance, 0),
+            DeadlineConsequence::Donate { bps, recipient } => {
+                let donated = bps_share(balance, bps as u64);
+                if donated > 0 {
+                    let donation_token_account = self
+                        .donation_token_account
+                        .as_ref()
+                        .ok_or(ErrorCode::DonationRecipientMismatch)?;
+                    require_keys_eq!(
+                        donation_token_account.owner,
+                        recipient,
+                        ErrorCode::DonationRecipientMismatch
+                    );
+                    // Blocked wallets can't receive donations either
+                    let blocked_recipient = self
+                        .blocked_recipient
+                        .as_ref()
+                        .ok_or(ErrorCode::DonationRecipientMismatch)?;
+                    assert_wallet_not_blocked(blocked_recipient, &recipient)?;
+                }
+                (
+                    balance
+                        .checked_sub(donated)
+                        .ok_or(ErrorCode::MathOverflow)?,
+                    donated,
+                )
+            }
+        };
+
+        if returned > 0 || donated > 0 {
+            // Funds leave the vault, so the same checks as a withdrawal apply
+            assert_not_frozen(&self.state, false)?;
+            assert_no_advance(&self.advance)?;
+            assert_not_self_excluded(&self.self_exclusion, clock.unix_timestamp)?;
+            if returned > 0 {
+                assert_not_blocked(&self.blocked_owner)?;
+            }
+
+            self.rewards
+                .accrue(clock.unix_timestamp, balance, self.mint.decimals);
+
+            let seeds = &[
+                VAULT_SEED, // Seed prefix
+                self.state.to_account_info().key.as_ref(), // State account key
+                &[self.state.vault_bump], // Bump seed
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let mut transfers = vec![(self.owner_token_account.to_account_info(), returned)];
+            if let Some(donation_token_account) = &self.donation_token_account {
+                transfers.push((donation_token_account.to_account_info(), donated));
+            }
+            for (to, amount) in transfers {
+                if amount == 0 {
+                    continue;
+                }
+
+                let cpi_accounts = TransferChecked {
+                    from: self.vault_token_account.to_account_info(), // Source: vault's token account
+                    mint: self.mint.to_account_info(), // Mint: validated by the token program
+                    to,
+                    authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+                };
+                let cpi_ctx = CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer_seeds,
+                );
+                token::transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+            }
+
+            self.outbox.record(ActivityKind::Release, balance)?;
+            self.mint_stats.record_release(balance);
+        }
+
+        Ok((deadline.consequence, returned, donated, extended_until))
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitMintStats<'info> {
+    /// Whoever creates the stats (must sign the transaction, pays for the account)
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The mint the stats are for
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's stats, for mints whose vaults were all created before stats existed
+    #[account(
+        init, // Create a new account
+        payer = payer, // Payer pays for account creation
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + MintStats::INIT_SPACE, // Allocate space for account data
+    )]
+    pub mint_stats: Account<'info, MintStats>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitMintStats<'info> {
+    pub fn init_mint_stats(&mut self, bumps: &InitMintStatsBumps) {
+        // Existing vaults aren't counted, only activity from now on
+        self.mint_stats.mint = self.mint.key();
+        self.mint_stats.bump = bumps.mint_stats;
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    /// Whoever migrates the vault (must sign the transaction, pays for the extra space)
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The vault owner, whose vault is migrated
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault state account, still in the version 1 layout
+    /// CHECK: Can't be deserialized as a `Vault` until it's grown, so the owner, discriminator
+    /// and size are checked by hand
+    #[account(
+        mut,
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub state: UncheckedAccount<'info>,
+
+    /// The vault's token mint, whose decimals are stored in the migrated vault
+    pub mint: Account<'info, Mint>,
+
+    /// The System program (required to top up the rent)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateVault<'info> {
+    pub fn migrate_vault(&mut self) -> Result<()> {
+        let state = self.state.to_account_info();
+        require!(
+            state.owner == &crate::ID && state.data_len() == VAULT_V1_ACCOUNT_SIZE,
+            ErrorCode::VaultNotMigratable
+        );
+        require!(
+            state.try_borrow_data()?.starts_with(Vault::DISCRIMINATOR),
+            ErrorCode::VaultNotMigratable
+        );
+
+        // Top up the rent for the larger account, then grow it with a zeroed tail
+        let rent = Rent::get()?.minimum_balance(VAULT_ACCOUNT_SIZE);
+        let top_up = rent.saturating_sub(state.lamports());
+        if top_up > 0 {
+            let cpi_accounts = system_program::Transfer {
+                from: self.payer.to_account_info(),
+                to: state.clone(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            system_program::transfer(cpi_ctx, top_up)?;
+        }
+        state.resize(VAULT_ACCOUNT_SIZE)?;
+
+        // With the zeroed tail the vault reads in the current layout, so fill in the new fields
+        let mut vault = Vault::try_deserialize(&mut &state.try_borrow_data()?[..])?;
+        require!(vault.mint == self.mint.key(), ErrorCode::MintMismatch);
+        vault.version = Vault::VERSION;
+        vault.decimals = self.mint.decimals;
+        vault.try_serialize(&mut &mut state.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitVaultExtension<'info> {
+    /// The vault owner (must sign the transaction, pays for the extension account)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's zero-copy extension account
+    #[account(
+        init, // Create the account, once per vault
+        payer = user, // User pays for account creation
+        seeds = [EXTENSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = VAULT_EXTENSION_SIZE, // Allocate space for account data
+    )]
+    pub extension: AccountLoader<'info, VaultExtension>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitVaultExtension<'info> {
+    pub fn init_vault_extension(&mut self, bumps: &InitVaultExtensionBumps) -> Result<()> {
+        let mut extension = self.extension.load_init()?;
+        extension.state = self.state.key();
+        extension.bump = bumps.extension;
+        extension.version = VaultExtension::VERSION;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RecordBalanceSnapshot<'info> {
+    /// The vault owner
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The owner's vault state account
+    #[account(
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's token account whose balance is recorded
+    #[account(
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's zero-copy extension account
+    #[account(
+        mut,
+        seeds = [EXTENSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = extension.load()?.bump, // Use stored bump seed for validation
+    )]
+    pub extension: AccountLoader<'info, VaultExtension>,
+}
+
+impl<'info> RecordBalanceSnapshot<'info> {
+    pub fn record_balance_snapshot(&mut self) -> Result<BalanceSnapshot> {
+        let snapshot = BalanceSnapshot {
+            timestamp: Clock::get()?.unix_timestamp,
+            balance: self.vault_token_account.amount,
+        };
+        self.extension.load_mut()?.record_snapshot(snapshot)?;
+
+        Ok(snapshot)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimPoints<'info> {
+    /// The vault owner claiming the points (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's token account, read to accrue points up to now
+    #[account(
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's token mint, read for its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The vault's reward points
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+}
+
+/// Implementation for the ClaimPoints accounts
+impl<'info> ClaimPoints<'info> {
+    /// Returns the number of points claimed
+    pub fn claim_points(&mut self) -> Result<u128> {
+        self.rewards.accrue(
+            Clock::get()?.unix_timestamp,
+            self.vault_token_account.amount,
+            self.mint.decimals,
+        );
+
+        let points = self.rewards.points;
+        require!(points > 0, ErrorCode::ZeroAmount);
+
+        // Move the points from unclaimed to claimed
+        self.rewards.points = 0;
+        self.rewards.claimed_points = self
+            .rewards
+            .claimed_points
+            .checked_add(points)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(points)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(badge: Badge)]
+pub struct ClaimBadge<'info> {
+    /// The vault owner claiming the badge (must sign the transaction, pays for the badge accounts)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's reward points, which record the badges earned
+    #[account(
+        mut,
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// CHECK: Created by `claim_badge` as a non-transferable Token-2022 mint, which can't be
+    /// done through `init` since the extension must be set up before the mint
+    #[account(
+        mut,
+        seeds = [BADGE_SEED, user.key().as_ref(), &[badge as u8]], // PDA seeds for deterministic address
+        bump, // Bump seed for PDA derivation
+    )]
+    pub badge_mint: UncheckedAccount<'info>,
+
+    /// CHECK: The owner's badge token account, created by `claim_badge` through the associated
+    /// token program, which checks its address
+    #[account(mut)]
+    pub user_badge_account: UncheckedAccount<'info>,
+
+    /// The Token-2022 program (required for the non-transferable extension)
+    pub token_program: Program<'info, Token2022>,
+
+    /// The Associated Token program (required for creating the badge token account)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimBadge<'info> {
+    pub fn claim_badge(&mut self, badge: Badge, bumps: &ClaimBadgeBumps) -> Result<()> {
+        require!(self.rewards.has_earned(badge), ErrorCode::BadgeNotEarned);
+        // The mint outlives the vault, so an owner who closes and re-creates their vault can't
+        // claim the same badge twice
+        require!(
+            !self.rewards.has_claimed(badge) && self.badge_mint.data_is_empty(),
+            ErrorCode::BadgeAlreadyClaimed
+        );
+
+        let user = self.user.key();
+        let index = [badge as u8];
+        let seeds = &[
+            BADGE_SEED, // Seed prefix
+            user.as_ref(), // Owner key
+            index.as_ref(), // Badge index
+            &[bumps.badge_mint], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Create the mint account with room for the non-transferable extension
+        let space = ExtensionType::try_calculate_account_len::<
+            token_2022::spl_token_2022::state::Mint,
+        >(&[ExtensionType::NonTransferable])?;
+        create_pda_account(
+            &self.user.to_account_info(),
+            &self.badge_mint.to_account_info(),
+            &self.system_program.to_account_info(),
+            space,
+            &token_2022::ID,
+            signer_seeds,
+        )?;
+
+        // The extension must be initialized before the mint itself
+        let cpi_accounts = NonTransferableMintInitialize {
+            token_program_id: self.token_program.to_account_info(),
+            mint: self.badge_mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token_2022_extensions::non_transferable_mint_initialize(cpi_ctx)?;
+
+        let cpi_accounts = token_2022::InitializeMint2 {
+            mint: self.badge_mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        token_2022::initialize_mint2(cpi_ctx, 0, &self.badge_mint.key(), None)?;
+
+        let cpi_accounts = associated_token::Create {
+            payer: self.user.to_account_info(), // Payer: the owner
+            associated_token: self.user_badge_account.to_account_info(), // New token account
+            authority: self.user.to_account_info(), // Owner of the token account
+            mint: self.badge_mint.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(
+            self.associated_token_program.to_account_info(),
+            cpi_accounts,
+        );
+        associated_token::create(cpi_ctx)?;
+
+        let cpi_accounts = token_2022::MintTo {
+            mint: self.badge_mint.to_account_info(),
+            to: self.user_badge_account.to_account_info(), // Destination: owner's badge account
+            authority: self.badge_mint.to_account_info(), // Authority: badge mint PDA
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_2022::mint_to(cpi_ctx, 1)?;
+
+        // Drop the mint authority so the badge stays one of a kind
+        let cpi_accounts = token_2022::SetAuthority {
+            current_authority: self.badge_mint.to_account_info(),
+            account_or_mint: self.badge_mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_2022::set_authority(cpi_ctx, AuthorityType::MintTokens, None)?;
+
+        self.rewards.badges_claimed |= badge.bit();
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CloseVault<'info> {
+    /// The vault owner closing the vault (receives the rent of all closed accounts)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, closed at the end of the instruction
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+        close = user, // Refund the state rent to the user
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's token account, closed through the token program
+    /// Validates that this is the correct vault token account for this state
+    #[account(
+        mut, // Account will be closed
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The vault authority PDA that must sign to close the vault token account
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        close = user, // Refund the outbox rent to the user
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, closed with the vault
+    #[account(
+        mut,
+        close = user, // Refund the rewards rent to the user
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// The vault's notes, closed with the vault if the owner has written any
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [NOTES_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub notes: UncheckedAccount<'info>,
+
+    /// The vault's price condition, closed with the vault if one is set
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [PRICE_CONDITION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_condition: UncheckedAccount<'info>,
+
+    /// The vault's withdrawal policy, closed with the vault if the owner ever set one
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The vault's pending withdrawal, closed with the vault if one was never executed
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub pending_withdrawal: UncheckedAccount<'info>,
+
+    /// The vault's guardians, closed with the vault if the owner ever set them
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [GUARDIANS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub guardians: UncheckedAccount<'info>,
+
+    /// The vault's rollover setting, closed with the vault if one is set
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub rollover: UncheckedAccount<'info>,
+
+    /// The template terms the vault was created with, closed with the vault if any
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub vault_template: UncheckedAccount<'info>,
+
+    /// The vault's extension account, closed with the vault if the owner created one
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [EXTENSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub extension: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze themselves. Keeps the vault open until
+    /// the freeze ends, and is closed with the vault afterwards
+    /// CHECK: May not exist, checked by `assert_not_self_excluded` and closed by
+    /// `close_child_account`
+    #[account(
+        mut,
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, closed with the vault if the owner ever set one
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The SPL Token program (required to close the token account)
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Keeps the vault open while it is in
+    /// custody, and is closed with the vault once released
+    #[account(
+        mut,
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Keeps the vault open until the owner
+    /// repays their advance, and is closed with the vault once repaid
+    #[account(
+        mut,
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// CHECK: Only checked for existence. Keeps the vault open while a recovery is pending
+    #[account(
+        seeds = [RECOVERY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub recovery_proposal: UncheckedAccount<'info>,
+}
+
+/// Implementation for the CloseVault accounts
+impl<'info> CloseVault<'info> {
+    pub fn close_vault(&mut self) -> Result<()> {
+        // Nothing that outlives the state may be left at its address for the next vault
+        assert_vault_closable(
+            &self.state,
+            &self.custody,
+            &self.advance,
+            &self.recovery_proposal,
+            &self.self_exclusion,
+            Clock::get()?.unix_timestamp,
+        )?;
+        close_child_accounts(
+            &[
+                &self.notes,
+                &self.price_condition,
+                &self.withdrawal_policy,
+                &self.pending_withdrawal,
+                &self.guardians,
+                &self.rollover,
+                &self.vault_template,
+                &self.extension,
+                &self.unlock_schedule,
+                &self.custody,
+                &self.advance,
+                &self.self_exclusion,
+            ],
+            &self.user,
+        )?;
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        // Only empty vaults can be closed so no tokens are stranded
+        require!(
+            self.vault_token_account.amount == 0,
+            ErrorCode::VaultNotEmpty
+        );
+
+        // Close the vault token account and send its rent to the user
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_accounts = token::CloseAccount {
+            account: self.vault_token_account.to_account_info(), // Account being closed
+            destination: self.user.to_account_info(), // Rent goes back to the user
+            authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+        };
+
+        // The vault authority PDA must sign to close the token account
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        token::close_account(cpi_ctx)?;
+
+        // The state, outbox and rewards accounts are closed by their `close = user` constraints
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Finalize<'info> {
+    /// The vault owner (receives the released tokens and the rent of all closed accounts)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account, closed at the end of the instruction
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+        close = user, // Refund the state rent to the user
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's token account, emptied and closed
+    /// Validates that this is the correct vault token account for this state
+    #[account(
+        mut, // Account will be closed
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// The vault authority PDA that signs the release and the token account close
+    /// CHECK: This is the vault authority PDA (no need to deserialize)
+    #[account(
+        seeds = [VAULT_SEED, state.key().as_ref()], // Vault authority PDA seeds
+        bump = state.vault_bump, // Use stored bump seed for validation
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// The owner's token account receiving the released tokens
+    #[account(
+        mut, // Account will be modified (token balance increases)
+        constraint = user_token_account.key() != vault_token_account.key() @ ErrorCode::DuplicateAccount, // Can't also be the vault's token account
+        constraint = user_token_account.owner == user.key(), // Ensure user owns the token account
+        constraint = user_token_account.mint == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's token mint, passed so the release can be checked against its decimals
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// Aggregate stats of every vault of the mint, updated as tokens move
+    #[account(
+        mut,
+        seeds = [STATS_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump = mint_stats.bump, // Use stored bump seed for validation
+    )]
+    pub mint_stats: Box<Account<'info, MintStats>>,
+
+    /// The vault's activity outbox
+    #[account(
+        mut,
+        close = user, // Refund the outbox rent to the user
+        seeds = [OUTBOX_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = outbox.bump, // Use stored bump seed for validation
+    )]
+    pub outbox: Box<Account<'info, Outbox>>,
+
+    /// The vault's reward points, closed with the vault
+    #[account(
+        mut,
+        close = user, // Refund the rewards rent to the user
+        seeds = [REWARDS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = rewards.bump, // Use stored bump seed for validation
+    )]
+    pub rewards: Box<Account<'info, RewardState>>,
+
+    /// The vault's notes, closed with the vault if the owner has written any
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [NOTES_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub notes: UncheckedAccount<'info>,
+
+    /// The vault's price condition, closed with the vault if one is set
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [PRICE_CONDITION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_condition: UncheckedAccount<'info>,
+
+    /// The vault's withdrawal policy, closed with the vault if the owner ever set one
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_POLICY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub withdrawal_policy: UncheckedAccount<'info>,
+
+    /// The vault's pending withdrawal, closed with the vault if one was never executed
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub pending_withdrawal: UncheckedAccount<'info>,
+
+    /// The vault's guardians, closed with the vault if the owner ever set them
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [GUARDIANS_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub guardians: UncheckedAccount<'info>,
+
+    /// The vault's rollover setting, closed with the vault if one is set
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [ROLLOVER_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub rollover: UncheckedAccount<'info>,
+
+    /// The template terms the vault was created with, closed with the vault if any
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [VAULT_TEMPLATE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub vault_template: UncheckedAccount<'info>,
+
+    /// The vault's extension account, closed with the vault if the owner created one
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [EXTENSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub extension: UncheckedAccount<'info>,
+
+    /// The owner's self-exclusion, if they ever froze themselves. Keeps the vault open until
+    /// the freeze ends, and is closed with the vault afterwards
+    /// CHECK: May not exist, checked by `assert_not_self_excluded` and closed by
+    /// `close_child_account`
+    #[account(
+        mut,
+        seeds = [SELF_EXCLUSION_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub self_exclusion: UncheckedAccount<'info>,
+
+    /// The vault's unlock stream, closed with the vault if the owner ever set one
+    /// CHECK: May not exist, closed by `close_child_account`
+    #[account(
+        mut,
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The Pyth price update of the vault's mint, required when the vault has a USD target
+    /// CHECK: Checked against the vault's USD target and parsed by `oracle::read_price`
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+
+    /// The admin's blocklist entry for the user, if they are blocked
+    /// CHECK: May not exist, parsed by `assert_not_blocked`
+    #[account(
+        seeds = [BLOCKED_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub blocked_user: UncheckedAccount<'info>,
+
+    /// The SPL Token program (required for the release and to close the token account)
+    pub token_program: Program<'info, Token>,
+
+    /// CHECK: May not exist, parsed by `load_custody`. Keeps the vault open while it is in
+    /// custody, and is closed with the vault once released
+    #[account(
+        mut,
+        seeds = [CUSTODY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub custody: UncheckedAccount<'info>,
+
+    /// CHECK: May not exist, parsed by `load_advance`. Keeps the vault open until the owner
+    /// repays their advance, and is closed with the vault once repaid
+    #[account(
+        mut,
+        seeds = [ADVANCE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub advance: UncheckedAccount<'info>,
+
+    /// CHECK: Only checked for existence. Keeps the vault open while a recovery is pending
+    #[account(
+        seeds = [RECOVERY_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub recovery_proposal: UncheckedAccount<'info>,
+}
+
+/// Implementation for the Finalize accounts
+impl<'info> Finalize<'info> {
+    /// Returns the amount released to the owner, 0 if a release already emptied the vault
+    pub fn finalize(&mut self) -> Result<u64> {
+        // Nothing that outlives the state may be left at its address for the next vault
+        assert_vault_closable(
+            &self.state,
+            &self.custody,
+            &self.advance,
+            &self.recovery_proposal,
+            &self.self_exclusion,
+            Clock::get()?.unix_timestamp,
+        )?;
+        close_child_accounts(
+            &[
+                &self.notes,
+                &self.price_condition,
+                &self.withdrawal_policy,
+                &self.pending_withdrawal,
+                &self.guardians,
+                &self.rollover,
+                &self.vault_template,
+                &self.extension,
+                &self.unlock_schedule,
+                &self.custody,
+                &self.advance,
+                &self.self_exclusion,
+            ],
+            &self.user,
+        )?;
+
+        // Make sure the vault token account hasn't been substituted
+        assert_vault_token_account(
+            &self.state,
+            &self.vault_token_account,
+            &self.vault_authority.key(),
+        )?;
+
+        let seeds = &[
+            VAULT_SEED, // Seed prefix
+            self.state.to_account_info().key.as_ref(), // State account key
+            &[self.state.vault_bump], // Bump seed
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // A vault a release already emptied only has to be closed. Otherwise the goal must be
+        // met now, as it would be for an auto-release
+        let balance = self.vault_token_account.amount;
+        if balance > 0 {
+            assert_not_frozen(&self.state, false)?;
+            // Blocked wallets can't move tokens into or out of vaults
+            assert_not_blocked(&self.blocked_user)?;
+            let price_update = self
+                .price_update
+                .as_ref()
+                .map(|account| account.to_account_info());
+            require!(
+                self.state
+                    .target_reached(balance, &self.mint, price_update.as_ref(), &self.price_feed)?,
+                ErrorCode::TargetNotReached
+            );
+
+            // Send everything back to the owner
+            let cpi_accounts = TransferChecked {
+                from: self.vault_token_account.to_account_info(), // Source: vault's token account
+                mint: self.mint.to_account_info(), // Mint: validated by the token program
+                to: self.user_token_account.to_account_info(), // Destination: user's token account
+                authority: self.vault_authority.to_account_info(), // Authority: vault authority PDA
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            token::transfer_checked(cpi_ctx, balance, self.mint.decimals)?;
+            self.mint_stats.record_release(balance);
+        }
+
+        // Then close the emptied vault token account
+        close_vault_token_account(
+            self.token_program.to_account_info(),
+            self.vault_token_account.to_account_info(),
+            self.user.to_account_info(),
+            self.vault_authority.to_account_info(),
+            signer_seeds,
+        )?;
+
+        // The state, outbox and rewards accounts are closed by their `close = user` constraints
+        Ok(balance)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AddNote<'info> {
+    /// The vault owner writing the note (pays for the notes account on first use)
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The vault's notes, created with the first note
+    #[account(
+        init_if_needed, // Create the account on the first note
+        payer = user, // User pays for account creation
+        seeds = [NOTES_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Notes::INIT_SPACE, // Allocate space for account data
+    )]
+    pub notes: Box<Account<'info, Notes>>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+/// Implementation for the AddNote accounts
+impl<'info> AddNote<'info> {
+    pub fn add_note(&mut self, text: String, bumps: &AddNoteBumps) -> Result<()> {
+        // Notes must be non-empty and fit in the reserved space
+        require!(
+            !text.is_empty() && text.len() <= MAX_NOTE_LENGTH,
+            ErrorCode::InvalidNoteLength
+        );
+
+        // First note: bind the account to this vault
+        if self.notes.count == 0 {
+            self.notes.state = self.state.key();
+            self.notes.bump = bumps.notes;
+        }
+
+        let clock = Clock::get()?;
+        self.notes.append(Note {
+            timestamp: clock.unix_timestamp,
+            text,
+        })?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct GetVaultStatus<'info> {
+    /// The vault state account to report on
+    pub state: Account<'info, Vault>,
+
+    /// The vault's token account holding the saved tokens
+    /// Validates that this is the correct vault token account for this state
+    #[account(
+        constraint = vault_token_account.key() == state.vault_token_account, // Ensure correct vault token account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// The vault's notes, if the owner has written any
+    #[account(
+        seeds = [NOTES_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump = notes.bump, // Use stored bump seed for validation
+    )]
+    pub notes: Option<Box<Account<'info, Notes>>>,
+
+    /// The vault's unlock stream, if the owner ever set one
+    /// CHECK: May not exist, parsed by `load_unlock_schedule`
+    #[account(
+        seeds = [UNLOCK_SCHEDULE_SEED, state.key().as_ref()], // PDA seeds for deterministic address
+        bump,
+    )]
+    pub unlock_schedule: UncheckedAccount<'info>,
+
+    /// The vault's token mint, used to value the balance against a USD target
+    #[account(
+        constraint = mint.key() == state.mint @ ErrorCode::MintMismatch, // Ensure correct token mint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// The Pyth price update of the vault's mint, required when the vault has a USD target
+    /// CHECK: Checked against the vault's USD target and parsed by `oracle::read_price`
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// The admin's expected Pyth feed for the vault's mint, which the price update must publish
+    /// CHECK: May not exist, parsed by `load_price_feed`
+    #[account(
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Derive the canonical bump
+    )]
+    pub price_feed: UncheckedAccount<'info>,
+}
+
+/// Implementation for the GetVaultStatus accounts
+impl<'info> GetVaultStatus<'info> {
+    pub fn get_vault_status(&self) -> Result<VaultStatus> {
+        let clock = Clock::get()?;
+        let balance = self.vault_token_account.amount;
+        let price_update = self.price_update.as_ref().map(|account| account.to_account_info());
+        let (value, target) =
+            self.state
+                .target_progress(
+                    balance,
+                    self.mint.decimals,
+                    price_update.as_ref(),
+                    &self.price_feed,
+                )?;
+
+        let lock_tranches: Vec<LockTranche> = self
+            .state
+            .lock_tranches
+            .iter()
+            .filter(|tranche| tranche.is_active(clock.unix_timestamp))
+            .copied()
+            .collect();
+
+        // Seconds left until the last tranche expires (0 when nothing is locked)
+        let seconds_remaining = lock_tranches
+            .iter()
+            .map(|tranche| tranche.unlock_ts.saturating_sub(clock.unix_timestamp))
+            .max()
+            .unwrap_or(0);
+
+        // The unlock stream, if any, can hold back more than the tranches
+        let streamed = load_unlock_schedule(&self.unlock_schedule)?
+            .map(|schedule| schedule.locked_at(clock.unix_timestamp))
+            .unwrap_or(0);
+
+        Ok(VaultStatus {
+            balance,
+            target: self.state.amount,
+            decimals: self.mint.decimals,
+            progress_bps: progress_bps(value, target),
+            unlocked: unlocked_amount(balance, clock.unix_timestamp, &lock_tranches)
+                .min(balance.saturating_sub(streamed)),
+            lock_tranches,
+            seconds_remaining,
+            notes: self
+                .notes
+                .as_ref()
+                .map(|notes| notes.ordered())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    /// The program's upgrade authority, who becomes the config admin
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account (a single PDA derived from "config")
+    #[account(
+        init, // Create a new account
+        payer = admin, // Admin pays for account creation
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + Config::INIT_SPACE, // Allocate space for account data
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes (a single PDA derived from "config_history")
+    #[account(
+        init, // Create a new account
+        payer = admin, // Admin pays for account creation
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = ConfigHistory::space(10), // Room for the ten initial values
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// This program, used to find its program data account
+    #[account(
+        constraint = vault_program.programdata_address()? == Some(program_data.key()) @ ErrorCode::Unauthorized,
+    )]
+    pub vault_program: Program<'info, crate::program::Vault2>,
+
+    /// The program data account holding the upgrade authority
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::Unauthorized, // Only the upgrade authority can create the config
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+/// Implementation for the InitializeConfig accounts
+impl<'info> InitializeConfig<'info> {
+    pub fn initialize_config(&mut self, bumps: &InitializeConfigBumps) -> Result<()> {
+        // Store the admin allowed to manage program-wide settings
+        self.config.admin = self.admin.key();
+
+        // Store the config bump seed for validation
+        self.config.bump = bumps.config;
+
+        // Owners may pick any non-zero balance cap until the admin narrows the bounds
+        self.config.min_balance_cap = 1;
+        self.config.max_balance_cap = u64::MAX;
+
+        // Any mint can be used until the admin enables the allowlist
+        self.config.mint_allowlist_enabled = false;
+
+        // Deposits are free until the admin sets a fee
+        self.config.deposit_fee_bps = 0;
+        self.config.treasury = Pubkey::default();
+
+        // Mints with a freeze authority are accepted (with a warning) until the admin opts out
+        self.config.reject_freezable_mints = false;
+
+        // Locks may run up to the program-wide maximum until the admin lowers it
+        self.config.max_lock_duration_seconds = MAX_LOCK_DURATION_SECONDS;
+
+        // Deposits and withdrawals may share a slot until the admin turns the guard on
+        self.config.same_slot_guard = false;
+
+        // A reached target releases the whole balance until the admin picks another policy
+        self.config.surplus_policy = SurplusPolicy::Release;
+
+        // Start the history with the initial values, so it covers every parameter
+        let admin = self.admin.key();
+        self.config_history.bump = bumps.config_history;
+        self.config_history.record(
+            ConfigField::Admin,
+            ConfigValue::Pubkey(Pubkey::default()),
+            ConfigValue::Pubkey(admin),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::MinBalanceCap,
+            ConfigValue::U64(0),
+            ConfigValue::U64(self.config.min_balance_cap),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::MaxBalanceCap,
+            ConfigValue::U64(0),
+            ConfigValue::U64(self.config.max_balance_cap),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::MintAllowlistEnabled,
+            ConfigValue::Bool(false),
+            ConfigValue::Bool(self.config.mint_allowlist_enabled),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::DepositFeeBps,
+            ConfigValue::U64(0),
+            ConfigValue::U64(self.config.deposit_fee_bps as u64),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::Treasury,
+            ConfigValue::Pubkey(Pubkey::default()),
+            ConfigValue::Pubkey(self.config.treasury),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::RejectFreezableMints,
+            ConfigValue::Bool(false),
+            ConfigValue::Bool(self.config.reject_freezable_mints),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::MaxLockDuration,
+            ConfigValue::U64(0),
+            ConfigValue::U64(self.config.max_lock_duration_seconds as u64),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::SameSlotGuard,
+            ConfigValue::Bool(false),
+            ConfigValue::Bool(self.config.same_slot_guard),
+            admin,
+        )?;
+        self.config_history.record(
+            ConfigField::SurplusPolicy,
+            ConfigValue::U64(SurplusPolicy::Release as u64),
+            ConfigValue::U64(self.config.surplus_policy as u64),
+            admin,
+        )?;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ApproveDepositHook<'info> {
+    /// The config admin approving the hook (pays for the approval account)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can approve hooks
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The program being approved as a deposit hook
+    /// CHECK: Only its address is recorded, it must be an executable program
+    #[account(
+        constraint = hook_program.executable @ ErrorCode::DepositHookNotApproved, // Hooks must be programs
+    )]
+    pub hook_program: UncheckedAccount<'info>,
+
+    /// The approval record for the hook program
+    #[account(
+        init, // Create a new account
+        payer = admin, // Admin pays for account creation
+        seeds = [HOOK_SEED, hook_program.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + ApprovedHook::INIT_SPACE, // Allocate space for account data
+    )]
+    pub approved_hook: Account<'info, ApprovedHook>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+/// Implementation for the ApproveDepositHook accounts
+impl<'info> ApproveDepositHook<'info> {
+    pub fn approve_deposit_hook(&mut self, bumps: &ApproveDepositHookBumps) -> Result<()> {
+        // Record which program is approved
+        self.approved_hook.program_id = self.hook_program.key();
+
+        // Store the approval bump seed for validation
+        self.approved_hook.bump = bumps.approved_hook;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeDepositHook<'info> {
+    /// The config admin revoking the hook (receives the approval rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can revoke hooks
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The approval record being removed
+    /// Vaults using this hook stop invoking it, deposits fail until the owner changes hook
+    #[account(
+        mut,
+        seeds = [HOOK_SEED, approved_hook.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_hook.bump, // Use stored bump seed for validation
+        close = admin, // Refund the rent to the admin
+    )]
+    pub approved_hook: Account<'info, ApprovedHook>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ApproveSwapProgram<'info> {
+    /// The config admin approving the swap program (pays for the approval account)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can approve swap programs
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The program being approved for `deposit_and_swap`
+    /// CHECK: Only its address is recorded, it must be an executable program
+    #[account(
+        constraint = swap_program.executable @ ErrorCode::SwapProgramNotApproved, // Swaps must be programs
+    )]
+    pub swap_program: UncheckedAccount<'info>,
+
+    /// The approval record for the swap program
+    #[account(
+        init, // Create a new account
+        payer = admin, // Admin pays for account creation
+        seeds = [SWAP_SEED, swap_program.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + ApprovedSwap::INIT_SPACE, // Allocate space for account data
+    )]
+    pub approved_swap: Account<'info, ApprovedSwap>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+/// Implementation for the ApproveSwapProgram accounts
+impl<'info> ApproveSwapProgram<'info> {
+    pub fn approve_swap_program(&mut self, bumps: &ApproveSwapProgramBumps) -> Result<()> {
+        // Record which program is approved
+        self.approved_swap.program_id = self.swap_program.key();
+
+        // Store the approval bump seed for validation
+        self.approved_swap.bump = bumps.approved_swap;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeSwapProgram<'info> {
+    /// The config admin revoking the swap program (receives the approval rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can revoke swap programs
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The approval record being removed, `deposit_and_swap` can no longer use the program
+    #[account(
+        mut,
+        seeds = [SWAP_SEED, approved_swap.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_swap.bump, // Use stored bump seed for validation
+        close = admin, // Refund the rent to the admin
+    )]
+    pub approved_swap: Account<'info, ApprovedSwap>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ApproveMint<'info> {
+    /// The config admin approving the mint (pays for the approval account)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can approve mints
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The mint being approved
+    pub mint: Account<'info, Mint>,
+
+    /// The approval record for the mint
+    #[account(
+        init, // Create a new account
+        payer = admin, // Admin pays for account creation
+        seeds = [MINT_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + ApprovedMint::INIT_SPACE, // Allocate space for account data
+    )]
+    pub approved_mint: Account<'info, ApprovedMint>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ApproveMint<'info> {
+    pub fn approve_mint(&mut self, bumps: &ApproveMintBumps) -> Result<()> {
+        // Record which mint is approved
+        self.approved_mint.mint = self.mint.key();
+
+        // Store the approval bump seed for validation
+        self.approved_mint.bump = bumps.approved_mint;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetPriceFeed<'info> {
+    /// The config admin setting the feed (pays for the feed account)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can set price feeds
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The mint whose price the feed publishes
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's expected price feed, replaced if already set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = admin, // Admin pays for account creation
+        seeds = [PRICE_FEED_SEED, mint.key().as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + PriceFeed::INIT_SPACE, // Allocate space for account data
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetPriceFeed<'info> {
+    pub fn set_price_feed(&mut self, feed_id: [u8; 32], bumps: &SetPriceFeedBumps) -> Result<()> {
+        // Record which Pyth feed prices the mint
+        self.price_feed.mint = self.mint.key();
+        self.price_feed.feed_id = feed_id;
+
+        // Store the feed bump seed for validation
+        self.price_feed.bump = bumps.price_feed;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeMint<'info> {
+    /// The config admin revoking the mint (receives the approval rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can revoke mints
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The approval record being removed
+    /// Existing vaults for this mint keep working, only new vaults are refused
+    #[account(
+        mut,
+        seeds = [MINT_SEED, approved_mint.mint.as_ref()], // PDA seeds for deterministic address
+        bump = approved_mint.bump, // Use stored bump seed for validation
+        close = admin, // Refund the rent to the admin
+    )]
+    pub approved_mint: Account<'info, ApprovedMint>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(address: Pubkey)]
+pub struct BlockAddress<'info> {
+    /// The config admin blocking the address (pays for the blocklist entry)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can block addresses
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The blocklist entry for the address
+    #[account(
+        init, // Create a new account
+        payer = admin, // Admin pays for account creation
+        seeds = [BLOCKED_SEED, address.as_ref()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + BlockedAddress::INIT_SPACE, // Allocate space for account data
+    )]
+    pub blocked_address: Account<'info, BlockedAddress>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> BlockAddress<'info> {
+    pub fn block_address(&mut self, address: Pubkey, bumps: &BlockAddressBumps) -> Result<()> {
+        // Record which address is blocked
+        self.blocked_address.address = address;
+
+        // Store the entry bump seed for validation
+        self.blocked_address.bump = bumps.blocked_address;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UnblockAddress<'info> {
+    /// The config admin unblocking the address (receives the entry rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can unblock addresses
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The blocklist entry being removed
+    #[account(
+        mut,
+        seeds = [BLOCKED_SEED, blocked_address.address.as_ref()], // PDA seeds for deterministic address
+        bump = blocked_address.bump, // Use stored bump seed for validation
+        close = admin, // Refund the rent to the admin
+    )]
+    pub blocked_address: Account<'info, BlockedAddress>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(template_id: u16)]
+pub struct SetGoalTemplate<'info> {
+    /// The config admin curating the template (pays for the template account)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can curate templates
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The goal template, created the first time the id is used
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = admin, // Admin pays for account creation
+        seeds = [TEMPLATE_SEED, &template_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + GoalTemplate::INIT_SPACE, // Allocate space for account data
+    )]
+    pub template: Account<'info, GoalTemplate>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetGoalTemplate<'info> {
+    pub fn set_goal_template(
+        &mut self,
+        template_id: u16,
+        terms: GoalTemplateTerms,
+        bumps: &SetGoalTemplateBumps,
+    ) -> Result<()> {
+        require!(
+            !terms.name.is_empty()
+                && terms.name.len() <= MAX_TEMPLATE_NAME_LEN
+                && terms.target > 0
+                && (terms.lock_seconds == 0
+                    || (MIN_LOCK_DURATION_SECONDS..=MAX_LOCK_DURATION_SECONDS)
+                        .contains(&terms.lock_seconds))
+                && (0..=MAX_WITHDRAW_COOLDOWN_SECONDS).contains(&terms.cooldown_seconds)
+                && terms
+                    .deposit_fee_bps
+                    .is_none_or(|fee_bps| fee_bps <= MAX_DEPOSIT_FEE_BPS),
+            ErrorCode::InvalidGoalTemplate
+        );
+
+        // Setting a template again replaces its terms for vaults created from now on
+        self.template.template_id = template_id;
+        self.template.bump = bumps.template;
+        self.template.terms = terms;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RemoveGoalTemplate<'info> {
+    /// The config admin removing the template (receives the template rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can curate templates
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The goal template being removed
+    /// Vaults already created from it keep their terms, only new vaults are refused
+    #[account(
+        mut,
+        seeds = [TEMPLATE_SEED, &template.template_id.to_le_bytes()], // PDA seeds for deterministic address
+        bump = template.bump, // Use stored bump seed for validation
+        close = admin, // Refund the rent to the admin
+    )]
+    pub template: Account<'info, GoalTemplate>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(enabled: bool)]
+pub struct SetMintAllowlist<'info> {
+    /// The config admin, who pays to grow the config history
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can switch the allowlist
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes, grown if the switch changes
+    #[account(
+        mut,
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump = config_history.bump, // Use stored bump seed for validation
+        realloc = ConfigHistory::space(
+            config_history.entries.len()
+                + usize::from(enabled != config.mint_allowlist_enabled)
+        ),
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// The System program (required to grow the history)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetMintAllowlist<'info> {
+    pub fn set_mint_allowlist(&mut self, enabled: bool) -> Result<()> {
+        if enabled != self.config.mint_allowlist_enabled {
+            self.config_history.record(
+                ConfigField::MintAllowlistEnabled,
+                ConfigValue::Bool(self.config.mint_allowlist_enabled),
+                ConfigValue::Bool(enabled),
+                self.admin.key(),
+            )?;
+        }
+
+        // Existing vaults are unaffected, the allowlist only applies to new vaults
+        self.config.mint_allowlist_enabled = enabled;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(enabled: bool)]
+pub struct SetRejectFreezableMints<'info> {
+    /// The config admin, who pays to grow the config history
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can switch the policy
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes, grown if the switch changes
+    #[account(
+        mut,
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump = config_history.bump, // Use stored bump seed for validation
+        realloc = ConfigHistory::space(
+            config_history.entries.len()
+                + usize::from(enabled != config.reject_freezable_mints)
+        ),
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// The System program (required to grow the history)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetRejectFreezableMints<'info> {
+    pub fn set_reject_freezable_mints(&mut self, enabled: bool) -> Result<()> {
+        if enabled != self.config.reject_freezable_mints {
+            self.config_history.record(
+                ConfigField::RejectFreezableMints,
+                ConfigValue::Bool(self.config.reject_freezable_mints),
+                ConfigValue::Bool(enabled),
+                self.admin.key(),
+            )?;
+        }
+
+        // Existing vaults are unaffected, the policy only applies to new vaults
+        self.config.reject_freezable_mints = enabled;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(enabled: bool)]
+pub struct SetSameSlotGuard<'info> {
+    /// The config admin, who pays to grow the config history
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can switch the guard
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes, grown if the switch changes
+    #[account(
+        mut,
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump = config_history.bump, // Use stored bump seed for validation
+        realloc = ConfigHistory::space(
+            config_history.entries.len() + usize::from(enabled != config.same_slot_guard)
+        ),
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// The System program (required to grow the history)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetSameSlotGuard<'info> {
+    pub fn set_same_slot_guard(&mut self, enabled: bool) -> Result<()> {
+        if enabled != self.config.same_slot_guard {
+            self.config_history.record(
+                ConfigField::SameSlotGuard,
+                ConfigValue::Bool(self.config.same_slot_guard),
+                ConfigValue::Bool(enabled),
+                self.admin.key(),
+            )?;
+        }
+
+        // Applies to every vault from the next deposit or withdrawal on
+        self.config.same_slot_guard = enabled;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(policy: SurplusPolicy)]
+pub struct SetSurplusPolicy<'info> {
+    /// The config admin, who pays to grow the config history
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can change the policy
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes, grown if the policy changes
+    #[account(
+        mut,
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump = config_history.bump, // Use stored bump seed for validation
+        realloc = ConfigHistory::space(
+            config_history.entries.len() + usize::from(policy != config.surplus_policy)
+        ),
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// The System program (required to grow the history)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetSurplusPolicy<'info> {
+    pub fn set_surplus_policy(&mut self, policy: SurplusPolicy) -> Result<()> {
+        if policy != self.config.surplus_policy {
+            self.config_history.record(
+                ConfigField::SurplusPolicy,
+                ConfigValue::U64(self.config.surplus_policy as u64),
+                ConfigValue::U64(policy as u64),
+                self.admin.key(),
+            )?;
+        }
+
+        // Applies to every vault from the next deposit that reaches its target
+        self.config.surplus_policy = policy;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(seconds: i64)]
+pub struct SetMaxLockDuration<'info> {
+    /// The config admin, who pays to grow the config history
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can bound lock durations
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes, grown if the bound changes
+    #[account(
+        mut,
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump = config_history.bump, // Use stored bump seed for validation
+        realloc = ConfigHistory::space(
+            config_history.entries.len()
+                + usize::from(seconds != config.max_lock_duration_seconds)
+        ),
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// The System program (required to grow the history)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetMaxLockDuration<'info> {
+    pub fn set_max_lock_duration(&mut self, seconds: i64) -> Result<()> {
+        // The admin can only tighten the program-wide bound
+        require!(
+            (MIN_LOCK_DURATION_SECONDS..=MAX_LOCK_DURATION_SECONDS).contains(&seconds),
+            ErrorCode::InvalidLockDuration
+        );
+
+        if seconds != self.config.max_lock_duration_seconds {
+            self.config_history.record(
+                ConfigField::MaxLockDuration,
+                ConfigValue::U64(self.config.max_lock_duration_seconds as u64),
+                ConfigValue::U64(seconds as u64),
+                self.admin.key(),
+            )?;
+        }
+
+        // Existing tranches keep their expiries, the bound only applies to new locks
+        self.config.max_lock_duration_seconds = seconds;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(fee_bps: u16, treasury: Pubkey)]
+pub struct SetDepositFee<'info> {
+    /// The config admin, who pays to grow the config history
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can set the fee
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes, grown by one entry per changed setting
+    #[account(
+        mut,
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump = config_history.bump, // Use stored bump seed for validation
+        realloc = ConfigHistory::space(
+            config_history.entries.len() + config.fee_changes(fee_bps, treasury)
+        ),
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// The System program (required to grow the history)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetDepositFee<'info> {
+    pub fn set_deposit_fee(&mut self, fee_bps: u16, treasury: Pubkey) -> Result<()> {
+        require!(fee_bps <= MAX_DEPOSIT_FEE_BPS, ErrorCode::InvalidDepositFee);
+        // A fee needs somewhere to go
+        require!(
+            fee_bps == 0 || treasury != Pubkey::default(),
+            ErrorCode::InvalidDepositFee
+        );
+
+        let admin = self.admin.key();
+        if fee_bps != self.config.deposit_fee_bps {
+            self.config_history.record(
+                ConfigField::DepositFeeBps,
+                ConfigValue::U64(self.config.deposit_fee_bps as u64),
+                ConfigValue::U64(fee_bps as u64),
+                admin,
+            )?;
+        }
+        if treasury != self.config.treasury {
+            self.config_history.record(
+                ConfigField::Treasury,
+                ConfigValue::Pubkey(self.config.treasury),
+                ConfigValue::Pubkey(treasury),
+                admin,
+            )?;
+        }
+
+        self.config.deposit_fee_bps = fee_bps;
+        self.config.treasury = treasury;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetFeeTiers<'info> {
+    /// The config admin, who pays for the tiers account
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can set fee tiers
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The fee discount tiers, replaced as a whole
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = admin, // Admin pays for account creation
+        seeds = [FEE_TIERS_SEED], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + FeeTiers::INIT_SPACE, // Allocate space for account data
+    )]
+    pub fee_tiers: Account<'info, FeeTiers>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetFeeTiers<'info> {
+    pub fn set_fee_tiers(
+        &mut self,
+        membership_mint: Pubkey,
+        tiers: Vec<FeeTier>,
+        bumps: &SetFeeTiersBumps,
+    ) -> Result<()> {
+        require!(tiers.len() <= MAX_FEE_TIERS, ErrorCode::InvalidFeeTiers);
+        require!(
+            tiers.iter().all(|tier| tier.discount_bps as u64 <= BPS_DENOMINATOR),
+            ErrorCode::InvalidFeeTiers
+        );
+        // Larger balances must never get a smaller discount
+        require!(
+            tiers.windows(2).all(|pair| pair[0].min_balance < pair[1].min_balance
+                && pair[0].discount_bps <= pair[1].discount_bps),
+            ErrorCode::InvalidFeeTiers
+        );
+
+        self.fee_tiers.bump = bumps.fee_tiers;
+        self.fee_tiers.membership_mint = membership_mint;
+        self.fee_tiers.tiers = tiers;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetCredentialGate<'info> {
+    /// The config admin, who pays for the gate account
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can gate vaults
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The credential gate, created the first time it is set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = admin, // Admin pays for account creation
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + CredentialGate::INIT_SPACE, // Allocate space for account data
+    )]
+    pub credential_gate: Account<'info, CredentialGate>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetCredentialGate<'info> {
+    pub fn set_credential_gate(
+        &mut self,
+        credential_mint: Pubkey,
+        bumps: &SetCredentialGateBumps,
+    ) -> Result<()> {
+        self.credential_gate.bump = bumps.credential_gate;
+        self.credential_gate.credential_mint = credential_mint;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RemoveCredentialGate<'info> {
+    /// The config admin removing the gate (receives the gate rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can gate vaults
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The credential gate being removed
+    #[account(
+        mut,
+        seeds = [CREDENTIAL_GATE_SEED], // PDA seeds for deterministic address
+        bump = credential_gate.bump, // Use stored bump seed for validation
+        close = admin, // Refund the rent to the admin
+    )]
+    pub credential_gate: Account<'info, CredentialGate>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetCreationGate<'info> {
+    /// The config admin, who pays for the gate account
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can gate vault creation
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The vault creation gate, created the first time it is set
+    #[account(
+        init_if_needed, // Create the account on first use
+        payer = admin, // Admin pays for account creation
+        seeds = [CREATION_GATE_SEED], // PDA seeds for deterministic address
+        bump, // Store the bump seed for later use
+        space = 8 + CreationGate::INIT_SPACE, // Allocate space for account data
+    )]
+    pub creation_gate: Account<'info, CreationGate>,
+
+    /// The System program (required for account creation)
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetCreationGate<'info> {
+    pub fn set_creation_gate(
+        &mut self,
+        membership_mint: Pubkey,
+        min_balance: u64,
+        bumps: &SetCreationGateBumps,
+    ) -> Result<()> {
+        // A gate nobody has to hold anything for is no gate, remove it instead
+        require!(min_balance > 0, ErrorCode::ZeroAmount);
+
+        self.creation_gate.bump = bumps.creation_gate;
+        self.creation_gate.membership_mint = membership_mint;
+        self.creation_gate.min_balance = min_balance;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RemoveCreationGate<'info> {
+    /// The config admin removing the gate (receives the gate rent)
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can gate vault creation
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The vault creation gate being removed
+    /// Vaults created while it was set keep working, it only ever checks new vaults
+    #[account(
+        mut,
+        seeds = [CREATION_GATE_SEED], // PDA seeds for deterministic address
+        bump = creation_gate.bump, // Use stored bump seed for validation
+        close = admin, // Refund the rent to the admin
+    )]
+    pub creation_gate: Account<'info, CreationGate>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AdminFreezeVault<'info> {
+    /// The config admin freezing the vault
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can freeze vaults
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The owner of the vault being frozen
+    /// CHECK: Only used to derive the vault state PDA
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault state account being frozen
+    #[account(
+        mut,
+        seeds = [STATE_SEED, owner.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+}
+
+/// Implementation for the AdminFreezeVault accounts
+impl<'info> AdminFreezeVault<'info> {
+    pub fn admin_freeze_vault(&mut self, duration_seconds: i64, block_deposits: bool) -> Result<()> {
+        require!(
+            (MIN_LOCK_DURATION_SECONDS..=MAX_FREEZE_DURATION_SECONDS).contains(&duration_seconds),
+            ErrorCode::InvalidFreezeDuration
+        );
+
+        // A freeze can't be extended or chained: the owner always gets the cooldown in between
+        let clock = Clock::get()?;
+        if let Some(frozen_until) = self.state.frozen_until {
+            require!(
+                clock.unix_timestamp >= frozen_until.saturating_add(FREEZE_COOLDOWN_SECONDS),
+                ErrorCode::FreezeCooldown
+            );
+        }
+
+        let frozen_until = lock_expiry(clock.unix_timestamp, duration_seconds)
+            .ok_or(ErrorCode::InvalidFreezeDuration)?;
+        self.state.frozen_until = Some(frozen_until);
+        self.state
+            .set_flag(VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS, block_deposits);
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(min_cap: u64, max_cap: u64)]
+pub struct SetBalanceCapBounds<'info> {
+    /// The config admin, who pays to grow the config history
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program-wide config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+        has_one = admin @ ErrorCode::Unauthorized, // Only the admin can change the bounds
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The append-only log of config changes, grown to fit the changed bounds
+    #[account(
+        mut,
+        seeds = [CONFIG_HISTORY_SEED], // PDA seeds for deterministic address
+        bump = config_history.bump, // Use stored bump seed for validation
+        realloc = ConfigHistory::space(
+            config_history.entries.len() + config.bound_changes(min_cap, max_cap)
+        ),
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+
+    /// The System program (required to grow the history)
+    pub system_program: Program<'info, System>,
+}
+
+/// Implementation for the SetBalanceCapBounds accounts
+impl<'info> SetBalanceCapBounds<'info> {
+    pub fn set_balance_cap_bounds(&mut self, min_cap: u64, max_cap: u64) -> Result<()> {
+        // A zero cap would block every deposit
+        require!(
+            min_cap > 0 && min_cap <= max_cap,
+            ErrorCode::BalanceCapOutOfBounds
+        );
+
+        // Log only the bounds that actually change
+        let admin = self.admin.key();
+        if min_cap != self.config.min_balance_cap {
+            self.config_history.record(
+                ConfigField::MinBalanceCap,
+                ConfigValue::U64(self.config.min_balance_cap),
+                ConfigValue::U64(min_cap),
+                admin,
+            )?;
+        }
+        if max_cap != self.config.max_balance_cap {
+            self.config_history.record(
+                ConfigField::MaxBalanceCap,
+                ConfigValue::U64(self.config.max_balance_cap),
+                ConfigValue::U64(max_cap),
+                admin,
+            )?;
+        }
+
+        // Caps already set on vaults are only checked against the bounds when they change
+        self.config.min_balance_cap = min_cap;
+        self.config.max_balance_cap = max_cap;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetBalanceCap<'info> {
+    /// The vault owner (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The program-wide config account holding the allowed cap range
+    #[account(
+        seeds = [CONFIG_SEED], // PDA seeds for deterministic address
+        bump = config.bump, // Use stored bump seed for validation
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/// Implementation for the SetBalanceCap accounts
+impl<'info> SetBalanceCap<'info> {
+    pub fn set_balance_cap(&mut self, cap: Option<u64>, policy: CapPolicy) -> Result<()> {
+        // Removing the cap is always allowed
+        if let Some(cap) = cap {
+            require!(
+                (self.config.min_balance_cap..=self.config.max_balance_cap).contains(&cap),
+                ErrorCode::BalanceCapOutOfBounds
+            );
+        }
+
+        self.state.balance_cap = cap;
+        self.state.cap_policy = policy;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetDepositHook<'info> {
+    /// The vault owner (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+
+    /// The admin approval of the new hook program, required when setting a hook
+    #[account(
+        seeds = [HOOK_SEED, approved_hook.program_id.as_ref()], // PDA seeds for deterministic address
+        bump = approved_hook.bump, // Use stored bump seed for validation
+    )]
+    pub approved_hook: Option<Account<'info, ApprovedHook>>,
+}
+
+/// Implementation for the SetDepositHook accounts
+impl<'info> SetDepositHook<'info> {
+    pub fn set_deposit_hook(&mut self, hook: Option<Pubkey>) -> Result<()> {
+        // Only admin-approved programs can be set, clearing the hook is always allowed
+        if let Some(hook) = hook {
+            let approved_hook = self
+                .approved_hook
+                .as_ref()
+                .ok_or(ErrorCode::DepositHookNotApproved)?;
+            require_keys_eq!(approved_hook.program_id, hook, ErrorCode::DepositHookNotApproved);
+        }
+
+        self.state.deposit_hook = hook;
+
+        Ok(())
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetVaultKey<'info> {
+    /// The vault owner (must sign the transaction)
+    pub user: Signer<'info>,
+
+    /// The vault's current offline key, required when the vault already has one
+    pub current_vault_key: Option<Signer<'info>>,
+
+    /// The vault state account
+    /// Validates using PDA seeds and stored bump seed
+    #[account(
+        mut,
+        seeds = [STATE_SEED, user.key().as_ref()], // PDA seeds for deterministic address
+        bump = state.state_bump, // Use stored bump seed for validation
+    )]
+    pub state: Account<'info, Vault>,
+}
+
+/// Implementation for the SetVaultKey accounts
+impl<'info> SetVaultKey<'info> {
+    pub fn set_vault_key(&mut self, vault_key: Option<Pubkey>, threshold: u64) -> Result<()> {
+        // Otherwise a stolen owner key could simply remove the offline key
+        if let Some(current) = self.state.vault_key {
+            assert_vault_key_signed(current, self.current_vault_key.as_ref())?;
+        }
+
+        self.state.vault_key = vault_key;
+        self.state.vault_key_threshold = threshold;
+
+        Ok(())
+    }
+}
+
+/// Fails while an admin freeze is active. Deposits are only blocked if the freeze says so.
+pub fn assert_not_frozen(state: &Vault, deposit: bool) -> Result<()> {
+    if state.frozen_until.is_none()
+        || (deposit && !state.has_flag(VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS))
+    {
+        return Ok(());
+    }
+
+    // A freeze expires exactly like a lock does
+    let clock = Clock::get()?;
+    require!(
+        !is_locked(clock.unix_timestamp, state.frozen_until),
+        ErrorCode::VaultFrozen
+    );
+
+    Ok(())
+}
+
+/// Checks that the vault's offline key was passed and signed the transaction
+pub fn assert_vault_key_signed(vault_key: Pubkey, signer: Option<&Signer>) -> Result<()> {
+    let signer = signer.ok_or(ErrorCode::VaultKeyRequired)?;
+    require_keys_eq!(signer.key(), vault_key, ErrorCode::VaultKeyRequired);
+
+    Ok(())
+}
+
+/// Checks that the mint has been approved by the admin, if the config enables the mint
+/// allowlist. Deployments that never created a config accept every mint.
+pub fn assert_mint_allowed(config: &AccountInfo, approved_mint: &AccountInfo) -> Result<()> {
+    let Some(config) = load_config(config)? else {
+        return Ok(());
+    };
+
+    if config.mint_allowlist_enabled {
+        // Revoking an approval closes its account
+        let approved = approved_mint.owner == &crate::ID
+            && ApprovedMint::try_deserialize(&mut &approved_mint.try_borrow_data()?[..]).is_ok();
+        require!(approved, ErrorCode::MintNotApproved);
+    }
+
+    Ok(())
+}
+
+/// Refuses wallets on the admin blocklist. Unblocking a wallet closes its entry.
+pub fn assert_not_blocked(blocked_address: &AccountInfo) -> Result<()> {
+    let blocked = blocked_address.owner == &crate::ID
+        && BlockedAddress::try_deserialize(&mut &blocked_address.try_borrow_data()?[..]).is_ok();
+    require!(!blocked, ErrorCode::AddressBlocked);
+
+    Ok(())
+}
+
+/// Refuses a wallet on the admin blocklist whose entry the accounts constraints can't derive,
+/// such as the owner of a destination token account
+pub fn assert_wallet_not_blocked(blocked_address: &AccountInfo, wallet: &Pubkey) -> Result<()> {
+    let (entry, _) = Pubkey::find_program_address(&[BLOCKED_SEED, wallet.as_ref()], &crate::ID);
+    require_keys_eq!(blocked_address.key(), entry, ErrorCode::BlocklistMismatch);
+
+    assert_not_blocked(blocked_address)
+}
+
+/// Refuses mints with a freeze authority, if the config says so. A frozen vault token
+/// account can't be withdrawn from or closed, so the savings would be trapped.
+pub fn assert_mint_not_freezable(config: &AccountInfo, mint: &Mint) -> Result<()> {
+    if mint.freeze_authority.is_none() {
+        return Ok(());
+    }
+
+    if let Some(config) = load_config(config)? {
+        require!(!config.reject_freezable_mints, ErrorCode::FreezableMint);
+    }
+
+    Ok(())
+}
+
+/// Returns the longest lock `lock_tokens` accepts: the admin's bound if set, else
+/// `MAX_LOCK_DURATION_SECONDS`
+pub fn max_lock_duration(config: &AccountInfo) -> Result<i64> {
+    // Configs created before the bound existed read it as zero from the reserved bytes
+    Ok(load_config(config)?
+        .map(|config| config.max_lock_duration_seconds)
+        .filter(|&seconds| seconds > 0)
+        .unwrap_or(MAX_LOCK_DURATION_SECONDS))
+}
+
+/// Closes a vault's child account at `account`, refunding its rent to `destination`. Does
+/// nothing if the vault never created it.
+pub fn close_child_account<'info>(
+    account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    if account.owner != &crate::ID || account.data_is_empty() {
+        return Ok(());
+    }
+
+    let rent = account.lamports();
+    let refunded = destination
+        .lamports()
+        .checked_add(rent)
+        .ok_or(ErrorCode::MathOverflow)?;
+    **destination.try_borrow_mut_lamports()? = refunded;
+    **account.try_borrow_mut_lamports()? = 0;
+
+    // Hand the address back to the System program, like Anchor's `close`
+    account.assign(&system_program::ID);
+    account.resize(0)?;
+
+    Ok(())
+}
+
+/// Closes the child accounts of a vault being closed, refunding their rent to the owner
+pub fn close_child_accounts<'info>(
+    children: &[&AccountInfo<'info>],
+    owner: &AccountInfo<'info>,
+) -> Result<()> {
+    for child in children {
+        close_child_account(child, owner)?;
+    }
+    Ok(())
+}
+
+/// Fails while the vault has records that must not outlive it, as they would carry over to
+/// a vault re-created at the same address: open delegate sessions or subscriptions, an
+/// unpaid advance, a custody before its release date, a running self-exclusion or a
+/// pending recovery
+pub fn assert_vault_closable(
+    state: &Vault,
+    custody: &AccountInfo,
+    advance: &AccountInfo,
+    recovery_proposal: &AccountInfo,
+    self_exclusion: &AccountInfo,
+    now: i64,
+) -> Result<()> {
+    require!(state.open_grants == 0, ErrorCode::GrantsOutstanding);
+    assert_no_advance(advance)?;
+    assert_not_in_custody(custody, now)?;
+    assert_not_self_excluded(self_exclusion, now)?;
+    require!(
+        recovery_proposal.owner != &crate::ID || recovery_proposal.data_is_empty(),
+        ErrorCode::RecoveryPending
+    );
+    Ok(())
+}
+
+/// Creates a PDA account of `space` bytes owned by `owner`, paid for by `payer`. Like Anchor's
+/// `init`, it still works if someone sent lamports to the address beforehand, which would
+/// make a plain `create_account` fail.
+pub fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = account.lamports();
+
+    if lamports == 0 {
+        let cpi_accounts = system_program::CreateAccount {
+            from: payer.clone(), // Payer: funds the rent
+            to: account.clone(), // New account: the PDA
+        };
+        let cpi_ctx =
+            CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer_seeds);
+        return system_program::create_account(cpi_ctx, rent, space as u64, owner);
+    }
+
+    // Top the existing balance up to rent exemption, then allocate and assign the account
+    if lamports < rent {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.clone(), // Payer: funds the rest of the rent
+            to: account.clone(), // Destination: the PDA
+        };
+        let cpi_ctx = CpiContext::new(system_program.clone(), cpi_accounts);
+        system_program::transfer(cpi_ctx, rent - lamports)?;
+    }
+    let cpi_accounts = system_program::Allocate {
+        account_to_allocate: account.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer_seeds);
+    system_program::allocate(cpi_ctx, space as u64)?;
+    let cpi_accounts = system_program::Assign {
+        account_to_assign: account.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer_seeds);
+    system_program::assign(cpi_ctx, owner)
+}
+
+/// Reads the program config, if the admin has initialized it
+pub fn load_config(config: &AccountInfo) -> Result<Option<Config>> {
+    if config.owner != &crate::ID || config.data_is_empty() {
+        return Ok(None);
+    }
+
+    let config = Config::try_deserialize(&mut &config.try_borrow_data()?[..])?;
+    Ok(Some(config))
+}
+
+/// CPIs the vault's deposit hook, if one is set, with the `depositor` and the gross `amount`
+/// they deposited, fee included. `remaining_accounts` are forwarded to the hook without
+/// signer privileges.
+pub fn invoke_deposit_hook<'info>(
+    state: &Account<'info, Vault>,
+    depositor: &AccountInfo<'info>,
+    deposit_hook_program: Option<&UncheckedAccount<'info>>,
+    approved_hook: Option<&Account<'info, ApprovedHook>>,
+    amount: u64,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let Some(hook) = state.deposit_hook else {
+        return Ok(());
+    };
+
+    // The hook must still be approved by the admin and match the one set on the vault
+    let hook_program = deposit_hook_program.ok_or(ErrorCode::DepositHookMismatch)?;
+    let approved_hook = approved_hook.ok_or(ErrorCode::DepositHookNotApproved)?;
+    require_keys_eq!(hook_program.key(), hook, ErrorCode::DepositHookMismatch);
+    require_keys_eq!(approved_hook.program_id, hook, ErrorCode::DepositHookNotApproved);
+
+    // Never lend the depositor's signature (or anyone else's) to the hook
+    let mut accounts = vec![
+        AccountMeta::new_readonly(state.key(), false),
+        AccountMeta::new_readonly(depositor.key(), false),
+    ];
+    accounts.extend(remaining_accounts.iter().map(|account| AccountMeta {
+        pubkey: account.key(),
+        is_signer: false,
+        is_writable: account.is_writable,
+    }));
+
+    let mut data = deposit_hook_discriminator().to_vec();
+    DepositHookArgs {
+        state: state.key(),
+        depositor: depositor.key(),
+        mint: state.mint,
+        amount,
+    }
+    .serialize(&mut data)?;
+
+    let ix = anchor_lang::solana_program::instruction::Instruction {
+        program_id: hook,
+        accounts,
+        data,
+    };
+
+    let mut infos = vec![state.to_account_info(), depositor.clone()];
+    infos.extend_from_slice(remaining_accounts);
+    infos.push(hook_program.to_account_info());
+
+    anchor_lang::solana_program::program::invoke(&ix, &infos)?;
+
+    Ok(())
+}
+
+/// The accounts a deposit's protocol fee is worked out with, borrowed from whichever
+/// instruction takes the deposit
+pub struct DepositFee<'a, 'info> {
+    pub config: &'a AccountInfo<'info>,
+    pub fee_tiers: &'a AccountInfo<'info>,
+    pub vault_template: &'a AccountInfo<'info>,
+    /// The depositor's token account of the membership mint, for a fee tier discount
+    pub membership_token_account: Option<&'a Account<'info, TokenAccount>>,
+    pub treasury_token_account: Option<&'a Account<'info, TokenAccount>>,
+}
+
+impl DepositFee<'_, '_> {
+    /// Returns the protocol fee due on a deposit of `amount` by `depositor`, discounted by the
+    /// fee tier their membership token account qualifies for. Whenever a fee is due, the
+    /// treasury token account must be the config treasury's account for the vault's `mint`.
+    pub fn amount(&self, depositor: &Pubkey, mint: &Pubkey, amount: u64) -> Result<u64> {
+        let Some(config) = load_config(self.config)? else {
+            return Ok(0);
+        };
+
+        // Vaults created from a template may be charged the template's fee instead
+        let fee_bps = load_vault_template(self.vault_template)?
+            .and_then(|terms| terms.deposit_fee_bps)
+            .unwrap_or(config.deposit_fee_bps);
+        let mut fee = bps_share(amount, fee_bps as u64);
+        if let (Some(tiers), Some(membership_token_account), true) =
+            (load_fee_tiers(self.fee_tiers)?, self.membership_token_account, fee > 0)
+        {
+            require_keys_eq!(
+                membership_token_account.owner,
+                *depositor,
+                ErrorCode::MembershipMismatch
+            );
+            require_keys_eq!(
+                membership_token_account.mint,
+                tiers.membership_mint,
+                ErrorCode::MembershipMismatch
+            );
+            let discount_bps = tiers.discount_bps(membership_token_account.amount);
+            fee = discounted_fee(fee, discount_bps);
+        }
+
+        if fee > 0 {
+            let treasury_token_account = self
+                .treasury_token_account
+                .ok_or(ErrorCode::TreasuryMismatch)?;
+            require_keys_eq!(
+                treasury_token_account.owner,
+                config.treasury,
+                ErrorCode::TreasuryMismatch
+            );
+            require_keys_eq!(treasury_token_account.mint, *mint, ErrorCode::MintMismatch);
+        }
+
+        Ok(fee)
+    }
+}
+
+/// Returns the wallet advance fees are paid to, if the admin has set a treasury
+pub fn fee_treasury(config: &AccountInfo) -> Result<Option<Pubkey>> {
+    Ok(load_config(config)?
+        .map(|config| config.treasury)
+        .filter(|treasury| *treasury != Pubkey::default()))
+}
+
+/// Returns what is left of `fee` after a discount of `discount_bps`
+pub fn discounted_fee(fee: u64, discount_bps: u16) -> u64 {
+    fee - bps_share(fee, discount_bps as u64)
+}
+
+/// Checks a memo attached to a transfer and stores its hash on the vault's extension
+pub fn record_memo(
+    extension: &Option<AccountLoader<VaultExtension>>,
+    memo: &str,
+) -> Result<[u8; 32]> {
+    require!(
+        !memo.is_empty() && memo.len() <= MAX_MEMO_LEN,
+        ErrorCode::InvalidMemo
+    );
+    let extension = extension.as_ref().ok_or(ErrorCode::ExtensionRequired)?;
+
+    let memo_hash = anchor_lang::solana_program::hash::hash(memo.as_bytes()).to_bytes();
+    extension.load_mut()?.record_memo(memo_hash);
+
+    Ok(memo_hash)
+}
+
+/// Reads the credential gate, if the admin requires vault owners to hold a credential
+pub fn load_credential_gate(credential_gate: &AccountInfo) -> Result<Option<CredentialGate>> {
+    if credential_gate.owner != &crate::ID || credential_gate.data_is_empty() {
+        return Ok(None);
+    }
+
+    let gate = CredentialGate::try_deserialize(&mut &credential_gate.try_borrow_data()?[..])?;
+    Ok(Some(gate))
+}
+
+/// Checks that the vault owner holds the credential token, if the admin has set a credential
+/// gate. Deployments without a gate accept every owner.
+pub fn assert_credential_held(
+    credential_gate: &AccountInfo,
+    credential_token_account: Option<&InterfaceAccount<InterfaceTokenAccount>>,
+    owner: &Pubkey,
+) -> Result<()> {
+    let Some(gate) = load_credential_gate(credential_gate)? else {
+        return Ok(());
+    };
+
+    let credential_token_account = credential_token_account.ok_or(ErrorCode::CredentialRequired)?;
+    require_keys_eq!(
+        credential_token_account.owner,
+        *owner,
+        ErrorCode::CredentialMismatch
+    );
+    require_keys_eq!(
+        credential_token_account.mint,
+        gate.credential_mint,
+        ErrorCode::CredentialMismatch
+    );
+    require!(
+        credential_token_account.amount > 0,
+        ErrorCode::CredentialRequired
+    );
+
+    Ok(())
+}
+
+/// The admin's checks on a new vault, borrowed from whichever instruction creates it
+pub struct CreationChecks<'a, 'info> {
+    pub config: &'a AccountInfo<'info>,
+    pub approved_mint: &'a AccountInfo<'info>,
+    pub creation_gate: &'a AccountInfo<'info>,
+    /// The creator's token account of the membership mint, required while creation is gated
+    pub membership_token_account: Option<&'a Account<'info, TokenAccount>>,
+    pub credential_gate: &'a AccountInfo<'info>,
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<&'a InterfaceAccount<'info, InterfaceTokenAccount>>,
+    pub mint: &'a Mint,
+}
+
+impl CreationChecks<'_, '_> {
+    /// Checks that a vault of the mint can be created for `owner`: the mint is allowed and not
+    /// freezable if the config says so, the creator holds the membership token and the owner
+    /// holds the credential while the respective gates are set
+    pub fn check(&self, owner: &Pubkey) -> Result<()> {
+        // Curated deployments only accept admin-approved mints
+        assert_mint_allowed(self.config, self.approved_mint)?;
+        // Gated deployments only let membership holders create vaults
+        assert_creation_allowed(self.creation_gate, self.membership_token_account)?;
+        // ... and only serve owners holding the credential
+        assert_credential_held(self.credential_gate, self.credential_token_account, owner)?;
+        assert_mint_not_freezable(self.config, self.mint)
+    }
+}
+
+/// The admin's checks on a payout from a vault to its owner or whoever they pay, borrowed from
+/// whichever instruction pays out
+pub struct PayoutChecks<'a, 'info> {
+    /// The blocklist entries of the owner and every other wallet taking part
+    pub blocked: &'a [&'a AccountInfo<'info>],
+    pub credential_gate: &'a AccountInfo<'info>,
+    /// The owner's token account of the credential mint, required while a credential gate is set
+    pub credential_token_account: Option<&'a InterfaceAccount<'info, InterfaceTokenAccount>>,
+}
+
+impl PayoutChecks<'_, '_> {
+    /// Checks that no wallet taking part is blocked and that `owner` holds the credential
+    /// while a credential gate is set
+    pub fn check(&self, owner: &Pubkey) -> Result<()> {
+        // Blocked wallets can't move tokens into or out of vaults
+        for blocked in self.blocked {
+            assert_not_blocked(blocked)?;
+        }
+        // Gated deployments only serve owners holding the credential
+        assert_credential_held(self.credential_gate, self.credential_token_account, owner)
+    }
+}
+
+/// Reads the vault creation gate, if the admin limits vault creation to membership holders
+pub fn load_creation_gate(creation_gate: &AccountInfo) -> Result<Option<CreationGate>> {
+    if creation_gate.owner != &crate::ID || creation_gate.data_is_empty() {
+        return Ok(None);
+    }
+
+    let gate = CreationGate::try_deserialize(&mut &creation_gate.try_borrow_data()?[..])?;
+    Ok(Some(gate))
+}
+
+/// Checks that the vault creator holds enough of the membership token, if the admin has set
+/// a creation gate. The token account's owner is checked by the accounts constraints.
+pub fn assert_creation_allowed(
+    creation_gate: &AccountInfo,
+    membership_token_account: Option<&Account<TokenAccount>>,
+) -> Result<()> {
+    let Some(gate) = load_creation_gate(creation_gate)? else {
+        return Ok(());
+    };
+
+    let membership_token_account = membership_token_account.ok_or(ErrorCode::MembershipRequired)?;
+    require_keys_eq!(
+        membership_token_account.mint,
+        gate.membership_mint,
+        ErrorCode::MembershipMismatch
+    );
+    require!(
+        membership_token_account.amount >= gate.min_balance,
+        ErrorCode::MembershipRequired
+    );
+
+    Ok(())
+}
+
+/// Returns the Pyth feed id the admin set for a mint, if any
+pub fn load_price_feed(price_feed: &AccountInfo) -> Result<Option<[u8; 32]>> {
+    if price_feed.owner != &crate::ID || price_feed.data_is_empty() {
+        return Ok(None);
+    }
+
+    let feed = PriceFeed::try_deserialize(&mut &price_feed.try_borrow_data()?[..])?;
+    Ok(Some(feed.feed_id))
+}
+
+/// Reads the fee discount tiers, if the admin has set them
+pub fn load_fee_tiers(fee_tiers: &AccountInfo) -> Result<Option<FeeTiers>> {
+    if fee_tiers.owner != &crate::ID || fee_tiers.data_is_empty() {
+        return Ok(None);
+    }
+
+    let tiers = FeeTiers::try_deserialize(&mut &fee_tiers.try_borrow_data()?[..])?;
+    Ok(Some(tiers))
+}
+
+/// Returns the address of a treasury token account passed to a deposit, for fee events
+pub fn treasury_key(treasury_token_account: &Option<Box<Account<TokenAccount>>>) -> Pubkey {
+    treasury_token_account
+        .as_ref()
+        .map(|account| account.key())
+        .unwrap_or_default()
+}
+
+/// Reads the vault's unlock stream, if the owner ever set one
+pub fn load_unlock_schedule(unlock_schedule: &AccountInfo) -> Result<Option<UnlockSchedule>> {
+    if unlock_schedule.owner != &crate::ID || unlock_schedule.data_is_empty() {
+        return Ok(None);
+    }
+
+    let schedule = UnlockSchedule::try_deserialize(&mut &unlock_schedule.try_borrow_data()?[..])?;
+    Ok(Some(schedule))
+}
+
+/// Reads a vault state account, if it exists
+pub fn load_vault(state: &AccountInfo) -> Result<Option<Vault>> {
+    if state.owner != &crate::ID || state.data_is_empty() {
+        return Ok(None);
+    }
+
+    let vault = Vault::try_deserialize(&mut &state.try_borrow_data()?[..])?;
+    Ok(Some(vault))
+}
+
+/// Reads a vault's reward points, if they exist
+pub fn load_rewards(rewards: &AccountInfo) -> Result<Option<RewardState>> {
+    if rewards.owner != &crate::ID || rewards.data_is_empty() {
+        return Ok(None);
+    }
+
+    let rewards = RewardState::try_deserialize(&mut &rewards.try_borrow_data()?[..])?;
+    Ok(Some(rewards))
+}
+
+/// Reads the vault's custody record, if a custodian created the vault
+pub fn load_custody(custody: &AccountInfo) -> Result<Option<Custody>> {
+    if custody.owner != &crate::ID || custody.data_is_empty() {
+        return Ok(None);
+    }
+
+    let custody = Custody::try_deserialize(&mut &custody.try_borrow_data()?[..])?;
+    Ok(Some(custody))
+}
+
+/// Returns true while a custodial vault is waiting for its release date
+pub fn in_custody(custody: &AccountInfo, now: i64) -> Result<bool> {
+    Ok(load_custody(custody)?.is_some_and(|custody| now < custody.release_ts))
+}
+
+/// Fails while a custodial vault is waiting for its release date
+pub fn assert_not_in_custody(custody: &AccountInfo, now: i64) -> Result<()> {
+    require!(!in_custody(custody, now)?, ErrorCode::VaultInCustody);
+    Ok(())
+}
+
+/// Reads the template terms a vault was created with, if it was created from a template
+pub fn load_vault_template(vault_template: &AccountInfo) -> Result<Option<VaultTemplate>> {
+    if vault_template.owner != &crate::ID || vault_template.data_is_empty() {
+        return Ok(None);
+    }
+
+    let template = VaultTemplate::try_deserialize(&mut &vault_template.try_borrow_data()?[..])?;
+    Ok(Some(template))
+}
+
+/// Returns whether the config refuses deposits and withdrawals in the same slot
+pub fn same_slot_guard(config: &AccountInfo) -> Result<bool> {
+    Ok(load_config(config)?.is_some_and(|config| config.same_slot_guard))
+}
+
+/// Returns what the config does with the balance above a target when a deposit reaches it
+pub fn surplus_policy(config: &AccountInfo) -> Result<SurplusPolicy> {
+    Ok(load_config(config)?
+        .map(|config| config.surplus_policy)
+        .unwrap_or_default())
+}
+
+/// Reads the vault's outstanding advance, if the owner borrowed against the locked balance
+pub fn load_advance(advance: &AccountInfo) -> Result<Option<Advance>> {
+    if advance.owner != &crate::ID || advance.data_is_empty() {
+        return Ok(None);
+    }
+
+    let advance = Advance::try_deserialize(&mut &advance.try_borrow_data()?[..])?;
+    Ok(Some(advance))
+}
+
+/// Returns what the owner still owes the vault for their advance, 0 without one
+pub fn outstanding_debt(advance: &AccountInfo) -> Result<u64> {
+    Ok(load_advance(advance)?.map_or(0, |advance| advance.debt()))
+}
+
+/// Fails while the owner hasn't repaid their advance
+pub fn assert_no_advance(advance: &AccountInfo) -> Result<()> {
+    require!(
+        outstanding_debt(advance)? == 0,
+        ErrorCode::AdvanceOutstanding
+    );
+    Ok(())
+}
+
+/// Reads the vault's rollover setting, if the owner set one
+pub fn load_rollover(rollover: &AccountInfo) -> Result<Option<Rollover>> {
+    if rollover.owner != &crate::ID || rollover.data_is_empty() {
+        return Ok(None);
+    }
+
+    let rollover = Rollover::try_deserialize(&mut &rollover.try_borrow_data()?[..])?;
+    Ok(Some(rollover))
+}
+
+/// Fails while the owner's self-exclusion is running
+pub fn assert_not_self_excluded(self_exclusion: &AccountInfo, now: i64) -> Result<()> {
+    if self_exclusion.owner != &crate::ID || self_exclusion.data_is_empty() {
+        return Ok(());
+    }
+
+    let exclusion = SelfExclusion::try_deserialize(&mut &self_exclusion.try_borrow_data()?[..])?;
+    require!(now >= exclusion.frozen_until, ErrorCode::SelfExcluded);
+
+    Ok(())
+}
+
+/// Reads the vault's withdrawal policy, if the owner ever set a withdrawal delay
+pub fn load_withdrawal_policy(withdrawal_policy: &AccountInfo) -> Result<Option<WithdrawalPolicy>> {
+    if withdrawal_policy.owner != &crate::ID || withdrawal_policy.data_is_empty() {
+        return Ok(None);
+    }
+
+    let policy = WithdrawalPolicy::try_deserialize(&mut &withdrawal_policy.try_borrow_data()?[..])?;
+    Ok(Some(policy))
+}
+
+/// Fails if the vault's withdrawal policy sets a delay or approvers, so its withdrawals can only
+/// go through `request_withdrawal` and `execute_withdrawal`
+pub fn assert_immediate_withdrawal(withdrawal_policy: &AccountInfo, now: i64) -> Result<()> {
+    if let Some(policy) = load_withdrawal_policy(withdrawal_policy)? {
+        require!(
+            policy.delay_at(now) == 0,
+            ErrorCode::WithdrawalDelayRequired
+        );
+        require!(policy.threshold == 0, ErrorCode::WithdrawalApprovalRequired);
+    }
+
+    Ok(())
+}
+
+/// Checks that the vault token account is still owned by the vault authority and holds the
+/// vault's mint. This is defence in depth on top of the account constraints.
+pub fn assert_vault_token_account(
+    state: &Account<Vault>,
+    vault_token_account: &Account<TokenAccount>,
+    vault_authority: &Pubkey,
+) -> Result<()> {
+    if vault_token_account.owner != *vault_authority || vault_token_account.mint != state.mint {
+        // Use a log-based event here: CPI events are rolled back with the failing
+        // transaction, while logs are kept in its metadata
+        emit!(VaultTokenAccountMismatch {
+            state: state.key(),
+            vault_token_account: vault_token_account.key(),
+            owner: vault_token_account.owner,
+            mint: vault_token_account.mint,
+        });
+
+        return err!(ErrorCode::VaultTokenAccountMismatch);
+    }
+
+    Ok(())
+}
+
+/// Reads the vault token account, if it wasn't closed along with the vault (possibly earlier
+/// in the same transaction, which leaves it without lamports until the transaction ends)
+pub fn load_vault_token_account(vault_token_account: &AccountInfo) -> Result<Option<TokenAccount>> {
+    if vault_token_account.lamports() == 0 || vault_token_account.data_is_empty() {
+        return Ok(None);
+    }
+
+    require_keys_eq!(
+        *vault_token_account.owner,
+        token::ID,
+        ErrorCode::VaultTokenAccountMismatch
+    );
+    let account = TokenAccount::try_deserialize(&mut &vault_token_account.try_borrow_data()?[..])?;
+    Ok(Some(account))
+}
+
+/// Closes the emptied vault token account, signed by the vault authority PDA, and sends its
+/// rent to `destination`
+pub fn close_vault_token_account<'info>(
+    token_program: AccountInfo<'info>,
+    vault_token_account: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    vault_authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let cpi_accounts = token::CloseAccount {
+        account: vault_token_account, // Account being closed
+        destination, // Rent goes back to the owner
+        authority: vault_authority, // Authority: vault authority PDA
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds);
+    token::close_account(cpi_ctx)
+}
+
+/// Returns the instruction discriminator deposit hooks must handle:
+/// the first 8 bytes of sha256("global:on_vault_deposit"), like an Anchor `on_vault_deposit` instruction
+pub fn deposit_hook_discriminator() -> [u8; 8] {
+    let hash = anchor_lang::solana_program::hash::hash(b"global:on_vault_deposit");
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+/// Returns how far `balance` is towards `target` in basis points, capped at 100%
+pub fn progress_bps(balance: u64, target: u64) -> u16 {
+    if target == 0 || balance >= target {
+        return BPS_DENOMINATOR as u16;
+    }
+
+    // balance < target, so the result is always below BPS_DENOMINATOR
+    ((balance as u128 * BPS_DENOMINATOR as u128) / target as u128) as u16
+}
+
+/// Returns how much of `amount` fits on top of `balance` under `cap`, or None if the deposit
+/// must be rejected. Nothing is deposited into a full vault, whatever the policy.
+pub fn capped_amount(amount: u64, balance: u64, cap: u64, policy: CapPolicy) -> Option<u64> {
+    let room = cap.saturating_sub(balance);
+    if amount <= room {
+        return Some(amount);
+    }
+
+    match policy {
+        CapPolicy::Truncate if room > 0 => Some(room),
+        _ => None,
+    }
+}
+
+/// Returns what a vault holding `balance` still needs to reach `target`, if crediting `credited`
+/// would overshoot it, or None if the whole of `credited` fits
+pub fn refund_cut(target: u64, balance: u64, credited: u64) -> Option<u64> {
+    let needed = target.saturating_sub(balance);
+    (credited > needed).then_some(needed)
+}
+
+/// Returns the points earned by holding `balance` (in the mint's smallest unit) for `elapsed`
+/// seconds: one point per whole token per second
+pub fn token_seconds(balance: u64, elapsed: i64, decimals: u8) -> u128 {
+    if elapsed <= 0 {
+        return 0;
+    }
+
+    // Mints with absurd decimals can't hold a whole token, so they never earn points
+    10u128
+        .checked_pow(decimals as u32)
+        .map_or(0, |scale| (balance as u128 * elapsed as u128) / scale)
+}
+
+/// Returns `bps` basis points of `amount`, never more than `amount` for `bps` up to `BPS_DENOMINATOR`
+pub fn bps_share(amount: u64, bps: u64) -> u64 {
+    ((amount as u128 * bps.min(BPS_DENOMINATOR) as u128) / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Converts a UI amount such as "12.5" into the raw amount of a mint with `decimals`, like the
+/// token program's `UiAmountToAmount`. Fails with `InvalidUiAmount` unless it is a plain decimal
+/// number with at most `decimals` fractional digits
+pub fn ui_amount_to_amount(ui_amount: &str, decimals: u8) -> Result<u64> {
+    let (whole, fraction) = ui_amount.split_once('.').unwrap_or((ui_amount, ""));
+    // A u64 holds 10^19 but not 10^20, so mints with more decimals can't scale a UI amount
+    require!(
+        decimals <= 19
+            && !whole.is_empty()
+            && whole.bytes().all(|digit| digit.is_ascii_digit())
+            && fraction.bytes().all(|digit| digit.is_ascii_digit())
+            && fraction.len() <= decimals as usize,
+        ErrorCode::InvalidUiAmount
+    );
+
+    // Pad the fraction with zeros up to the mint's decimals
+    let scale = 10u64
+        .checked_pow(decimals as u32)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let fraction_scale = 10u64
+        .checked_pow((decimals as usize - fraction.len()) as u32)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let fraction = match fraction {
+        "" => 0,
+        digits => digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|fraction| fraction.checked_mul(fraction_scale))
+            .ok_or(ErrorCode::MathOverflow)?,
+    };
+
+    let whole = whole.parse::<u64>().map_err(|_| ErrorCode::MathOverflow)?;
+    let amount = whole
+        .checked_mul(scale)
+        .and_then(|amount| amount.checked_add(fraction))
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(amount)
+}
+
+/// Returns a vault's target, given either as `amount` in the mint's smallest unit or as a UI
+/// amount. A UI amount only counts with a zero `amount`, so the two can't disagree
+pub fn target_amount(amount: u64, ui_amount: Option<&str>, decimals: u8) -> Result<u64> {
+    match ui_amount {
+        Some(ui_amount) => {
+            require!(amount == 0, ErrorCode::InvalidUiAmount);
+            ui_amount_to_amount(ui_amount, decimals)
+        }
+        None => Ok(amount),
+    }
+}
+
+/// Returns the part of a cranked release of `amount` paid to the cranker, never more than `amount`
+pub fn crank_reward(amount: u64) -> u64 {
+    bps_share(amount, CRANK_REWARD_BPS)
+}
+
+/// Returns true if `mint` is an NFT: a single indivisible token that can't be minted again
+pub fn is_nft_mint(mint: &Mint) -> bool {
+    mint.decimals == 0 && mint.supply == 1 && mint.mint_authority.is_none()
+}
+
+/// Returns the smallest target accepted for a mint with `decimals` decimals
+pub fn min_target(decimals: u8) -> u64 {
+    // Mints with more decimals than fit in a u64 can't express any sensible target
+    10u64
+        .checked_pow(decimals.saturating_sub(MIN_TARGET_FRACTION_DIGITS) as u32)
+        .unwrap_or(u64::MAX)
+}
+
+/// Returns the timestamp at which a lock of `duration_seconds` taken at `now` expires,
+/// or `None` if the expiry does not fit in an `i64`
+pub fn lock_expiry(now: i64, duration_seconds: i64) -> Option<i64> {
+    now.checked_add(duration_seconds)
+}
+
+/// Returns true if a vault with the given `locked_until` is still locked at `now`
+pub fn is_locked(now: i64, locked_until: Option<i64>) -> bool {
+    match locked_until {
+        Some(locked_until) => now < locked_until,
+        None => false,
+    }
+}
+
+/// Returns the total amount held by the tranches still locked at `now`
+pub fn locked_amount(now: i64, tranches: &[LockTranche]) -> u64 {
+    tranches
+        .iter()
+        .filter(|tranche| tranche.is_active(now))
+        .fold(0u64, |locked, tranche| locked.saturating_add(tranche.amount))
+}
+
+/// Returns how much of `balance` can be withdrawn at `now` given the vault's lock tranches
+pub fn unlocked_amount(balance: u64, now: i64, tranches: &[LockTranche]) -> u64 {
+    balance.saturating_sub(locked_amount(now, tranches))
+}
+
+/// Returns how much of `amount`, streaming out linearly between `start_ts` and `end_ts`, is
+/// still held back at `now`. Everything is held back before `cliff_ts`. Rounds up, so the
+/// stream never unlocks early.
+pub fn streamed_locked_amount(
+    amount: u64,
+    start_ts: i64,
+    cliff_ts: i64,
+    end_ts: i64,
+    now: i64,
+) -> u64 {
+    if now >= end_ts {
+        return 0;
+    }
+    if now <= start_ts || now < cliff_ts {
+        return amount;
+    }
+
+    let remaining = (end_ts as i128 - now as i128) as u128;
+    let duration = (end_ts as i128 - start_ts as i128) as u128;
+    ((amount as u128) * remaining).div_ceil(duration) as u64
+}
+
+/// Returns how many seconds are left before a withdrawal is allowed again, after one at
+/// `last_withdraw_ts` (0 for never) with `cooldown_seconds` between withdrawals
+pub fn cooldown_remaining(now: i64, last_withdraw_ts: i64, cooldown_seconds: i64) -> i64 {
+    if last_withdraw_ts == 0 {
+        return 0;
+    }
+
+    last_withdraw_ts
+        .saturating_add(cooldown_seconds)
+        .saturating_sub(now)
+        .max(0)
+}
+
+/// Returns how much still counts against a daily withdrawal limit of `max_per_day`, `elapsed`
+/// seconds after `withdrawn` was counted. The usage drains linearly over a day.
+pub fn withdraw_window_usage(max_per_day: u64, withdrawn: u64, elapsed: i64) -> u64 {
+    let elapsed = elapsed.clamp(0, WITHDRAW_LIMIT_WINDOW_SECONDS) as u128;
+    let drained = max_per_day as u128 * elapsed / WITHDRAW_LIMIT_WINDOW_SECONDS as u128;
+
+    // Never more than the limit itself, even if the limit was lowered since
+    (withdrawn as u128)
+        .saturating_sub(drained)
+        .min(max_per_day as u128) as u64
+}
+
+/// Returns true if a price of `price_cents` crosses `threshold_cents` in `direction`
+pub fn price_condition_met(direction: PriceDirection, price_cents: u64, threshold_cents: u64) -> bool {
+    match direction {
+        PriceDirection::AtOrAbove => price_cents >= threshold_cents,
+        PriceDirection::AtOrBelow => price_cents <= threshold_cents,
+    }
+}
+
+/// Snapshot of a vault returned by `get_vault_status`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VaultStatus {
+    /// Current balance of the vault's token account
+    pub balance: u64,
+
+    /// The savings target
+    pub target: u64,
+
+    /// Decimals of the vault's mint, for displaying `balance` and `target` as UI amounts
+    pub decimals: u8,
+
+    /// Progress towards the target in basis points (10000 = target reached)
+    pub progress_bps: u16,
+
+    /// The part of the balance that can be withdrawn now
+    pub unlocked: u64,
+
+    /// The lock tranches that haven't expired yet
+    pub lock_tranches: Vec<LockTranche>,
+
+    /// Seconds until every tranche has expired, 0 if nothing is locked
+    pub seconds_remaining: i64,
+
+    /// The owner's notes, oldest first (empty if the notes account wasn't passed)
+    pub notes: Vec<Note>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Vault {
+    /// The target amount of tokens to save (in token's smallest unit)
+    /// When the vault balance reaches this amount, all tokens are automatically released
+    pub amount: u64,
+
+    /// The bump seed for the vault authority PDA
+    /// This is used to sign transactions on behalf of the vault
+    pub vault_bump: u8,
+
+    /// The bump seed for the state account PDA
+    /// This is used for validation when accessing the state account
+    pub state_bump: u8,
+
+    /// The mint address of the token type stored in this vault
+    /// This ensures all operations are performed on the correct token type
+    pub mint: Pubkey,
+
+    /// The address of the vault's token account
+    /// This is where the actual tokens are stored
+    pub vault_token_account: Pubkey,
+
+    /// Amounts locked until a given time (Unix timestamp in seconds), each independent of the
+    /// others. Withdrawals can't dip into the amount of a tranche until it expires, and
+    /// unused slots have a zero amount
+    pub lock_tranches: [LockTranche; MAX_LOCK_TRANCHES],
+
+    /// The admin-approved program invoked after every deposit, if any
+    pub deposit_hook: Option<Pubkey>,
+
+    /// A secondary key, meant to be kept offline, that must co-sign large withdrawals
+    pub vault_key: Option<Pubkey>,
+
+    /// Withdrawals above this amount need the vault key's signature (when one is set)
+    pub vault_key_threshold: u64,
+
+    /// The timestamp until which the admin has frozen the vault, if it was ever frozen
+    pub frozen_until: Option<i64>,
+
+    /// Boolean settings, one bit each (see the `VAULT_FLAG_*` constants). Bit 0 took over the
+    /// byte of the `freeze_blocks_deposits` bool it replaced, so vaults read unchanged
+    pub flags: u8,
+
+    /// Whether anyone may crank the release once the lock has expired
+    pub release_on_unlock: bool,
+
+    /// The most the vault may hold, if capped
+    pub balance_cap: Option<u64>,
+
+    /// What happens to deposits that would exceed the balance cap
+    pub cap_policy: CapPolicy,
+
+    /// The date the target should be reached by, and what happens if it isn't
+    pub deadline: Option<GoalDeadline>,
+
+    /// A target in USD, valued through an oracle, that replaces the token `amount` target
+    pub usd_target: Option<UsdTarget>,
+
+    /// Time that must pass after a withdrawal before the next one (in seconds, 0 for none)
+    pub cooldown_seconds: i64,
+
+    /// The timestamp of the last withdrawal (0 if the vault was never withdrawn from)
+    pub last_withdraw_ts: i64,
+
+    /// Most that can be withdrawn over any day (0 for no limit)
+    pub max_withdraw_per_day: u64,
+
+    /// Amount counted against the daily limit as of `last_withdraw_ts`. It drains at
+    /// `max_withdraw_per_day` per day, so the limit applies over a rolling window
+    pub withdrawn_in_window: u64,
+
+    /// Layout version, bumped whenever fields are carved out of `reserved`
+    pub version: u8,
+
+    /// Delegate sessions and subscriptions granted and not yet revoked. The vault can't be
+    /// closed while any are open, since they'd carry over to a vault re-created at its address
+    pub open_grants: u16,
+
+    /// The decimals of the vault's mint, for showing amounts without fetching the mint
+    pub decimals: u8,
+
+    /// Zeroed space for future fields (see `VAULT_RESERVED_BYTES`)
+    pub reserved: [u8; VAULT_RESERVED_BYTES],
+}
+
+const _: () = assert!(8 + Vault::INIT_SPACE == VAULT_ACCOUNT_SIZE);
+
+impl Vault {
+    /// The current layout version. Version 1 vaults predate the field and are
+    /// `VAULT_V1_ACCOUNT_SIZE` bytes long, version 2 vaults read `open_grants` as zero and
+    /// version 3 vaults read `decimals` as zero
+    pub const VERSION: u8 = 4;
+
+    /// Returns true if every bit of `flag` (a `VAULT_FLAG_*` constant) is set
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag == flag
+    }
+
+    /// Sets or clears the bits of `flag`
+    pub fn set_flag(&mut self, flag: u8, enabled: bool) {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+}
+
+/// Program-wide settings managed by the admin
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    /// The admin allowed to change program-wide settings
+    pub admin: Pubkey,
+
+    /// The bump seed for the config PDA
+    pub bump: u8,
+
+    /// Smallest balance cap an owner may set on a vault
+    pub min_balance_cap: u64,
+
+    /// Largest balance cap an owner may set on a vault
+    pub max_balance_cap: u64,
+
+    /// Whether new vaults may only be created for admin-approved mints
+    pub mint_allowlist_enabled: bool,
+
+    /// Protocol fee skimmed from every deposit, in basis points (0 for none)
+    pub deposit_fee_bps: u16,
+
+    /// The wallet whose token accounts receive deposit fees
+    pub treasury: Pubkey,
+
+    /// Whether new vaults are refused for mints with a freeze authority
+    pub reject_freezable_mints: bool,
+
+    /// Longest lock `lock_tokens` accepts, in seconds (0 for `MAX_LOCK_DURATION_SECONDS`)
+    pub max_lock_duration_seconds: i64,
+
+    /// Whether a vault refuses withdrawals in the slot of a deposit, and vice versa
+    pub same_slot_guard: bool,
+
+    /// What happens to the part of a vault balance above its target when a deposit reaches it
+    pub surplus_policy: SurplusPolicy,
+
+    /// Zeroed space for future fields (see `CONFIG_RESERVED_BYTES`)
+    pub reserved: [u8; CONFIG_RESERVED_BYTES],
+}
+
+const _: () = assert!(8 + Config::INIT_SPACE == CONFIG_ACCOUNT_SIZE);
+
+impl Config {
+    /// Number of balance cap bounds that setting `min_cap` and `max_cap` would change
+    pub fn bound_changes(&self, min_cap: u64, max_cap: u64) -> usize {
+        usize::from(min_cap != self.min_balance_cap) + usize::from(max_cap != self.max_balance_cap)
+    }
+
+    /// Number of deposit fee settings that setting `fee_bps` and `treasury` would change
+    pub fn fee_changes(&self, fee_bps: u16, treasury: Pubkey) -> usize {
+        usize::from(fee_bps != self.deposit_fee_bps) + usize::from(treasury != self.treasury)
+    }
+}
+
+/// Append-only log of every change to the program config, oldest first. The account grows
+/// by one entry per change, so the full parameter timeline can be read from chain.
+#[account]
+pub struct ConfigHistory {
+    /// The bump seed for the config history PDA
+    pub bump: u8,
+
+    /// Every change made to the config, including its initial values
+    pub entries: Vec<ConfigChange>,
+}
+
+impl ConfigHistory {
+    /// Account size (including the discriminator) holding `entries` changes
+    pub const fn space(entries: usize) -> usize {
+        8 + 1 + 4 + entries * ConfigChange::INIT_SPACE
+    }
+
+    /// Appends a change made by `admin` in the current slot
+    pub fn record(
+        &mut self,
+        field: ConfigField,
+        old: ConfigValue,
+        new: ConfigValue,
+        admin: Pubkey,
+    ) -> Result<()> {
+        self.entries.push(ConfigChange {
+            field,
+            old,
+            new,
+            slot: Clock::get()?.slot,
+            admin,
+        });
+
+        Ok(())
+    }
+}
+
+/// A single change to the program config
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct ConfigChange {
+    /// The config field that changed
+    pub field: ConfigField,
+    /// The value before the change
+    pub old: ConfigValue,
+    /// The value after the change
+    pub new: ConfigValue,
+    /// The slot the change was made in
+    pub slot: u64,
+    /// The admin who made the change
+    pub admin: Pubkey,
+}
+
+/// The fields of the program config
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum ConfigField {
+    /// `Config::admin`
+    Admin,
+    /// `Config::min_balance_cap`
+    MinBalanceCap,
+    /// `Config::max_balance_cap`
+    MaxBalanceCap,
+    /// `Config::mint_allowlist_enabled`
+    MintAllowlistEnabled,
+    /// `Config::deposit_fee_bps`
+    DepositFeeBps,
+    /// `Config::treasury`
+    Treasury,
+    /// `Config::reject_freezable_mints`
+    RejectFreezableMints,
+    /// `Config::max_lock_duration_seconds`
+    MaxLockDuration,
+    /// `Config::same_slot_guard`
+    SameSlotGuard,
+    /// `Config::surplus_policy`
+    SurplusPolicy,
+}
+
+/// A config value as recorded in the history
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum ConfigValue {
+    /// An address setting
+    Pubkey(Pubkey),
+    /// A numeric setting
+    U64(u64),
+    /// A switch
+    Bool(bool),
+}
+
+/// Marks a program as approved by the admin for use as a deposit hook
+#[account]
+#[derive(InitSpace)]
+pub struct ApprovedHook {
+    /// The approved hook program
+    pub program_id: Pubkey,
+
+    /// The bump seed for the approval PDA
+    pub bump: u8,
+}
+
+/// Marks a program as approved by the admin for swaps in `deposit_and_swap`
+#[account]
+#[derive(InitSpace)]
+pub struct ApprovedSwap {
+    /// The approved swap program
+    pub program_id: Pubkey,
+
+    /// The bump seed for the approval PDA
+    pub bump: u8,
+}
+
+/// Marks a mint as approved by the admin for new vaults while the mint allowlist is enabled
+#[account]
+#[derive(InitSpace)]
+pub struct ApprovedMint {
+    /// The approved mint
+    pub mint: Pubkey,
+
+    /// The bump seed for the approval PDA
+    pub bump: u8,
+}
+
+/// The Pyth feed the admin set as the price source of a mint. Price updates of any other
+/// feed are refused when valuing vaults of the mint.
+#[account]
+#[derive(InitSpace)]
+pub struct PriceFeed {
+    /// The priced mint
+    pub mint: Pubkey,
+
+    /// The id of the Pyth feed publishing the mint's USD price
+    pub feed_id: [u8; 32],
+
+    /// The bump seed for the feed PDA
+    pub bump: u8,
+}
+
+/// Marks an address as blocked by the admin from depositing into or withdrawing from vaults
+#[account]
+#[derive(InitSpace)]
+pub struct BlockedAddress {
+    /// The blocked address
+    pub address: Pubkey,
+
+    /// The bump seed for the blocklist entry PDA
+    pub bump: u8,
+}
+
+/// Arguments passed to a deposit hook, borsh-encoded after the hook discriminator.
+/// The hook also receives the vault state and the depositor as its first two accounts
+/// (neither as a signer), followed by any remaining accounts of the deposit.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DepositHookArgs {
+    /// The vault state account
+    pub state: Pubkey,
+    /// The depositing user
+    pub depositor: Pubkey,
+    /// The deposited mint
+    pub mint: Pubkey,
+    /// The amount deposited
+    pub amount: u64,
+}
+
+/// Ring buffer of the latest activity on a vault, for integrations that poll instead of
+/// subscribing to logs. Records carry increasing ids, so a poller only needs to remember
+/// the last id it has seen.
+#[account]
+#[derive(InitSpace)]
+pub struct Outbox {
+    /// The vault state account this outbox belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the outbox PDA
+    pub bump: u8,
+
+    /// Id of the most recent record (0 if nothing has been recorded yet)
+    pub last_id: u64,
+
+    /// The latest `OUTBOX_CAPACITY` records, the record with id `n` lives at
+    /// index `n % OUTBOX_CAPACITY`
+    pub records: [ActivityRecord; OUTBOX_CAPACITY],
+}
+
+impl Outbox {
+    /// Sets up the outbox of `state` and records the vault's creation
+    pub fn initialize(&mut self, state: Pubkey, bump: u8, target: u64) -> Result<()> {
+        self.state = state;
+        self.bump = bump;
+        self.record(ActivityKind::Initialized, target)
+    }
+
+    /// Appends a record, overwriting the oldest one once the outbox is full
+    pub fn record(&mut self, kind: ActivityKind, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let id = self.last_id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+
+        self.records[(id % OUTBOX_CAPACITY as u64) as usize] = ActivityRecord {
+            id,
+            kind,
+            amount,
+            timestamp: clock.unix_timestamp,
+            slot: clock.slot,
+        };
+        self.last_id = id;
+
+        Ok(())
+    }
+}
+
+/// Reward points earned by a vault: one point per whole token saved per second, plus a
+/// bonus for every `STREAK_MILESTONE_DAYS` days in a row with a deposit
+#[account]
+#[derive(InitSpace)]
+pub struct RewardState {
+    /// The vault state account these points belong to
+    pub state: Pubkey,
+
+    /// The bump seed for the rewards PDA
+    pub bump: u8,
+
+    /// Unix timestamp up to which points have been accrued
+    pub last_update: i64,
+
+    /// Points earned and not yet claimed
+    pub points: u128,
+
+    /// Points claimed so far
+    pub claimed_points: u128,
+
+    /// Number of consecutive days with at least one deposit
+    pub streak_days: u16,
+
+    /// Day (Unix timestamp / 86400) of the latest deposit
+    pub last_deposit_day: i64,
+
+    /// Milestone badges the vault has earned, as a mask of `Badge::bit`
+    pub badges_earned: u8,
+
+    /// Milestone badges the owner has claimed, as a mask of `Badge::bit`
+    pub badges_claimed: u8,
+
+    /// Slot of the latest deposit
+    pub last_deposit_slot: u64,
+
+    /// Slot of the latest withdrawal
+    pub last_withdraw_slot: u64,
+
+    /// Zeroed space for future fields (see `REWARDS_RESERVED_BYTES`)
+    pub reserved: [u8; REWARDS_RESERVED_BYTES],
+}
+
+const _: () = assert!(8 + RewardState::INIT_SPACE == REWARDS_ACCOUNT_SIZE);
+
+impl RewardState {
+    /// Sets up the rewards of `state`, accruing from `now`
+    pub fn initialize(&mut self, state: Pubkey, bump: u8, now: i64) {
+        self.state = state;
+        self.bump = bump;
+        self.last_update = now;
+    }
+
+    /// Adds the points earned by holding `balance` since the last update
+    pub fn accrue(&mut self, now: i64, balance: u64, decimals: u8) {
+        let earned = token_seconds(balance, now.saturating_sub(self.last_update), decimals);
+        self.points = self.points.saturating_add(earned);
+        self.last_update = self.last_update.max(now);
+    }
+
+    /// Records a deposit in `slot`. With the same-slot `guard` on, fails if the vault already
+    /// paid out a withdrawal in that slot.
+    pub fn record_deposit_slot(&mut self, slot: u64, guard: bool) -> Result<()> {
+        require!(
+            !guard || self.last_withdraw_slot != slot,
+            ErrorCode::SameSlotActivity
+        );
+        self.last_deposit_slot = slot;
+        Ok(())
+    }
+
+    /// Records a withdrawal in `slot`. With the same-slot `guard` on, fails if the vault already
+    /// took a deposit in that slot.
+    pub fn record_withdraw_slot(&mut self, slot: u64, guard: bool) -> Result<()> {
+        require!(
+            !guard || self.last_deposit_slot != slot,
+            ErrorCode::SameSlotActivity
+        );
+        self.last_withdraw_slot = slot;
+        Ok(())
+    }
+
+    /// Extends (or restarts) the deposit streak and awards the milestone bonus and badges
+    pub fn record_deposit(&mut self, now: i64) {
+        self.earn(Badge::FirstDeposit);
+
+        let day = now.div_euclid(24 * 60 * 60);
+        if self.streak_days > 0 && day == self.last_deposit_day {
+            return;
+        }
+
+        let next_day = self.last_deposit_day.checked_add(1) == Some(day);
+        self.streak_days = if self.streak_days > 0 && next_day {
+            self.streak_days.saturating_add(1)
+        } else {
+            1
+        };
+        self.last_deposit_day = day;
+
+        if self.streak_days.checked_rem(STREAK_MILESTONE_DAYS) == Some(0) {
+            self.points = self.points.saturating_add(STREAK_MILESTONE_POINTS as u128);
+        }
+        if self.streak_days >= BADGE_STREAK_DAYS {
+            self.earn(Badge::Streak);
+        }
+    }
+
+    /// Records that the vault earned `badge`, which the owner can then claim
+    pub fn earn(&mut self, badge: Badge) {
+        self.badges_earned |= badge.bit();
+    }
+
+    /// Returns true if the vault has earned `badge`
+    pub fn has_earned(&self, badge: Badge) -> bool {
+        self.badges_earned & badge.bit() != 0
+    }
+
+    /// Returns true if the owner has claimed `badge`
+    pub fn has_claimed(&self, badge: Badge) -> bool {
+        self.badges_claimed & badge.bit() != 0
+    }
+
+    /// Returns the streak still running at `now`: a day without a deposit ends it
+    pub fn current_streak(&self, now: i64) -> u16 {
+        let day = now.div_euclid(24 * 60 * 60);
+        if self.last_deposit_day.saturating_add(1) >= day {
+            self.streak_days
+        } else {
+            0
+        }
+    }
+}
+
+/// Deposit fee discounts for holders of a membership (e.g. governance) token, held in a
+/// PDA (`["fee_tiers"]`) next to the config
+#[account]
+#[derive(InitSpace)]
+pub struct FeeTiers {
+    /// The bump seed for the fee tiers PDA
+    pub bump: u8,
+
+    /// The mint whose balance decides the discount
+    pub membership_mint: Pubkey,
+
+    /// The tiers, by increasing minimum balance and discount
+    #[max_len(MAX_FEE_TIERS)]
+    pub tiers: Vec<FeeTier>,
+}
+
+/// The credential (e.g. a non-transferable KYC token) vault owners must hold, held in a PDA
+/// (`["credential_gate"]`) next to the config
+#[account]
+#[derive(InitSpace)]
+pub struct CredentialGate {
+    /// The bump seed for the credential gate PDA
+    pub bump: u8,
+
+    /// The mint of the credential token
+    pub credential_mint: Pubkey,
+}
+
+/// The membership token vault creators must hold, and how much of it, held in a PDA
+/// (`["creation_gate"]`) next to the config
+#[account]
+#[derive(InitSpace)]
+pub struct CreationGate {
+    /// The bump seed for the creation gate PDA
+    pub bump: u8,
+
+    /// The mint of the membership token
+    pub membership_mint: Pubkey,
+
+    /// The membership balance a creator needs, in base units
+    pub min_balance: u64,
+}
+
+impl FeeTiers {
+    /// Returns the discount of the highest tier a membership `balance` reaches (0 if none)
+    pub fn discount_bps(&self, balance: u64) -> u16 {
+        self.tiers
+            .iter()
+            .filter(|tier| balance >= tier.min_balance)
+            .map(|tier| tier.discount_bps)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// A deposit fee discount for holding at least `min_balance` of the membership mint
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct FeeTier {
+    /// Membership tokens needed for the tier (in the mint's smallest unit)
+    pub min_balance: u64,
+    /// Share of the fee waived, in basis points (`BPS_DENOMINATOR` waives it entirely)
+    pub discount_bps: u16,
+}
+
+/// What one depositor has gifted to a vault, held in a PDA (`["contribution", state,
+/// depositor]`) so group payouts and thank-you screens can attribute the balance
+#[account]
+#[derive(InitSpace)]
+pub struct Contribution {
+    /// The vault state account contributed to
+    pub state: Pubkey,
+
+    /// The depositor who made the contributions
+    pub contributor: Pubkey,
+
+    /// The bump seed for the contribution PDA
+    pub bump: u8,
+
+    /// Total amount ever credited to the vault from this depositor, fees excluded
+    pub total_contributed: u64,
+
+    /// Number of gifts made
+    pub deposit_count: u64,
+
+    /// When the last gift was made (Unix timestamp in seconds)
+    pub last_deposit_ts: i64,
+}
+
+impl Contribution {
+    /// Counts a gift of `amount`, setting the record up on the depositor's first one
+    pub fn record(&mut self, state: Pubkey, contributor: Pubkey, bump: u8, amount: u64, now: i64) {
+        self.state = state;
+        self.contributor = contributor;
+        self.bump = bump;
+        self.total_contributed = self.total_contributed.saturating_add(amount);
+        self.deposit_count = self.deposit_count.saturating_add(1);
+        self.last_deposit_ts = now;
+    }
+}
+
+/// Aggregate stats of every vault of one mint, held in a PDA (`["stats", mint]`) so analytics
+/// and fee logic can read the total value locked without indexing every vault
+#[account]
+#[derive(InitSpace)]
+pub struct MintStats {
+    /// The mint these stats are for
+    pub mint: Pubkey,
+
+    /// The bump seed for the stats PDA
+    pub bump: u8,
+
+    /// Number of vaults ever created for the mint
+    pub total_vaults: u64,
+
+    /// Total amount ever deposited into vaults of the mint
+    pub total_deposited: u64,
+
+    /// Total amount ever withdrawn from vaults of the mint, including recoveries
+    pub total_withdrawn: u64,
+
+    /// Total amount ever released from vaults of the mint, by target, crank or deadline
+    pub total_released: u64,
+}
+
+impl MintStats {
+    /// Counts a new vault, setting the stats up if it's the mint's first
+    pub fn register_vault(&mut self, mint: Pubkey, bump: u8) {
+        self.mint = mint;
+        self.bump = bump;
+        self.total_vaults = self.total_vaults.saturating_add(1);
+    }
+
+    /// Counts tokens deposited into a vault of the mint
+    pub fn record_deposit(&mut self, amount: u64) {
+        self.total_deposited = self.total_deposited.saturating_add(amount);
+    }
+
+    /// Counts tokens withdrawn from a vault of the mint
+    pub fn record_withdrawal(&mut self, amount: u64) {
+        self.total_withdrawn = self.total_withdrawn.saturating_add(amount);
+    }
+
+    /// Counts tokens released from a vault of the mint
+    pub fn record_release(&mut self, amount: u64) {
+        self.total_released = self.total_released.saturating_add(amount);
+    }
+
+    /// Total value locked: tokens deposited and not yet withdrawn or released
+    pub fn total_value_locked(&self) -> u64 {
+        self.total_deposited
+            .saturating_sub(self.total_withdrawn)
+            .saturating_sub(self.total_released)
+    }
+}
+
+/// Size of the vault extension account, including its discriminator
+#[constant]
+pub const VAULT_EXTENSION_SIZE: usize = 8 + std::mem::size_of::<VaultExtension>();
+
+/// Extended vault data that doesn't fit the `Vault` account, held in a child PDA
+/// (`["extension", state]`). It is zero-copy, so its fixed-size arrays are read in place
+/// rather than deserialized, and can grow well past what fits on the stack.
+#[account(zero_copy)]
+pub struct VaultExtension {
+    /// The vault state account this extension belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the extension PDA
+    pub bump: u8,
+
+    /// Layout version, bumped whenever fields are carved out of `reserved`
+    pub version: u8,
+
+    /// Keeps the following fields 8-byte aligned
+    pub padding: [u8; 6],
+
+    /// Number of balance snapshots ever recorded. Snapshot `n` is stored at index
+    /// `n % EXTENSION_HISTORY_LEN`
+    pub snapshot_count: u64,
+
+    /// The latest balance snapshots, as a ring buffer
+    pub snapshots: [BalanceSnapshot; EXTENSION_HISTORY_LEN],
+
+    /// SHA-256 of the memo attached to the latest deposit or withdrawal with a memo
+    /// (zeroed until one is attached)
+    pub last_memo_hash: [u8; 32],
+
+    /// Zeroed space for future fields (see `EXTENSION_RESERVED_BYTES`)
+    pub reserved: [u8; EXTENSION_RESERVED_BYTES],
+}
+
+impl VaultExtension {
+    /// The current layout version
+    pub const VERSION: u8 = 2;
+
+    /// Returns the most recent snapshot, if any was recorded
+    pub fn latest_snapshot(&self) -> Option<BalanceSnapshot> {
+        let count = self.snapshot_count as usize;
+        (count > 0).then(|| self.snapshots[(count - 1) % EXTENSION_HISTORY_LEN])
+    }
+
+    /// Appends a snapshot, at most one per `SNAPSHOT_INTERVAL_SECONDS`
+    pub fn record_snapshot(&mut self, snapshot: BalanceSnapshot) -> Result<()> {
+        if let Some(latest) = self.latest_snapshot() {
+            require!(
+                snapshot.timestamp.saturating_sub(latest.timestamp) >= SNAPSHOT_INTERVAL_SECONDS,
+                ErrorCode::SnapshotTooSoon
+            );
+        }
+
+        self.snapshots[self.snapshot_count as usize % EXTENSION_HISTORY_LEN] = snapshot;
+        self.snapshot_count = self
+            .snapshot_count
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Remembers the hash of the latest memo. Version 1 extensions are upgraded in place,
+    /// since the memo hash was carved out of their zeroed reserved space.
+    pub fn record_memo(&mut self, memo_hash: [u8; 32]) {
+        self.last_memo_hash = memo_hash;
+        self.version = Self::VERSION;
+    }
+}
+
+/// The vault balance at a point in time
+#[zero_copy]
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct BalanceSnapshot {
+    /// When the snapshot was taken (Unix timestamp in seconds)
+    pub timestamp: i64,
+    /// The vault token account balance at that time
+    pub balance: u64,
+}
+
+/// A hot key or dApp session key allowed to act on the owner's vault until `expiry`
+#[account]
+#[derive(InitSpace)]
+pub struct SessionKey {
+    /// The vault state account this session belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the session PDA
+    pub bump: u8,
+
+    /// The session key
+    pub delegate: Pubkey,
+
+    /// What the session key may do
+    pub permissions: DelegatePermissions,
+
+    /// When the session ends (Unix timestamp in seconds)
+    pub expiry: i64,
+
+    /// Most the session key may withdraw over the whole session
+    pub max_amount: u64,
+
+    /// Amount the session key has withdrawn so far
+    pub withdrawn: u64,
+}
+
+impl SessionKey {
+    /// Checks that the session is still live and allows the action
+    pub fn authorize(&self, now: i64, permitted: bool) -> Result<()> {
+        require!(now < self.expiry, ErrorCode::DelegateExpired);
+        require!(permitted, ErrorCode::DelegateNotPermitted);
+
+        Ok(())
+    }
+}
+
+/// What a session key may do on the owner's behalf
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct DelegatePermissions {
+    /// Deposit the session key's own tokens with `session_deposit`
+    pub deposit: bool,
+    /// Withdraw to the owner's token account with `session_withdraw`, up to the session cap
+    pub withdraw: bool,
+}
+
+/// A merchant allowed to pull a fixed amount from the owner's vault once per interval
+#[account]
+#[derive(InitSpace)]
+pub struct SubscriptionGrant {
+    /// The vault state account this subscription draws on
+    pub state: Pubkey,
+
+    /// The bump seed for the subscription PDA
+    pub bump: u8,
+
+    /// The merchant allowed to pull
+    pub merchant: Pubkey,
+
+    /// What the merchant pulls each interval
+    pub amount: u64,
+
+    /// Shortest time between two pulls (in seconds)
+    pub interval_seconds: i64,
+
+    /// When the merchant last pulled, if ever (Unix timestamp in seconds)
+    pub last_pull_ts: Option<i64>,
+}
+
+impl SubscriptionGrant {
+    /// Returns true if the merchant has never pulled, or a whole interval has passed since
+    pub fn is_due(&self, now: i64) -> bool {
+        self.last_pull_ts.is_none_or(|last_pull_ts| {
+            now >= last_pull_ts.saturating_add(self.interval_seconds)
+        })
+    }
+}
+
+/// The auto-save service allowed to pull deposits into the owner's vault with
+/// `deposit_via_delegate`, up to `max_amount` at a time and once per interval
+#[account]
+#[derive(InitSpace)]
+pub struct AutoSaveGrant {
+    /// The vault state account the service deposits into
+    pub state: Pubkey,
+
+    /// The bump seed for the auto-save PDA
+    pub bump: u8,
+
+    /// The service allowed to trigger pulls
+    pub service: Pubkey,
+
+    /// The most a single pull may take from the owner, fee included
+    pub max_amount: u64,
+
+    /// Shortest time between two pulls (in seconds)
+    pub interval_seconds: i64,
+
+    /// When the service last pulled, if ever (Unix timestamp in seconds)
+    pub last_pull_ts: Option<i64>,
+}
+
+impl AutoSaveGrant {
+    /// Returns true if the service has never pulled, or a whole interval has passed since
+    pub fn is_due(&self, now: i64) -> bool {
+        self.last_pull_ts.is_none_or(|last_pull_ts| {
+            now >= last_pull_ts.saturating_add(self.interval_seconds)
+        })
+    }
+}
+
+/// Makes the owner's withdrawals go through `request_withdrawal` and `execute_withdrawal`,
+/// at least `delay_seconds` apart and approved by `threshold` of the `approvers`, so a stolen
+/// owner key can't drain the vault unnoticed
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawalPolicy {
+    /// The vault state account this policy belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the withdrawal policy PDA
+    pub bump: u8,
+
+    /// Time between requesting and executing a withdrawal (in seconds, 0 for none)
+    pub delay_seconds: i64,
+
+    /// A shorter delay waiting to replace `delay_seconds`
+    pub pending_delay_seconds: i64,
+
+    /// When `pending_delay_seconds` takes effect (0 if nothing is pending)
+    pub pending_delay_at: i64,
+
+    /// Keys allowed to approve the owner's withdrawal requests
+    #[max_len(MAX_WITHDRAWAL_APPROVERS)]
+    pub approvers: Vec<Pubkey>,
+
+    /// Approvals a request needs before it can be executed (0 for none)
+    pub threshold: u8,
+}
+
+impl WithdrawalPolicy {
+    /// Returns how many of `approvals` come from the current approvers
+    pub fn count_approvals(&self, approvals: &[Pubkey]) -> usize {
+        approvals
+            .iter()
+            .filter(|approval| self.approvers.contains(approval))
+            .count()
+    }
+
+    /// Returns the delay in force at `now`
+    pub fn delay_at(&self, now: i64) -> i64 {
+        if self.pending_delay_at != 0 && now >= self.pending_delay_at {
+            self.pending_delay_seconds
+        } else {
+            self.delay_seconds
+        }
+    }
+
+    /// Sets a new delay and returns when it takes effect. A longer delay applies right away,
+    /// a shorter one only once the current delay has passed, so it can't be used to skip it.
+    pub fn set_delay(&mut self, now: i64, delay_seconds: i64) -> i64 {
+        let current = self.delay_at(now);
+        if delay_seconds >= current {
+            self.delay_seconds = delay_seconds;
+            self.pending_delay_seconds = 0;
+            self.pending_delay_at = 0;
+            return now;
+        }
+
+        self.delay_seconds = current;
+        self.pending_delay_seconds = delay_seconds;
+        self.pending_delay_at = now.saturating_add(current);
+        self.pending_delay_at
+    }
+}
+
+/// A linear unlock of a vault's balance, held in a PDA (`["unlock_schedule", state]`): none
+/// of `amount` can be withdrawn before `cliff_ts`, then what has accrued since `start_ts`
+/// becomes withdrawable, and the rest second by second until all of it is at `end_ts`
+#[account]
+#[derive(InitSpace)]
+pub struct UnlockSchedule {
+    /// The vault state account this schedule belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the schedule PDA
+    pub bump: u8,
+
+    /// The amount being unlocked: the vault's balance when the stream was set
+    pub amount: u64,
+
+    /// When the amount starts accruing (Unix timestamp in seconds)
+    pub start_ts: i64,
+
+    /// Nothing is withdrawable before this time (Unix timestamp in seconds, `start_ts` when
+    /// the stream has no cliff)
+    pub cliff_ts: i64,
+
+    /// When the whole amount is unlocked (Unix timestamp in seconds)
+    pub end_ts: i64,
+}
+
+impl UnlockSchedule {
+    /// Returns how much of the streamed amount is still held back at `now`
+    pub fn locked_at(&self, now: i64) -> u64 {
+        streamed_locked_amount(self.amount, self.start_ts, self.cliff_ts, self.end_ts, now)
+    }
+}
+
+/// A freeze the owner put on their own withdrawals, to protect their savings from impulsive
+/// spending. Nobody can lift it early, the owner included.
+#[account]
+#[derive(InitSpace)]
+pub struct SelfExclusion {
+    /// The vault state account this self-exclusion belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the self-exclusion PDA
+    pub bump: u8,
+
+    /// Withdrawals are refused until this time (Unix timestamp in seconds)
+    pub frozen_until: i64,
+}
+
+impl SelfExclusion {
+    /// Freezes withdrawals until `until`, unless a running freeze already lasts longer.
+    /// Returns the resulting end of the freeze.
+    pub fn extend(&mut self, until: i64) -> i64 {
+        self.frozen_until = self.frozen_until.max(until);
+        self.frozen_until
+    }
+}
+
+/// The roles of a custodial vault, held in a PDA (`["custody", state]`). The custodian
+/// created and funds the vault, and the owner is the beneficiary, who can't take anything
+/// out before `release_ts`
+#[account]
+#[derive(InitSpace)]
+pub struct Custody {
+    /// The vault state account held in custody
+    pub state: Pubkey,
+
+    /// The bump seed for the custody PDA
+    pub bump: u8,
+
+    /// The custodian who created the vault
+    pub custodian: Pubkey,
+
+    /// When the beneficiary gets access to the savings (Unix timestamp in seconds)
+    pub release_ts: i64,
+}
+
+/// What happened to a vault when a deposit reached its target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetOutcome {
+    /// The balance was sent back to the owner (just the target under `SurplusPolicy::Keep`)
+    Released(u64),
+    /// The vault kept its balance and moved on to a new target (see `Rollover`)
+    RolledOver {
+        /// The target that was reached
+        previous_target: u64,
+        /// The vault's new target
+        new_target: u64,
+    },
+}
+
+/// Makes a vault that reaches its target keep its balance and move on to a new goal,
+/// `increase_bps` above the one it reached, instead of releasing
+#[account]
+#[derive(InitSpace)]
+pub struct Rollover {
+    /// The vault state account this setting belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the rollover PDA
+    pub bump: u8,
+
+    /// How much higher each new target is than the one reached (in basis points)
+    pub increase_bps: u16,
+}
+
+/// An advance the owner took against their locked balance, held in a PDA
+/// (`["advance", state]`). Its debt stays in the vault until `repay` returns it.
+#[account]
+#[derive(InitSpace)]
+pub struct Advance {
+    /// The vault state account the advance was paid from
+    pub state: Pubkey,
+
+    /// The bump seed for the advance PDA
+    pub bump: u8,
+
+    /// The part of the advances not yet repaid
+    pub principal: u64,
+
+    /// The fees charged on the advances and not yet repaid
+    pub fee: u64,
+}
+
+impl Advance {
+    /// Everything the owner still owes the vault
+    pub fn debt(&self) -> u64 {
+        self.principal.saturating_add(self.fee)
+    }
+}
+
+/// A reusable goal curated by the admin, e.g. "Emergency fund: 3 months, locked", held in a
+/// PDA (`["template", template_id]`). Vaults are created from it with `initialize_from_template`.
+#[account]
+#[derive(InitSpace)]
+pub struct GoalTemplate {
+    /// The template id, part of the PDA seeds
+    pub template_id: u16,
+
+    /// The bump seed for the template PDA
+    pub bump: u8,
+
+    /// What vaults created from the template start with
+    pub terms: GoalTemplateTerms,
+}
+
+/// The terms of a goal template
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct GoalTemplateTerms {
+    /// A short name for apps to show (1 to `MAX_TEMPLATE_NAME_LEN` bytes)
+    #[max_len(MAX_TEMPLATE_NAME_LEN)]
+    pub name: String,
+
+    /// The mint of vaults created from the template
+    pub mint: Pubkey,
+
+    /// The savings target (in the mint's smallest unit)
+    pub target: u64,
+
+    /// How long withdrawals are refused after the vault is created (in seconds, 0 for none)
+    pub lock_seconds: i64,
+
+    /// The vault's withdrawal cooldown (in seconds, 0 for none)
+    pub cooldown_seconds: i64,
+
+    /// Most the vault pays out over any day (0 for no limit)
+    pub max_withdraw_per_day: u64,
+
+    /// The deposit fee charged instead of the config's (in basis points), if overridden
+    pub deposit_fee_bps: Option<u16>,
+}
+
+/// The template terms a vault keeps after its creation, held in a PDA
+/// (`["vault_template", state]`). Later changes to the template don't affect the vault.
+#[account]
+#[derive(InitSpace)]
+pub struct VaultTemplate {
+    /// The vault state account created from the template
+    pub state: Pubkey,
+
+    /// The bump seed for the vault template PDA
+    pub bump: u8,
+
+    /// The template the vault was created from
+    pub template_id: u16,
+
+    /// The deposit fee charged instead of the config's (in basis points), if overridden
+    pub deposit_fee_bps: Option<u16>,
+}
+
+/// Keys the owner trusts to recover the vault if they lose their own
+#[account]
+#[derive(InitSpace)]
+pub struct Guardians {
+    /// The vault state account these guardians belong to
+    pub state: Pubkey,
+
+    /// The bump seed for the guardians PDA
+    pub bump: u8,
+
+    /// The guardian keys
+    #[max_len(MAX_GUARDIANS)]
+    pub guardians: Vec<Pubkey>,
+
+    /// Votes a recovery proposal needs before it can be executed
+    pub threshold: u8,
+}
+
+impl Guardians {
+    /// Returns how many of `votes` come from the current guardians
+    pub fn count_votes(&self, votes: &[Pubkey]) -> usize {
+        votes
+            .iter()
+            .filter(|vote| self.guardians.contains(vote))
+            .count()
+    }
+}
+
+/// A guardian proposal to recover the vault to a new wallet, executable after
+/// `RECOVERY_DELAY_SECONDS` unless the owner vetoes it
+#[account]
+#[derive(InitSpace)]
+pub struct RecoveryProposal {
+    /// The vault state account this proposal belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the proposal PDA
+    pub bump: u8,
+
+    /// The guardian who proposed the recovery (and paid its rent)
+    pub proposer: Pubkey,
+
+    /// The wallet the vault's balance is recovered to
+    pub new_owner: Pubkey,
+
+    /// When the recovery can be executed (Unix timestamp in seconds)
+    pub executable_at: i64,
+
+    /// The guardians who voted for the recovery so far
+    #[max_len(MAX_GUARDIANS)]
+    pub votes: Vec<Pubkey>,
+}
+
+/// A rotating savings circle (ROSCA): each round, every member pays `contribution` and the
+/// pot goes to the next member in `members` order
+#[account]
+#[derive(InitSpace)]
+pub struct Circle {
+    /// The member who organized the circle
+    pub creator: Pubkey,
+
+    /// Tells apart the circles of one creator
+    pub circle_id: u64,
+
+    /// The bump seed for the circle PDA
+    pub bump: u8,
+
+    /// The token mint the members contribute in
+    pub mint: Pubkey,
+
+    /// What every member pays into each round
+    pub contribution: u64,
+
+    /// How long each round collects contributions for (in seconds)
+    pub period_seconds: i64,
+
+    /// When the first round started (Unix timestamp in seconds)
+    pub start_ts: i64,
+
+    /// The round being collected, which is also its recipient's index in `members`. Equal to
+    /// the number of members once every round has been paid out
+    pub round: u8,
+
+    /// The members, in payout order
+    #[max_len(MAX_CIRCLE_MEMBERS)]
+    pub members: Vec<CircleMember>,
+}
+
+impl Circle {
+    /// Returns when `round` stops collecting, or `None` if that overflows
+    pub fn round_end(&self, round: u8) -> Option<i64> {
+        self.period_seconds
+            .checked_mul(round as i64 + 1)
+            .and_then(|elapsed| self.start_ts.checked_add(elapsed))
+    }
+
+    /// Returns true once every member has had their round
+    pub fn is_complete(&self) -> bool {
+        self.round as usize >= self.members.len()
+    }
+}
+
+/// A circle member's record
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct CircleMember {
+    /// The member's wallet
+    pub member: Pubkey,
+
+    /// The last round the member paid into, if any
+    pub last_paid_round: Option<u8>,
+
+    /// Rounds closed without the member's contribution
+    pub missed_rounds: u8,
+
+    /// Contributions returned to the member from rounds whose recipient had defaulted
+    pub refundable: u64,
+}
+
+/// An employer's streaming payroll for one employee, held in a PDA
+/// (`["payroll", employer, employee]`). Pay accrues per completed period and is paid out of
+/// the tokens the employer funded the payroll's token account with.
+#[account]
+#[derive(InitSpace)]
+pub struct Payroll {
+    /// The employer funding the payroll
+    pub employer: Pubkey,
+
+    /// The employee being paid
+    pub employee: Pubkey,
+
+    /// The bump seed for the payroll PDA
+    pub bump: u8,
+
+    /// The token mint the employee is paid in
+    pub mint: Pubkey,
+
+    /// What the employee earns per completed pay period
+    pub amount_per_period: u64,
+
+    /// How long each pay period lasts (in seconds)
+    pub period_seconds: i64,
+
+    /// When the first pay period started (Unix timestamp in seconds)
+    pub start_ts: i64,
+
+    /// When the employer stopped the accrual, if they did
+    pub stopped_at: Option<i64>,
+
+    /// Total pay the employee has claimed
+    pub claimed: u64,
+}
+
+impl Payroll {
+    /// Returns the pay accrued by `now`: one `amount_per_period` per pay period completed
+    /// before `now` or the stop, whichever is earlier
+    pub fn accrued(&self, now: i64) -> u64 {
+        let end = self.stopped_at.map_or(now, |stopped_at| stopped_at.min(now));
+        let periods = end.saturating_sub(self.start_ts).max(0) / self.period_seconds;
+        self.amount_per_period.saturating_mul(periods as u64)
+    }
+
+    /// Returns the accrued pay the employee hasn't claimed yet
+    pub fn owed(&self, now: i64) -> u64 {
+        self.accrued(now).saturating_sub(self.claimed)
+    }
+}
+
+/// Tokens locked up by a depositor for a counterparty, held in a PDA
+/// (`["escrow", depositor, escrow_id]`). They go to the counterparty once two of the parties
+/// approve, and back to the depositor after `timeout_ts` otherwise.
+#[account]
+#[derive(InitSpace)]
+pub struct Escrow {
+    /// The party who locked up the tokens
+    pub depositor: Pubkey,
+
+    /// Tells apart the escrows of one depositor
+    pub escrow_id: u64,
+
+    /// The bump seed for the escrow PDA
+    pub bump: u8,
+
+    /// The party the tokens are released to
+    pub counterparty: Pubkey,
+
+    /// The third party who can approve in place of the depositor or the counterparty, if any
+    pub arbiter: Option<Pubkey>,
+
+    /// The token mint of the escrowed tokens
+    pub mint: Pubkey,
+
+    /// The amount escrowed
+    pub amount: u64,
+
+    /// When the depositor can take the tokens back (Unix timestamp in seconds)
+    pub timeout_ts: i64,
+
+    /// Whether the depositor approved the release
+    pub depositor_approved: bool,
+
+    /// Whether the counterparty approved the release
+    pub counterparty_approved: bool,
+
+    /// Whether the arbiter approved the release
+    pub arbiter_approved: bool,
+}
+
+impl Escrow {
+    /// Returns true once two of the parties have approved the release
+    pub fn is_releasable(&self) -> bool {
+        [
+            self.depositor_approved,
+            self.counterparty_approved,
+            self.arbiter_approved,
+        ]
+        .into_iter()
+        .filter(|approved| *approved)
+        .count()
+            >= 2
+    }
+}
+
+/// A hashlock/timelock contract, held in a PDA (`["htlc", owner, htlc_id]`). The counterparty
+/// gets the tokens by revealing the SHA-256 preimage of `hashlock` before `timeout_ts`, and
+/// the owner gets them back afterwards otherwise.
+#[account]
+#[derive(InitSpace)]
+pub struct Htlc {
+    /// The party who locked up the tokens
+    pub owner: Pubkey,
+
+    /// Tells apart the contracts of one owner
+    pub htlc_id: u64,
+
+    /// The bump seed for the contract PDA
+    pub bump: u8,
+
+    /// The party who can claim the tokens with the preimage
+    pub counterparty: Pubkey,
+
+    /// The token mint of the locked tokens
+    pub mint: Pubkey,
+
+    /// The amount locked
+    pub amount: u64,
+
+    /// SHA-256 hash of the secret that unlocks the tokens
+    pub hashlock: [u8; 32],
+
+    /// When claims end and the owner can take the tokens back (Unix timestamp in seconds)
+    pub timeout_ts: i64,
+}
+
+/// The top vaults of one mint, held in a PDA (`["leaderboard", mint]`) so front-ends can show
+/// a ranking without trusting an indexer. Entries are only as fresh as their last update.
+#[account]
+#[derive(InitSpace)]
+pub struct Leaderboard {
+    /// The mint whose vaults are ranked
+    pub mint: Pubkey,
+
+    /// The bump seed for the leaderboard PDA
+    pub bump: u8,
+
+    /// The vaults holding the most tokens, highest first
+    #[max_len(LEADERBOARD_SIZE)]
+    pub top_balances: Vec<LeaderboardEntry>,
+
+    /// The vaults with the longest running deposit streaks, longest first
+    #[max_len(LEADERBOARD_SIZE)]
+    pub top_streaks: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    /// Moves `state` to where `value` ranks in `entries`, dropping it if it no longer makes the
+    /// top `LEADERBOARD_SIZE`. Returns its position (0 for first), if it made it
+    pub fn rank(entries: &mut Vec<LeaderboardEntry>, state: Pubkey, value: u64) -> Option<u8> {
+        entries.retain(|entry| entry.state != state);
+        if value == 0 {
+            return None;
+        }
+
+        // Ties keep their order, so an earlier entry isn't pushed out by an equal newcomer
+        let position = entries
+            .iter()
+            .take_while(|entry| entry.value >= value)
+            .count();
+        if position >= LEADERBOARD_SIZE {
+            return None;
+        }
+        entries.insert(position, LeaderboardEntry { state, value });
+        entries.truncate(LEADERBOARD_SIZE);
+
+        Some(position as u8)
+    }
+}
+
+/// A ranked vault and the value it was ranked by
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct LeaderboardEntry {
+    /// The vault state account
+    pub state: Pubkey,
+
+    /// The vault's balance or streak length as of its last update
+    pub value: u64,
+}
+
+/// A withdrawal requested by the owner, executable once its delay has passed
+#[account]
+#[derive(InitSpace)]
+pub struct PendingWithdrawal {
+    /// The vault state account this request belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the request PDA
+    pub bump: u8,
+
+    /// The amount to withdraw
+    pub amount: u64,
+
+    /// When the withdrawal was requested (Unix timestamp in seconds)
+    pub requested_at: i64,
+
+    /// When the withdrawal can be executed (Unix timestamp in seconds)
+    pub executable_at: i64,
+
+    /// The approvers who approved the withdrawal so far
+    #[max_len(MAX_WITHDRAWAL_APPROVERS)]
+    pub approvals: Vec<Pubkey>,
+}
+
+/// Releases a vault to its owner once an oracle price crosses a threshold, like a simple
+/// conditional order. Checked by anyone through `check_price_condition`.
+#[account]
+#[derive(InitSpace)]
+pub struct PriceCondition {
+    /// The vault state account this condition belongs to
+    pub state: Pubkey,
+
+    /// The bump seed for the price condition PDA
+    pub bump: u8,
+
+    /// The Pyth price update account (`PriceUpdateV2`) pricing the mint in USD
+    pub price_account: Pubkey,
+
+    /// The price of one whole token (in USD cents) that triggers the release
+    pub threshold_cents: u64,
+
+    /// Whether the price must rise to or fall to the threshold
+    pub direction: PriceDirection,
+}
+
+/// Which way a price must cross a price condition's threshold
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum PriceDirection {
+    /// Release once the price is at or above the threshold
+    AtOrAbove,
+    /// Release once the price is at or below the threshold
+    AtOrBelow,
+}
+
+/// Short timestamped notes the owner attaches to a vault, keeping the latest
+/// `NOTES_CAPACITY` of them
+#[account]
+#[derive(InitSpace)]
+pub struct Notes {
+    /// The vault state account these notes belong to
+    pub state: Pubkey,
+
+    /// The bump seed for the notes PDA
+    pub bump: u8,
+
+    /// Total number of notes ever written, note `n` (starting at 0) lives at
+    /// index `n % NOTES_CAPACITY`
+    pub count: u64,
+
+    /// The latest notes
+    pub notes: [Note; NOTES_CAPACITY],
+}
+
+impl Notes {
+    /// Appends a note, overwriting the oldest one once full
+    pub fn append(&mut self, note: Note) -> Result<()> {
+        self.notes[(self.count % NOTES_CAPACITY as u64) as usize] = note;
+        self.count = self.count.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Returns the stored notes, oldest first
+    pub fn ordered(&self) -> Vec<Note> {
+        let stored = self.count.min(NOTES_CAPACITY as u64);
+        (self.count - stored..self.count)
+            .map(|n| self.notes[(n % NOTES_CAPACITY as u64) as usize].clone())
+            .collect()
+    }
+}
+
+/// A single note on a vault
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug, PartialEq, Eq, InitSpace)]
+pub struct Note {
+    /// Unix timestamp of when the note was written
+    pub timestamp: i64,
+    /// The note text
+    #[max_len(MAX_NOTE_LENGTH)]
+    pub text: String,
+}
+
+/// A single entry in a vault's outbox
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct ActivityRecord {
+    /// Monotonically increasing record id (0 marks an unused slot)
+    pub id: u64,
+    /// What happened
+    pub kind: ActivityKind,
+    /// The amount of tokens involved, 0 for activity that doesn't move tokens
+    pub amount: u64,
+    /// Unix timestamp of the activity
+    pub timestamp: i64,
+    /// Slot of the activity
+    pub slot: u64,
+}
+
+/// The kinds of activity recorded in a vault's outbox
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub enum ActivityKind {
+    #[default]
+    Initialized,
+    Deposit,
+    Release,
+    Withdraw,
+    Lock,
+    Recovery,
+}
+
+/// A date a vault's target should be reached by, and what happens if it isn't
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct GoalDeadline {
+    /// Unix timestamp after which a missed target can be settled
+    pub deadline_ts: i64,
+    /// What `settle_deadline` does with the vault
+    pub consequence: DeadlineConsequence,
+}
+
+impl GoalDeadline {
+    /// Checks that the deadline is in the future and its consequence is well-formed
+    pub fn validate(&self, now: i64) -> Result<()> {
+        require!(self.deadline_ts > now, ErrorCode::InvalidDeadline);
+
+        match self.consequence {
+            DeadlineConsequence::ReturnFunds => {}
+            DeadlineConsequence::Donate { bps, .. } => {
+                require!(
+                    bps > 0 && bps as u64 <= BPS_DENOMINATOR,
+                    ErrorCode::InvalidDeadline
+                );
+            }
+            DeadlineConsequence::ExtendLock { seconds } => {
+                require!(
+                    (MIN_LOCK_DURATION_SECONDS..=MAX_LOCK_DURATION_SECONDS).contains(&seconds),
+                    ErrorCode::InvalidLockDuration
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A savings target in US dollars, valued with a Pyth price feed for the vault's mint
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct UsdTarget {
+    /// The target in USD cents
+    pub cents: u64,
+
+    /// The Pyth price update account (`PriceUpdateV2`) pricing the mint in USD
+    pub price_account: Pubkey,
+}
+
+/// An amount of a vault's balance that can't be withdrawn before `unlock_ts`
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug, PartialEq, Eq, InitSpace,
+)]
+pub struct LockTranche {
+    /// The locked amount (0 for an unused slot)
+    pub amount: u64,
+
+    /// The timestamp the tranche expires at (Unix timestamp in seconds)
+    pub unlock_ts: i64,
+}
+
+impl LockTranche {
+    /// Returns true if the tranche still holds tokens at `now`
+    pub fn is_active(&self, now: i64) -> bool {
+        self.amount > 0 && now < self.unlock_ts
+    }
+}
+
+/// What happens to a vault whose deadline passes before its target is reached
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum DeadlineConsequence {
+    /// Send the whole balance back to the owner
+    ReturnFunds,
+    /// Donate `bps` basis points of the balance to `recipient`, return the rest to the owner
+    Donate { bps: u16, recipient: Pubkey },
+    /// Lock the vault for another `seconds` from settlement
+    ExtendLock { seconds: i64 },
+}
+
+/// A milestone a vault can earn a soulbound badge for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum Badge {
+    /// The vault received its first deposit
+    FirstDeposit,
+    /// The vault was deposited into on `BADGE_STREAK_DAYS` days in a row
+    Streak,
+    /// The vault reached its savings target and was released
+    GoalReached,
+}
+
+impl Badge {
+    /// The badge's bit in `RewardState::badges_earned` and `badges_claimed`
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// How a vault handles a deposit that would take it over its balance cap
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub enum CapPolicy {
+    /// Reject the whole deposit
+    #[default]
+    Reject,
+    /// Deposit only what fits under the cap
+    Truncate,
+}
+
+/// What happens to the part of a vault balance above its target when a deposit reaches it
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub enum SurplusPolicy {
+    /// Release the whole balance, surplus included
+    #[default]
+    Release,
+    /// Release exactly the target and keep the surplus in the vault
+    Keep,
+    /// Take only what the target still needs from a deposit, the rest stays with the depositor
+    Refund,
+}
+
+/// Emitted when the admin creates or replaces a goal template
+#[event]
+pub struct GoalTemplateSet {
+    /// The template id
+    pub template_id: u16,
+    /// The template's terms
+    pub terms: GoalTemplateTerms,
+}
+
+/// Emitted when the admin removes a goal template
+#[event]
+pub struct GoalTemplateRemoved {
+    /// The template id
+    pub template_id: u16,
+}
+
+/// Emitted next to `VaultInitialized` when a vault is created from a goal template
+#[event]
+pub struct VaultCreatedFromTemplate {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The template the vault was created from
+    pub template_id: u16,
+    /// Withdrawals are refused until this time (Unix timestamp in seconds)
+    pub locked_until: i64,
+}
+
+/// Emitted when a new vault is created
+#[event]
+pub struct VaultInitialized {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The mint of the token saved in the vault
+    pub mint: Pubkey,
+    /// The vault's token account
+    pub vault_token_account: Pubkey,
+    /// The savings target (in token's smallest unit)
+    pub target: u64,
+}
+
+/// Emitted when tokens are deposited into a vault
+#[event]
+pub struct TokensDeposited {
+    /// The depositing user
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount deposited
+    pub amount: u64,
+}
+
+/// Emitted next to `TokensDeposited` when someone other than the owner funds a vault
+#[event]
+pub struct GiftDeposited {
+    /// The account that funded the deposit
+    pub depositor: Pubkey,
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount deposited
+    pub amount: u64,
+    /// Everything the depositor has gifted to the vault so far, this deposit included
+    pub total_contributed: u64,
+}
+
+/// Emitted when a deposit reaches the savings target and the vault is released
+#[event]
+pub struct SavingsTargetReached {
+    /// The vault owner receiving the released tokens
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The savings target that was reached
+    pub target: u64,
+    /// The amount sent back to the user
+    pub released: u64,
+}
+
+/// Emitted when a vault that rolls over reaches its target and moves on to the next goal
+#[event]
+pub struct GoalRolledOver {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The target that was reached, in the target's unit (tokens, or USD cents)
+    pub previous_target: u64,
+    /// The vault's new target, in the same unit
+    pub new_target: u64,
+}
+
+/// Emitted when tokens are withdrawn from a vault
+#[event]
+pub struct TokensWithdrawn {
+    /// The withdrawing user
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount withdrawn
+    pub amount: u64,
+}
+
+/// Emitted alongside `TokensWithdrawn` when `withdraw_to` pays out to another token account
+#[event]
+pub struct WithdrawalRedirected {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The token account that received the tokens
+    pub destination_token_account: Pubkey,
+    /// The amount withdrawn
+    pub amount: u64,
+}
+
+/// Emitted when the owner takes an advance against their locked balance
+#[event]
+pub struct AdvanceTaken {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount paid out
+    pub amount: u64,
+    /// The fee added to the debt
+    pub fee: u64,
+    /// The debt outstanding afterwards
+    pub debt: u64,
+}
+
+/// Emitted when the owner repays (part of) their advance
+#[event]
+pub struct AdvanceRepaid {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount moved back into the vault
+    pub amount: u64,
+    /// The debt still outstanding
+    pub debt: u64,
+}
+
+/// Emitted when a vault is locked
+#[event]
+pub struct VaultLocked {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount locked by the new tranche
+    pub amount: u64,
+    /// The timestamp until which the tranche can't be withdrawn
+    pub locked_until: i64,
+}
+
+/// Emitted when an empty vault is closed and its rent refunded
+#[event]
+pub struct VaultClosed {
+    /// The vault owner receiving the rent
+    pub user: Pubkey,
+    /// The closed vault state account
+    pub state: Pubkey,
+}
+
+/// Emitted when a vault whose goal was met is released and closed in one go by `finalize`
+#[event]
+pub struct VaultCompleted {
+    /// The vault owner receiving the tokens and the rent
+    pub user: Pubkey,
+    /// The closed vault state account
+    pub state: Pubkey,
+    /// Tokens released by `finalize` itself (0 if a release already emptied the vault)
+    pub released: u64,
+}
+
+/// Emitted when the owner adds a note to a vault
+#[event]
+pub struct NoteAdded {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Total number of notes written on the vault so far
+    pub count: u64,
+}
+
+/// Emitted when the program config is created
+#[event]
+pub struct ConfigInitialized {
+    /// The config admin
+    pub admin: Pubkey,
+}
+
+/// Emitted when the admin approves a deposit hook program
+#[event]
+pub struct DepositHookApproved {
+    /// The approved program
+    pub program_id: Pubkey,
+}
+
+/// Emitted when the admin revokes a deposit hook program
+#[event]
+pub struct DepositHookRevoked {
+    /// The revoked program
+    pub program_id: Pubkey,
+}
+
+/// Emitted when the admin approves a swap program
+#[event]
+pub struct SwapProgramApproved {
+    /// The approved program
+    pub program_id: Pubkey,
+}
+
+/// Emitted when the admin revokes a swap program
+#[event]
+pub struct SwapProgramRevoked {
+    /// The revoked program
+    pub program_id: Pubkey,
+}
+
+/// Emitted when the admin approves a mint for new vaults
+#[event]
+pub struct MintApproved {
+    /// The approved mint
+    pub mint: Pubkey,
+}
+
+/// Emitted when the admin sets the Pyth feed expected for a mint's price
+#[event]
+pub struct PriceFeedSet {
+    /// The priced mint
+    pub mint: Pubkey,
+    /// The id of the Pyth feed
+    pub feed_id: [u8; 32],
+}
+
+/// Emitted when the admin revokes a mint's approval
+#[event]
+pub struct MintRevoked {
+    /// The revoked mint
+    pub mint: Pubkey,
+}
+
+/// Emitted when the admin adds an address to the blocklist
+#[event]
+pub struct AddressBlocked {
+    /// The blocked address
+    pub address: Pubkey,
+}
+
+/// Emitted when the admin removes an address from the blocklist
+#[event]
+pub struct AddressUnblocked {
+    /// The unblocked address
+    pub address: Pubkey,
+}
+
+/// Emitted when the admin enables or disables the mint allowlist
+#[event]
+pub struct MintAllowlistSet {
+    /// Whether new vaults now need an approved mint
+    pub enabled: bool,
+}
+
+/// Emitted when the admin switches the rejection of mints with a freeze authority
+#[event]
+pub struct RejectFreezableMintsSet {
+    /// Whether new vaults are now refused for freezable mints
+    pub enabled: bool,
+}
+
+/// Emitted when the admin switches the guard against deposits and withdrawals in the same slot
+#[event]
+pub struct SameSlotGuardSet {
+    /// Whether deposits and withdrawals now have to be in different slots
+    pub enabled: bool,
+}
+
+/// Emitted when a version 1 vault is grown to the current layout
+#[event]
+pub struct VaultMigrated {
+    /// The vault owner
+    pub owner: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The layout version the vault is now on
+    pub version: u8,
+}
+
+/// Emitted when the admin changes what happens to the balance above a reached target
+#[event]
+pub struct SurplusPolicySet {
+    /// The new policy
+    pub policy: SurplusPolicy,
+}
+
+/// Emitted when the admin changes the longest lock `lock_tokens` accepts
+#[event]
+pub struct MaxLockDurationSet {
+    /// The new maximum lock duration, in seconds
+    pub seconds: i64,
+}
+
+/// Emitted when a vault is created for a mint with a freeze authority, which could freeze
+/// the vault token account and trap the savings
+#[event]
+pub struct FreezableMintAccepted {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The vault's mint
+    pub mint: Pubkey,
+    /// The mint's freeze authority
+    pub freeze_authority: Pubkey,
+}
+
+/// Emitted when a deposit is swapped into the vault's mint, next to its `TokensDeposited`
+#[event]
+pub struct TokensSwapped {
+    /// The depositor
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The mint the depositor paid in
+    pub input_mint: Pubkey,
+    /// The amount of the input mint spent by the swap
+    pub amount_in: u64,
+    /// The amount of the vault's mint deposited
+    pub amount_out: u64,
+}
+
+/// Emitted when the owner sets or replaces the vault's price condition
+#[event]
+pub struct PriceConditionSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The Pyth price update account
+    pub price_account: Pubkey,
+    /// The price of one whole token (in USD cents) that triggers the release
+    pub threshold_cents: u64,
+    /// Which way the price must cross the threshold
+    pub direction: PriceDirection,
+}
+
+/// Emitted when the owner sets or replaces the vault's rollover setting
+#[event]
+pub struct RolloverSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// How much higher each new target is than the one reached (in basis points)
+    pub increase_bps: u16,
+}
+
+/// Emitted when the owner clears the vault's rollover setting
+#[event]
+pub struct RolloverCleared {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+}
+
+/// Emitted when the owner clears the vault's price condition
+#[event]
+pub struct PriceConditionCleared {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+}
+
+/// Emitted when a price condition triggers and releases the vault
+#[event]
+pub struct PriceConditionTriggered {
+    /// The vault owner
+    pub owner: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Whoever checked the condition
+    pub caller: Pubkey,
+    /// The price of one whole token (in USD cents) that triggered the release
+    pub price_cents: u64,
+    /// The amount released to the owner
+    pub released: u64,
+}
+
+/// Emitted when the owner claims a milestone badge
+#[event]
+pub struct BadgeClaimed {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The milestone the badge is for
+    pub badge: Badge,
+    /// The badge's non-transferable mint
+    pub badge_mint: Pubkey,
+}
+
+/// Emitted when the owner claims a vault's reward points
+#[event]
+pub struct PointsClaimed {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The points claimed by this call
+    pub points: u128,
+    /// All points claimed on the vault so far
+    pub total_claimed: u128,
+}
+
+/// Emitted when the owner opts in or out of release on unlock
+#[event]
+pub struct ReleaseOnUnlockSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Whether the vault can be cranked once its lock expires
+    pub enabled: bool,
+}
+
+/// Emitted when the owner sets the vault's withdrawal cooldown
+#[event]
+pub struct WithdrawCooldownSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Time that must pass after a withdrawal before the next one (in seconds)
+    pub cooldown_seconds: i64,
+}
+
+/// Emitted when the owner sets the vault's daily withdrawal limit
+#[event]
+pub struct WithdrawLimitSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Most that can be withdrawn over any day (0 for no limit)
+    pub max_withdraw_per_day: u64,
+}
+
+/// Emitted when the owner sets the vault's withdrawal delay
+#[event]
+pub struct WithdrawalDelaySet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Time between requesting and executing a withdrawal (in seconds)
+    pub delay_seconds: i64,
+    /// When the new delay takes effect (later than now if it shortens the current one)
+    pub effective_at: i64,
+}
+
+/// Emitted when the owner sets the vault's withdrawal approvers
+#[event]
+pub struct WithdrawalApproversSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Keys allowed to approve withdrawal requests
+    pub approvers: Vec<Pubkey>,
+    /// Approvals a request needs before it can be executed
+    pub threshold: u8,
+}
+
+/// Emitted when an approver approves a pending withdrawal
+#[event]
+pub struct WithdrawalApproved {
+    /// The approver
+    pub approver: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount requested
+    pub amount: u64,
+    /// Approvals the request now has
+    pub approvals: u8,
+}
+
+/// Emitted when the admin sets the protocol deposit fee
+#[event]
+pub struct DepositFeeSet {
+    /// The fee, in basis points of every deposit
+    pub fee_bps: u16,
+    /// The wallet whose token accounts receive the fee
+    pub treasury: Pubkey,
+}
+
+/// Emitted when the admin sets the deposit fee discount tiers
+#[event]
+pub struct FeeTiersSet {
+    /// The mint whose balance decides the discount
+    pub membership_mint: Pubkey,
+    /// The tiers, by increasing minimum balance
+    pub tiers: Vec<FeeTier>,
+}
+
+/// Emitted when the admin requires vault owners to hold a credential token
+#[event]
+pub struct CredentialGateSet {
+    /// The mint of the credential token
+    pub credential_mint: Pubkey,
+}
+
+/// Emitted when the admin stops requiring a credential token
+#[event]
+pub struct CredentialGateRemoved {
+    /// The mint of the credential token that was required
+    pub credential_mint: Pubkey,
+}
+
+/// Emitted when the admin limits vault creation to holders of a membership token
+#[event]
+pub struct CreationGateSet {
+    /// The mint of the membership token
+    pub membership_mint: Pubkey,
+    /// The membership balance a creator needs, in base units
+    pub min_balance: u64,
+}
+
+/// Emitted when the admin opens vault creation to everyone again
+#[event]
+pub struct CreationGateRemoved {
+    /// The mint of the membership token that was required
+    pub membership_mint: Pubkey,
+}
+
+/// Emitted when a deposit pays the protocol fee
+#[event]
+pub struct FeeCharged {
+    /// The vault state account deposited into
+    pub state: Pubkey,
+    /// Whoever made the deposit
+    pub depositor: Pubkey,
+    /// The treasury token account the fee went to
+    pub treasury_token_account: Pubkey,
+    /// The fee, on top of the amount credited to the vault
+    pub fee: u64,
+}
+
+/// Emitted when a mint's stats account is created outside of vault creation
+#[event]
+pub struct MintStatsCreated {
+    /// The mint the stats are for
+    pub mint: Pubkey,
+    /// The stats account
+    pub mint_stats: Pubkey,
+}
+
+/// Emitted when a vault is submitted to its mint's leaderboard
+#[event]
+pub struct LeaderboardUpdated {
+    /// The mint whose leaderboard was updated
+    pub mint: Pubkey,
+    /// The vault state account submitted
+    pub state: Pubkey,
+    /// The vault's balance, as ranked
+    pub balance: u64,
+    /// The vault's running deposit streak, as ranked
+    pub streak_days: u16,
+    /// The vault's position by balance (0 for first), if it made the top
+    pub balance_rank: Option<u8>,
+    /// The vault's position by streak (0 for first), if it made the top
+    pub streak_rank: Option<u8>,
+}
+
+/// Emitted when a custodian creates a vault for a beneficiary
+#[event]
+pub struct CustodialVaultCreated {
+    /// The custodian who created and pays for the vault
+    pub custodian: Pubkey,
+    /// The beneficiary who owns the vault
+    pub beneficiary: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// When the beneficiary gets access to the savings
+    pub release_ts: i64,
+}
+
+/// Emitted when the owner creates the vault's extension account
+#[event]
+pub struct VaultExtensionCreated {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The extension account
+    pub extension: Pubkey,
+}
+
+/// Emitted when a balance snapshot is recorded in a vault's extension
+#[event]
+pub struct BalanceSnapshotRecorded {
+    /// The vault owner
+    pub owner: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// When the snapshot was taken
+    pub timestamp: i64,
+    /// The vault balance at that time
+    pub balance: u64,
+}
+
+/// Emitted when a memo is attached to a deposit or withdrawal, next to the transfer's own event
+#[event]
+pub struct MemoAttached {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Whether the memo is on a deposit or a withdrawal
+    pub kind: ActivityKind,
+    /// The memo
+    pub memo: String,
+    /// SHA-256 of the memo, as stored on the vault extension
+    pub memo_hash: [u8; 32],
+}
+
+/// Emitted when the owner sets the vault's unlock stream
+#[event]
+pub struct UnlockStreamSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount being unlocked
+    pub amount: u64,
+    /// When the amount starts accruing
+    pub start_ts: i64,
+    /// Nothing is withdrawable before this time
+    pub cliff_ts: i64,
+    /// When the whole amount is unlocked
+    pub end_ts: i64,
+}
+
+/// Emitted when the owner freezes their own withdrawals
+#[event]
+pub struct SelfFreezeSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// Withdrawals are refused until this time
+    pub frozen_until: i64,
+}
+
+/// Emitted when the owner grants a session key
+#[event]
+pub struct DelegateGranted {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The session key
+    pub delegate: Pubkey,
+    /// What the session key may do
+    pub permissions: DelegatePermissions,
+    /// When the session ends
+    pub expiry: i64,
+    /// Most the session key may withdraw
+    pub max_amount: u64,
+}
+
+/// Emitted when the owner revokes a session key
+#[event]
+pub struct DelegateRevoked {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The session key
+    pub delegate: Pubkey,
+}
+
+/// Emitted when the owner authorizes a merchant to pull from the vault
+#[event]
+pub struct SubscriptionGranted {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The merchant
+    pub merchant: Pubkey,
+    /// What the merchant pulls each interval
+    pub amount: u64,
+    /// Shortest time between two pulls (in seconds)
+    pub interval_seconds: i64,
+}
+
+/// Emitted when the owner revokes a merchant's subscription
+#[event]
+pub struct SubscriptionRevoked {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The merchant
+    pub merchant: Pubkey,
+}
+
+/// Emitted when the owner authorizes an auto-save service to pull deposits
+#[event]
+pub struct AutoSaveSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The auto-save service
+    pub service: Pubkey,
+    /// The most a single pull may take
+    pub max_amount: u64,
+    /// Shortest time between two pulls (in seconds)
+    pub interval_seconds: i64,
+}
+
+/// Emitted when the owner clears the vault's auto-save authorization
+#[event]
+pub struct AutoSaveCleared {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+}
+
+/// Emitted when a merchant pulls a subscription payment, next to `TokensWithdrawn`
+#[event]
+pub struct SubscriptionPulled {
+    /// The merchant
+    pub merchant: Pubkey,
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount pulled
+    pub amount: u64,
+}
+
+/// Emitted when the owner sets the vault's guardians
+#[event]
+pub struct GuardiansSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The guardian keys
+    pub guardians: Vec<Pubkey>,
+    /// Votes a recovery needs
+    pub threshold: u8,
+}
+
+/// Emitted when a guardian proposes to recover a vault
+#[event]
+pub struct RecoveryProposed {
+    /// The proposing guardian
+    pub guardian: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The wallet the vault would be recovered to
+    pub new_owner: Pubkey,
+    /// When the recovery can be executed, unless vetoed
+    pub executable_at: i64,
+}
+
+/// Emitted when a guardian votes for a recovery
+#[event]
+pub struct RecoveryVoted {
+    /// The voting guardian
+    pub guardian: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The wallet the vault would be recovered to
+    pub new_owner: Pubkey,
+    /// Votes the proposal now has
+    pub votes: u8,
+}
+
+/// Emitted when the owner vetoes a recovery
+#[event]
+pub struct RecoveryVetoed {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The wallet the vault would have been recovered to
+    pub new_owner: Pubkey,
+}
+
+/// Emitted when a vault is recovered to a new wallet
+#[event]
+pub struct VaultRecovered {
+    /// The vault owner who lost their key
+    pub owner: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The wallet the balance was recovered to
+    pub new_owner: Pubkey,
+    /// The amount recovered
+    pub recovered: u64,
+}
+
+/// Emitted when a savings circle is created
+#[event]
+pub struct CircleCreated {
+    /// The member who organized the circle
+    pub creator: Pubkey,
+    /// The circle account
+    pub circle: Pubkey,
+    /// The token mint the members contribute in
+    pub mint: Pubkey,
+    /// The members, in payout order
+    pub members: Vec<Pubkey>,
+    /// What every member pays into each round
+    pub contribution: u64,
+    /// How long each round collects contributions for (in seconds)
+    pub period_seconds: i64,
+    /// When the first round started
+    pub start_ts: i64,
+}
+
+/// Emitted when a member pays into a circle round
+#[event]
+pub struct CircleContributed {
+    /// The paying member
+    pub member: Pubkey,
+    /// The circle account
+    pub circle: Pubkey,
+    /// The round paid into
+    pub round: u8,
+    /// The amount paid
+    pub amount: u64,
+}
+
+/// Emitted for every member who didn't pay into a round when it closed
+#[event]
+pub struct CircleMemberDefaulted {
+    /// The circle account
+    pub circle: Pubkey,
+    /// The member who didn't pay
+    pub member: Pubkey,
+    /// The round they missed
+    pub round: u8,
+}
+
+/// Emitted when a circle round closes
+#[event]
+pub struct CirclePaidOut {
+    /// The circle account
+    pub circle: Pubkey,
+    /// The member whose turn it was
+    pub recipient: Pubkey,
+    /// The round closed
+    pub round: u8,
+    /// The pot paid to the recipient (0 if forfeited)
+    pub amount: u64,
+    /// Whether the recipient had missed a round and forfeited the pot to its payers
+    pub forfeited: bool,
+}
+
+/// Emitted when a member claims contributions refunded by forfeited rounds
+#[event]
+pub struct CircleRefundClaimed {
+    /// The member
+    pub member: Pubkey,
+    /// The circle account
+    pub circle: Pubkey,
+    /// The amount refunded
+    pub amount: u64,
+}
+
+/// Emitted when a settled circle is closed
+#[event]
+pub struct CircleClosed {
+    /// The member who organized the circle
+    pub creator: Pubkey,
+    /// The circle account
+    pub circle: Pubkey,
+}
+
+/// Emitted when an employer sets up a payroll
+#[event]
+pub struct PayrollCreated {
+    /// The employer funding the payroll
+    pub employer: Pubkey,
+    /// The employee being paid
+    pub employee: Pubkey,
+    /// The payroll account
+    pub payroll: Pubkey,
+    /// The token mint the employee is paid in
+    pub mint: Pubkey,
+    /// What the employee earns per completed pay period
+    pub amount_per_period: u64,
+    /// How long each pay period lasts (in seconds)
+    pub period_seconds: i64,
+    /// When the first pay period started
+    pub start_ts: i64,
+}
+
+/// Emitted when the employer funds a payroll
+#[event]
+pub struct PayrollFunded {
+    /// The employer
+    pub employer: Pubkey,
+    /// The payroll account
+    pub payroll: Pubkey,
+    /// The amount funded
+    pub amount: u64,
+}
+
+/// Emitted when the employee claims accrued pay
+#[event]
+pub struct PayrollClaimed {
+    /// The employee
+    pub employee: Pubkey,
+    /// The payroll account
+    pub payroll: Pubkey,
+    /// The amount paid out
+    pub amount: u64,
+    /// Total pay claimed from the payroll so far
+    pub total_claimed: u64,
+}
+
+/// Emitted when the employer stops a payroll's accrual
+#[event]
+pub struct PayrollStopped {
+    /// The employer
+    pub employer: Pubkey,
+    /// The payroll account
+    pub payroll: Pubkey,
+    /// When the accrual stopped
+    pub stopped_at: i64,
+    /// Funding beyond the accrued pay, returned to the employer
+    pub refunded: u64,
+}
+
+/// Emitted when a settled payroll is closed
+#[event]
+pub struct PayrollClosed {
+    /// The employer
+    pub employer: Pubkey,
+    /// The payroll account
+    pub payroll: Pubkey,
+}
+
+/// Emitted when a depositor locks up tokens in an escrow
+#[event]
+pub struct EscrowCreated {
+    /// The party who locked up the tokens
+    pub depositor: Pubkey,
+    /// The escrow account
+    pub escrow: Pubkey,
+    /// The party the tokens are released to
+    pub counterparty: Pubkey,
+    /// The third party who can approve, if any
+    pub arbiter: Option<Pubkey>,
+    /// The token mint of the escrowed tokens
+    pub mint: Pubkey,
+    /// The amount escrowed
+    pub amount: u64,
+    /// When the depositor can take the tokens back
+    pub timeout_ts: i64,
+}
+
+/// Emitted when a party approves an escrow's release
+#[event]
+pub struct EscrowApproved {
+    /// The escrow account
+    pub escrow: Pubkey,
+    /// The approving party
+    pub approver: Pubkey,
+    /// Whether the escrow has enough approvals to be released
+    pub releasable: bool,
+}
+
+/// Emitted when an escrow is released to its counterparty
+#[event]
+pub struct EscrowReleased {
+    /// The escrow account
+    pub escrow: Pubkey,
+    /// The counterparty
+    pub counterparty: Pubkey,
+    /// The amount released
+    pub amount: u64,
+}
+
+/// Emitted when an expired escrow is refunded to its depositor
+#[event]
+pub struct EscrowRefunded {
+    /// The escrow account
+    pub escrow: Pubkey,
+    /// The depositor
+    pub depositor: Pubkey,
+    /// The amount refunded
+    pub amount: u64,
+}
+
+/// Emitted when an owner locks up tokens in a hashlock/timelock contract
+#[event]
+pub struct HtlcCreated {
+    /// The party who locked up the tokens
+    pub owner: Pubkey,
+    /// The contract account
+    pub htlc: Pubkey,
+    /// The party who can claim the tokens with the preimage
+    pub counterparty: Pubkey,
+    /// The token mint of the locked tokens
+    pub mint: Pubkey,
+    /// The amount locked
+    pub amount: u64,
+    /// SHA-256 hash of the secret that unlocks the tokens
+    pub hashlock: [u8; 32],
+    /// When claims end and the owner can take the tokens back
+    pub timeout_ts: i64,
+}
+
+/// Emitted when a contract is claimed with its preimage
+#[event]
+pub struct HtlcClaimed {
+    /// The contract account
+    pub htlc: Pubkey,
+    /// The counterparty paid
+    pub counterparty: Pubkey,
+    /// The amount paid
+    pub amount: u64,
+    /// The revealed secret
+    pub preimage: [u8; 32],
+}
+
+/// Emitted when an expired contract is refunded to its owner
+#[event]
+pub struct HtlcRefunded {
+    /// The contract account
+    pub htlc: Pubkey,
+    /// The owner
+    pub owner: Pubkey,
+    /// The amount refunded
+    pub amount: u64,
+}
+
+/// Emitted when the owner requests a delayed withdrawal
+#[event]
+pub struct WithdrawalRequested {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount requested
+    pub amount: u64,
+    /// When the withdrawal can be executed
+    pub executable_at: i64,
+}
+
+/// Emitted when the owner cancels a pending withdrawal
+#[event]
+pub struct WithdrawalCancelled {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The amount that was requested
+    pub amount: u64,
+}
+
+/// Emitted when a vault is released by a permissionless crank
+#[event]
+pub struct VaultCranked {
+    /// The vault owner receiving the released tokens
+    pub owner: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The account that cranked the release
+    pub cranker: Pubkey,
+    /// The amount sent to the owner
+    pub released: u64,
+    /// The reward paid to the cranker
+    pub reward: u64,
+}
+
+/// Emitted when the admin freezes a vault
+#[event]
+pub struct VaultFrozen {
+    /// The config admin
+    pub admin: Pubkey,
+    /// The frozen vault state account
+    pub state: Pubkey,
+    /// The timestamp at which the freeze expires
+    pub frozen_until: i64,
+    /// Whether deposits are blocked too
+    pub block_deposits: bool,
+}
+
+/// Emitted when a missed deadline is settled
+#[event]
+pub struct DeadlineSettled {
+    /// The vault owner
+    pub owner: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The consequence that ran
+    pub consequence: DeadlineConsequence,
+    /// The amount returned to the owner
+    pub returned: u64,
+    /// The amount donated
+    pub donated: u64,
+    /// Until when the whole balance is locked, if the consequence extended the lock
+    pub locked_until: Option<i64>,
+}
+
+/// Emitted when the admin changes the allowed balance cap range
+#[event]
+pub struct BalanceCapBoundsSet {
+    /// Smallest cap an owner may set
+    pub min_cap: u64,
+    /// Largest cap an owner may set
+    pub max_cap: u64,
+}
+
+/// Emitted when a vault owner sets or clears the balance cap
+#[event]
+pub struct BalanceCapSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The new cap, None if cleared
+    pub cap: Option<u64>,
+    /// What happens to deposits over the cap
+    pub policy: CapPolicy,
+}
+
+/// Emitted when a vault owner sets or clears the deposit hook
+#[event]
+pub struct DepositHookSet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The new hook program, None if cleared
+    pub hook: Option<Pubkey>,
+}
+
+/// Emitted when a vault owner sets, changes or clears the vault key
+#[event]
+pub struct VaultKeySet {
+    /// The vault owner
+    pub user: Pubkey,
+    /// The vault state account
+    pub state: Pubkey,
+    /// The new vault key, None if cleared
+    pub vault_key: Option<Pubkey>,
+    /// Withdrawals above this amount need the vault key's signature
+    pub threshold: u64,
+}
+
+/// Logged when a vault token account fails the ownership/mint sanity check
+#[event]
+pub struct VaultTokenAccountMismatch {
+    /// The vault state account
+    pub state: Pubkey,
+    /// The offending token account
+    pub vault_token_account: Pubkey,
+    /// The token account's actual owner
+    pub owner: Pubkey,
+    /// The token account's actual mint
+    pub mint: Pubkey,
+}
+
+/// Custom error codes for the vault program
+#[error_code]
+pub enum ErrorCode {
+    /// Tokens are currently locked and cannot be withdrawn
+    #[msg("Tokens are currently locked and cannot be withdrawn")]
+    TokensLocked,
+
+    /// Invalid lock duration provided
+    #[msg("Invalid lock duration provided")]
+    InvalidLockDuration,
+
+    /// The vault token account is not owned by the vault authority or holds the wrong mint
+    #[msg("Vault token account owner or mint does not match the vault")]
+    VaultTokenAccountMismatch,
+
+    /// The vault still holds tokens and cannot be closed
+    #[msg("Vault still holds tokens and cannot be closed")]
+    VaultNotEmpty,
+
+    /// The amount to move, or the savings target, is zero
+    #[msg("Amount must be greater than zero")]
+    ZeroAmount,
+
+    /// The signer is not allowed to perform this action
+    #[msg("Signer is not authorized to perform this action")]
+    Unauthorized,
+
+    /// The deposit hook program is not approved by the admin
+    #[msg("Deposit hook program is not approved")]
+    DepositHookNotApproved,
+
+    /// The deposit hook program passed does not match the one set on the vault
+    #[msg("Deposit hook program does not match the vault's hook")]
+    DepositHookMismatch,
+
+    /// The note is empty or longer than MAX_NOTE_LENGTH bytes
+    #[msg("Note must be between 1 and MAX_NOTE_LENGTH bytes")]
+    InvalidNoteLength,
+
+    /// A mint argument or account does not match the vault's mint
+    #[msg("Mint does not match the vault's mint")]
+    MintMismatch,
+
+    /// The target is below the smallest sensible amount for the mint's decimals
+    #[msg("Target is too small for the mint's decimals")]
+    TargetTooSmall,
+
+    /// The target is larger than the mint's total supply
+    #[msg("Target exceeds the mint's total supply")]
+    TargetExceedsSupply,
+
+    /// The action needs the vault key's signature and it wasn't provided
+    #[msg("Vault key signature required")]
+    VaultKeyRequired,
+
+    /// The vault is frozen by the admin
+    #[msg("Vault is frozen")]
+    VaultFrozen,
+
+    /// The freeze duration is outside the allowed range
+    #[msg("Freeze duration must be between MIN_LOCK_DURATION_SECONDS and MAX_FREEZE_DURATION_SECONDS")]
+    InvalidFreezeDuration,
+
+    /// The vault was frozen too recently to be frozen again
+    #[msg("Vault can't be frozen again until the freeze cooldown has passed")]
+    FreezeCooldown,
+
+    /// The vault is empty, or neither reached its target nor hit an opted-in unlock
+    #[msg("Vault is not ready to be released")]
+    NotReleasable,
+
+    /// The deposit would take the vault over its balance cap
+    #[msg("Deposit would exceed the vault's balance cap")]
+    BalanceCapExceeded,
+
+    /// The balance cap (or cap range) is outside the allowed bounds
+    #[msg("Balance cap is outside the allowed bounds")]
+    BalanceCapOutOfBounds,
+
+    /// The deadline is in the past or its consequence is malformed
+    #[msg("Invalid goal deadline")]
+    InvalidDeadline,
+
+    /// The vault has no deadline, it hasn't passed yet, or the target was reached
+    #[msg("Deadline cannot be settled")]
+    DeadlineNotSettleable,
+
+    /// The donation token account is missing or isn't owned by the donation recipient
+    #[msg("Donation token account does not belong to the recipient")]
+    DonationRecipientMismatch,
+
+    /// Every lock tranche slot holds an active lock
+    #[msg("Too many active lock tranches")]
+    TooManyLockTranches,
+
+    /// The mint allowlist is enabled and the mint hasn't been approved by the admin
+    #[msg("Mint is not approved for new vaults")]
+    MintNotApproved,
+
+    /// The vault has a USD target and the price account is missing or isn't the vault's
+    #[msg("Price account does not match the vault's USD target")]
+    PriceAccountMismatch,
+
+    /// The price account isn't a fully verified Pyth price update
+    #[msg("Invalid price account")]
+    InvalidPriceAccount,
+
+    /// The oracle price is older than `MAX_PRICE_AGE_SECONDS`
+    #[msg("Oracle price is stale")]
+    StalePrice,
+
+    /// The oracle price is not positive or its confidence interval is too wide
+    #[msg("Oracle price is too uncertain")]
+    PriceTooUncertain,
+
+    /// The oracle price hasn't crossed the price condition's threshold
+    #[msg("Price condition not met")]
+    PriceConditionNotMet,
+
+    /// The swap program isn't approved by the admin, or was passed the vault authority
+    #[msg("Swap program not approved")]
+    SwapProgramNotApproved,
+
+    /// The swap delivered less than the minimum amount out to the vault
+    #[msg("Swap output below minimum")]
+    SlippageExceeded,
+
+    /// The swap spent more of the input mint than allowed
+    #[msg("Swap spent more than the input amount")]
+    SwapOverspent,
+
+    /// The last withdrawal's cooldown hasn't elapsed yet
+    #[msg("Withdrawal cooldown has not elapsed")]
+    WithdrawalCooldown,
+
+    /// The withdrawal cooldown is negative or longer than MAX_WITHDRAW_COOLDOWN_SECONDS
+    #[msg("Invalid withdrawal cooldown")]
+    InvalidCooldown,
+
+    /// The withdrawal would take the vault over its daily withdrawal limit
+    #[msg("Daily withdrawal limit exceeded")]
+    RateLimitExceeded,
+
+    /// The vault has a withdrawal delay, so withdrawals must be requested first
+    #[msg("Withdrawal must be requested first")]
+    WithdrawalDelayRequired,
+
+    /// The requested withdrawal's delay hasn't passed yet
+    #[msg("Withdrawal delay has not passed")]
+    WithdrawalNotReady,
+
+    /// The withdrawal delay is negative or longer than MAX_WITHDRAWAL_DELAY_SECONDS
+    #[msg("Invalid withdrawal delay")]
+    InvalidWithdrawalDelay,
+
+    /// The withdrawal (or approver change) doesn't have enough approvals
+    #[msg("Withdrawal needs more approvals")]
+    WithdrawalApprovalRequired,
+
+    /// The approvers are duplicated, too many, or don't fit the threshold
+    #[msg("Invalid withdrawal approvers")]
+    InvalidApprovers,
+
+    /// The guardians are duplicated, too many, include the owner or don't fit the threshold
+    #[msg("Invalid guardians")]
+    InvalidGuardians,
+
+    /// The recovery proposal doesn't have enough guardian votes
+    #[msg("Recovery needs more guardian votes")]
+    RecoveryNotApproved,
+
+    /// The recovery proposal's veto period hasn't passed yet
+    #[msg("Recovery veto period has not passed")]
+    RecoveryNotReady,
+
+    /// The session key's grant has expired (or would already be expired)
+    #[msg("Session key expired")]
+    DelegateExpired,
+
+    /// The session key isn't allowed to do this
+    #[msg("Session key not permitted")]
+    DelegateNotPermitted,
+
+    /// The withdrawal would take the session key over its cap
+    #[msg("Session key limit exceeded")]
+    DelegateLimitExceeded,
+
+    /// The previous balance snapshot is less than `SNAPSHOT_INTERVAL_SECONDS` old
+    #[msg("Balance snapshot recorded too soon")]
+    SnapshotTooSoon,
+
+    /// The deposit fee is above `MAX_DEPOSIT_FEE_BPS`, or has no treasury
+    #[msg("Invalid deposit fee")]
+    InvalidDepositFee,
+
+    /// The treasury token account is missing or doesn't belong to the config treasury
+    #[msg("Treasury token account mismatch")]
+    TreasuryMismatch,
+
+    /// Too many fee tiers, or tiers that aren't increasing or discount more than the fee
+    #[msg("Invalid fee tiers")]
+    InvalidFeeTiers,
+
+    /// The membership token account isn't the depositor's account of the membership mint
+    #[msg("Membership token account mismatch")]
+    MembershipMismatch,
+    /// A wrapped SOL path was used on a vault of another mint
+    #[msg("Vault mint is not wrapped SOL")]
+    NotNativeMint,
+    /// `deposit_sol` was called without the System program
+    #[msg("System program required to wrap SOL")]
+    SystemProgramRequired,
+    /// The memo is empty or longer than `MAX_MEMO_LEN`
+    #[msg("Invalid memo")]
+    InvalidMemo,
+    /// A memo was attached without the vault's extension account
+    #[msg("Vault extension required")]
+    ExtensionRequired,
+    /// The config refuses mints with a freeze authority
+    #[msg("Mint has a freeze authority")]
+    FreezableMint,
+    /// `self_freeze` was called with a duration out of bounds
+    #[msg("Self-freeze duration must be between MIN_LOCK_DURATION_SECONDS and MAX_SELF_FREEZE_SECONDS")]
+    InvalidSelfFreezeDuration,
+    /// The owner froze their own withdrawals and the freeze is still running
+    #[msg("Withdrawals are self-frozen")]
+    SelfExcluded,
+    /// The unlock stream doesn't end after it starts, its cliff is outside of it, or it ends
+    /// too soon or too far in the future
+    #[msg("Unlock stream must end after it starts, with its cliff in between, within the lock duration bounds")]
+    InvalidUnlockStream,
+    /// The vault's unlock stream is still holding tokens back
+    #[msg("Unlock stream still running")]
+    UnlockStreamActive,
+    /// A withdrawal asks for more than the vault token account holds
+    #[msg("Vault balance is too low for this withdrawal")]
+    InsufficientVaultBalance,
+    /// The vault holds tokens but hasn't reached its savings target
+    #[msg("Savings target not reached")]
+    TargetNotReached,
+    /// The vault still has active lock tranches, so its whole balance can't be moved
+    #[msg("Lock has not expired yet")]
+    LockNotExpired,
+    /// An amount, fee, timestamp or counter would overflow or go negative
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    /// The lock duration exceeds the maximum allowed by the config
+    #[msg("Lock duration too long")]
+    LockDurationTooLong,
+    /// NFT vaults take deposits of exactly one token
+    #[msg("NFT deposits must be exactly one token")]
+    InvalidNftAmount,
+    /// The circle's members, contribution or period are invalid
+    #[msg("Invalid savings circle")]
+    InvalidCircle,
+    /// The signer isn't a member of the circle
+    #[msg("Not a circle member")]
+    NotCircleMember,
+    /// The member already paid into the current round
+    #[msg("Already paid into this round")]
+    CircleAlreadyPaid,
+    /// The round is still collecting contributions
+    #[msg("Circle round not over")]
+    CircleRoundNotOver,
+    /// Every round of the circle has been paid out
+    #[msg("Circle complete")]
+    CircleComplete,
+    /// The recipient isn't the member whose turn it is
+    #[msg("Not this round's recipient")]
+    InvalidCircleRecipient,
+    /// The circle still has rounds to pay out or refunds to claim
+    #[msg("Circle not settled")]
+    CircleNotSettled,
+    /// The vault hasn't reached the badge's milestone
+    #[msg("Badge not earned")]
+    BadgeNotEarned,
+    /// The owner already claimed the badge
+    #[msg("Badge already claimed")]
+    BadgeAlreadyClaimed,
+    /// The custodian is the beneficiary, or the release date is out of range
+    #[msg("Invalid custodial vault")]
+    InvalidCustody,
+    /// The vault is held in custody until its release date
+    #[msg("Vault is in custody until its release date")]
+    VaultInCustody,
+    /// The payroll's employee, pay or period is invalid
+    #[msg("Invalid payroll")]
+    InvalidPayroll,
+    /// The employer already stopped the payroll
+    #[msg("Payroll already stopped")]
+    PayrollAlreadyStopped,
+    /// The payroll is still running or holds pay the employee hasn't claimed
+    #[msg("Payroll not settled")]
+    PayrollNotSettled,
+    /// The subscription amount or interval is zero
+    #[msg("Invalid subscription")]
+    InvalidSubscription,
+    /// The merchant already pulled within the current interval
+    #[msg("Subscription not due yet")]
+    SubscriptionNotDue,
+    /// The escrow's amount, timeout or parties are invalid
+    #[msg("Invalid escrow")]
+    InvalidEscrow,
+    /// The escrow's timeout has passed, so it can no longer be approved
+    #[msg("Escrow expired")]
+    EscrowExpired,
+    /// The escrow doesn't have two approvals yet
+    #[msg("Escrow not approved")]
+    EscrowNotApproved,
+    /// The escrow's timeout hasn't passed yet
+    #[msg("Escrow not expired")]
+    EscrowNotExpired,
+    /// The escrow was approved in time, so it can only be released
+    #[msg("Escrow approved for release")]
+    EscrowReleasable,
+    /// The contract's amount, timeout or counterparty is invalid
+    #[msg("Invalid hashlock contract")]
+    InvalidHtlc,
+    /// The contract's timeout has passed, so it can no longer be claimed
+    #[msg("Hashlock contract expired")]
+    HtlcExpired,
+    /// The contract's timeout hasn't passed yet
+    #[msg("Hashlock contract not expired")]
+    HtlcNotExpired,
+    /// The preimage doesn't hash to the contract's hashlock
+    #[msg("Wrong preimage")]
+    WrongPreimage,
+    /// The withdrawal destination is the vault's own token account
+    #[msg("Invalid withdrawal destination")]
+    InvalidDestination,
+    /// The rollover increase is above the maximum
+    #[msg("Invalid rollover increase")]
+    InvalidRollover,
+    /// The goal template's terms are out of range
+    #[msg("Invalid goal template")]
+    InvalidGoalTemplate,
+    /// The advance would exceed `MAX_ADVANCE_BPS` of the locked balance
+    #[msg("Advance exceeds the share of the locked balance that can be borrowed")]
+    AdvanceLimitExceeded,
+    /// The owner's advance must be repaid first
+    #[msg("Vault has an outstanding advance")]
+    AdvanceOutstanding,
+    /// The vault already had a deposit or withdrawal in this slot
+    #[msg("Deposits and withdrawals can't share a slot")]
+    SameSlotActivity,
+    /// A token account was passed both as the vault's and as the other side of a transfer
+    #[msg("Token account can't also be the vault's token account")]
+    DuplicateAccount,
+    /// A counterparty token account belongs to the vault authority
+    #[msg("Token account belongs to the vault authority")]
+    VaultOwnedAccount,
+    /// A wallet taking part in a deposit or withdrawal is on the admin blocklist
+    #[msg("Address is blocked")]
+    AddressBlocked,
+    /// The blocklist entry passed for a wallet isn't the wallet's
+    #[msg("Blocklist entry doesn't match the wallet")]
+    BlocklistMismatch,
+    /// The vault owner doesn't hold the credential token the config requires
+    #[msg("The vault owner must hold the credential token")]
+    CredentialRequired,
+    /// The credential token account isn't the vault owner's account of the credential mint
+    #[msg("Credential token account doesn't match")]
+    CredentialMismatch,
+    /// The vault creator doesn't hold enough of the membership token the config requires
+    #[msg("Vault creators must hold the membership token")]
+    MembershipRequired,
+    /// The UI amount isn't a decimal number within the mint's decimals
+    #[msg("Invalid UI amount")]
+    InvalidUiAmount,
+    /// The account isn't a vault in the version 1 layout
+    #[msg("Vault can't be migrated")]
+    VaultNotMigratable,
+    #[msg("Revoke the vault's sessions and subscriptions first")]
+    GrantsOutstanding,
+    #[msg("A recovery of the vault is pending")]
+    RecoveryPending,
+    #[msg("Auto-save needs a positive pull limit and a non-negative interval")]
+    InvalidAutoSave,
+    #[msg("The caller isn't the vault's authorized auto-save service")]
+    AutoSaveNotAuthorized,
+    #[msg("The pull exceeds the auto-save limit")]
+    AutoSaveLimitExceeded,
+    #[msg("The auto-save interval hasn't passed since the last pull")]
+    AutoSaveNotDue,
+    #[msg("The admin hasn't set a price feed for the mint")]
+    PriceFeedNotSet,
+    #[msg("The price update is for another feed than the mint's")]
+    PriceFeedMismatch,
 }
@@ -0,0 +1,114 @@
+//! Minimal reader for Pyth pull-oracle price updates (`PriceUpdateV2` accounts owned by the
+//! Pyth receiver program), so USD targets don't need the full Pyth SDK.
+
+use super::*;
+
+/// The Pyth Solana receiver program, owner of every `PriceUpdateV2` account
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey =
+    anchor_lang::solana_program::pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+/// How many Wormhole guardian signatures were checked when the update was posted
+#[derive(AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationLevel {
+    /// Only some signatures were checked
+    Partial { num_signatures: u8 },
+    /// The guardian quorum was checked
+    Full,
+}
+
+/// A price as published by Pyth: `price * 10^exponent` USD per whole token, give or take `conf`
+#[derive(AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+/// Layout of a `PriceUpdateV2` account after its discriminator
+#[derive(AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceFeedMessage,
+    pub posted_slot: u64,
+}
+
+/// Returns the Anchor account discriminator of `PriceUpdateV2`
+pub fn price_update_discriminator() -> [u8; 8] {
+    let hash = anchor_lang::solana_program::hash::hash(b"account:PriceUpdateV2");
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+/// Reads a fully verified, fresh and confident price from a Pyth price update account, which
+/// must publish `feed_id`: the feed the admin set for the priced mint (see `load_price_feed`)
+pub fn read_price(
+    price_update: &AccountInfo,
+    feed_id: Option<[u8; 32]>,
+    now: i64,
+) -> Result<PriceFeedMessage> {
+    require_keys_eq!(
+        *price_update.owner,
+        PYTH_RECEIVER_PROGRAM_ID,
+        ErrorCode::InvalidPriceAccount
+    );
+
+    let data = price_update.try_borrow_data()?;
+    require!(
+        data.len() > 8 && data[..8] == price_update_discriminator(),
+        ErrorCode::InvalidPriceAccount
+    );
+    let update = PriceUpdateV2::deserialize(&mut &data[8..])
+        .map_err(|_| error!(ErrorCode::InvalidPriceAccount))?;
+    require!(
+        update.verification_level == VerificationLevel::Full,
+        ErrorCode::InvalidPriceAccount
+    );
+
+    let price = update.price_message;
+    let feed_id = feed_id.ok_or(ErrorCode::PriceFeedNotSet)?;
+    require!(price.feed_id == feed_id, ErrorCode::PriceFeedMismatch);
+    require!(
+        now.saturating_sub(price.publish_time) <= MAX_PRICE_AGE_SECONDS,
+        ErrorCode::StalePrice
+    );
+    require!(
+        price.price > 0 && price_is_confident(price.price as u64, price.conf),
+        ErrorCode::PriceTooUncertain
+    );
+
+    Ok(price)
+}
+
+/// Returns true if the confidence interval is within `MAX_PRICE_CONFIDENCE_BPS` of the price
+pub fn price_is_confident(price: u64, conf: u64) -> bool {
+    (conf as u128) * (BPS_DENOMINATOR as u128)
+        <= (price as u128) * (MAX_PRICE_CONFIDENCE_BPS as u128)
+}
+
+/// Returns the value in USD cents of `amount` base units of a mint with `decimals`, at
+/// `price * 10^exponent` USD per whole token. Rounds down, and saturates at `u64::MAX`.
+pub fn usd_cents(amount: u64, decimals: u8, price: u64, exponent: i32) -> u64 {
+    // cents = amount * price * 10^(exponent + 2 - decimals)
+    let scale = exponent as i64 + 2 - decimals as i64;
+    let value = (amount as u128) * (price as u128);
+    let cents = if scale >= 0 {
+        10u128
+            .checked_pow(scale as u32)
+            .and_then(|factor| value.checked_mul(factor))
+            .unwrap_or(u128::MAX)
+    } else {
+        10u128
+            .checked_pow(scale.unsigned_abs() as u32)
+            .map(|factor| value / factor)
+            .unwrap_or(0)
+    };
+
+    u64::try_from(cents).unwrap_or(u64::MAX)
+}
@@ -0,0 +1,344 @@
+//! Unit tests for the pure vault math, at the boundaries and where it could overflow.
+//!
+//! Run with `cargo test -p vault2`. The Kani harnesses in `verification.rs` are separate.
+
+use super::*;
+
+/// A vault with a token target of `amount` and nothing else configured
+fn vault(amount: u64) -> Vault {
+    Vault {
+        amount,
+        vault_bump: 0,
+        state_bump: 0,
+        mint: Pubkey::default(),
+        vault_token_account: Pubkey::default(),
+        lock_tranches: [LockTranche::default(); MAX_LOCK_TRANCHES],
+        deposit_hook: None,
+        vault_key: None,
+        vault_key_threshold: 0,
+        frozen_until: None,
+        flags: 0,
+        release_on_unlock: false,
+        balance_cap: None,
+        cap_policy: CapPolicy::Reject,
+        deadline: None,
+        usd_target: None,
+        cooldown_seconds: 0,
+        last_withdraw_ts: 0,
+        max_withdraw_per_day: 0,
+        withdrawn_in_window: 0,
+        version: 0,
+        open_grants: 0,
+        decimals: 0,
+        reserved: [0; VAULT_RESERVED_BYTES],
+    }
+}
+
+fn tranche(amount: u64, unlock_ts: i64) -> LockTranche {
+    LockTranche { amount, unlock_ts }
+}
+
+#[test]
+fn ui_amount_scales_to_the_mint_decimals() {
+    assert_eq!(ui_amount_to_amount("12.5", 6).unwrap(), 12_500_000);
+    assert_eq!(ui_amount_to_amount("12", 6).unwrap(), 12_000_000);
+    assert_eq!(ui_amount_to_amount("0.000001", 6).unwrap(), 1);
+    assert_eq!(ui_amount_to_amount("7", 0).unwrap(), 7);
+    assert_eq!(ui_amount_to_amount("1.", 2).unwrap(), 100);
+}
+
+#[test]
+fn ui_amount_rejects_malformed_input() {
+    for ui_amount in ["", ".5", "-1", "1.2.3", "1e6", " 1", "1,5", "0x10"] {
+        assert_eq!(
+            ui_amount_to_amount(ui_amount, 6),
+            Err(ErrorCode::InvalidUiAmount.into()),
+            "{ui_amount:?}"
+        );
+    }
+}
+
+#[test]
+fn ui_amount_rejects_more_fraction_digits_than_decimals() {
+    assert_eq!(ui_amount_to_amount("1.123456", 6).unwrap(), 1_123_456);
+    assert_eq!(
+        ui_amount_to_amount("1.1234567", 6),
+        Err(ErrorCode::InvalidUiAmount.into())
+    );
+    assert_eq!(
+        ui_amount_to_amount("1.0", 0),
+        Err(ErrorCode::InvalidUiAmount.into())
+    );
+}
+
+#[test]
+fn ui_amount_supports_up_to_19_decimals() {
+    assert_eq!(ui_amount_to_amount("1", 19).unwrap(), 10u64.pow(19));
+    assert_eq!(ui_amount_to_amount("0.0000000000000000001", 19).unwrap(), 1);
+    assert_eq!(
+        ui_amount_to_amount("1", 20),
+        Err(ErrorCode::InvalidUiAmount.into())
+    );
+}
+
+#[test]
+fn ui_amount_overflow_is_an_error() {
+    assert_eq!(
+        ui_amount_to_amount(&u64::MAX.to_string(), 0).unwrap(),
+        u64::MAX
+    );
+    assert_eq!(
+        ui_amount_to_amount("18446744073709551616", 0),
+        Err(ErrorCode::MathOverflow.into())
+    );
+    assert_eq!(
+        ui_amount_to_amount("18446744073709.551615", 6).unwrap(),
+        u64::MAX
+    );
+    assert_eq!(
+        ui_amount_to_amount("18446744073709.551616", 6),
+        Err(ErrorCode::MathOverflow.into())
+    );
+    assert_eq!(
+        ui_amount_to_amount("2", 19),
+        Err(ErrorCode::MathOverflow.into())
+    );
+}
+
+#[test]
+fn target_amount_takes_either_unit() {
+    assert_eq!(target_amount(500, None, 6).unwrap(), 500);
+    assert_eq!(target_amount(0, Some("2.5"), 6).unwrap(), 2_500_000);
+    assert_eq!(
+        target_amount(500, Some("2.5"), 6),
+        Err(ErrorCode::InvalidUiAmount.into())
+    );
+    assert_eq!(
+        target_amount(0, Some("abc"), 6),
+        Err(ErrorCode::InvalidUiAmount.into())
+    );
+}
+
+#[test]
+fn capped_deposit_without_a_cap_takes_everything() {
+    let vault = vault(1_000);
+    assert_eq!(vault.capped_deposit(u64::MAX, u64::MAX).unwrap(), u64::MAX);
+}
+
+#[test]
+fn capped_deposit_rejects_what_does_not_fit() {
+    let mut vault = vault(1_000);
+    vault.balance_cap = Some(100);
+
+    assert_eq!(vault.capped_deposit(40, 60).unwrap(), 40);
+    assert_eq!(
+        vault.capped_deposit(41, 60),
+        Err(ErrorCode::BalanceCapExceeded.into())
+    );
+}
+
+#[test]
+fn capped_deposit_truncates_to_the_room_left() {
+    let mut vault = vault(1_000);
+    vault.balance_cap = Some(100);
+    vault.cap_policy = CapPolicy::Truncate;
+
+    assert_eq!(vault.capped_deposit(41, 60).unwrap(), 40);
+    assert_eq!(vault.capped_deposit(u64::MAX, 0).unwrap(), 100);
+    // A full vault takes nothing, even under `Truncate`
+    assert_eq!(
+        vault.capped_deposit(1, 100),
+        Err(ErrorCode::BalanceCapExceeded.into())
+    );
+    assert_eq!(
+        vault.capped_deposit(1, 150),
+        Err(ErrorCode::BalanceCapExceeded.into())
+    );
+}
+
+#[test]
+fn capped_amount_lets_a_zero_deposit_through() {
+    assert_eq!(capped_amount(0, 100, 100, CapPolicy::Reject), Some(0));
+    assert_eq!(capped_amount(0, 100, 100, CapPolicy::Truncate), Some(0));
+}
+
+#[test]
+fn withdraw_window_drains_over_a_day() {
+    let day = WITHDRAW_LIMIT_WINDOW_SECONDS;
+    assert_eq!(withdraw_window_usage(1_000, 1_000, 0), 1_000);
+    assert_eq!(withdraw_window_usage(1_000, 1_000, day / 2), 500);
+    assert_eq!(withdraw_window_usage(1_000, 1_000, day - 1), 1);
+    assert_eq!(withdraw_window_usage(1_000, 1_000, day), 0);
+}
+
+#[test]
+fn withdraw_window_clamps_elapsed_time() {
+    assert_eq!(withdraw_window_usage(1_000, 600, -1), 600);
+    assert_eq!(withdraw_window_usage(1_000, 600, i64::MIN), 600);
+    assert_eq!(withdraw_window_usage(1_000, 600, i64::MAX), 0);
+    assert_eq!(withdraw_window_usage(u64::MAX, u64::MAX, i64::MAX), 0);
+    assert_eq!(
+        withdraw_window_usage(u64::MAX, u64::MAX, 1),
+        u64::MAX - u64::MAX / 86_400
+    );
+}
+
+#[test]
+fn withdraw_window_never_exceeds_a_lowered_limit() {
+    assert_eq!(withdraw_window_usage(100, 1_000, 0), 100);
+    assert_eq!(withdraw_window_usage(0, 1_000, 0), 0);
+}
+
+#[test]
+fn locked_amount_counts_only_active_tranches() {
+    let tranches = [tranche(100, 50), tranche(200, 100), tranche(0, 1_000)];
+
+    assert_eq!(locked_amount(49, &tranches), 300);
+    assert_eq!(locked_amount(50, &tranches), 200);
+    assert_eq!(locked_amount(99, &tranches), 200);
+    assert_eq!(locked_amount(100, &tranches), 0);
+    assert_eq!(locked_amount(0, &[]), 0);
+}
+
+#[test]
+fn locked_amount_saturates() {
+    let tranches = [tranche(u64::MAX, i64::MAX), tranche(u64::MAX, i64::MAX)];
+    assert_eq!(locked_amount(0, &tranches), u64::MAX);
+    assert_eq!(unlocked_amount(u64::MAX, 0, &tranches), 0);
+}
+
+#[test]
+fn unlocked_amount_never_exceeds_the_balance() {
+    let tranches = [tranche(100, 50)];
+
+    assert_eq!(unlocked_amount(150, 0, &tranches), 50);
+    assert_eq!(unlocked_amount(80, 0, &tranches), 0);
+    assert_eq!(unlocked_amount(150, 50, &tranches), 150);
+    assert_eq!(unlocked_amount(0, 50, &tranches), 0);
+}
+
+#[test]
+fn bps_share_bounds() {
+    assert_eq!(bps_share(1_000, 0), 0);
+    assert_eq!(bps_share(1_000, 1), 0);
+    assert_eq!(bps_share(10_000, 1), 1);
+    assert_eq!(bps_share(1_000, 2_500), 250);
+    assert_eq!(bps_share(1_000, BPS_DENOMINATOR), 1_000);
+    // More than 100% is clamped
+    assert_eq!(bps_share(1_000, BPS_DENOMINATOR + 1), 1_000);
+    assert_eq!(bps_share(1_000, u64::MAX), 1_000);
+}
+
+#[test]
+fn bps_share_does_not_overflow() {
+    assert_eq!(bps_share(u64::MAX, BPS_DENOMINATOR), u64::MAX);
+    assert_eq!(bps_share(u64::MAX, 5_000), u64::MAX / 2);
+    assert_eq!(crank_reward(u64::MAX), u64::MAX / 1_000);
+}
+
+#[test]
+fn discounted_fee_bounds() {
+    assert_eq!(discounted_fee(1_000, 0), 1_000);
+    assert_eq!(discounted_fee(1_000, 2_500), 750);
+    assert_eq!(discounted_fee(1_000, 10_000), 0);
+    // A discount above 100% waives the fee, it never underflows
+    assert_eq!(discounted_fee(1_000, u16::MAX), 0);
+    assert_eq!(discounted_fee(u64::MAX, 1), u64::MAX - u64::MAX / 10_000);
+    assert_eq!(discounted_fee(0, 5_000), 0);
+}
+
+#[test]
+fn progress_bps_bounds() {
+    assert_eq!(progress_bps(0, 0), 10_000);
+    assert_eq!(progress_bps(5, 0), 10_000);
+    assert_eq!(progress_bps(0, 100), 0);
+    assert_eq!(progress_bps(50, 100), 5_000);
+    assert_eq!(progress_bps(99, 100), 9_900);
+    assert_eq!(progress_bps(100, 100), 10_000);
+    assert_eq!(progress_bps(200, 100), 10_000);
+}
+
+#[test]
+fn progress_bps_does_not_overflow() {
+    assert_eq!(progress_bps(u64::MAX - 1, u64::MAX), 9_999);
+    assert_eq!(progress_bps(u64::MAX / 2, u64::MAX), 4_999);
+    assert_eq!(progress_bps(1, u64::MAX), 0);
+}
+
+#[test]
+fn roll_over_raises_the_token_target() {
+    let mut vault = vault(1_000);
+    vault.deadline = Some(GoalDeadline {
+        deadline_ts: 100,
+        consequence: DeadlineConsequence::ReturnFunds,
+    });
+
+    assert_eq!(vault.roll_over(2_500).unwrap(), (1_000, 1_250));
+    assert_eq!(vault.amount, 1_250);
+    assert!(vault.deadline.is_none());
+
+    assert_eq!(vault.roll_over(0).unwrap(), (1_250, 1_250));
+}
+
+#[test]
+fn roll_over_raises_the_usd_target() {
+    let mut vault = vault(1_000);
+    vault.usd_target = Some(UsdTarget {
+        cents: 10_000,
+        price_account: Pubkey::default(),
+    });
+
+    assert_eq!(vault.roll_over(10_000).unwrap(), (10_000, 20_000));
+    assert_eq!(vault.usd_target.unwrap().cents, 20_000);
+    assert_eq!(vault.amount, 1_000);
+}
+
+#[test]
+fn roll_over_overflow_is_an_error() {
+    let mut full = vault(u64::MAX);
+    assert_eq!(full.roll_over(0).unwrap(), (u64::MAX, u64::MAX));
+    assert_eq!(full.roll_over(1), Err(ErrorCode::MathOverflow.into()));
+    assert_eq!(full.amount, u64::MAX);
+
+    // An increase that doesn't fit in a u64 is an error, not truncated
+    let mut half = vault(u64::MAX / 2);
+    assert_eq!(
+        half.roll_over(u16::MAX),
+        Err(ErrorCode::MathOverflow.into())
+    );
+}
+
+#[test]
+fn refund_cut_only_applies_to_an_overshoot() {
+    assert_eq!(refund_cut(1_000, 600, 400), None);
+    assert_eq!(refund_cut(1_000, 600, 399), None);
+    assert_eq!(refund_cut(1_000, 600, 401), Some(400));
+    assert_eq!(refund_cut(1_000, 0, u64::MAX), Some(1_000));
+}
+
+#[test]
+fn refund_cut_of_a_reached_target_is_everything() {
+    assert_eq!(refund_cut(1_000, 1_000, 1), Some(0));
+    assert_eq!(refund_cut(1_000, 1_500, 1), Some(0));
+    assert_eq!(refund_cut(1_000, 1_500, 0), None);
+}
+
+#[test]
+fn vault_flags_set_and_clear_single_bits() {
+    let mut state = vault(100);
+    state.flags = 1 << 7;
+
+    state.set_flag(VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS, true);
+    assert!(state.has_flag(VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS));
+    state.set_flag(VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS, false);
+    assert!(!state.has_flag(VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS));
+    assert_eq!(state.flags, 1 << 7);
+}
+
+#[test]
+fn vault_flags_read_the_bool_they_replaced() {
+    // Vaults written while the byte was `freeze_blocks_deposits: bool` hold a 0 or a 1 there
+    let mut encoded = Vec::new();
+    true.serialize(&mut encoded).unwrap();
+    assert_eq!(encoded, [VAULT_FLAG_FREEZE_BLOCKS_DEPOSITS]);
+}
@@ -0,0 +1,346 @@
+//! Kani proofs over the pure lock math used by `withdraw` and `lock_tokens`.
+//!
+//! Run with `cargo kani -p vault2`. These harnesses are not part of the normal build, so
+//! `cargo test` and CI never check them.
+
+use super::*;
+
+/// A lock taken with a non-negative duration never expires before it was taken
+#[kani::proof]
+fn lock_expiry_never_precedes_now() {
+    let now: i64 = kani::any();
+    let duration: i64 = kani::any();
+    kani::assume(duration >= 0);
+
+    if let Some(expiry) = lock_expiry(now, duration) {
+        assert!(expiry >= now);
+    }
+}
+
+/// `lock_expiry` only fails when the expiry would overflow
+#[kani::proof]
+fn lock_expiry_fails_only_on_overflow() {
+    let now: i64 = kani::any();
+    let duration: i64 = kani::any();
+
+    let expected = (now as i128) + (duration as i128);
+    match lock_expiry(now, duration) {
+        Some(expiry) => assert!(expiry as i128 == expected),
+        None => assert!(expected > i64::MAX as i128 || expected < i64::MIN as i128),
+    }
+}
+
+/// Longer locks never expire earlier than shorter ones
+#[kani::proof]
+fn lock_expiry_is_monotonic_in_duration() {
+    let now: i64 = kani::any();
+    let shorter: i64 = kani::any();
+    let longer: i64 = kani::any();
+    kani::assume(shorter <= longer);
+
+    if let (Some(a), Some(b)) = (lock_expiry(now, shorter), lock_expiry(now, longer)) {
+        assert!(a <= b);
+    }
+}
+
+/// A freshly taken lock with a positive duration is in effect immediately
+#[kani::proof]
+fn positive_lock_is_in_effect_immediately() {
+    let now: i64 = kani::any();
+    let duration: i64 = kani::any();
+    kani::assume(duration > 0);
+
+    if let Some(expiry) = lock_expiry(now, duration) {
+        assert!(is_locked(now, Some(expiry)));
+    }
+}
+
+/// Once a vault unlocks it stays unlocked as time moves forward
+#[kani::proof]
+fn unlock_is_permanent() {
+    let locked_until: Option<i64> = kani::any();
+    let earlier: i64 = kani::any();
+    let later: i64 = kani::any();
+    kani::assume(earlier <= later);
+
+    if !is_locked(earlier, locked_until) {
+        assert!(!is_locked(later, locked_until));
+    }
+}
+
+/// Arbitrary lock tranches, including unused and expired ones
+fn any_tranches() -> [LockTranche; MAX_LOCK_TRANCHES] {
+    [(); MAX_LOCK_TRANCHES].map(|_| LockTranche {
+        amount: kani::any(),
+        unlock_ts: kani::any(),
+    })
+}
+
+/// The withdrawable amount never exceeds the balance
+#[kani::proof]
+fn unlocked_amount_never_exceeds_balance() {
+    let balance: u64 = kani::any();
+    let now: i64 = kani::any();
+    let tranches = any_tranches();
+
+    assert!(unlocked_amount(balance, now, &tranches) <= balance);
+}
+
+/// The withdrawable amount never decreases as time moves forward
+#[kani::proof]
+fn unlocked_amount_is_monotonic_in_time() {
+    let balance: u64 = kani::any();
+    let tranches = any_tranches();
+    let earlier: i64 = kani::any();
+    let later: i64 = kani::any();
+    kani::assume(earlier <= later);
+
+    assert!(
+        unlocked_amount(balance, earlier, &tranches)
+            <= unlocked_amount(balance, later, &tranches)
+    );
+}
+
+/// Once every tranche has expired, the whole balance can be withdrawn
+#[kani::proof]
+fn expired_tranches_lock_nothing() {
+    let balance: u64 = kani::any();
+    let now: i64 = kani::any();
+    let tranches = any_tranches();
+    kani::assume(tranches.iter().all(|tranche| tranche.unlock_ts <= now));
+
+    assert!(unlocked_amount(balance, now, &tranches) == balance);
+}
+
+/// Returns an unlock stream's start, cliff and end, in order with a non-empty span
+fn any_stream() -> (i64, i64, i64) {
+    let start_ts: i64 = kani::any();
+    let cliff_ts: i64 = kani::any();
+    let end_ts: i64 = kani::any();
+    kani::assume(start_ts < end_ts);
+    kani::assume(start_ts <= cliff_ts && cliff_ts <= end_ts);
+    (start_ts, cliff_ts, end_ts)
+}
+
+/// An unlock stream never holds back more than it streams
+#[kani::proof]
+fn streamed_locked_amount_never_exceeds_amount() {
+    let amount: u64 = kani::any();
+    let (start_ts, cliff_ts, end_ts) = any_stream();
+    let now: i64 = kani::any();
+
+    assert!(streamed_locked_amount(amount, start_ts, cliff_ts, end_ts, now) <= amount);
+}
+
+/// An unlock stream only ever releases more as time moves forward
+#[kani::proof]
+fn streamed_locked_amount_is_monotonic_in_time() {
+    let amount: u64 = kani::any();
+    let (start_ts, cliff_ts, end_ts) = any_stream();
+    let earlier: i64 = kani::any();
+    let later: i64 = kani::any();
+    kani::assume(earlier <= later);
+
+    assert!(
+        streamed_locked_amount(amount, start_ts, cliff_ts, end_ts, earlier)
+            >= streamed_locked_amount(amount, start_ts, cliff_ts, end_ts, later)
+    );
+}
+
+/// An unlock stream holds everything back until its cliff and nothing once it ends
+#[kani::proof]
+fn streamed_locked_amount_spans_cliff_to_end() {
+    let amount: u64 = kani::any();
+    let (start_ts, cliff_ts, end_ts) = any_stream();
+    let now: i64 = kani::any();
+
+    if now < cliff_ts {
+        assert!(streamed_locked_amount(amount, start_ts, cliff_ts, end_ts, now) == amount);
+    }
+    assert!(streamed_locked_amount(amount, start_ts, cliff_ts, end_ts, end_ts) == 0);
+}
+
+/// Progress is always a valid basis-point value and only hits 100% at the target
+#[kani::proof]
+fn progress_bps_is_bounded() {
+    let balance: u64 = kani::any();
+    let target: u64 = kani::any();
+
+    let progress = progress_bps(balance, target);
+    assert!(progress as u64 <= BPS_DENOMINATOR);
+    if target > 0 && balance < target {
+        assert!((progress as u64) < BPS_DENOMINATOR);
+    }
+}
+
+/// Every mint accepts some target, and the minimum never drops below one base unit
+#[kani::proof]
+fn min_target_is_at_least_one() {
+    let decimals: u8 = kani::any();
+
+    assert!(min_target(decimals) >= 1);
+}
+
+/// The cranker's reward never exceeds the amount being released
+#[kani::proof]
+fn crank_reward_is_bounded() {
+    let amount: u64 = kani::any();
+
+    assert!(crank_reward(amount) <= amount);
+}
+
+/// A basis-point share never exceeds the amount it is taken from
+#[kani::proof]
+fn bps_share_is_bounded() {
+    let amount: u64 = kani::any();
+    let bps: u64 = kani::any();
+
+    assert!(bps_share(amount, bps) <= amount);
+}
+
+/// Nothing is earned without time passing, and holding more never earns less
+#[kani::proof]
+fn token_seconds_is_monotonic_in_balance() {
+    let smaller: u64 = kani::any();
+    let larger: u64 = kani::any();
+    let elapsed: i64 = kani::any();
+    let decimals: u8 = kani::any();
+    kani::assume(smaller <= larger);
+
+    assert!(token_seconds(smaller, elapsed, decimals) <= token_seconds(larger, elapsed, decimals));
+    if elapsed <= 0 {
+        assert!(token_seconds(larger, elapsed, decimals) == 0);
+    }
+}
+
+/// A capped deposit never exceeds the requested amount nor takes the vault over its cap
+#[kani::proof]
+fn capped_amount_respects_the_cap() {
+    let amount: u64 = kani::any();
+    let balance: u64 = kani::any();
+    let cap: u64 = kani::any();
+    let truncate: bool = kani::any();
+    let policy = if truncate {
+        CapPolicy::Truncate
+    } else {
+        CapPolicy::Reject
+    };
+
+    if let Some(deposited) = capped_amount(amount, balance, cap, policy) {
+        assert!(deposited <= amount);
+        assert!(deposited == 0 || balance as u128 + deposited as u128 <= cap as u128);
+    }
+}
+
+/// The wait after a withdrawal never exceeds the cooldown, and is over once it has elapsed
+#[kani::proof]
+fn cooldown_remaining_is_bounded() {
+    let now: i64 = kani::any();
+    let last_withdraw_ts: i64 = kani::any();
+    let cooldown_seconds: i64 = kani::any();
+    kani::assume(0 < last_withdraw_ts && last_withdraw_ts <= now);
+    kani::assume((0..=MAX_WITHDRAW_COOLDOWN_SECONDS).contains(&cooldown_seconds));
+
+    let remaining = cooldown_remaining(now, last_withdraw_ts, cooldown_seconds);
+    assert!((0..=cooldown_seconds).contains(&remaining));
+    if now - last_withdraw_ts >= cooldown_seconds {
+        assert!(remaining == 0);
+    }
+}
+
+/// The daily limit usage never exceeds the limit and only drains as time passes
+#[kani::proof]
+fn withdraw_window_usage_drains() {
+    let max_per_day: u64 = kani::any();
+    let withdrawn: u64 = kani::any();
+    let earlier: i64 = kani::any();
+    let later: i64 = kani::any();
+    kani::assume(earlier <= later);
+
+    let usage = withdraw_window_usage(max_per_day, withdrawn, earlier);
+    assert!(usage <= max_per_day);
+    assert!(withdraw_window_usage(max_per_day, withdrawn, later) <= usage);
+    if withdrawn <= max_per_day && later >= WITHDRAW_LIMIT_WINDOW_SECONDS {
+        assert!(withdraw_window_usage(max_per_day, withdrawn, later) == 0);
+    }
+}
+
+/// Changing the withdrawal delay never lowers the delay in force before the old one has passed
+#[kani::proof]
+fn withdrawal_delay_cannot_be_skipped() {
+    let mut policy = WithdrawalPolicy {
+        state: Pubkey::default(),
+        bump: 0,
+        delay_seconds: kani::any(),
+        pending_delay_seconds: kani::any(),
+        pending_delay_at: kani::any(),
+        approvers: Vec::new(),
+        threshold: 0,
+    };
+    let now: i64 = kani::any();
+    let later: i64 = kani::any();
+    let delay_seconds: i64 = kani::any();
+    kani::assume(now > 0 && now <= later);
+    kani::assume((0..=MAX_WITHDRAWAL_DELAY_SECONDS).contains(&delay_seconds));
+    kani::assume((0..=MAX_WITHDRAWAL_DELAY_SECONDS).contains(&policy.delay_seconds));
+    kani::assume((0..=MAX_WITHDRAWAL_DELAY_SECONDS).contains(&policy.pending_delay_seconds));
+
+    let current = policy.delay_at(now);
+    policy.set_delay(now, delay_seconds);
+    if later < now.saturating_add(current) {
+        assert!(policy.delay_at(later) >= current);
+    }
+    assert!(policy.delay_at(later) >= delay_seconds.min(current));
+}
+
+/// Valuing more tokens at the same price never gives a smaller USD value
+#[kani::proof]
+fn usd_cents_is_monotonic_in_amount() {
+    let smaller: u64 = kani::any();
+    let larger: u64 = kani::any();
+    let decimals: u8 = kani::any();
+    let price: u64 = kani::any();
+    let exponent: i32 = kani::any();
+    kani::assume(smaller <= larger);
+    kani::assume(decimals <= 18 && (-18..=18).contains(&exponent));
+
+    assert!(
+        oracle::usd_cents(smaller, decimals, price, exponent)
+            <= oracle::usd_cents(larger, decimals, price, exponent)
+    );
+}
+
+/// A recorded snapshot is always the latest one, however far the ring has wrapped
+#[kani::proof]
+fn recorded_snapshot_is_latest() {
+    let mut extension = VaultExtension {
+        state: Pubkey::default(),
+        bump: 0,
+        version: VaultExtension::VERSION,
+        padding: [0; 6],
+        snapshot_count: kani::any(),
+        snapshots: [BalanceSnapshot::default(); EXTENSION_HISTORY_LEN],
+        last_memo_hash: [0; 32],
+        reserved: [0; EXTENSION_RESERVED_BYTES],
+    };
+    let snapshot = BalanceSnapshot {
+        timestamp: kani::any(),
+        balance: kani::any(),
+    };
+    kani::assume(extension.snapshot_count < u64::MAX);
+
+    if extension.record_snapshot(snapshot).is_ok() {
+        assert!(extension.latest_snapshot() == Some(snapshot));
+    }
+}
+
+/// A fee tier discount never raises the fee, and a full discount waives it
+#[kani::proof]
+fn discounted_fee_is_bounded() {
+    let fee: u64 = kani::any();
+    let discount_bps: u16 = kani::any();
+    kani::assume(discount_bps as u64 <= BPS_DENOMINATOR);
+
+    assert!(discounted_fee(fee, discount_bps) <= fee);
+    assert!(discounted_fee(fee, BPS_DENOMINATOR as u16) == 0);
+}
@@ -2,7 +2,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { PublicKey, Keypair, Connection, Commitment } from "@solana/web3.js";
 import { BN } from "@coral-xyz/anchor";
-import { getAccount } from "@solana/spl-token";
+import { getAccount, getAssociatedTokenAddressSync } from "@solana/spl-token";
 import * as fs from "fs";
 import * as path from "path";
 
@@ -26,12 +26,17 @@ function getStatePDA(user: PublicKey, programID: PublicKey): PublicKey {
   )[0];
 }
 
-// get vault PDA
-function getVaultPDA(state: PublicKey, programID: PublicKey): PublicKey {
-  return PublicKey.findProgramAddressSync(
+// get vault token account (the vault authority's ATA for the mint)
+function getVaultTokenAccount(
+  state: PublicKey,
+  mint: PublicKey,
+  programID: PublicKey
+): PublicKey {
+  const vaultAuthority = PublicKey.findProgramAddressSync(
     [Buffer.from("vault"), state.toBytes()],
     programID
   )[0];
+  return getAssociatedTokenAddressSync(mint, vaultAuthority, true);
 }
 
 // Load wallet keypair from file
@@ -68,6 +73,7 @@ function parseArgs() {
       args.wallet || process.env.WALLET_PATH || "~/.config/solana/id.json", // wallet path
     tokenMint: args.tokenMint || process.env.TOKEN_MINT, // token mint address
     lockDuration: args.duration || process.env.LOCK_DURATION, // duration of the lock in seconds
+    lockAmount: args.amount || process.env.LOCK_AMOUNT, // amount to lock, defaults to the vault balance
     targetAmount: args.target || process.env.TARGET_AMOUNT, // savings goal
     depositAmount: args.deposit || process.env.DEPOSIT_AMOUNT || "0", // amount to deposit
   };
@@ -91,6 +97,9 @@ async function main() {
     console.error(
       "--wallet <WALLET_PATH> (optional, defaults to ~/.config/solana/id.json)"
     );
+    console.error(
+      "--amount <LOCK_AMOUNT> (optional, defaults to the vault balance)"
+    );
     console.error("--target <TARGET_AMOUNT> (optional)");
     console.error("--deposit <DEPOSIT_AMOUNT> (optional, defaults to 0)");
     console.error("\nExample:");
@@ -255,7 +264,7 @@ async function main() {
 
   // Calculate PDAs
   const statePDA = getStatePDA(wallet.publicKey, PROGRAM_ID);
-  const vaultPDA = getVaultPDA(statePDA, PROGRAM_ID);
+  const vaultPDA = getVaultTokenAccount(statePDA, tokenMint, PROGRAM_ID);
 
   // Get vault token account balance
   let vaultTokenBalance2 = "0";
@@ -268,11 +277,20 @@ async function main() {
   }
   console.log(`Vault Token Balance: ${vaultTokenBalance2}\n`);
 
+  // Lock the requested amount, or everything in the vault
+  const lockAmount = new BN(config.lockAmount || vaultTokenBalance2);
+  if (lockAmount.isZero()) {
+    console.error("Error: Nothing to lock, the vault is empty");
+    process.exit(1);
+  }
+
   // Lock tokens
-  console.log(`Locking tokens for ${lockDurationSeconds} seconds`);
+  console.log(
+    `Locking ${lockAmount.toString()} tokens for ${lockDurationSeconds} seconds`
+  );
   
   const lockTx = await program.methods
-    .lockTokens(new BN(lockDurationSeconds))
+    .lockTokens(lockAmount, new BN(lockDurationSeconds))
     .accounts({
       user: wallet.publicKey,
     })
@@ -2,19 +2,32 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Vault2 } from "../target/types/vault2";
 
-import { PublicKey, Keypair } from "@solana/web3.js";
+import { PublicKey, Keypair, LAMPORTS_PER_SOL } from "@solana/web3.js";
 import { BN } from "@coral-xyz/anchor";
 
 import { assert } from "chai";
+import { createHash } from "crypto";
 
 import {
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
   createMint,
   createAccount,
   mintTo,
   getAccount,
+  getAssociatedTokenAddressSync,
+  closeAccount,
+  approve,
+  createAssociatedTokenAccount,
+  setAuthority,
+  AuthorityType,
+  NATIVE_MINT,
 } from "@solana/spl-token";
 
+const MEMO_PROGRAM_ID = new PublicKey(
+  "MemoSq4gqABAXKb96qnH8TysNkWPKh6jYRrUCZwUT7Kz"
+);
+
 describe("vault2", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
 
@@ -29,12 +42,34 @@ describe("vault2", () => {
   let bobTokenAccount: PublicKey;
   let vaultTokenAccount: PublicKey;
 
+  // The provider wallet deployed the program, so it is the upgrade authority and config admin
+  const admin = (program.provider as anchor.AnchorProvider).wallet;
+  const configPDA = PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  )[0];
+  // The admin blocklist entry of a wallet
+  const blockedPDA = (address: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("blocked"), address.toBuffer()],
+      program.programId
+    )[0];
+
+  before(async () => {
+    await program.methods
+      .initializeConfig()
+      .accounts({
+        admin: admin.publicKey,
+        programData: getProgramDataAddress(program.programId),
+      })
+      .rpc();
+  });
+
   beforeEach(async () => {
     bob = anchor.web3.Keypair.generate();
     await airdrop(bob.publicKey, 2_000_000_000);
 
     statePDA = getStatePDA(bob.publicKey, program.programId);
-    vaultPDA = getVaultPDA(statePDA, program.programId);
     vaultAuthorityPDA = getVaultAuthorityPDA(statePDA, program.programId);
 
     // Create fresh token mint for each test
@@ -46,6 +81,9 @@ describe("vault2", () => {
       6
     );
 
+    // The vault token account is the vault authority's ATA for the mint
+    vaultPDA = getAssociatedTokenAddressSync(mint, vaultAuthorityPDA, true);
+
     // Create fresh user token account
     bobTokenAccount = await createAccount(
       program.provider.connection,
@@ -68,7 +106,7 @@ describe("vault2", () => {
   it("Is initialized!", async () => {
     // Initialize the vault with token mint
     const tx = await program.methods
-      .initialize(new BN(100 * 10 ** 6), mint) // 100 tokens target
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null) // 100 tokens target
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -99,7 +137,7 @@ describe("vault2", () => {
   it("Can deposit tokens!", async () => {
     // First initialize the vault
     await program.methods
-      .initialize(new BN(100 * 10 ** 6), mint)
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -131,6 +169,7 @@ describe("vault2", () => {
       .accounts({
         user: bob.publicKey,
         userTokenAccount: bobTokenAccount,
+        mint: mint,
         vaultTokenAccount: vaultTokenAccount,
         state: statePDA,
         vaultAuthority: vaultAuthorityPDA,
@@ -204,7 +243,7 @@ describe("vault2", () => {
   it("can withdraw tokens!", async () => {
     // First initialize and deposit tokens
     await program.methods
-      .initialize(new BN(100 * 10 ** 6), mint)
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -225,6 +264,7 @@ describe("vault2", () => {
       .accounts({
         user: bob.publicKey,
         userTokenAccount: bobTokenAccount,
+        mint: mint,
         vaultTokenAccount: vaultTokenAccount,
         state: statePDA,
         vaultAuthority: vaultAuthorityPDA,
@@ -250,6 +290,7 @@ describe("vault2", () => {
       .accounts({
         user: bob.publicKey,
         userTokenAccount: bobTokenAccount,
+        mint: mint,
         vaultTokenAccount: vaultTokenAccount,
         state: statePDA,
         vaultAuthority: vaultAuthorityPDA,
@@ -285,10 +326,139 @@ describe("vault2", () => {
     );
   });
 
+  it("names what's wrong with a withdrawal the vault can't cover", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const withdraw = (amount: number) =>
+      program.methods
+        .withdraw(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    for (const [amount, expected] of [
+      [0, "ZeroAmount"],
+      [51, "InsufficientVaultBalance"],
+    ] as const) {
+      try {
+        await withdraw(amount);
+        assert.fail("Withdrawal should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), expected);
+      }
+    }
+  });
+
+  it("rejects zero targets and zero deposits", async () => {
+    try {
+      await program.methods
+        .initialize(new BN(0), mint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Initialization should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "ZeroAmount");
+    }
+
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    try {
+      await program.methods
+        .deposit(new BN(0))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "ZeroAmount");
+    }
+  });
+
+  it("scales a UI amount target by the mint's decimals", async () => {
+    const initialize = (uiAmount: string, amount = 0) =>
+      program.methods
+        .initialize(new BN(amount), mint, null, null, uiAmount)
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // The mint has 6 decimals, so a seventh fractional digit can't be represented
+    try {
+      await initialize("12.5000001");
+      assert.fail("Initialization should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidUiAmount");
+    }
+
+    // A UI amount can't be combined with a raw amount, which would be ignored
+    try {
+      await initialize("12.5", 12_500_000);
+      assert.fail("Initialization should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidUiAmount");
+    }
+
+    await initialize("12.5");
+    const vault = await program.account.vault.fetch(statePDA);
+    assert.equal(vault.amount.toString(), (12.5 * 10 ** 6).toString());
+    assert.equal(vault.decimals, 6);
+  });
+
   it("can lock tokens in vault", async () => {
     // Initialize vault
     await program.methods
-      .initialize(new BN(100 * 10 ** 6), mint)
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -309,6 +479,7 @@ describe("vault2", () => {
       .accounts({
         user: bob.publicKey,
         userTokenAccount: bobTokenAccount,
+        mint: mint,
         vaultTokenAccount: vaultTokenAccount,
         state: statePDA,
         vaultAuthority: vaultAuthorityPDA,
@@ -317,10 +488,10 @@ describe("vault2", () => {
       .signers([bob])
       .rpc();
 
-    // Lock tokens for 1 hour (3600 seconds)
+    // Lock the deposited tokens for 1 hour (3600 seconds)
     const lockDuration = new BN(3600);
     await program.methods
-      .lockTokens(lockDuration)
+      .lockTokens(new BN(50 * 10 ** 6), lockDuration)
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -328,14 +499,14 @@ describe("vault2", () => {
       .signers([bob])
       .rpc();
 
-    // Verify the lock was set
+    // Verify the lock was set in the first tranche
     const stateAccount = await program.account.vault.fetch(statePDA);
-    assert.isNotNull(stateAccount.lockedUntil);
-    assert(stateAccount.lockedUntil !== null);
+    const tranche = stateAccount.lockTranches[0];
+    assert.equal(tranche.amount.toString(), (50 * 10 ** 6).toString());
 
-    // Verify locked_until is in the future (should be approximately current time + 3600)
+    // Verify the unlock time is in the future (should be approximately current time + 3600)
     const currentTime = Math.floor(Date.now() / 1000);
-    const lockedUntil = stateAccount.lockedUntil.toNumber();
+    const lockedUntil = tranche.unlockTs.toNumber();
     assert.isAtLeast(lockedUntil, currentTime);
     assert.isAtMost(lockedUntil, currentTime + 3700); // Allow some buffer for transaction time
   });
@@ -343,7 +514,7 @@ describe("vault2", () => {
   it("prevents withdrawal when tokens are locked", async () => {
     // Initialize vault
     await program.methods
-      .initialize(new BN(100 * 10 ** 6), mint)
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -364,6 +535,7 @@ describe("vault2", () => {
       .accounts({
         user: bob.publicKey,
         userTokenAccount: bobTokenAccount,
+        mint: mint,
         vaultTokenAccount: vaultTokenAccount,
         state: statePDA,
         vaultAuthority: vaultAuthorityPDA,
@@ -372,10 +544,10 @@ describe("vault2", () => {
       .signers([bob])
       .rpc();
 
-    // Lock tokens for 1 hour (3600 seconds)
+    // Lock the deposited tokens for 1 hour (3600 seconds)
     const lockDuration = new BN(3600);
     await program.methods
-      .lockTokens(lockDuration)
+      .lockTokens(new BN(50 * 10 ** 6), lockDuration)
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -391,6 +563,7 @@ describe("vault2", () => {
         .accounts({
           user: bob.publicKey,
           userTokenAccount: bobTokenAccount,
+          mint: mint,
           vaultTokenAccount: vaultTokenAccount,
           state: statePDA,
           vaultAuthority: vaultAuthorityPDA,
@@ -410,7 +583,7 @@ describe("vault2", () => {
   it("allows withdrawal when tokens are not locked", async () => {
     // Initialize vault
     await program.methods
-      .initialize(new BN(100 * 10 ** 6), mint)
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
       .accounts({
         user: bob.publicKey,
         state: statePDA,
@@ -431,6 +604,7 @@ describe("vault2", () => {
       .accounts({
         user: bob.publicKey,
         userTokenAccount: bobTokenAccount,
+        mint: mint,
         vaultTokenAccount: vaultTokenAccount,
         state: statePDA,
         vaultAuthority: vaultAuthorityPDA,
@@ -441,7 +615,9 @@ describe("vault2", () => {
 
     // Verify tokens are not locked initially
     const stateAccountBefore = await program.account.vault.fetch(statePDA);
-    assert.isNull(stateAccountBefore.lockedUntil);
+    assert.isTrue(
+      stateAccountBefore.lockTranches.every((t) => t.amount.isZero())
+    );
 
     // Withdraw should succeed when tokens are not locked
     const withdrawAmount = new BN(20 * 10 ** 6);
@@ -450,6 +626,7 @@ describe("vault2", () => {
       .accounts({
         user: bob.publicKey,
         userTokenAccount: bobTokenAccount,
+        mint: mint,
         vaultTokenAccount: vaultTokenAccount,
         state: statePDA,
         vaultAuthority: vaultAuthorityPDA,
@@ -469,27 +646,5134 @@ describe("vault2", () => {
     );
   });
 
-  // HELPERS
-  const airdrop = async (publicKey: anchor.web3.PublicKey, amount: number) => {
-    const sig = await program.provider.connection.requestAirdrop(
-      publicKey,
-      amount
+  it("only withdraws the part of the balance outside lock tranches", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(200 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // 100 tokens for 30 days and another 50 for 90 days
+    const lock = (amount: number, days: number) =>
+      program.methods
+        .lockTokens(new BN(amount * 10 ** 6), new BN(days * 24 * 60 * 60))
+        .accounts({ user: bob.publicKey })
+        .signers([bob])
+        .rpc();
+    await lock(100, 30);
+    await lock(50, 90);
+
+    const withdraw = (amount: number) =>
+      program.methods
+        .withdraw(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // 50 of the 200 tokens are outside the tranches
+    try {
+      await withdraw(51);
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TokensLocked");
+    }
+    await withdraw(50);
+
+    const vaultBalance = await getAccount(
+      program.provider.connection,
+      vaultPDA
     );
-    await program.provider.connection.confirmTransaction(sig, "confirmed");
-  };
+    assert.equal(vaultBalance.amount.toString(), (150 * 10 ** 6).toString());
+  });
 
-  // returns PDA of account
-  const getStatePDA = (user: PublicKey, programID: PublicKey) => {
-    return PublicKey.findProgramAddressSync(
-      [Buffer.from("state"), user.toBuffer()],
-      programID
-    )[0];
-  };
+  it("pledges an NFT until its lock expires", async () => {
+    const connection = program.provider.connection;
+    const nft = await createMint(connection, bob, bob.publicKey, null, 0);
+    const bobNftAccount = await createAssociatedTokenAccount(
+      connection,
+      bob,
+      nft,
+      bob.publicKey
+    );
+    await mintTo(connection, bob, nft, bobNftAccount, bob, 1);
+    // Without a mint authority, the single token is an NFT
+    await setAuthority(
+      connection,
+      bob,
+      nft,
+      bob,
+      AuthorityType.MintTokens,
+      null
+    );
+    const vaultNftAccount = getAssociatedTokenAddressSync(
+      nft,
+      vaultAuthorityPDA,
+      true
+    );
 
-  // returns PDA of vault account
-  const getVaultPDA = (state: PublicKey, programID: PublicKey) => {
-    return PublicKey.findProgramAddressSync(
-      [Buffer.from("vault"), state.toBytes()],
+    await program.methods
+      .initialize(new BN(1), nft, null, null, null)
+      .accounts({ user: bob.publicKey, mint: nft, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .lockTokens(new BN(1), new BN(3600))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    // Depositing the NFT meets the "target" but doesn't bounce it back
+    await program.methods
+      .deposit(new BN(1))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobNftAccount,
+        mint: nft,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const vaultAccount = await getAccount(connection, vaultNftAccount);
+    assert.equal(vaultAccount.amount.toString(), "1");
+
+    // The NFT stays pledged while it's locked
+    try {
+      await program.methods
+        .finalize()
+        .accounts({
+          user: bob.publicKey,
+          vaultTokenAccount: vaultNftAccount,
+          userTokenAccount: bobNftAccount,
+          mint: nft,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Finalize should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TargetNotReached");
+    }
+    try {
+      await program.methods
+        .withdraw(new BN(1))
+        .accounts({
+          user: bob.publicKey,
+          mint: nft,
+          vaultTokenAccount: vaultNftAccount,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdraw should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TokensLocked");
+    }
+  });
+
+  it("advances part of the locked balance until it's repaid", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(200 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .lockTokens(new BN(100 * 10 ** 6), new BN(3600))
+      .accounts({ user: bob.publicKey, state: statePDA })
+      .signers([bob])
+      .rpc();
+
+    // Advance fees are paid to the treasury, so there are none without one
+    const treasury = Keypair.generate();
+    const treasuryTokenAccount = await createAssociatedTokenAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      treasury.publicKey
+    );
+    const setTreasury = (treasury: PublicKey) =>
+      program.methods
+        .setDepositFee(0, treasury)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    await setTreasury(treasury.publicKey);
+
+    const borrow = (amount: number) =>
+      program.methods
+        .borrow(new BN(amount * 10 ** 6))
+        .accounts({ user: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+        .signers([bob])
+        .rpc();
+    const withdraw = (amount: number) =>
+      program.methods
+        .withdraw(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    try {
+      // At most half of the 100 locked tokens
+      try {
+        await borrow(60);
+        assert.fail("Advance should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "AdvanceLimitExceeded");
+      }
+
+      const sig = await borrow(40);
+      const events = await getCpiEvents(sig);
+      const taken = events.find((e) => e.name === "advanceTaken");
+      assert.isDefined(taken);
+      assert.equal(taken.data.debt.toString(), "40400000");
+
+      // 60 of the remaining 160 tokens are unlocked, but 40.4 of them are owed
+      try {
+        await withdraw(20);
+        assert.fail("Withdrawal should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "AdvanceOutstanding");
+      }
+
+      // Paying back more than is owed settles the debt in full, with the fee going to the
+      // treasury
+      const [advancePDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("advance"), statePDA.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .repay(new BN(50 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          treasuryTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.isNull(await program.provider.connection.getAccountInfo(advancePDA));
+      const vault = await getAccount(program.provider.connection, vaultPDA);
+      const fees = await getAccount(
+        program.provider.connection,
+        treasuryTokenAccount
+      );
+      assert.equal(vault.amount.toString(), (200 * 10 ** 6).toString());
+      assert.equal(fees.amount.toString(), "400000");
+
+      await withdraw(20);
+    } finally {
+      // Other tests run without a treasury
+      await setTreasury(PublicKey.default);
+    }
+  });
+
+  it("streams the balance out between the unlock stream's timestamps", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(200 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // A 100-day stream that is about halfway through
+    const day = 24 * 60 * 60;
+    const now = Math.floor(Date.now() / 1000);
+    const setStream = () =>
+      program.methods
+        .setUnlockStream(new BN(now - 50 * day), new BN(now + 50 * day), null)
+        .accounts({ user: bob.publicKey, vaultTokenAccount: vaultPDA })
+        .signers([bob])
+        .rpc();
+    await setStream();
+
+    const withdraw = (amount: number) =>
+      program.methods
+        .withdraw(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // Only about half of the 200 tokens has been released so far
+    try {
+      await withdraw(110);
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TokensLocked");
+    }
+    await withdraw(90);
+
+    // The running stream can't be restarted to release more
+    try {
+      await setStream();
+      assert.fail("Replacing the stream should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "UnlockStreamActive");
+    }
+  });
+
+  it("holds the whole stream back until its cliff", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(200 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Halfway through the stream, but the cliff is still ten days out
+    const day = 24 * 60 * 60;
+    const now = Math.floor(Date.now() / 1000);
+    const setStream = (cliff: number) =>
+      program.methods
+        .setUnlockStream(
+          new BN(now - 50 * day),
+          new BN(now + 50 * day),
+          new BN(cliff)
+        )
+        .accounts({ user: bob.publicKey, vaultTokenAccount: vaultPDA })
+        .signers([bob])
+        .rpc();
+
+    // The cliff has to fall within the stream
+    try {
+      await setStream(now + 60 * day);
+      assert.fail("Setting the stream should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidUnlockStream");
+    }
+    await setStream(now + 10 * day);
+
+    try {
+      await program.methods
+        .withdraw(new BN(1 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TokensLocked");
+    }
+  });
+
+  it("spaces withdrawals out by the vault's cooldown", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const setCooldown = (hours: number) =>
+      program.methods
+        .setWithdrawCooldown(new BN(hours * 60 * 60))
+        .accounts({ user: bob.publicKey })
+        .signers([bob])
+        .rpc();
+    const withdraw = () =>
+      program.methods
+        .withdraw(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    await setCooldown(24);
+    await withdraw();
+
+    // The second withdrawal has to wait a day
+    try {
+      await withdraw();
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "WithdrawalCooldown");
+    }
+
+    // Shortening the cooldown doesn't skip the running one
+    try {
+      await setCooldown(0);
+      assert.fail("Shortening the cooldown should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "WithdrawalCooldown");
+    }
+
+    const vault = await program.account.vault.fetch(statePDA);
+    assert.equal(vault.cooldownSeconds.toString(), (24 * 60 * 60).toString());
+    assert.isTrue(vault.lastWithdrawTs.gtn(0));
+  });
+
+  it("caps withdrawals over a day at the vault's limit", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .setWithdrawLimit(new BN(30 * 10 ** 6))
+      .accounts({ user: bob.publicKey, vaultKey: null })
+      .signers([bob])
+      .rpc();
+
+    const withdraw = (amount: number) =>
+      program.methods
+        .withdraw(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // 20 + 10 fit in the day's limit, anything more doesn't
+    await withdraw(20);
+    await withdraw(10);
+    try {
+      await withdraw(1);
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "RateLimitExceeded");
+    }
+
+    const vault = await program.account.vault.fetch(statePDA);
+    assert.equal(vault.withdrawnInWindow.toString(), (30 * 10 ** 6).toString());
+  });
+
+  it("makes delayed vaults request withdrawals ahead of time", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .setWithdrawalDelay(new BN(48 * 60 * 60))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    // Direct withdrawals are no longer allowed
+    try {
+      await program.methods
+        .withdraw(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "WithdrawalDelayRequired");
+    }
+
+    await program.methods
+      .requestWithdrawal(new BN(10 * 10 ** 6))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const pendingPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("withdrawal"), statePDA.toBuffer()],
+      program.programId
+    )[0];
+    const pending = await program.account.pendingWithdrawal.fetch(pendingPDA);
+    assert.equal(
+      pending.executableAt.sub(pending.requestedAt).toString(),
+      (48 * 60 * 60).toString()
+    );
+
+    // The request can't be executed before its delay has passed
+    try {
+      await program.methods
+        .executeWithdrawal()
+        .accounts({
+          user: bob.publicKey,
+          vaultKey: null,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Execution should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "WithdrawalNotReady");
+    }
+
+    // ... but it can be cancelled
+    await program.methods
+      .cancelWithdrawal()
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    assert.isNull(await program.provider.connection.getAccountInfo(pendingPDA));
+  });
+
+  it("refuses every withdrawal while the owner has frozen themselves", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const selfFreeze = (days: number) =>
+      program.methods
+        .selfFreeze(new BN(days * 24 * 60 * 60))
+        .accounts({ user: bob.publicKey })
+        .signers([bob])
+        .rpc();
+
+    await selfFreeze(30);
+
+    // The tokens aren't locked, but the freeze still refuses the withdrawal
+    try {
+      await program.methods
+        .withdraw(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "SelfExcluded");
+    }
+
+    // A shorter freeze doesn't lift the running one
+    const selfExclusionPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("self_exclusion"), statePDA.toBuffer()],
+      program.programId
+    )[0];
+    const before = await program.account.selfExclusion.fetch(selfExclusionPDA);
+    await selfFreeze(1);
+    const after = await program.account.selfExclusion.fetch(selfExclusionPDA);
+    assert.equal(after.frozenUntil.toString(), before.frozenUntil.toString());
+
+    // Nor can the owner set up anything that pays the vault out during the freeze
+    const refused = [
+      program.methods
+        .setReleaseOnUnlock(true)
+        .accounts({ user: bob.publicKey, vaultKey: null }),
+      program.methods
+        .setPriceCondition(Keypair.generate().publicKey, new BN(1), {
+          atOrAbove: {},
+        })
+        .accounts({ user: bob.publicKey, vaultKey: null }),
+      program.methods
+        .setGuardians([Keypair.generate().publicKey], 1)
+        .accounts({ user: bob.publicKey, vaultKey: null }),
+    ];
+    for (const instruction of refused) {
+      try {
+        await instruction.signers([bob]).rpc();
+        assert.fail("Instruction should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "SelfExcluded");
+      }
+    }
+  });
+
+  it("keeps a self-excluded vault open until the freeze ends", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .selfFreeze(new BN(24 * 60 * 60))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    // Reaching the target still releases the savings
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // ... but closing the vault would drop the freeze for the next one
+    try {
+      await program.methods
+        .finalize()
+        .accounts({
+          user: bob.publicKey,
+          vaultTokenAccount: vaultPDA,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Finalize should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "SelfExcluded");
+    }
+    try {
+      await program.methods
+        .closeVault()
+        .accounts({
+          user: bob.publicKey,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Close should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "SelfExcluded");
+    }
+    assert.isNotNull(await program.provider.connection.getAccountInfo(statePDA));
+  });
+
+  it("executes withdrawals once enough approvers approve them", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // 2-of-3 approvers
+    const approvers = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    await program.methods
+      .setWithdrawalApprovers(
+        approvers.map((approver) => approver.publicKey),
+        2
+      )
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .requestWithdrawal(new BN(10 * 10 ** 6))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const approve = (approver: Keypair) =>
+      program.methods
+        .approveWithdrawal()
+        .accounts({ approver: approver.publicKey, owner: bob.publicKey })
+        .signers([approver])
+        .rpc();
+    const execute = () =>
+      program.methods
+        .executeWithdrawal()
+        .accounts({
+          user: bob.publicKey,
+          vaultKey: null,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // One approval, even given twice, isn't enough
+    await approve(approvers[0]);
+    await approve(approvers[0]);
+    try {
+      await execute();
+      assert.fail("Execution should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "WithdrawalApprovalRequired");
+    }
+
+    await approve(approvers[2]);
+    await execute();
+
+    const vaultBalance = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(vaultBalance.amount.toString(), (90 * 10 ** 6).toString());
+  });
+
+  it("lets guardians recover a vault unless the owner vetoes", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // The provider wallet pays for the proposal, so it is one of the guardians
+    const guardian = Keypair.generate();
+    await program.methods
+      .setGuardians([admin.publicKey, guardian.publicKey], 2)
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const newOwner = Keypair.generate().publicKey;
+    await program.methods
+      .proposeRecovery(newOwner)
+      .accounts({ guardian: admin.publicKey, owner: bob.publicKey })
+      .rpc();
+
+    const execute = () =>
+      program.methods
+        .executeRecovery()
+        .accounts({
+          caller: admin.publicKey,
+          owner: bob.publicKey,
+          newOwner,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          proposer: admin.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    // One vote isn't a quorum
+    try {
+      await execute();
+      assert.fail("Recovery should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "RecoveryNotApproved");
+    }
+
+    // A quorum still has to wait out the veto period
+    await program.methods
+      .voteRecovery()
+      .accounts({ guardian: guardian.publicKey, owner: bob.publicKey })
+      .signers([guardian])
+      .rpc();
+    try {
+      await execute();
+      assert.fail("Recovery should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "RecoveryNotReady");
+    }
+
+    // ... during which the owner can veto it
+    await program.methods
+      .vetoRecovery()
+      .accounts({ user: bob.publicKey, proposer: admin.publicKey })
+      .signers([bob])
+      .rpc();
+    const proposalPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("recovery"), statePDA.toBuffer()],
+      program.programId
+    )[0];
+    assert.isNull(await program.provider.connection.getAccountInfo(proposalPDA));
+  });
+
+  it("needs the vault key and the approvers to change guardians", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({ user: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+    const vaultKey = Keypair.generate();
+    await program.methods
+      .setVaultKey(vaultKey.publicKey, new BN(0))
+      .accounts({ user: bob.publicKey, currentVaultKey: null })
+      .signers([bob])
+      .rpc();
+    const approver = Keypair.generate();
+    await program.methods
+      .setWithdrawalApprovers([approver.publicKey], 1)
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const guardians = [Keypair.generate().publicKey];
+    const setGuardians = (signers: Keypair[], vaultKeySigner: Keypair | null) =>
+      program.methods
+        .setGuardians(guardians, 1)
+        .accounts({
+          user: bob.publicKey,
+          vaultKey: vaultKeySigner ? vaultKeySigner.publicKey : null,
+        })
+        .remainingAccounts(
+          signers.map((signer) => ({
+            pubkey: signer.publicKey,
+            isSigner: true,
+            isWritable: false,
+          }))
+        )
+        .signers([bob, ...(vaultKeySigner ? [vaultKeySigner] : []), ...signers])
+        .rpc();
+
+    // Guardians can hand the vault over, so the owner key alone can't name them
+    try {
+      await setGuardians([approver], null);
+      assert.fail("Set guardians should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultKeyRequired");
+    }
+    try {
+      await setGuardians([], vaultKey);
+      assert.fail("Set guardians should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "WithdrawalApprovalRequired");
+    }
+
+    await setGuardians([approver], vaultKey);
+  });
+
+  it("ranks a submitted vault on its mint's leaderboard", async () => {
+    const connection = program.provider.connection;
+    const carol = Keypair.generate();
+    await airdrop(carol.publicKey, 1_000_000_000);
+    const carolTokenAccount = await createAccount(
+      connection,
+      carol,
+      mint,
+      carol.publicKey
+    );
+    await mintTo(connection, bob, mint, carolTokenAccount, bob, 100 * 10 ** 6);
+
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({ user: carol.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([carol])
+      .rpc();
+    await program.methods
+      .deposit(new BN(30 * 10 ** 6))
+      .accounts({
+        user: carol.publicKey,
+        userTokenAccount: carolTokenAccount,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([carol])
+      .rpc();
+
+    const [carolState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("state"), carol.publicKey.toBuffer()],
+      program.programId
+    );
+    const [carolAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), carolState.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .updateLeaderboard()
+      .accounts({
+        payer: admin.publicKey,
+        mint,
+        state: carolState,
+        vaultTokenAccount: getAssociatedTokenAddressSync(
+          mint,
+          carolAuthority,
+          true
+        ),
+      })
+      .rpc();
+
+    const [leaderboardPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("leaderboard"), mint.toBuffer()],
+      program.programId
+    );
+    const leaderboard = await program.account.leaderboard.fetch(leaderboardPDA);
+    const byBalance = leaderboard.topBalances.find((entry) =>
+      entry.state.equals(carolState)
+    );
+    assert.equal(byBalance.value.toString(), (30 * 10 ** 6).toString());
+    const byStreak = leaderboard.topStreaks.find((entry) =>
+      entry.state.equals(carolState)
+    );
+    assert.equal(byStreak.value.toString(), "1");
+  });
+
+  it("pays each savings circle round to the next member", async () => {
+    const connection = program.provider.connection;
+    const alice = Keypair.generate();
+    await airdrop(alice.publicKey, 1_000_000_000);
+    const aliceTokenAccount = await createAccount(
+      connection,
+      alice,
+      mint,
+      alice.publicKey
+    );
+    await mintTo(connection, bob, mint, aliceTokenAccount, bob, 100 * 10 ** 6);
+
+    const circleId = new BN(1);
+    const [circlePDA] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("circle"),
+        bob.publicKey.toBuffer(),
+        circleId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    await program.methods
+      .createCircle(
+        circleId,
+        [bob.publicKey, alice.publicKey],
+        new BN(10 * 10 ** 6),
+        new BN(24 * 60 * 60)
+      )
+      .accounts({ creator: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+
+    const contribute = (member: Keypair, memberTokenAccount: PublicKey) =>
+      program.methods
+        .contributeToCircle()
+        .accounts({
+          member: member.publicKey,
+          circle: circlePDA,
+          mint,
+          memberTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([member])
+        .rpc();
+    const payout = (recipient: PublicKey) =>
+      program.methods
+        .payoutCircle()
+        .accounts({
+          caller: admin.publicKey,
+          circle: circlePDA,
+          recipient,
+          mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    await contribute(bob, bobTokenAccount);
+    try {
+      await contribute(bob, bobTokenAccount);
+      assert.fail("Contribution should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "CircleAlreadyPaid");
+    }
+
+    // The round isn't over until everyone has paid or its period has passed
+    try {
+      await payout(bob.publicKey);
+      assert.fail("Payout should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "CircleRoundNotOver");
+    }
+    await contribute(alice, aliceTokenAccount);
+
+    // The first round goes to the first member, and no one else
+    try {
+      await payout(alice.publicKey);
+      assert.fail("Payout should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidCircleRecipient");
+    }
+    const before = await getAccount(connection, bobTokenAccount);
+    await payout(bob.publicKey);
+
+    const after = await getAccount(connection, bobTokenAccount);
+    assert.equal(
+      (after.amount - before.amount).toString(),
+      (20 * 10 ** 6).toString()
+    );
+    const circle = await program.account.circle.fetch(circlePDA);
+    assert.equal(circle.round, 1);
+  });
+
+  it("streams accrued pay to the employee until the employer stops it", async () => {
+    const connection = program.provider.connection;
+    const alice = Keypair.generate();
+    await airdrop(alice.publicKey, 1_000_000_000);
+    const aliceTokenAccount = await createAccount(
+      connection,
+      alice,
+      mint,
+      alice.publicKey
+    );
+
+    // Bob pays alice 10 tokens a second and funds 100 tokens up front
+    const [payrollPDA] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("payroll"),
+        bob.publicKey.toBuffer(),
+        alice.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+    const payrollTokenAccount = getAssociatedTokenAddressSync(
+      mint,
+      payrollPDA,
+      true
+    );
+    await program.methods
+      .createPayroll(new BN(10 * 10 ** 6), new BN(1))
+      .accounts({
+        employer: bob.publicKey,
+        employee: alice.publicKey,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .fundPayroll(new BN(100 * 10 ** 6))
+      .accounts({
+        employer: bob.publicKey,
+        payroll: payrollPDA,
+        mint,
+        employerTokenAccount: bobTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const claim = () =>
+      program.methods
+        .claimPayroll()
+        .accounts({
+          employee: alice.publicKey,
+          payroll: payrollPDA,
+          mint,
+          employeeTokenAccount: aliceTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([alice])
+        .rpc();
+
+    // Alice claims whole pay periods as they complete
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+    await claim();
+    const claimed = (await getAccount(connection, aliceTokenAccount)).amount;
+    assert.isTrue(claimed > BigInt(0));
+    assert.equal(claimed % BigInt(10 * 10 ** 6), BigInt(0));
+
+    // Stopping returns only the funding alice hasn't earned yet
+    const before = await getAccount(connection, bobTokenAccount);
+    await program.methods
+      .stopPayroll()
+      .accounts({
+        employer: bob.publicKey,
+        payroll: payrollPDA,
+        mint,
+        employerTokenAccount: bobTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const after = await getAccount(connection, bobTokenAccount);
+    const refunded = after.amount - before.amount;
+    const owed = (await getAccount(connection, payrollTokenAccount)).amount;
+    assert.equal(
+      (claimed + refunded + owed).toString(),
+      (100 * 10 ** 6).toString()
+    );
+
+    // Once alice has all her accrued pay, bob closes the payroll
+    if (owed > BigInt(0)) {
+      await claim();
+    }
+    assert.equal(
+      (await getAccount(connection, aliceTokenAccount)).amount.toString(),
+      (claimed + owed).toString()
+    );
+    await program.methods
+      .closePayroll()
+      .accounts({
+        employer: bob.publicKey,
+        payroll: payrollPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    assert.isNull(await connection.getAccountInfo(payrollPDA));
+  });
+
+  it("releases an escrow to the counterparty once two parties approve", async () => {
+    const connection = program.provider.connection;
+    const seller = Keypair.generate();
+    const arbiter = Keypair.generate();
+
+    // Bob escrows 40 tokens for the seller, with an arbiter, for an hour
+    const escrowId = new BN(1);
+    const [escrowPDA] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("escrow"),
+        bob.publicKey.toBuffer(),
+        escrowId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const timeout = Math.floor(Date.now() / 1000) + 3_600;
+    await program.methods
+      .createEscrow(
+        escrowId,
+        seller.publicKey,
+        arbiter.publicKey,
+        new BN(40 * 10 ** 6),
+        new BN(timeout)
+      )
+      .accounts({
+        depositor: bob.publicKey,
+        mint,
+        depositorTokenAccount: bobTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const approve = (approver: Keypair) =>
+      program.methods
+        .approveEscrow()
+        .accounts({ approver: approver.publicKey, escrow: escrowPDA })
+        .signers([approver])
+        .rpc();
+    const release = () =>
+      program.methods
+        .releaseEscrow()
+        .accounts({
+          caller: admin.publicKey,
+          escrow: escrowPDA,
+          depositor: bob.publicKey,
+          counterparty: seller.publicKey,
+          mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    // One approval isn't enough, and the depositor can't take it back before the timeout
+    await approve(seller);
+    try {
+      await release();
+      assert.fail("Release should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "EscrowNotApproved");
+    }
+    try {
+      await program.methods
+        .refundEscrow()
+        .accounts({
+          depositor: bob.publicKey,
+          escrow: escrowPDA,
+          mint,
+          depositorTokenAccount: bobTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Refund should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "EscrowNotExpired");
+    }
+
+    // The arbiter's approval stands in for bob's
+    await approve(arbiter);
+    await release();
+
+    const sellerTokenAccount = getAssociatedTokenAddressSync(
+      mint,
+      seller.publicKey
+    );
+    const sellerBalance = await getAccount(connection, sellerTokenAccount);
+    assert.equal(sellerBalance.amount.toString(), (40 * 10 ** 6).toString());
+    assert.isNull(await connection.getAccountInfo(escrowPDA));
+  });
+
+  it("pays a hashlock contract to whoever reveals the preimage", async () => {
+    const connection = program.provider.connection;
+    const counterparty = Keypair.generate();
+    const secret = Buffer.alloc(32, 7);
+    const hashlock = createHash("sha256").update(secret).digest();
+
+    const htlcId = new BN(1);
+    const [htlcPDA] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("htlc"),
+        bob.publicKey.toBuffer(),
+        htlcId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    const timeout = Math.floor(Date.now() / 1000) + 3_600;
+    await program.methods
+      .createHtlc(
+        htlcId,
+        counterparty.publicKey,
+        Array.from(hashlock),
+        new BN(30 * 10 ** 6),
+        new BN(timeout)
+      )
+      .accounts({
+        owner: bob.publicKey,
+        mint,
+        ownerTokenAccount: bobTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const claim = (preimage: Buffer) =>
+      program.methods
+        .claimHtlc(Array.from(preimage))
+        .accounts({
+          caller: admin.publicKey,
+          htlc: htlcPDA,
+          owner: bob.publicKey,
+          counterparty: counterparty.publicKey,
+          mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    try {
+      await claim(Buffer.alloc(32, 8));
+      assert.fail("Claim should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "WrongPreimage");
+    }
+
+    // The claim publishes the secret for the other leg of the swap
+    const signature = await claim(secret);
+    const events = await getCpiEvents(signature);
+    const claimed = events.find((e) => e.name === "htlcClaimed");
+    assert.isDefined(claimed);
+    assert.deepEqual(Buffer.from(claimed.data.preimage), secret);
+
+    const counterpartyBalance = await getAccount(
+      connection,
+      getAssociatedTokenAddressSync(mint, counterparty.publicKey)
+    );
+    assert.equal(counterpartyBalance.amount.toString(), (30 * 10 ** 6).toString());
+    assert.isNull(await connection.getAccountInfo(htlcPDA));
+  });
+
+  it("lets a session key deposit and withdraw up to its cap", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // The session key holds tokens of its own, and pays for bob's token account
+    const session = Keypair.generate();
+    await airdrop(session.publicKey, 1_000_000_000);
+    const sessionTokenAccount = await createAssociatedTokenAccount(
+      program.provider.connection,
+      session,
+      mint,
+      session.publicKey
+    );
+    await mintTo(
+      program.provider.connection,
+      bob,
+      mint,
+      sessionTokenAccount,
+      bob,
+      50 * 10 ** 6
+    );
+
+    const expiry = Math.floor(Date.now() / 1000) + 3_600;
+    await program.methods
+      .grantDelegate(
+        session.publicKey,
+        { deposit: true, withdraw: true },
+        new BN(expiry),
+        new BN(10 * 10 ** 6)
+      )
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const deposit = (amount: number) =>
+      program.methods
+        .sessionDeposit(new BN(amount * 10 ** 6))
+        .accounts({
+          delegate: session.publicKey,
+          owner: bob.publicKey,
+          delegateTokenAccount: sessionTokenAccount,
+          vaultTokenAccount: vaultPDA,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([session])
+        .rpc();
+    const withdraw = (amount: number) =>
+      program.methods
+        .sessionWithdraw(new BN(amount * 10 ** 6))
+        .accounts({
+          delegate: session.publicKey,
+          owner: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([session])
+        .rpc();
+
+    await deposit(20);
+    await withdraw(6);
+    const bobAta = await getAccount(
+      program.provider.connection,
+      getAssociatedTokenAddressSync(mint, bob.publicKey)
+    );
+    assert.equal(bobAta.amount.toString(), (6 * 10 ** 6).toString());
+
+    // The cap covers the whole session, not each withdrawal
+    try {
+      await withdraw(6);
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "DelegateLimitExceeded");
+    }
+
+    // Once revoked the session key can't touch the vault
+    await program.methods
+      .revokeDelegate()
+      .accounts({
+        user: bob.publicKey,
+        session: PublicKey.findProgramAddressSync(
+          [Buffer.from("delegate"), statePDA.toBuffer(), session.publicKey.toBuffer()],
+          program.programId
+        )[0],
+      })
+      .signers([bob])
+      .rpc();
+    try {
+      await deposit(1);
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "AccountOwnedByWrongProgram");
+    }
+  });
+
+  it("lets a merchant pull its subscription once per interval", async () => {
+    const connection = program.provider.connection;
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const merchant = Keypair.generate();
+    await airdrop(merchant.publicKey, 1_000_000_000);
+    const merchantTokenAccount = await createAccount(
+      connection,
+      merchant,
+      mint,
+      merchant.publicKey
+    );
+
+    // 5 tokens a month
+    const subscriptionPDA = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("subscription"),
+        statePDA.toBuffer(),
+        merchant.publicKey.toBuffer(),
+      ],
+      program.programId
+    )[0];
+    await program.methods
+      .grantSubscription(
+        merchant.publicKey,
+        new BN(5 * 10 ** 6),
+        new BN(30 * 24 * 60 * 60)
+      )
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const pull = () =>
+      program.methods
+        .pullSubscription()
+        .accounts({
+          merchant: merchant.publicKey,
+          owner: bob.publicKey,
+          mint: mint,
+          merchantTokenAccount,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([merchant])
+        .rpc();
+
+    await pull();
+    const merchantBalance = await getAccount(connection, merchantTokenAccount);
+    assert.equal(merchantBalance.amount.toString(), (5 * 10 ** 6).toString());
+
+    // The next pull isn't due for another month
+    try {
+      await pull();
+      assert.fail("Pull should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "SubscriptionNotDue");
+    }
+
+    // Once revoked, the merchant can't pull at all
+    await program.methods
+      .revokeSubscription()
+      .accounts({ user: bob.publicKey, subscription: subscriptionPDA })
+      .signers([bob])
+      .rpc();
+    try {
+      await pull();
+      assert.fail("Pull should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "AccountNotInitialized");
+    }
+  });
+
+  it("refuses to migrate a vault that is already on the current layout", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const state = await program.account.vault.fetch(statePDA);
+    assert.equal(state.version, 4);
+    assert.equal(state.decimals, 6);
+
+    try {
+      await program.methods
+        .migrateVault()
+        .accounts({ payer: bob.publicKey, owner: bob.publicKey, mint: mint })
+        .signers([bob])
+        .rpc();
+      assert.fail("Expected the migration to fail");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultNotMigratable");
+    }
+  });
+
+  it("records balance snapshots in the zero-copy vault extension", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .initVaultExtension()
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const snapshot = () =>
+      program.methods
+        .recordBalanceSnapshot()
+        .accounts({ owner: bob.publicKey, vaultTokenAccount: vaultPDA })
+        .rpc();
+
+    // Anyone can record a snapshot, but only one per interval
+    await snapshot();
+    try {
+      await snapshot();
+      assert.fail("Snapshot should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "SnapshotTooSoon");
+    }
+
+    const extensionPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("extension"), statePDA.toBuffer()],
+      program.programId
+    )[0];
+    const extension = await program.account.vaultExtension.fetch(extensionPDA);
+    assert.equal(extension.state.toString(), statePDA.toString());
+    assert.equal(extension.snapshotCount.toNumber(), 1);
+    assert.equal(extension.snapshots[0].balance.toNumber(), 0);
+  });
+
+  it("attaches memos to deposits and withdrawals", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const extensionPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("extension"), statePDA.toBuffer()],
+      program.programId
+    )[0];
+    const depositWithMemo = (memo: string) =>
+      program.methods
+        .depositWithMemo(new BN(10 * 10 ** 6), memo)
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          extension: extensionPDA,
+        })
+        .signers([bob])
+        .rpc();
+
+    // The memo hash lives on the vault extension, so it has to exist first
+    try {
+      await depositWithMemo("invoice 42");
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "AccountOwnedByWrongProgram");
+    }
+    await program.methods
+      .initVaultExtension()
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    await depositWithMemo("invoice 42");
+    let extension = await program.account.vaultExtension.fetch(extensionPDA);
+    assert.deepEqual(
+      Buffer.from(extension.lastMemoHash),
+      createHash("sha256").update("invoice 42").digest()
+    );
+
+    await program.methods
+      .withdrawWithMemo(new BN(4 * 10 ** 6), "payout 7")
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        extension: extensionPDA,
+      })
+      .signers([bob])
+      .rpc();
+    extension = await program.account.vaultExtension.fetch(extensionPDA);
+    assert.deepEqual(
+      Buffer.from(extension.lastMemoHash),
+      createHash("sha256").update("payout 7").digest()
+    );
+
+    try {
+      await depositWithMemo("x".repeat(129));
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidMemo");
+    }
+  });
+
+  it("tracks per-mint totals in the mint stats", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .withdraw(new BN(30 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const stats = await program.account.mintStats.fetch(
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("stats"), mint.toBuffer()],
+        program.programId
+      )[0]
+    );
+    assert.equal(stats.totalVaults.toNumber(), 1);
+    assert.equal(stats.totalDeposited.toString(), (100 * 10 ** 6).toString());
+    assert.equal(stats.totalWithdrawn.toString(), (30 * 10 ** 6).toString());
+    assert.equal(stats.totalReleased.toNumber(), 0);
+  });
+
+  it("wraps lamports on deposit_sol and unwraps them on withdraw_sol", async () => {
+    const connection = program.provider.connection;
+    const wsolVault = getAssociatedTokenAddressSync(
+      NATIVE_MINT,
+      vaultAuthorityPDA,
+      true
+    );
+    await program.methods
+      .initialize(new BN(10 * LAMPORTS_PER_SOL), NATIVE_MINT, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: NATIVE_MINT,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const bobWsol = await createAssociatedTokenAccount(
+      connection,
+      bob,
+      NATIVE_MINT,
+      bob.publicKey
+    );
+
+    const depositAmount = new BN(LAMPORTS_PER_SOL / 2);
+    await program.methods
+      .depositSol(depositAmount)
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobWsol,
+        mint: NATIVE_MINT,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+    const vaultAccount = await getAccount(connection, wsolVault);
+    assert.equal(vaultAccount.amount.toString(), depositAmount.toString());
+
+    // Withdrawing closes bob's wSOL account and pays him plain SOL
+    const withdrawAmount = new BN(LAMPORTS_PER_SOL / 5);
+    const lamportsBefore = await connection.getBalance(bob.publicKey);
+    await program.methods
+      .withdrawSol(withdrawAmount)
+      .accounts({
+        user: bob.publicKey,
+        mint: NATIVE_MINT,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    assert.isNull(await connection.getAccountInfo(bobWsol));
+    const lamportsAfter = await connection.getBalance(bob.publicKey);
+    assert.isAbove(
+      lamportsAfter - lamportsBefore,
+      withdrawAmount.toNumber() - 10_000
+    );
+  });
+
+  it("emits deposit events through CPI", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    const sig = await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+    const events = await getCpiEvents(sig);
+    const deposited = events.find((e) => e.name === "tokensDeposited");
+    assert.isDefined(deposited);
+    assert.equal(deposited.data.state.toString(), statePDA.toString());
+    assert.equal(deposited.data.amount.toString(), (50 * 10 ** 6).toString());
+
+    // Target not reached yet, so nothing was released
+    assert.isUndefined(events.find((e) => e.name === "savingsTargetReached"));
+  });
+
+  it("reports vault status through return data", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(25 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .lockTokens(new BN(10 * 10 ** 6), new BN(3600))
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+      })
+      .signers([bob])
+      .rpc();
+
+    const status = await program.methods
+      .getVaultStatus()
+      .accounts({
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        mint: mint,
+      })
+      .view();
+
+    assert.equal(status.balance.toString(), (25 * 10 ** 6).toString());
+    assert.equal(status.target.toString(), (100 * 10 ** 6).toString());
+    assert.equal(status.decimals, 6);
+    assert.equal(status.progressBps, 2500);
+    assert.equal(status.unlocked.toString(), (15 * 10 ** 6).toString());
+    assert.equal(status.lockTranches.length, 1);
+    assert.isAbove(status.secondsRemaining.toNumber(), 0);
+    assert.isAtMost(status.secondsRemaining.toNumber(), 3600);
+  });
+
+  it("rejects lock durations above the published maximum", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    // The bound is published in the IDL so clients validate against the same value
+    const maxLock = program.idl.constants.find(
+      (c) => c.name === "maxLockDurationSeconds"
+    );
+    const tooLong = new BN(maxLock.value).add(new BN(1));
+
+    try {
+      await program.methods
+        .lockTokens(new BN(1), tooLong)
+        .accounts({
+          user: bob.publicKey,
+          state: statePDA,
+        })
+        .signers([bob])
+        .rpc();
+
+      assert.fail("Lock should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "LockDurationTooLong");
+    }
+  });
+
+  it("closes an empty vault and refunds its rent", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .closeVault()
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Both the state and the vault token account are gone
+    assert.isNull(await program.provider.connection.getAccountInfo(statePDA));
+    assert.isNull(await program.provider.connection.getAccountInfo(vaultPDA));
+  });
+
+  it("closes a vault's children with it, so a re-created vault starts clean", async () => {
+    const initialize = () =>
+      program.methods
+        .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const closeVault = () =>
+      program.methods
+        .closeVault()
+        .accounts({
+          user: bob.publicKey,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const conditionPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("price_condition"), statePDA.toBuffer()],
+      program.programId
+    )[0];
+    const delegate = Keypair.generate().publicKey;
+
+    await initialize();
+    await program.methods
+      .setPriceCondition(Keypair.generate().publicKey, new BN(1), {
+        atOrAbove: {},
+      })
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .grantDelegate(
+        delegate,
+        { deposit: true, withdraw: false },
+        new BN(Math.floor(Date.now() / 1000) + 3600),
+        new BN(0)
+      )
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    assert.equal((await program.account.vault.fetch(statePDA)).openGrants, 1);
+
+    // An open session would carry over to the next vault at this address
+    try {
+      await closeVault();
+      assert.fail("Close should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "GrantsOutstanding");
+    }
+
+    await program.methods
+      .revokeDelegate()
+      .accounts({
+        user: bob.publicKey,
+        session: PublicKey.findProgramAddressSync(
+          [Buffer.from("delegate"), statePDA.toBuffer(), delegate.toBuffer()],
+          program.programId
+        )[0],
+      })
+      .signers([bob])
+      .rpc();
+    await closeVault();
+    assert.isNull(await program.provider.connection.getAccountInfo(conditionPDA));
+
+    // The next goal at the same address starts clean
+    await initialize();
+    const state = await program.account.vault.fetch(statePDA);
+    assert.equal(state.amount.toString(), (100 * 10 ** 6).toString());
+    assert.equal(state.openGrants, 0);
+    assert.isNull(state.deadline);
+  });
+
+  it("lets a relayer pay for creating the vault", async () => {
+    const connection = program.provider.connection;
+    const relayer = Keypair.generate();
+    await airdrop(relayer.publicKey, LAMPORTS_PER_SOL);
+
+    const lamportsBefore = await connection.getBalance(bob.publicKey);
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        payer: relayer.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob, relayer])
+      .rpc();
+
+    // Bob only signed as the owner, the relayer paid the rent
+    const vault = await program.account.vault.fetch(statePDA);
+    assert.equal(vault.amount.toString(), (100 * 10 ** 6).toString());
+    assert.equal(await connection.getBalance(bob.publicKey), lamportsBefore);
+    const stateRent = (await connection.getAccountInfo(statePDA)).lamports;
+    const vaultRent = (await connection.getAccountInfo(vaultPDA)).lamports;
+    assert.isAtMost(
+      await connection.getBalance(relayer.publicKey),
+      LAMPORTS_PER_SOL - stateRent - vaultRent
+    );
+  });
+
+  it("keeps the surplus above the target under the keep policy", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const deposit = (amount: number) =>
+      program.methods
+        .deposit(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const setSurplusPolicy = (policy: any) =>
+      program.methods
+        .setSurplusPolicy(policy)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+
+    await setSurplusPolicy({ keep: {} });
+    try {
+      await deposit(60);
+      const before = await getAccount(program.provider.connection, bobTokenAccount);
+
+      // 60 + 70 overshoots the target of 100: bob gets exactly 100, 30 stay saved
+      await deposit(70);
+      const after = await getAccount(program.provider.connection, bobTokenAccount);
+      assert.equal(
+        (after.amount - before.amount).toString(),
+        (30 * 10 ** 6).toString()
+      );
+      const vault = await getAccount(program.provider.connection, vaultPDA);
+      assert.equal(vault.amount.toString(), (30 * 10 ** 6).toString());
+    } finally {
+      // Other tests expect the whole balance to be released
+      await setSurplusPolicy({ release: {} });
+    }
+  });
+
+  it("leaves the excess of an overshooting deposit with the user under the refund policy", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    // Rolling over keeps the balance in the vault, so the split shows in it
+    await program.methods
+      .setRollover(0)
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const deposit = (amount: number) =>
+      program.methods
+        .deposit(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const setSurplusPolicy = (policy: any) =>
+      program.methods
+        .setSurplusPolicy(policy)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+
+    await setSurplusPolicy({ refund: {} });
+    try {
+      await deposit(60);
+      const before = await getAccount(program.provider.connection, bobTokenAccount);
+
+      // Only the 40 the target still needs leave bob's account
+      await deposit(70);
+      const after = await getAccount(program.provider.connection, bobTokenAccount);
+      assert.equal(
+        (before.amount - after.amount).toString(),
+        (40 * 10 ** 6).toString()
+      );
+      const vault = await getAccount(program.provider.connection, vaultPDA);
+      assert.equal(vault.amount.toString(), (100 * 10 ** 6).toString());
+    } finally {
+      // Other tests expect the whole deposit to go in
+      await setSurplusPolicy({ release: {} });
+    }
+  });
+
+  it("charges the deposit fee only on the part of a refunded deposit that enters", async () => {
+    const treasury = Keypair.generate();
+    const treasuryTokenAccount = await createAssociatedTokenAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      treasury.publicKey
+    );
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .setRollover(0)
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const setSurplusPolicy = (policy: any) =>
+      program.methods
+        .setSurplusPolicy(policy)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    const setFee = (feeBps: number, treasury: PublicKey) =>
+      program.methods
+        .setDepositFee(feeBps, treasury)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+
+    await setSurplusPolicy({ refund: {} });
+    // 1% of every deposit
+    await setFee(100, treasury.publicKey);
+    try {
+      const before = await getAccount(program.provider.connection, bobTokenAccount);
+      await program.methods
+        .deposit(new BN(500 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          treasuryTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      const after = await getAccount(program.provider.connection, bobTokenAccount);
+
+      // The vault takes its 100, and the fee is 1% of that rather than of the 500 offered
+      const vault = await getAccount(program.provider.connection, vaultPDA);
+      assert.equal(vault.amount.toString(), (100 * 10 ** 6).toString());
+      const fees = await getAccount(program.provider.connection, treasuryTokenAccount);
+      assert.equal(fees.amount.toString(), (1 * 10 ** 6).toString());
+      assert.equal(
+        (before.amount - after.amount).toString(),
+        (101 * 10 ** 6).toString()
+      );
+    } finally {
+      await setFee(0, PublicKey.default);
+      await setSurplusPolicy({ release: {} });
+    }
+  });
+
+  it("keeps the vault token account open when a deposit releases the vault", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const deposit = (amount: number) =>
+      program.methods
+        .deposit(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    await deposit(100);
+
+    // The tokens are back with bob, and the emptied account is still there
+    const bobTokenBalance = await getAccount(
+      program.provider.connection,
+      bobTokenAccount
+    );
+    assert.equal(bobTokenBalance.amount.toString(), (1000 * 10 ** 6).toString());
+    const vaultAccount = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(vaultAccount.amount.toString(), "0");
+
+    // So the vault can keep saving without re-creating it
+    await deposit(10);
+    const status = await program.methods
+      .getVaultStatus()
+      .accounts({ state: statePDA, vaultTokenAccount: vaultPDA, mint: mint })
+      .view();
+    assert.equal(status.balance.toString(), (10 * 10 ** 6).toString());
+  });
+
+  it("leaves the crossing deposit with the user instead of round-tripping it", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const deposit = (amount: number) =>
+      program.methods
+        .deposit(new BN(amount))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc({ commitment: "confirmed" });
+
+    await deposit(40 * 10 ** 6);
+    const signature = await deposit(60 * 10 ** 6);
+
+    // Only the 40 tokens already saved move, back to bob
+    const tx = await program.provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const transfers = tx.meta.logMessages.filter(
+      (log) => log === "Program log: Instruction: TransferChecked"
+    );
+    assert.equal(transfers.length, 1);
+
+    const bobTokenBalance = await getAccount(
+      program.provider.connection,
+      bobTokenAccount
+    );
+    assert.equal(bobTokenBalance.amount.toString(), (1000 * 10 ** 6).toString());
+    const vaultAccount = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(vaultAccount.amount.toString(), "0");
+  });
+
+  it("finalizes a completed vault in the deposit transaction", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const finalize = () =>
+      program.methods
+        .finalize()
+        .accounts({
+          user: bob.publicKey,
+          vaultTokenAccount: vaultPDA,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob]);
+
+    // The goal isn't met yet
+    try {
+      await finalize().rpc();
+      assert.fail("Finalize should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TargetNotReached");
+    }
+
+    // The deposit auto-releases, and finalize closes what is left
+    const sig = await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .postInstructions([await finalize().instruction()])
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+    assert.isNull(await program.provider.connection.getAccountInfo(statePDA));
+    assert.isNull(await program.provider.connection.getAccountInfo(vaultPDA));
+    const bobTokenBalance = await getAccount(
+      program.provider.connection,
+      bobTokenAccount
+    );
+    assert.equal(bobTokenBalance.amount.toString(), (1000 * 10 ** 6).toString());
+
+    const events = await getCpiEvents(sig);
+    const completed = events.find((e) => e.name === "vaultCompleted");
+    assert.isDefined(completed);
+    assert.equal(completed.data.released.toString(), "0");
+  });
+
+  it("refuses to close a vault that still holds tokens", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(10 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    try {
+      await program.methods
+        .closeVault()
+        .accounts({
+          user: bob.publicKey,
+          state: statePDA,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+      assert.fail("Close should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultNotEmpty");
+    }
+  });
+
+  it("records vault activity in the outbox", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .withdraw(new BN(20 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const outbox = await program.account.outbox.fetch(
+      getOutboxPDA(statePDA, program.programId)
+    );
+    assert.equal(outbox.lastId.toNumber(), 3);
+
+    // record n lives at index n % capacity
+    const [initialized, deposit, withdraw] = outbox.records.slice(1, 4);
+    assert.equal(initialized.id.toNumber(), 1);
+    assert.deepEqual(initialized.kind, { initialized: {} });
+    assert.deepEqual(deposit.kind, { deposit: {} });
+    assert.equal(deposit.amount.toString(), (50 * 10 ** 6).toString());
+    assert.deepEqual(withdraw.kind, { withdraw: {} });
+    assert.equal(withdraw.amount.toString(), (20 * 10 ** 6).toString());
+  });
+
+  it("deposits the whole token balance with deposit_all", async () => {
+    // Mint more tokens elsewhere so a target above bob's balance stays within supply
+    const otherTokenAccount = await createAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      anchor.web3.Keypair.generate().publicKey
+    );
+    await mintTo(
+      program.provider.connection,
+      bob,
+      mint,
+      otherTokenAccount,
+      bob,
+      1000 * 10 ** 6
+    );
+
+    await program.methods
+      .initialize(new BN(2000 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .depositAll()
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const bobTokenBalance = await getAccount(
+      program.provider.connection,
+      bobTokenAccount
+    );
+    const vaultTokenBalance = await getAccount(
+      program.provider.connection,
+      vaultPDA
+    );
+    assert.equal(bobTokenBalance.amount.toString(), "0");
+    assert.equal(vaultTokenBalance.amount.toString(), (1000 * 10 ** 6).toString());
+  });
+
+  it("only lets vaults use admin-approved deposit hooks", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Any executable program can stand in for a partner hook here
+    const hookProgram = MEMO_PROGRAM_ID;
+    const approvedHookPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("hook"), hookProgram.toBuffer()],
+      program.programId
+    )[0];
+
+    // Not approved yet: setting the hook fails
+    try {
+      await program.methods
+        .setDepositHook(hookProgram)
+        .accounts({
+          user: bob.publicKey,
+          state: statePDA,
+          approvedHook: null,
+        })
+        .signers([bob])
+        .rpc();
+
+      assert.fail("Setting an unapproved hook should have failed");
+    } catch (error: any) {
+      assert.include(error.toString(), "DepositHookNotApproved");
+    }
+
+    await program.methods
+      .approveDepositHook()
+      .accounts({
+        admin: admin.publicKey,
+        hookProgram,
+      })
+      .rpc();
+
+    await program.methods
+      .setDepositHook(hookProgram)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        approvedHook: approvedHookPDA,
+      })
+      .signers([bob])
+      .rpc();
+
+    const stateAccount = await program.account.vault.fetch(statePDA);
+    assert.equal(stateAccount.depositHook.toString(), hookProgram.toString());
+
+    // Gifts run the hook too, so one that leaves out the hook program fails
+    try {
+      await program.methods
+        .depositFor(new BN(10 * 10 ** 6))
+        .accounts({
+          depositor: bob.publicKey,
+          owner: bob.publicKey,
+          depositorTokenAccount: bobTokenAccount,
+          vaultTokenAccount: vaultPDA,
+          mint: mint,
+          depositHookProgram: null,
+          approvedHook: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Gift should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "DepositHookMismatch");
+    }
+
+    // Clean up so the approval doesn't leak into other tests
+    await program.methods
+      .revokeDepositHook()
+      .accounts({
+        admin: admin.publicKey,
+        approvedHook: approvedHookPDA,
+      })
+      .rpc();
+  });
+
+  it("only credits swap deposits that deliver the minimum output", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // The memo program stands in for a swap program: it runs but delivers nothing
+    const swapProgram = MEMO_PROGRAM_ID;
+    const approvedSwapPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("swap"), swapProgram.toBuffer()],
+      program.programId
+    )[0];
+    const depositAndSwap = () =>
+      program.methods
+        .depositAndSwap(new BN(10 * 10 ** 6), new BN(1), Buffer.from("swap"), 0)
+        .accounts({
+          user: bob.publicKey,
+          inputMint: mint,
+          userInputTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          swapProgram,
+          priceUpdate: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // Not approved yet: the swap program can't be used
+    try {
+      await depositAndSwap();
+      assert.fail("Swap deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "AccountOwnedByWrongProgram");
+    }
+
+    await program.methods
+      .approveSwapProgram()
+      .accounts({ admin: admin.publicKey, swapProgram })
+      .rpc();
+
+    try {
+      // Approved, but nothing reaches the vault
+      try {
+        await depositAndSwap();
+        assert.fail("Swap deposit should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "SlippageExceeded");
+      }
+    } finally {
+      // Clean up so the approval doesn't leak into other tests
+      await program.methods
+        .revokeSwapProgram()
+        .accounts({ admin: admin.publicKey, approvedSwap: approvedSwapPDA })
+        .rpc();
+    }
+  });
+
+  it("keeps the latest notes and returns them with the status", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Write one more note than the notes account can hold
+    for (let i = 0; i < 9; i++) {
+      await program.methods
+        .addNote(`note ${i}`)
+        .accounts({
+          user: bob.publicKey,
+          state: statePDA,
+        })
+        .signers([bob])
+        .rpc();
+    }
+
+    const notesPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("notes"), statePDA.toBytes()],
+      program.programId
+    )[0];
+
+    const status = await program.methods
+      .getVaultStatus()
+      .accounts({
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        mint: mint,
+        notes: notesPDA,
+      })
+      .view();
+
+    // The oldest note was overwritten
+    assert.equal(status.notes.length, 8);
+    assert.equal(status.notes[0].text, "note 1");
+    assert.equal(status.notes[7].text, "note 8");
+  });
+
+  it("rejects a mint argument that differs from the mint account", async () => {
+    const otherMint = await createMint(
+      program.provider.connection,
+      bob,
+      bob.publicKey,
+      null,
+      6
+    );
+
+    try {
+      await program.methods
+        .initialize(new BN(100 * 10 ** 6), otherMint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          state: statePDA,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([bob])
+        .rpc();
+
+      assert.fail("Initialize should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "MintMismatch");
+    }
+  });
+
+  it("rejects targets that don't make sense for the mint", async () => {
+    // 6 decimals: anything below 0.01 tokens is almost certainly a unit mistake
+    try {
+      await program.methods
+        .initialize(new BN(9_999), mint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          state: statePDA,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([bob])
+        .rpc();
+
+      assert.fail("Initialize should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TargetTooSmall");
+    }
+
+    // Only 1000 tokens exist
+    try {
+      await program.methods
+        .initialize(new BN(1001 * 10 ** 6), mint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          state: statePDA,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([bob])
+        .rpc();
+
+      assert.fail("Initialize should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "TargetExceedsSupply");
+    }
+  });
+
+  it("recreates the user's token account on withdrawal", async () => {
+    await program.methods
+      .initialize(new BN(1000 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Move everything into the vault, then close bob's now empty token account
+    await program.methods
+      .depositAll()
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await closeAccount(
+      program.provider.connection,
+      bob,
+      bobTokenAccount,
+      bob.publicKey,
+      bob
+    );
+
+    const withdrawAmount = new BN(20 * 10 ** 6);
+    await program.methods
+      .withdraw(withdrawAmount)
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const bobTokenBalance = await getAccount(
+      program.provider.connection,
+      bobTokenAccount
+    );
+    assert.equal(bobTokenBalance.amount.toString(), withdrawAmount.toString());
+  });
+
+  it("withdraws to any token account of the vault's mint", async () => {
+    const connection = program.provider.connection;
+    const exchange = Keypair.generate();
+    const exchangeTokenAccount = await createAccount(
+      connection,
+      bob,
+      mint,
+      exchange.publicKey
+    );
+
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({ user: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // The vault's own token account isn't a valid destination
+    try {
+      await program.methods
+        .withdrawTo(new BN(20 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint,
+          destinationTokenAccount: vaultPDA,
+          blockedDestination: blockedPDA(vaultAuthorityPDA),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("withdrawing into the vault should have failed");
+    } catch (err) {
+      assert.include(err.toString(), "InvalidDestination");
+    }
+
+    const sig = await program.methods
+      .withdrawTo(new BN(20 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        mint,
+        destinationTokenAccount: exchangeTokenAccount,
+        blockedDestination: blockedPDA(exchange.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const exchangeBalance = await getAccount(connection, exchangeTokenAccount);
+    assert.equal(exchangeBalance.amount.toString(), (20 * 10 ** 6).toString());
+    const vaultBalance = await getAccount(connection, vaultPDA);
+    assert.equal(vaultBalance.amount.toString(), (30 * 10 ** 6).toString());
+
+    const events = await getCpiEvents(sig);
+    const redirected = events.find((e) => e.name === "withdrawalRedirected");
+    assert.isDefined(redirected);
+    assert.equal(
+      redirected.data.destinationTokenAccount.toString(),
+      exchangeTokenAccount.toString()
+    );
+  });
+
+  it("rejects aliased token accounts", async () => {
+    const connection = program.provider.connection;
+
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({ user: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Depositing from the vault's own token account is rejected
+    try {
+      await program.methods
+        .deposit(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: vaultPDA,
+          mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("depositing from the vault should have failed");
+    } catch (err) {
+      assert.include(err.toString(), "DuplicateAccount");
+    }
+
+    // A second token account of the vault authority isn't a valid destination either
+    const shadowTokenAccount = await createAccount(
+      connection,
+      bob,
+      mint,
+      vaultAuthorityPDA,
+      Keypair.generate()
+    );
+    try {
+      await program.methods
+        .withdrawTo(new BN(20 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint,
+          destinationTokenAccount: shadowTokenAccount,
+          blockedDestination: blockedPDA(vaultAuthorityPDA),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("withdrawing to the vault authority should have failed");
+    } catch (err) {
+      assert.include(err.toString(), "VaultOwnedAccount");
+    }
+
+    const vault = await getAccount(connection, vaultPDA);
+    assert.equal(vault.amount.toString(), (50 * 10 ** 6).toString());
+  });
+
+  it("requires the vault key for withdrawals above the threshold", async () => {
+    const vaultKey = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .initialize(new BN(1000 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(500 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .setVaultKey(vaultKey.publicKey, new BN(100 * 10 ** 6))
+      .accounts({ user: bob.publicKey, currentVaultKey: null })
+      .signers([bob])
+      .rpc();
+
+    const withdraw = (amount: number, key: Keypair | null) =>
+      program.methods
+        .withdraw(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          vaultKey: key ? key.publicKey : null,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          state: statePDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers(key ? [bob, key] : [bob])
+        .rpc();
+
+    // Small withdrawals only need the owner
+    await withdraw(100, null);
+
+    // Large ones also need the vault key
+    try {
+      await withdraw(200, null);
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultKeyRequired");
+    }
+    await withdraw(200, vaultKey);
+
+    // The owner key alone can't remove the vault key
+    try {
+      await program.methods
+        .setVaultKey(null, new BN(0))
+        .accounts({ user: bob.publicKey, currentVaultKey: null })
+        .signers([bob])
+        .rpc();
+      assert.fail("Clearing the vault key should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultKeyRequired");
+    }
+  });
+
+  it("lets anyone gift tokens into someone else's vault", async () => {
+    const grandparent = anchor.web3.Keypair.generate();
+    await airdrop(grandparent.publicKey, 1_000_000_000);
+    const grandparentTokenAccount = await createAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      grandparent.publicKey
+    );
+    await mintTo(
+      program.provider.connection,
+      bob,
+      mint,
+      grandparentTokenAccount,
+      bob,
+      50 * 10 ** 6
+    );
+
+    await program.methods
+      .initialize(new BN(1000 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Only the grandparent signs, and the tokens come from their own account
+    const signature = await program.methods
+      .depositFor(new BN(50 * 10 ** 6))
+      .accounts({
+        depositor: grandparent.publicKey,
+        owner: bob.publicKey,
+        depositorTokenAccount: grandparentTokenAccount,
+        vaultTokenAccount: vaultPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([grandparent])
+      .rpc({ commitment: "confirmed" });
+
+    const vaultTokenBalance = await getAccount(
+      program.provider.connection,
+      vaultPDA
+    );
+    assert.equal(vaultTokenBalance.amount.toString(), (50 * 10 ** 6).toString());
+
+    const events = await getCpiEvents(signature);
+    const gift = events.find((e) => e.name === "giftDeposited");
+    assert.isDefined(gift);
+    assert.isTrue(gift.data.depositor.equals(grandparent.publicKey));
+    assert.isTrue(gift.data.user.equals(bob.publicKey));
+    assert.equal(gift.data.totalContributed.toString(), (50 * 10 ** 6).toString());
+
+    // The gift is attributed to the grandparent
+    const contributionPDA = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("contribution"),
+        statePDA.toBuffer(),
+        grandparent.publicKey.toBuffer(),
+      ],
+      program.programId
+    )[0];
+    const contribution = await program.account.contribution.fetch(contributionPDA);
+    assert.equal(contribution.totalContributed.toString(), (50 * 10 ** 6).toString());
+    assert.equal(contribution.depositCount.toString(), "1");
+  });
+
+  it("holds a custodial vault for its beneficiary until the release date", async () => {
+    const parent = anchor.web3.Keypair.generate();
+    await airdrop(parent.publicKey, 1_000_000_000);
+    const parentTokenAccount = await createAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      parent.publicKey
+    );
+    await mintTo(
+      program.provider.connection,
+      bob,
+      mint,
+      parentTokenAccount,
+      bob,
+      100 * 10 ** 6
+    );
+
+    // The parent creates and pays for bob's vault, released in a year
+    const releaseTs = Math.floor(Date.now() / 1000) + 365 * 24 * 60 * 60;
+    const initializeCustodial = (signers: Keypair[]) =>
+      program.methods
+        .initializeCustodial(new BN(100 * 10 ** 6), mint, new BN(releaseTs), null)
+        .accounts({
+          custodian: parent.publicKey,
+          beneficiary: bob.publicKey,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers(signers)
+        .rpc({ commitment: "confirmed" });
+
+    // ... but only with bob's consent, or anyone could take his vault address
+    try {
+      await initializeCustodial([parent]);
+      assert.fail("Initialize should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "Missing signature");
+    }
+    const signature = await initializeCustodial([parent, bob]);
+
+    const events = await getCpiEvents(signature);
+    const created = events.find((e) => e.name === "custodialVaultCreated");
+    assert.isDefined(created);
+    assert.isTrue(created.data.custodian.equals(parent.publicKey));
+    assert.isTrue(created.data.beneficiary.equals(bob.publicKey));
+
+    // The parent funds the vault up to its target
+    await program.methods
+      .depositFor(new BN(100 * 10 ** 6))
+      .accounts({
+        depositor: parent.publicKey,
+        owner: bob.publicKey,
+        depositorTokenAccount: parentTokenAccount,
+        vaultTokenAccount: vaultPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([parent])
+      .rpc();
+
+    // Bob can't withdraw before the release date ...
+    try {
+      await program.methods
+        .withdraw(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "SelfExcluded");
+    }
+
+    // ... and reaching the target doesn't release the vault early
+    try {
+      await program.methods
+        .crankRelease()
+        .accounts({
+          cranker: parent.publicKey,
+          crankerTokenAccount: parentTokenAccount,
+          blockedCranker: blockedPDA(parent.publicKey),
+          owner: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([parent])
+        .rpc();
+      assert.fail("Crank should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultInCustody");
+    }
+
+    const vaultBalance = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(vaultBalance.amount.toString(), (100 * 10 ** 6).toString());
+  });
+
+  it("pulls deposits through a delegate approval", async () => {
+    const saver = anchor.web3.Keypair.generate();
+    const stranger = anchor.web3.Keypair.generate();
+    await airdrop(saver.publicKey, 1_000_000_000);
+    await airdrop(stranger.publicKey, 1_000_000_000);
+
+    await program.methods
+      .initialize(new BN(1000 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Bob authorizes the saver to pull up to 25 tokens at a time, as often as it likes ...
+    await program.methods
+      .setAutoSave(saver.publicKey, new BN(25 * 10 ** 6), new BN(0))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    const state = await program.account.vault.fetch(statePDA);
+    assert.equal(state.openGrants, 1);
+
+    // ... and lets the vault pull up to 30 tokens from his account
+    await approve(
+      program.provider.connection,
+      bob,
+      bobTokenAccount,
+      vaultAuthorityPDA,
+      bob,
+      30 * 10 ** 6
+    );
+
+    const pull = (amount: number, caller = saver) =>
+      program.methods
+        .depositViaDelegate(new BN(amount * 10 ** 6))
+        .accounts({
+          caller: caller.publicKey,
+          owner: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          vaultTokenAccount: vaultPDA,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([caller])
+        .rpc();
+
+    // Nobody else can use the approval
+    try {
+      await pull(20, stranger);
+      assert.fail("Pull should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "AutoSaveNotAuthorized");
+    }
+
+    // The saver can't pull more than bob allowed per pull
+    try {
+      await pull(30);
+      assert.fail("Pull should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "AutoSaveLimitExceeded");
+    }
+
+    // But it can pull within the limit and the allowance without bob signing
+    await pull(20);
+    const vaultTokenBalance = await getAccount(
+      program.provider.connection,
+      vaultPDA
+    );
+    assert.equal(vaultTokenBalance.amount.toString(), (20 * 10 ** 6).toString());
+
+    // And not beyond the allowance
+    try {
+      await pull(20);
+      assert.fail("Pull should have failed but succeeded");
+    } catch (error: any) {
+      // Token program InsufficientFunds: the allowance is used up
+      assert.include(error.toString(), "0x1");
+    }
+  });
+
+  it("releases a vault when a delegated pull reaches the target", async () => {
+    const saver = anchor.web3.Keypair.generate();
+    await airdrop(saver.publicKey, 1_000_000_000);
+
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(90 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // One pull a day, of at most 50 tokens
+    await program.methods
+      .setAutoSave(saver.publicKey, new BN(50 * 10 ** 6), new BN(24 * 60 * 60))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    await approve(
+      program.provider.connection,
+      bob,
+      bobTokenAccount,
+      vaultAuthorityPDA,
+      bob,
+      50 * 10 ** 6
+    );
+    const pull = (amount: number) =>
+      program.methods
+        .depositViaDelegate(new BN(amount * 10 ** 6))
+        .accounts({
+          caller: saver.publicKey,
+          owner: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([saver])
+        .rpc({ commitment: "confirmed" });
+
+    // The pull reaches the target, so the savings go back to bob like after a deposit
+    const signature = await pull(10);
+    const events = await getCpiEvents(signature);
+    const reached = events.find((e) => e.name === "savingsTargetReached");
+    assert.isDefined(reached);
+    assert.equal(reached.data.released.toString(), (100 * 10 ** 6).toString());
+    const vault = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(vault.amount.toString(), "0");
+    const bobTokenBalance = await getAccount(
+      program.provider.connection,
+      bobTokenAccount
+    );
+    assert.equal(bobTokenBalance.amount.toString(), (1000 * 10 ** 6).toString());
+
+    // The next pull has to wait a day
+    try {
+      await pull(10);
+      assert.fail("Pull should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "AutoSaveNotDue");
+    }
+
+    // Clearing the authorization stops the pulls and frees the vault to close
+    await program.methods
+      .clearAutoSave()
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    const state = await program.account.vault.fetch(statePDA);
+    assert.equal(state.openGrants, 0);
+  });
+
+  it("lets the admin freeze a vault for a bounded time", async () => {
+    await program.methods
+      .initialize(new BN(1000 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    const deposit = () =>
+      program.methods
+        .deposit(new BN(50 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          state: statePDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    await deposit();
+
+    // Only the admin can freeze, and only for up to 7 days
+    try {
+      await program.methods
+        .adminFreezeVault(new BN(3600), false)
+        .accounts({ admin: bob.publicKey, owner: bob.publicKey })
+        .signers([bob])
+        .rpc();
+      assert.fail("Freeze should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "Unauthorized");
+    }
+    try {
+      await program.methods
+        .adminFreezeVault(new BN(8 * 24 * 60 * 60), false)
+        .accounts({ admin: admin.publicKey, owner: bob.publicKey })
+        .rpc();
+      assert.fail("Freeze should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidFreezeDuration");
+    }
+
+    await program.methods
+      .adminFreezeVault(new BN(3600), false)
+      .accounts({ admin: admin.publicKey, owner: bob.publicKey })
+      .rpc();
+
+    // Deposits still work, withdrawals don't
+    await deposit();
+    try {
+      await program.methods
+        .withdraw(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          state: statePDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultFrozen");
+    }
+
+    // ... and neither does a deposit that would release the vault by reaching its target
+    try {
+      await program.methods
+        .deposit(new BN(900 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultFrozen");
+    }
+
+    // The freeze can't be extended
+    try {
+      await program.methods
+        .adminFreezeVault(new BN(3600), true)
+        .accounts({ admin: admin.publicKey, owner: bob.publicKey })
+        .rpc();
+      assert.fail("Freeze should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "FreezeCooldown");
+    }
+  });
+
+  it("rolls a reached goal over into a bigger one instead of releasing", async () => {
+    const connection = program.provider.connection;
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({ user: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+
+    try {
+      await program.methods
+        .setRollover(20_000)
+        .accounts({ user: bob.publicKey })
+        .signers([bob])
+        .rpc();
+      assert.fail("an increase above the maximum should have failed");
+    } catch (err) {
+      assert.include(err.toString(), "InvalidRollover");
+    }
+
+    // Each new goal is 50% above the one reached
+    await program.methods
+      .setRollover(5_000)
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const sig = await program.methods
+      .deposit(new BN(100 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // The deposit stayed in the vault and the target grew
+    const vaultBalance = await getAccount(connection, vaultPDA);
+    assert.equal(vaultBalance.amount.toString(), (100 * 10 ** 6).toString());
+    const state = await program.account.vault.fetch(statePDA);
+    assert.equal(state.amount.toString(), (150 * 10 ** 6).toString());
+
+    const events = await getCpiEvents(sig);
+    assert.isUndefined(events.find((e) => e.name === "savingsTargetReached"));
+    const rolledOver = events.find((e) => e.name === "goalRolledOver");
+    assert.isDefined(rolledOver);
+    assert.equal(
+      rolledOver.data.previousTarget.toString(),
+      (100 * 10 ** 6).toString()
+    );
+    assert.equal(rolledOver.data.newTarget.toString(), (150 * 10 ** 6).toString());
+
+    // Without the rollover, reaching the new target releases the vault again
+    await program.methods
+      .clearRollover()
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    const bobBalance = await getAccount(connection, bobTokenAccount);
+    assert.equal(bobBalance.amount.toString(), (1000 * 10 ** 6).toString());
+  });
+
+  it("lets anyone crank the release of a vault that reached its target", async () => {
+    const keeper = anchor.web3.Keypair.generate();
+    await airdrop(keeper.publicKey, 1_000_000_000);
+    const keeperTokenAccount = await createAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      keeper.publicKey
+    );
+
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    const deposit = (amount: number) =>
+      program.methods
+        .deposit(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          state: statePDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const crank = () =>
+      program.methods
+        .crankRelease()
+        .accounts({
+          cranker: keeper.publicKey,
+          crankerTokenAccount: keeperTokenAccount,
+          blockedCranker: blockedPDA(keeper.publicKey),
+          owner: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([keeper])
+        .rpc();
+
+    // Below the target there is nothing to release
+    await deposit(60);
+    try {
+      await crank();
+      assert.fail("Crank should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "NotReleasable");
+    }
+
+    await deposit(40);
+    await crank();
+
+    // 0.1% of the 100 tokens goes to the keeper, the rest back to bob
+    const keeperBalance = await getAccount(
+      program.provider.connection,
+      keeperTokenAccount
+    );
+    const bobBalance = await getAccount(
+      program.provider.connection,
+      bobTokenAccount
+    );
+    const vaultBalance = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(keeperBalance.amount.toString(), (100_000).toString());
+    assert.equal(bobBalance.amount.toString(), (999_900_000).toString());
+    assert.equal(vaultBalance.amount.toString(), "0");
+  });
+
+  it("holds a release on unlock to the owner's withdrawal rules", async () => {
+    const connection = program.provider.connection;
+    const vaultKey = anchor.web3.Keypair.generate();
+    const keeper = anchor.web3.Keypair.generate();
+    await airdrop(keeper.publicKey, 1_000_000_000);
+    const keeperTokenAccount = await createAccount(
+      connection,
+      bob,
+      mint,
+      keeper.publicKey
+    );
+
+    await program.methods
+      .initialize(new BN(1000 * 10 ** 6), mint, null, null, null)
+      .accounts({ user: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(500 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .setVaultKey(vaultKey.publicKey, new BN(100 * 10 ** 6))
+      .accounts({ user: bob.publicKey, currentVaultKey: null })
+      .signers([bob])
+      .rpc();
+
+    // The owner key alone can't opt in once the vault has a vault key
+    try {
+      await program.methods
+        .setReleaseOnUnlock(true)
+        .accounts({ user: bob.publicKey, vaultKey: null })
+        .signers([bob])
+        .rpc();
+      assert.fail("Opting in should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultKeyRequired");
+    }
+    await program.methods
+      .setReleaseOnUnlock(true)
+      .accounts({ user: bob.publicKey, vaultKey: vaultKey.publicKey })
+      .signers([bob, vaultKey])
+      .rpc();
+
+    // Once a short lock expires, the crank still can't stand in for the vault key
+    await program.methods
+      .lockTokens(new BN(1), new BN(1))
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+    try {
+      await program.methods
+        .crankRelease()
+        .accounts({
+          cranker: keeper.publicKey,
+          crankerTokenAccount: keeperTokenAccount,
+          blockedCranker: blockedPDA(keeper.publicKey),
+          owner: bob.publicKey,
+          mint,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([keeper])
+        .rpc();
+      assert.fail("Crank should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultKeyRequired");
+    }
+
+    const vaultBalance = await getAccount(connection, vaultPDA);
+    assert.equal(vaultBalance.amount.toString(), (500 * 10 ** 6).toString());
+  });
+
+  it("reuses a pre-created vault token account in initialize", async () => {
+    // Someone creates the vault authority's ATA before the vault exists
+    await createAssociatedTokenAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      vaultAuthorityPDA,
+      undefined,
+      undefined,
+      undefined,
+      true
+    );
+
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    const stateAccount = await program.account.vault.fetch(statePDA);
+    assert.equal(stateAccount.vaultTokenAccount.toString(), vaultPDA.toString());
+  });
+
+  it("earns reward points for tokens saved over time", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // Let the 50 tokens sit in the vault for a couple of seconds
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    await program.methods
+      .claimPoints()
+      .accounts({
+        user: bob.publicKey,
+        vaultTokenAccount: vaultPDA,
+        mint: mint,
+      })
+      .signers([bob])
+      .rpc();
+
+    const rewardsPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("rewards"), statePDA.toBytes()],
+      program.programId
+    )[0];
+    const rewards = await program.account.rewardState.fetch(rewardsPDA);
+    assert.equal(rewards.points.toString(), "0");
+    assert.isTrue(rewards.claimedPoints.gte(new BN(50)));
+    assert.equal(rewards.streakDays, 1);
+  });
+
+  it("mints a soulbound badge for the first deposit", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        vaultTokenAccount: vaultPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .deposit(new BN(10 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const badgeMint = (badge: any) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("badge"),
+          bob.publicKey.toBuffer(),
+          Buffer.from([badge.firstDeposit ? 0 : 1]),
+        ],
+        program.programId
+      )[0];
+    const claimBadge = (badge: any) =>
+      program.methods
+        .claimBadge(badge)
+        .accounts({
+          user: bob.publicKey,
+          userBadgeAccount: getAssociatedTokenAddressSync(
+            badgeMint(badge),
+            bob.publicKey,
+            false,
+            TOKEN_2022_PROGRAM_ID
+          ),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    try {
+      await claimBadge({ streak: {} });
+      assert.fail("Claim should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "BadgeNotEarned");
+    }
+
+    await claimBadge({ firstDeposit: {} });
+    const badgeAccount = await getAccount(
+      program.provider.connection,
+      getAssociatedTokenAddressSync(
+        badgeMint({ firstDeposit: {} }),
+        bob.publicKey,
+        false,
+        TOKEN_2022_PROGRAM_ID
+      ),
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    assert.equal(badgeAccount.amount.toString(), "1");
+
+    try {
+      await claimBadge({ firstDeposit: {} });
+      assert.fail("Claim should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "BadgeAlreadyClaimed");
+    }
+  });
+
+  it("allocates accounts at their published reserved sizes", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    const constant = (name: string) =>
+      Number(program.idl.constants.find((c) => c.name === name).value);
+    const rewardsPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("rewards"), statePDA.toBytes()],
+      program.programId
+    )[0];
+
+    const stateInfo = await program.provider.connection.getAccountInfo(statePDA);
+    const rewardsInfo = await program.provider.connection.getAccountInfo(
+      rewardsPDA
+    );
+    assert.equal(stateInfo.data.length, constant("vaultAccountSize"));
+    assert.equal(rewardsInfo.data.length, constant("rewardsAccountSize"));
+
+    // The reserved tails start out zeroed
+    const vault = await program.account.vault.fetch(statePDA);
+    assert.equal(vault.reserved.length, constant("vaultReservedBytes"));
+    assert.isTrue(vault.reserved.every((b: number) => b === 0));
+  });
+
+  it("enforces the vault's balance cap", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    const setCap = (policy: any) =>
+      program.methods
+        .setBalanceCap(new BN(100 * 10 ** 6), policy)
+        .accounts({ user: bob.publicKey })
+        .signers([bob])
+        .rpc();
+    const deposit = (amount: number) =>
+      program.methods
+        .deposit(new BN(amount * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          state: statePDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    await setCap({ reject: {} });
+    await deposit(80);
+    try {
+      await deposit(50);
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "BalanceCapExceeded");
+    }
+
+    // Truncating only takes what fits under the cap
+    await setCap({ truncate: {} });
+    await deposit(50);
+    const vaultTokenBalance = await getAccount(
+      program.provider.connection,
+      vaultPDA
+    );
+    assert.equal(vaultTokenBalance.amount.toString(), (100 * 10 ** 6).toString());
+
+    // A full vault takes nothing
+    try {
+      await deposit(1);
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "BalanceCapExceeded");
+    }
+  });
+
+  it("logs every config change in the config history", async () => {
+    const historyPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("config_history")],
+      program.programId
+    )[0];
+    const before = await program.account.configHistory.fetch(historyPDA);
+
+    // The history starts with the initial admin and bounds
+    assert.deepEqual(before.entries[0].field, { admin: {} });
+    assert.isTrue(before.entries[0].new.pubkey[0].equals(admin.publicKey));
+
+    const setBounds = (max: BN) =>
+      program.methods
+        .setBalanceCapBounds(new BN(1), max)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    const uncapped = new BN("18446744073709551615");
+    await setBounds(new BN(10 ** 12));
+    await setBounds(uncapped);
+
+    // Only the max bound changed, once each way
+    const after = await program.account.configHistory.fetch(historyPDA);
+    assert.equal(after.entries.length, before.entries.length + 2);
+    const last = after.entries[after.entries.length - 1];
+    assert.deepEqual(last.field, { maxBalanceCap: {} });
+    assert.equal(last.old.u64[0].toString(), "1000000000000");
+    assert.equal(last.new.u64[0].toString(), uncapped.toString());
+    assert.isTrue(last.admin.equals(admin.publicKey));
+  });
+
+  it("needs the vault's price update to check a USD target", async () => {
+    const priceAccount = Keypair.generate().publicKey;
+    await program.methods
+      .initialize(
+        new BN(0),
+        mint,
+        null,
+        {
+          cents: new BN(500 * 100),
+          priceAccount,
+        },
+        null
+      )
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const vault = await program.account.vault.fetch(statePDA);
+    assert.equal(vault.usdTarget.cents.toString(), "50000");
+    assert.isTrue(vault.usdTarget.priceAccount.equals(priceAccount));
+
+    const deposit = (priceUpdate: PublicKey | null) =>
+      program.methods
+        .deposit(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          priceUpdate,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // The vault can't be valued without its price update
+    try {
+      await deposit(null);
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "PriceAccountMismatch");
+    }
+
+    // An account that isn't a Pyth price update is rejected too
+    try {
+      await deposit(priceAccount);
+      assert.fail("Deposit should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidPriceAccount");
+    }
+  });
+
+  it("releases a vault only once its price condition is met", async () => {
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const priceAccount = Keypair.generate().publicKey;
+    await program.methods
+      .setPriceCondition(priceAccount, new BN(150 * 100), { atOrAbove: {} })
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+
+    const conditionPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("price_condition"), statePDA.toBuffer()],
+      program.programId
+    )[0];
+    const condition = await program.account.priceCondition.fetch(conditionPDA);
+    assert.equal(condition.thresholdCents.toString(), "15000");
+    assert.isTrue(condition.priceAccount.equals(priceAccount));
+
+    const check = (priceUpdate: PublicKey) =>
+      program.methods
+        .checkPriceCondition()
+        .accounts({
+          caller: admin.publicKey,
+          owner: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          priceUpdate,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    // Only the price account named by the condition is accepted
+    try {
+      await check(Keypair.generate().publicKey);
+      assert.fail("Check should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "PriceAccountMismatch");
+    }
+
+    // ... and it must be a real Pyth price update
+    try {
+      await check(priceAccount);
+      assert.fail("Check should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidPriceAccount");
+    }
+
+    // Clearing the condition closes its account
+    await program.methods
+      .clearPriceCondition()
+      .accounts({ user: bob.publicKey })
+      .signers([bob])
+      .rpc();
+    assert.isNull(await program.provider.connection.getAccountInfo(conditionPDA));
+  });
+
+  it("needs the vault key to set a price condition", async () => {
+    const vaultKey = anchor.web3.Keypair.generate();
+    await program.methods
+      .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+      .accounts({ user: bob.publicKey, mint, tokenProgram: TOKEN_PROGRAM_ID })
+      .signers([bob])
+      .rpc();
+    await program.methods
+      .setVaultKey(vaultKey.publicKey, new BN(0))
+      .accounts({ user: bob.publicKey, currentVaultKey: null })
+      .signers([bob])
+      .rpc();
+
+    // Anyone can trigger a condition, so the owner key alone can't set one
+    const priceAccount = Keypair.generate().publicKey;
+    try {
+      await program.methods
+        .setPriceCondition(priceAccount, new BN(150 * 100), { atOrAbove: {} })
+        .accounts({ user: bob.publicKey, vaultKey: null })
+        .signers([bob])
+        .rpc();
+      assert.fail("Set price condition should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "VaultKeyRequired");
+    }
+
+    await program.methods
+      .setPriceCondition(priceAccount, new BN(150 * 100), { atOrAbove: {} })
+      .accounts({ user: bob.publicKey, vaultKey: vaultKey.publicKey })
+      .signers([bob, vaultKey])
+      .rpc();
+  });
+
+  it("lets only the admin set the price feed of a mint", async () => {
+    const feedId = Array.from(Keypair.generate().publicKey.toBytes());
+    try {
+      await program.methods
+        .setPriceFeed(feedId)
+        .accounts({ admin: bob.publicKey, mint: mint })
+        .signers([bob])
+        .rpc();
+      assert.fail("Set price feed should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "Unauthorized");
+    }
+
+    await program.methods
+      .setPriceFeed(feedId)
+      .accounts({ admin: admin.publicKey, mint: mint })
+      .rpc();
+    const feedPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("price_feed"), mint.toBuffer()],
+      program.programId
+    )[0];
+    const feed = await program.account.priceFeed.fetch(feedPDA);
+    assert.isTrue(feed.mint.equals(mint));
+    assert.deepEqual(feed.feedId, feedId);
+  });
+
+  it("only creates vaults for approved mints while the allowlist is on", async () => {
+    const initialize = () =>
+      program.methods
+        .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const setAllowlist = (enabled: boolean) =>
+      program.methods
+        .setMintAllowlist(enabled)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+
+    await setAllowlist(true);
+    try {
+      try {
+        await initialize();
+        assert.fail("Initialize should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "MintNotApproved");
+      }
+
+      await program.methods
+        .approveMint()
+        .accounts({ admin: admin.publicKey, mint: mint })
+        .rpc();
+      await initialize();
+
+      const vault = await program.account.vault.fetch(statePDA);
+      assert.isTrue(vault.mint.equals(mint));
+    } finally {
+      // Other tests create vaults for fresh mints
+      await setAllowlist(false);
+    }
+  });
+
+  it("refuses freezable mints while the config rejects them", async () => {
+    const freezableMint = await createMint(
+      program.provider.connection,
+      bob,
+      bob.publicKey,
+      bob.publicKey,
+      6
+    );
+    const initialize = () =>
+      program.methods
+        .initialize(new BN(100 * 10 ** 6), freezableMint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          mint: freezableMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc({ commitment: "confirmed" });
+    const setRejectFreezableMints = (enabled: boolean) =>
+      program.methods
+        .setRejectFreezableMints(enabled)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+
+    await setRejectFreezableMints(true);
+    try {
+      try {
+        await initialize();
+        assert.fail("Initialize should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "FreezableMint");
+      }
+    } finally {
+      // Other tests create vaults for fresh mints
+      await setRejectFreezableMints(false);
+    }
+
+    // Accepted by default, with a warning event
+    const events = await getCpiEvents(await initialize());
+    const warning = events.find((e) => e.name === "freezableMintAccepted");
+    assert.isDefined(warning);
+    assert.isTrue(warning.data.freezeAuthority.equals(bob.publicKey));
+  });
+
+  it("refuses a withdrawal in the slot of a deposit while the guard is on", async () => {
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const deposit = () =>
+      program.methods
+        .deposit(new BN(100 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob]);
+    const withdraw = () =>
+      program.methods
+        .withdraw(new BN(50 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob]);
+    const setGuard = (enabled: boolean) =>
+      program.methods
+        .setSameSlotGuard(enabled)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+
+    await setGuard(true);
+    try {
+      // One transaction runs both instructions in the same slot
+      try {
+        await deposit()
+          .postInstructions([await withdraw().instruction()])
+          .rpc();
+        assert.fail("Same-slot withdrawal should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "SameSlotActivity");
+      }
+
+      // In separate slots both go through
+      await deposit().rpc();
+      const slot = await program.provider.connection.getSlot();
+      while ((await program.provider.connection.getSlot()) === slot) {
+        await new Promise((resolve) => setTimeout(resolve, 100));
+      }
+      await withdraw().rpc();
+    } finally {
+      // Other tests deposit and withdraw back to back
+      await setGuard(false);
+    }
+
+    const vault = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(vault.amount.toString(), (50 * 10 ** 6).toString());
+  });
+
+  it("blocks deposits, withdrawals and releases of blocklisted wallets", async () => {
+    const connection = program.provider.connection;
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const deposit = () =>
+      program.methods
+        .deposit(new BN(100 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const withdraw = () =>
+      program.methods
+        .withdraw(new BN(50 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const crank = (cranker: PublicKey, crankerTokenAccount: PublicKey) =>
+      program.methods
+        .crankRelease()
+        .accounts({
+          cranker: bob.publicKey,
+          crankerTokenAccount,
+          blockedCranker: blockedPDA(cranker),
+          owner: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const block = (address: PublicKey) =>
+      program.methods
+        .blockAddress(address)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    const unblock = (address: PublicKey) =>
+      program.methods
+        .unblockAddress()
+        .accounts({
+          admin: admin.publicKey,
+          blockedAddress: blockedPDA(address),
+        })
+        .rpc();
+
+    await deposit();
+
+    await block(bob.publicKey);
+    try {
+      try {
+        await deposit();
+        assert.fail("Deposit should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "AddressBlocked");
+      }
+      try {
+        await withdraw();
+        assert.fail("Withdrawal should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "AddressBlocked");
+      }
+      try {
+        await crank(bob.publicKey, bobTokenAccount);
+        assert.fail("Crank should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "AddressBlocked");
+      }
+    } finally {
+      // Other tests deposit and withdraw with bob
+      await unblock(bob.publicKey);
+    }
+
+    // A blocked wallet can't receive a withdrawal either
+    const sanctioned = Keypair.generate();
+    const sanctionedTokenAccount = await createAccount(
+      connection,
+      bob,
+      mint,
+      sanctioned.publicKey
+    );
+    await block(sanctioned.publicKey);
+    try {
+      await program.methods
+        .withdrawTo(new BN(50 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint,
+          destinationTokenAccount: sanctionedTokenAccount,
+          blockedDestination: blockedPDA(sanctioned.publicKey),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdrawal to a blocked wallet should have failed");
+    } catch (error: any) {
+      assert.include(error.toString(), "AddressBlocked");
+    }
+    // ... nor a crank reward
+    try {
+      await crank(sanctioned.publicKey, sanctionedTokenAccount);
+      assert.fail("Crank rewarding a blocked wallet should have failed");
+    } catch (error: any) {
+      assert.include(error.toString(), "AddressBlocked");
+    }
+    await unblock(sanctioned.publicKey);
+
+    await withdraw();
+    const vault = await getAccount(connection, vaultPDA);
+    assert.equal(vault.amount.toString(), (50 * 10 ** 6).toString());
+  });
+
+  it("only serves credential holders while a credential gate is set", async () => {
+    const connection = program.provider.connection;
+    const credentialMint = await createMint(
+      connection,
+      bob,
+      bob.publicKey,
+      null,
+      0
+    );
+    const credentialTokenAccount = await createAssociatedTokenAccount(
+      connection,
+      bob,
+      credentialMint,
+      bob.publicKey
+    );
+
+    const initialize = (credential: PublicKey | null) =>
+      program.methods
+        .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          credentialTokenAccount: credential,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    const withdraw = (credential: PublicKey | null) =>
+      program.methods
+        .withdraw(new BN(50 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          credentialTokenAccount: credential,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    await program.methods
+      .setCredentialGate(credentialMint)
+      .accounts({ admin: admin.publicKey })
+      .rpc();
+    try {
+      try {
+        await initialize(null);
+        assert.fail("Initialize should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "CredentialRequired");
+      }
+      // Holding the account isn't enough, it must hold the credential
+      try {
+        await initialize(credentialTokenAccount);
+        assert.fail("Initialize should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "CredentialRequired");
+      }
+
+      await mintTo(
+        connection,
+        bob,
+        credentialMint,
+        credentialTokenAccount,
+        bob,
+        1
+      );
+      await initialize(credentialTokenAccount);
+      await program.methods
+        .deposit(new BN(100 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+      try {
+        await withdraw(null);
+        assert.fail("Withdrawal should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "CredentialRequired");
+      }
+      await withdraw(credentialTokenAccount);
+    } finally {
+      // Other tests create vaults and withdraw without a credential
+      await program.methods
+        .removeCredentialGate()
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    }
+
+    const vault = await getAccount(connection, vaultPDA);
+    assert.equal(vault.amount.toString(), (50 * 10 ** 6).toString());
+  });
+
+  it("only lets membership holders create vaults while creation is gated", async () => {
+    const connection = program.provider.connection;
+    const membershipMint = await createMint(
+      connection,
+      bob,
+      bob.publicKey,
+      null,
+      0
+    );
+    const membershipTokenAccount = await createAssociatedTokenAccount(
+      connection,
+      bob,
+      membershipMint,
+      bob.publicKey
+    );
+    await mintTo(
+      connection,
+      bob,
+      membershipMint,
+      membershipTokenAccount,
+      bob,
+      2
+    );
+
+    const initialize = (membership: PublicKey | null) =>
+      program.methods
+        .initialize(new BN(100 * 10 ** 6), mint, null, null, null)
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          membershipTokenAccount: membership,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    await program.methods
+      .setCreationGate(membershipMint, new BN(3))
+      .accounts({ admin: admin.publicKey })
+      .rpc();
+    try {
+      try {
+        await initialize(null);
+        assert.fail("Initialize should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "MembershipRequired");
+      }
+      // Two membership tokens are one short of the gate
+      try {
+        await initialize(membershipTokenAccount);
+        assert.fail("Initialize should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "MembershipRequired");
+      }
+
+      await mintTo(
+        connection,
+        bob,
+        membershipMint,
+        membershipTokenAccount,
+        bob,
+        1
+      );
+      await initialize(membershipTokenAccount);
+    } finally {
+      // Other tests create vaults without a membership
+      await program.methods
+        .removeCreationGate()
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    }
+
+    const vault = await program.account.vault.fetch(statePDA);
+    assert.isTrue(vault.mint.equals(mint));
+  });
+
+  it("lets the admin lower the maximum lock duration", async () => {
+    const setMaxLockDuration = (seconds: number) =>
+      program.methods
+        .setMaxLockDuration(new BN(seconds))
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    const historyPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("config_history")],
+      program.programId
+    )[0];
+    const maxLock = program.idl.constants.find(
+      (c) => c.name === "maxLockDurationSeconds"
+    );
+
+    await setMaxLockDuration(3600);
+    try {
+      const history = await program.account.configHistory.fetch(historyPDA);
+      const last = history.entries[history.entries.length - 1];
+      assert.deepEqual(last.field, { maxLockDuration: {} });
+      assert.equal(last.new.u64[0].toNumber(), 3600);
+
+      try {
+        await program.methods
+          .lockTokens(new BN(1), new BN(3601))
+          .accounts({ user: bob.publicKey })
+          .signers([bob])
+          .rpc();
+        assert.fail("Lock should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "LockDurationTooLong");
+      }
+    } finally {
+      // Other tests lock for longer
+      await setMaxLockDuration(Number(maxLock.value));
+    }
+
+    // The admin can't raise the bound past the program-wide maximum
+    try {
+      await setMaxLockDuration(Number(maxLock.value) + 1);
+      assert.fail("Setting the bound should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "InvalidLockDuration");
+    }
+  });
+
+  it("skims the protocol deposit fee into the treasury", async () => {
+    const treasury = Keypair.generate();
+    const treasuryTokenAccount = await createAssociatedTokenAccount(
+      program.provider.connection,
+      bob,
+      mint,
+      treasury.publicKey
+    );
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const setFee = (feeBps: number, treasury: PublicKey) =>
+      program.methods
+        .setDepositFee(feeBps, treasury)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    const deposit = (treasuryTokenAccount: PublicKey | null) =>
+      program.methods
+        .deposit(new BN(100 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          treasuryTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+    // 1% of every deposit
+    await setFee(100, treasury.publicKey);
+    try {
+      try {
+        await deposit(null);
+        assert.fail("Deposit should have failed but succeeded");
+      } catch (error: any) {
+        assert.include(error.toString(), "TreasuryMismatch");
+      }
+
+      await deposit(treasuryTokenAccount);
+      const vault = await getAccount(program.provider.connection, vaultPDA);
+      const fees = await getAccount(
+        program.provider.connection,
+        treasuryTokenAccount
+      );
+      assert.equal(vault.amount.toString(), (99 * 10 ** 6).toString());
+      assert.equal(fees.amount.toString(), (1 * 10 ** 6).toString());
+    } finally {
+      // Other tests deposit without a treasury
+      await setFee(0, PublicKey.default);
+    }
+  });
+
+  it("waives the deposit fee for membership token holders", async () => {
+    const membershipMint = await createMint(
+      program.provider.connection,
+      bob,
+      bob.publicKey,
+      null,
+      6
+    );
+    const membershipTokenAccount = await createAssociatedTokenAccount(
+      program.provider.connection,
+      bob,
+      membershipMint,
+      bob.publicKey
+    );
+    await mintTo(
+      program.provider.connection,
+      bob,
+      membershipMint,
+      membershipTokenAccount,
+      bob,
+      1000 * 10 ** 6
+    );
+    await program.methods
+      .initialize(new BN(500 * 10 ** 6), mint, null, null, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    // 1,000 membership tokens make deposits free
+    await program.methods
+      .setFeeTiers(membershipMint, [
+        { minBalance: new BN(1000 * 10 ** 6), discountBps: 10_000 },
+      ])
+      .accounts({ admin: admin.publicKey })
+      .rpc();
+    const treasury = Keypair.generate().publicKey;
+    await program.methods
+      .setDepositFee(100, treasury)
+      .accounts({ admin: admin.publicKey })
+      .rpc();
+    try {
+      await program.methods
+        .deposit(new BN(100 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          membershipTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+
+      // No fee is due, so no treasury account is needed either
+      const vault = await getAccount(program.provider.connection, vaultPDA);
+      assert.equal(vault.amount.toString(), (100 * 10 ** 6).toString());
+    } finally {
+      await program.methods
+        .setDepositFee(0, PublicKey.default)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    }
+  });
+
+  it("creates a vault from an admin-curated goal template", async () => {
+    // A fresh id per run, since the template outlives the test
+    const templateId = Math.floor(Math.random() * 65_536);
+    const templatePDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("template"), new BN(templateId).toArrayLike(Buffer, "le", 2)],
+      program.programId
+    )[0];
+    await program.methods
+      .setGoalTemplate(templateId, {
+        name: "Summer Vacation",
+        mint,
+        target: new BN(500 * 10 ** 6),
+        lockSeconds: new BN(30 * 24 * 60 * 60),
+        cooldownSeconds: new BN(60 * 60),
+        maxWithdrawPerDay: new BN(50 * 10 ** 6),
+        depositFeeBps: 0,
+      })
+      .accounts({ admin: admin.publicKey })
+      .rpc();
+
+    const sig = await program.methods
+      .initializeFromTemplate(templateId, null)
+      .accounts({
+        user: bob.publicKey,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const events = await getCpiEvents(sig);
+    const created = events.find((e) => e.name === "vaultCreatedFromTemplate");
+    assert.isDefined(created);
+    assert.equal(created.data.templateId, templateId);
+
+    const state = await program.account.vault.fetch(statePDA);
+    assert.equal(state.amount.toString(), (500 * 10 ** 6).toString());
+    assert.equal(state.cooldownSeconds.toString(), (60 * 60).toString());
+    assert.equal(state.maxWithdrawPerDay.toString(), (50 * 10 ** 6).toString());
+
+    // The template waives the 1% protocol fee, so no treasury account is needed
+    await program.methods
+      .setDepositFee(100, Keypair.generate().publicKey)
+      .accounts({ admin: admin.publicKey })
+      .rpc();
+    try {
+      await program.methods
+        .deposit(new BN(100 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          userTokenAccount: bobTokenAccount,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+    } finally {
+      await program.methods
+        .setDepositFee(0, PublicKey.default)
+        .accounts({ admin: admin.publicKey })
+        .rpc();
+    }
+    const vault = await getAccount(program.provider.connection, vaultPDA);
+    assert.equal(vault.amount.toString(), (100 * 10 ** 6).toString());
+
+    // The template's lock refuses withdrawals for now
+    try {
+      await program.methods
+        .withdraw(new BN(10 * 10 ** 6))
+        .accounts({
+          user: bob.publicKey,
+          mint: mint,
+          vaultTokenAccount: vaultPDA,
+          vaultAuthority: vaultAuthorityPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([bob])
+        .rpc();
+      assert.fail("Withdrawal should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "SelfExcluded");
+    }
+
+    await program.methods
+      .removeGoalTemplate()
+      .accounts({ admin: admin.publicKey, template: templatePDA })
+      .rpc();
+    assert.isNull(await program.provider.connection.getAccountInfo(templatePDA));
+  });
+
+  it("settles a missed deadline by returning the funds", async () => {
+    const keeper = anchor.web3.Keypair.generate();
+    await airdrop(keeper.publicKey, 1_000_000_000);
+
+    const connection = program.provider.connection;
+    const now = await connection.getBlockTime(await connection.getSlot());
+    await program.methods
+      .initialize(
+        new BN(100 * 10 ** 6),
+        mint,
+        {
+          deadlineTs: new BN(now + 3),
+          consequence: { returnFunds: {} },
+        },
+        null,
+        null
+      )
+      .accounts({
+        user: bob.publicKey,
+        state: statePDA,
+        vaultTokenAccount: vaultPDA,
+        vaultAuthority: vaultAuthorityPDA,
+        mint: mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([bob])
+      .rpc();
+
+    await program.methods
+      .deposit(new BN(50 * 10 ** 6))
+      .accounts({
+        user: bob.publicKey,
+        userTokenAccount: bobTokenAccount,
+        mint: mint,
+        vaultTokenAccount: vaultPDA,
+        state: statePDA,
+        vaultAuthority: vaultAuthorityPDA,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([bob])
+      .rpc();
+
+    const settle = () =>
+      program.methods
+        .settleDeadline()
+        .accounts({
+          caller: keeper.publicKey,
+          owner: bob.publicKey,
+          mint: mint,
+          donationTokenAccount: null,
+          blockedRecipient: null,
+          vaultTokenAccount: vaultPDA,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([keeper])
+        .rpc();
+
+    // Too early
+    try {
+      await settle();
+      assert.fail("Settlement should have failed but succeeded");
+    } catch (error: any) {
+      assert.include(error.toString(), "DeadlineNotSettleable");
+    }
+
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    await settle();
+
+    const bobBalance = await getAccount(connection, bobTokenAccount);
+    const vaultBalance = await getAccount(connection, vaultPDA);
+    assert.equal(bobBalance.amount.toString(), (1000 * 10 ** 6).toString());
+    assert.equal(vaultBalance.amount.toString(), "0");
+
+    const stateAccount = await program.account.vault.fetch(statePDA);
+    assert.isNull(stateAccount.deadline);
+  });
+
+  // HELPERS
+  const airdrop = async (publicKey: anchor.web3.PublicKey, amount: number) => {
+    const sig = await program.provider.connection.requestAirdrop(
+      publicKey,
+      amount
+    );
+    await program.provider.connection.confirmTransaction(sig, "confirmed");
+  };
+
+  // decodes the events emitted through emit_cpi! in a confirmed transaction
+  const getCpiEvents = async (sig: string) => {
+    const tx = await program.provider.connection.getTransaction(sig, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [];
+    for (const inner of tx.meta.innerInstructions) {
+      for (const ix of inner.instructions) {
+        const data = anchor.utils.bytes.bs58.decode(ix.data);
+        // skip the 8-byte event instruction tag
+        const event = program.coder.events.decode(
+          anchor.utils.bytes.base64.encode(data.subarray(8))
+        );
+        if (event) {
+          events.push(event);
+        }
+      }
+    }
+    return events;
+  };
+
+  // returns the program data account holding the program's upgrade authority
+  const getProgramDataAddress = (programID: PublicKey) => {
+    return PublicKey.findProgramAddressSync(
+      [programID.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    )[0];
+  };
+
+  // returns PDA of account
+  const getStatePDA = (user: PublicKey, programID: PublicKey) => {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("state"), user.toBuffer()],
+      programID
+    )[0];
+  };
+
+  // returns PDA of the vault's activity outbox
+  const getOutboxPDA = (state: PublicKey, programID: PublicKey) => {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("outbox"), state.toBytes()],
       programID
     )[0];
   };